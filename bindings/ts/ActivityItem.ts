@@ -1,3 +1,11 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type ActivityItem = { type: string, place_id: number, place_name?: string, osm_user_id?: number, osm_user_name?: string, osm_user_tip?: string, comment?: string, duration_days?: number, image: string, date: string, };
+export type ActivityItem = { type: string, place_id: number, place_name?: string, osm_user_id?: number, osm_user_name?: string, osm_user_tip?: string, comment?: string, 
+/**
+ * Set when the comment is a reply to another comment
+ */
+reply_to?: number, 
+/**
+ * Set when the comment is an official response from the merchant
+ */
+official?: boolean, duration_days?: number, image: string, date: string, };
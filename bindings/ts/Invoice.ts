@@ -1,3 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type Invoice = { id: string, status: string, };
+export type Invoice = { id: string, status: string, expires_at?: string, 
+/**
+ * Expired invoices can be replaced with a fresh one via `POST {id}/renew`
+ */
+renewable: boolean, };
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type PlaceBoost = { place_id: number, starts_at: string, expires_at: string, sponsor_name?: string, sponsor_message?: string, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type PlaceBoostHistoryEntry = { id: number, place_id: number, days: number, starts_at: string, expires_at: string, 
+/**
+ * Boosts granted by admins are free
+ */
+amount_sats: number, 
+/**
+ * Set on boosts which target an area page
+ */
+area_id?: number, 
+/**
+ * Set on boosts which target a category page
+ */
+category?: string, sponsor_name?: string, sponsor_message?: string, active: boolean, };
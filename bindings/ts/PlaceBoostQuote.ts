@@ -1,3 +1,16 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PlaceBoostPricePoint } from "./PlaceBoostPricePoint";
 
-export type PlaceBoostQuote = { quote_30d_sat: number, quote_90d_sat: number, quote_365d_sat: number, };
+export type PlaceBoostQuote = { quote_30d_sat: number, quote_90d_sat: number, quote_365d_sat: number, min_days: number, max_days: number, 
+/**
+ * Price curve, other durations are interpolated between these points
+ */
+prices: Array<PlaceBoostPricePoint>, 
+/**
+ * Price of the requested duration
+ */
+quote_sat?: number, max_sponsor_name_length: number, max_sponsor_message_length: number, 
+/**
+ * Set if fiat pricing is enabled, the `_fiat` fields are in this currency
+ */
+fiat_currency?: string, quote_30d_fiat?: number, quote_90d_fiat?: number, quote_365d_fiat?: number, quote_fiat?: number, };
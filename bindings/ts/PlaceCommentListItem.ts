@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type PlaceCommentListItem = { id: number, place_id: number, text: string, created_at: string, updated_at: string, deleted_at?: string, };
+export type PlaceCommentListItem = { id: number, place_id: number, text: string, parent_id?: number, official: boolean, created_at: string, updated_at: string, deleted_at?: string, };
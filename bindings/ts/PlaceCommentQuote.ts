@@ -1,3 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type PlaceCommentQuote = { quote_sat: number, };
+export type PlaceCommentQuote = { quote_sat: number, 
+/**
+ * Set if fiat pricing is enabled
+ */
+fiat_currency?: string, quote_fiat?: number, };
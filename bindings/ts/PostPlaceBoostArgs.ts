@@ -1,3 +1,15 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type PostPlaceBoostArgs = { place_id: string, days: number, };
+export type PostPlaceBoostArgs = { place_id: string, days: number, 
+/**
+ * Area ID or alias, boosts the place on that area's page only
+ */
+area?: string, 
+/**
+ * Boosts the place on its category page only
+ */
+category?: string, 
+/**
+ * Shown publicly, leave out for an anonymous boost
+ */
+sponsor_name?: string, sponsor_message?: string, };
@@ -0,0 +1,2 @@
+ALTER TABLE request ADD COLUMN rpc_failed INTEGER NOT NULL DEFAULT 0;
+CREATE INDEX request_rpc_failed ON request(rpc_method, date) WHERE rpc_failed = 1;
@@ -1,6 +1,6 @@
 use super::schema::{self};
 use crate::Result;
-use rusqlite::{named_params, Connection};
+use rusqlite::{named_params, params, Connection};
 use schema::Columns::*;
 use schema::TABLE_NAME as TABLE;
 use std::time::Instant;
@@ -17,6 +17,7 @@ pub struct InsertArgs {
     pub body: Option<String>,
     pub response_code: i64,
     pub processing_time_ns: i64,
+    pub rpc_failed: bool,
 }
 
 pub fn insert(request: InsertArgs, conn: &Connection) -> Result<()> {
@@ -31,7 +32,8 @@ pub fn insert(request: InsertArgs, conn: &Connection) -> Result<()> {
                 {Query},
                 {Body},
                 {ResponseCode},
-                {ProcessingTimeNs}
+                {ProcessingTimeNs},
+                {RpcFailed}
             ) VALUES (
                 :{Ip},
                 :{UserAgent},
@@ -41,7 +43,8 @@ pub fn insert(request: InsertArgs, conn: &Connection) -> Result<()> {
                 :{Query},
                 :{Body},
                 :{ResponseCode},
-                :{ProcessingTimeNs}
+                :{ProcessingTimeNs},
+                :{RpcFailed}
              );
           "#,
     );
@@ -57,6 +60,7 @@ pub fn insert(request: InsertArgs, conn: &Connection) -> Result<()> {
             ":body": request.body,
             ":response_code": request.response_code,
             ":processing_time_ns": request.processing_time_ns,
+            ":rpc_failed": request.rpc_failed,
         },
     )?;
     Ok(())
@@ -544,11 +548,121 @@ pub fn select_top_clients(conn: &Connection) -> Result<TopClientsReport> {
     })
 }
 
+/// Matches the requests which create invoices, see `service::rate_limit::group`
+const INVOICE_REQUESTS: &str = r#"
+    (path = '/rpc' AND rpc_method = 'create_invoice')
+    OR (method = 'POST' AND (path IN ('/v4/place-boosts', '/v4/place-comments') OR path LIKE '/v4/invoices/%/renew'))
+    OR (method = 'GET' AND path LIKE '/v4/place-boosts/lnurlp/%/callback')
+"#;
+
+/// IPs which made at least `min_count` requests since `since`, the busiest first
+pub fn select_busy_ips(
+    since: OffsetDateTime,
+    min_count: i64,
+    conn: &Connection,
+) -> Result<Vec<TopIp>> {
+    select_ips("1", since, min_count, conn)
+}
+
+/// IPs which failed to sign in at least `min_count` times since `since`
+pub fn select_failed_signin_ips(
+    since: OffsetDateTime,
+    min_count: i64,
+    conn: &Connection,
+) -> Result<Vec<TopIp>> {
+    select_ips(
+        "path = '/rpc' AND rpc_method = 'signin' AND rpc_failed = 1",
+        since,
+        min_count,
+        conn,
+    )
+}
+
+/// IPs which requested at least `min_count` invoices since `since`
+pub fn select_invoice_ips(
+    since: OffsetDateTime,
+    min_count: i64,
+    conn: &Connection,
+) -> Result<Vec<TopIp>> {
+    select_ips(INVOICE_REQUESTS, since, min_count, conn)
+}
+
+fn select_ips(
+    filter: &str,
+    since: OffsetDateTime,
+    min_count: i64,
+    conn: &Connection,
+) -> Result<Vec<TopIp>> {
+    let since = since
+        .format(&time::format_description::well_known::Rfc3339)
+        .map_err(crate::Error::from)?;
+    let sql = format!(
+        r#"
+            SELECT {Ip}, COUNT(*) AS count
+            FROM {TABLE}
+            WHERE {Date} > ?1 AND ({filter})
+            GROUP BY {Ip}
+            HAVING count >= ?2
+            ORDER BY count DESC
+        "#,
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![since, min_count], |row| {
+        Ok(TopIp {
+            ip: row.get(0)?,
+            count: row.get(1)?,
+        })
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+pub struct TopUsername {
+    pub username: String,
+    pub count: i64,
+    pub unique_ips: i64,
+}
+
+/// Usernames which failed to sign in at least `min_count` times since `since`,
+/// brute force spread over many IPs shows up here but not by IP
+pub fn select_failed_signin_usernames(
+    since: OffsetDateTime,
+    min_count: i64,
+    conn: &Connection,
+) -> Result<Vec<TopUsername>> {
+    let since = since
+        .format(&time::format_description::well_known::Rfc3339)
+        .map_err(crate::Error::from)?;
+    let sql = format!(
+        r#"
+            SELECT json_extract({Body}, '$.params.username') AS username, COUNT(*) AS count, COUNT(DISTINCT {Ip})
+            FROM {TABLE}
+            WHERE {Date} > ?1
+              AND path = '/rpc'
+              AND rpc_method = 'signin'
+              AND rpc_failed = 1
+              AND username IS NOT NULL
+            GROUP BY username
+            HAVING count >= ?2
+            ORDER BY count DESC
+        "#,
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![since, min_count], |row| {
+        Ok(TopUsername {
+            username: row.get(0)?,
+            count: row.get(1)?,
+            unique_ips: row.get(2)?,
+        })
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
 #[cfg(test)]
 mod test {
     use crate::db::log::request::blocking_queries::InsertArgs;
     use crate::db::log::request::schema::Request;
     use crate::db::log::test::conn;
+    use time::{Duration, OffsetDateTime};
 
     #[test]
     fn insert() -> crate::Result<()> {
@@ -565,6 +679,7 @@ mod test {
                 body: Some(r#"{"key": "value"}"#.to_string()),
                 response_code: 200,
                 processing_time_ns: 15000000,
+                rpc_failed: false,
             },
             &conn,
         )?;
@@ -603,6 +718,7 @@ mod test {
                 body: None,
                 response_code: 404,
                 processing_time_ns: 5000000,
+                rpc_failed: false,
             },
             &conn,
         )?;
@@ -641,6 +757,7 @@ mod test {
                 body: None,
                 response_code: 200,
                 processing_time_ns: 1000000,
+                rpc_failed: false,
             },
             &conn,
         )?;
@@ -656,6 +773,7 @@ mod test {
                 body: None,
                 response_code: 401,
                 processing_time_ns: 2000000,
+                rpc_failed: false,
             },
             &conn,
         )?;
@@ -805,4 +923,76 @@ mod test {
         );
         Ok(())
     }
+
+    #[test]
+    fn select_abusive_ips() -> crate::Result<()> {
+        let conn = conn();
+        let insert = |ip: &str, method: &str, path: &str, body: Option<&str>, rpc_failed: bool| {
+            super::insert(
+                InsertArgs {
+                    ip: ip.to_string(),
+                    user_agent: None,
+                    user_id: None,
+                    method: method.to_string(),
+                    path: path.to_string(),
+                    query: None,
+                    body: body.map(ToString::to_string),
+                    response_code: 200,
+                    processing_time_ns: 1000000,
+                    rpc_failed,
+                },
+                &conn,
+            )
+        };
+        let signin = |username: &str| {
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"signin","params":{{"username":"{username}","password":"x"}},"id":1}}"#
+            )
+        };
+        let create_invoice = r#"{"jsonrpc":"2.0","method":"create_invoice","params":{},"id":1}"#;
+        for _ in 0..3 {
+            insert("10.0.0.1", "GET", "/v4/places", None, false)?;
+            insert("10.0.0.2", "POST", "/rpc", Some(&signin("satoshi")), true)?;
+            insert("10.0.0.3", "POST", "/rpc", Some(create_invoice), false)?;
+            insert("10.0.0.3", "POST", "/v4/place-boosts", None, false)?;
+        }
+        insert("10.0.0.4", "POST", "/rpc", Some(&signin("satoshi")), true)?;
+        insert("10.0.0.4", "POST", "/rpc", Some(&signin("hal")), false)?;
+        insert(
+            "10.0.0.5",
+            "GET",
+            "/v4/place-boosts/lnurlp/1/callback",
+            None,
+            false,
+        )?;
+        conn.execute(
+            "INSERT INTO request (ip, path, response_code, processing_time_ns, date) VALUES ('10.0.0.6', '/v4/places', 200, 1000000, strftime('%Y-%m-%dT%H:%M:%fZ', 'now', '-2 hours'))",
+            [],
+        )?;
+        let since = OffsetDateTime::now_utc() - Duration::hours(1);
+        let ips = |it: Vec<super::TopIp>| {
+            it.into_iter()
+                .map(|it| (it.ip, it.count))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            vec![("10.0.0.3".to_string(), 6)],
+            ips(super::select_busy_ips(since, 4, &conn)?)
+        );
+        assert_eq!(
+            vec![("10.0.0.2".to_string(), 3)],
+            ips(super::select_failed_signin_ips(since, 2, &conn)?)
+        );
+        assert_eq!(
+            vec![("10.0.0.3".to_string(), 6), ("10.0.0.5".to_string(), 1)],
+            ips(super::select_invoice_ips(since, 1, &conn)?)
+        );
+        let usernames = super::select_failed_signin_usernames(since, 2, &conn)?;
+        assert_eq!(1, usernames.len());
+        assert_eq!("satoshi", usernames[0].username);
+        assert_eq!(4, usernames[0].count);
+        assert_eq!(2, usernames[0].unique_ips);
+        Ok(())
+    }
 }
@@ -1,8 +1,8 @@
 use super::super::LogPool;
 use super::blocking_queries;
 use super::blocking_queries::{
-    DailyInfraReport, InsertArgs, PlatformUniqueIps24h, TopClientsReport, TopRestApiCall,
-    TopRpcMethod, TopUserAgent,
+    DailyInfraReport, InsertArgs, PlatformUniqueIps24h, TopClientsReport, TopIp, TopRestApiCall,
+    TopRpcMethod, TopUserAgent, TopUsername,
 };
 use crate::db::log::request::schema::Request;
 use crate::Result;
@@ -78,3 +78,49 @@ pub async fn select_platform_unique_ips_24h(pool: &LogPool) -> Result<PlatformUn
         .interact(move |conn| blocking_queries::select_platform_unique_ips_24h(conn))
         .await?
 }
+
+pub async fn select_busy_ips(
+    since: OffsetDateTime,
+    min_count: i64,
+    pool: &LogPool,
+) -> Result<Vec<TopIp>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_busy_ips(since, min_count, conn))
+        .await?
+}
+
+pub async fn select_failed_signin_ips(
+    since: OffsetDateTime,
+    min_count: i64,
+    pool: &LogPool,
+) -> Result<Vec<TopIp>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_failed_signin_ips(since, min_count, conn))
+        .await?
+}
+
+pub async fn select_failed_signin_usernames(
+    since: OffsetDateTime,
+    min_count: i64,
+    pool: &LogPool,
+) -> Result<Vec<TopUsername>> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::select_failed_signin_usernames(since, min_count, conn)
+        })
+        .await?
+}
+
+pub async fn select_invoice_ips(
+    since: OffsetDateTime,
+    min_count: i64,
+    pool: &LogPool,
+) -> Result<Vec<TopIp>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_invoice_ips(since, min_count, conn))
+        .await?
+}
@@ -16,6 +16,7 @@ pub enum Columns {
     Body,
     ResponseCode,
     ProcessingTimeNs,
+    RpcFailed,
 }
 
 pub struct Request {
@@ -93,6 +94,7 @@ mod test {
         assert_eq!(Columns::Body.as_ref(), "body");
         assert_eq!(Columns::ResponseCode.as_ref(), "response_code");
         assert_eq!(Columns::ProcessingTimeNs.as_ref(), "processing_time_ns");
+        assert_eq!(Columns::RpcFailed.as_ref(), "rpc_failed");
     }
 
     #[test]
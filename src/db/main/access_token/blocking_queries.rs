@@ -1,5 +1,6 @@
-use super::schema::{self, AccessToken, AccessTokenInfo};
+use super::schema::{self, AccessToken, AccessTokenInfo, Restrictions};
 use crate::db::main::user::schema::Role;
+use crate::service::api_token;
 use crate::Result;
 use rusqlite::{params, Connection};
 use schema::Columns::*;
@@ -24,12 +25,47 @@ pub fn insert_with_import_origins(
     roles: &[Role],
     import_origins: &[String],
     conn: &Connection,
+) -> Result<AccessToken> {
+    insert_restricted(
+        user_id,
+        name,
+        secret,
+        roles,
+        import_origins,
+        &Restrictions::default(),
+        conn,
+    )
+}
+
+pub fn insert_restricted(
+    user_id: i64,
+    name: &str,
+    secret: &str,
+    roles: &[Role],
+    import_origins: &[String],
+    restrictions: &Restrictions,
+    conn: &Connection,
 ) -> Result<AccessToken> {
     let roles: Vec<String> = roles.iter().map(|it| it.to_string()).collect();
+    let expires_at = restrictions
+        .expires_at
+        .map(|it| it.format(&Rfc3339))
+        .transpose()?;
     let sql = format!(
         r#"
-            INSERT INTO {TABLE} ({UserId}, {Name}, {Secret}, {Roles}, {ImportOrigins})
-            VALUES (?1, ?2, ?3, json(?4), json(?5))
+            INSERT INTO {TABLE} (
+                {UserId},
+                {Name},
+                {Secret},
+                {SecretHash},
+                {Prefix},
+                {Roles},
+                {ImportOrigins},
+                {ExpiresAt},
+                {AllowedMethods},
+                {AllowedIps}
+            )
+            VALUES (?1, ?2, '', ?3, ?4, json(?5), json(?6), ?7, json(?8), json(?9))
             RETURNING {projection}
         "#,
         projection = AccessToken::projection(),
@@ -39,9 +75,13 @@ pub fn insert_with_import_origins(
         params![
             user_id,
             name,
-            secret,
+            api_token::hash(secret),
+            api_token::prefix(secret),
             serde_json::to_string(&roles)?,
             serde_json::to_string(import_origins)?,
+            expires_at,
+            serde_json::to_string(&restrictions.allowed_methods)?,
+            serde_json::to_string(&restrictions.allowed_ips)?,
         ],
         AccessToken::mapper(),
     )
@@ -81,14 +121,87 @@ pub fn select_by_secret(secret: &str, conn: &Connection) -> Result<AccessToken>
         r#"
         SELECT {projection}
         FROM {TABLE}
-        WHERE {Secret} = ?1 AND {DeletedAt} IS NULL
+        WHERE {SecretHash} = ?1 AND {DeletedAt} IS NULL
     "#,
         projection = AccessToken::projection(),
     );
-    conn.query_row(&sql, params![secret], AccessToken::mapper())
+    let token = conn.query_row(
+        &sql,
+        params![api_token::hash(secret)],
+        AccessToken::mapper(),
+    )?;
+    if token
+        .expires_at
+        .is_some_and(|it| it <= OffsetDateTime::now_utc())
+    {
+        return Err(rusqlite::Error::QueryReturnedNoRows.into());
+    }
+    Ok(token)
+}
+
+pub fn set_expires_at(
+    id: i64,
+    expires_at: Option<OffsetDateTime>,
+    conn: &Connection,
+) -> Result<AccessToken> {
+    let sql = format!(
+        r#"
+            UPDATE {TABLE}
+            SET {ExpiresAt} = ?2
+            WHERE {Id} = ?1
+            RETURNING {projection}
+        "#,
+        projection = AccessToken::projection(),
+    );
+    let expires_at = expires_at.map(|it| it.format(&Rfc3339)).transpose()?;
+    conn.query_row(&sql, params![id, expires_at], AccessToken::mapper())
         .map_err(Into::into)
 }
 
+pub fn set_last_used_at(id: i64, last_used_at: OffsetDateTime, conn: &Connection) -> Result<()> {
+    let sql = format!(
+        r#"
+            UPDATE {TABLE}
+            SET {LastUsedAt} = ?2
+            WHERE {Id} = ?1
+        "#
+    );
+    conn.execute(&sql, params![id, last_used_at.format(&Rfc3339)?])?;
+    Ok(())
+}
+
+/// Moves tokens issued before secrets were hashed over to hashes. Returns the
+/// number of hashed tokens.
+pub fn hash_plaintext_secrets(conn: &Connection) -> Result<usize> {
+    let sql = format!(
+        r#"
+            SELECT {Id}, {Secret}
+            FROM {TABLE}
+            WHERE {Secret} != ''
+        "#
+    );
+    let tokens = conn
+        .prepare(&sql)?
+        .query_map({}, |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let sql = format!(
+        r#"
+            UPDATE {TABLE}
+            SET {SecretHash} = ?2, {Prefix} = ?3, {Secret} = ''
+            WHERE {Id} = ?1
+        "#
+    );
+    for (id, secret) in &tokens {
+        conn.execute(
+            &sql,
+            params![id, api_token::hash(secret), api_token::prefix(secret)],
+        )?;
+    }
+    Ok(tokens.len())
+}
+
 pub fn select_by_user_id(user_id: i64, conn: &Connection) -> Result<Vec<AccessTokenInfo>> {
     let sql = format!(
         r#"
@@ -147,10 +260,12 @@ pub fn set_deleted_at(
 
 #[cfg(test)]
 mod test {
+    use crate::db::main::access_token::schema::Restrictions;
     use crate::db::main::test::conn;
     use crate::db::main::user::schema::Role;
+    use crate::service::api_token;
     use crate::Result;
-    use time::OffsetDateTime;
+    use time::{Duration, OffsetDateTime};
 
     #[test]
     fn insert() -> Result<()> {
@@ -165,7 +280,8 @@ mod test {
         assert_eq!(1, selected_token.id);
         assert_eq!(2, selected_token.user_id);
         assert_eq!(Some(name), selected_token.name.as_deref());
-        assert_eq!(secret, selected_token.secret);
+        assert_eq!(api_token::hash(secret), selected_token.secret_hash);
+        assert_eq!(secret, selected_token.prefix);
         assert_eq!(roles, selected_token.roles);
         assert!(selected_token.import_origins.is_empty());
         assert!(selected_token.deleted_at.is_none());
@@ -251,7 +367,7 @@ mod test {
         let updated = super::set_deleted_at(token.id, Some(OffsetDateTime::now_utc()), &conn)?;
         assert_eq!(token.id, updated.id);
         assert!(updated.deleted_at.is_some());
-        assert_eq!(token.secret, updated.secret);
+        assert_eq!(token.secret_hash, updated.secret_hash);
         Ok(())
     }
 
@@ -264,4 +380,84 @@ mod test {
         assert!(restored.deleted_at.is_none());
         Ok(())
     }
+
+    #[test]
+    fn select_by_secret_ignores_stored_value() -> Result<()> {
+        let conn = conn();
+        let token = super::insert(1, "", "btcmap_1a2b3c4d5e6f", &[], &conn)?;
+        assert_eq!("btcmap_1a2b3c4d", token.prefix);
+        // a DB copy only reveals the hash, which isn't accepted as a secret
+        assert!(super::select_by_secret(&token.secret_hash, &conn).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn hash_plaintext_secrets() -> Result<()> {
+        let conn = conn();
+        conn.execute(
+            "INSERT INTO access_token (user_id, secret) VALUES (1, 'f47ac10b-58cc-4372-a567-0e02b2c3d479')",
+            [],
+        )?;
+        assert_eq!(1, super::hash_plaintext_secrets(&conn)?);
+        assert_eq!(0, super::hash_plaintext_secrets(&conn)?);
+        let token = super::select_by_secret("f47ac10b-58cc-4372-a567-0e02b2c3d479", &conn)?;
+        assert_eq!("f47ac10b", token.prefix);
+        let secret: String =
+            conn.query_row("SELECT secret FROM access_token", [], |row| row.get(0))?;
+        assert_eq!("", secret);
+        Ok(())
+    }
+
+    #[test]
+    fn insert_restricted() -> Result<()> {
+        let conn = conn();
+        let restrictions = Restrictions {
+            expires_at: Some(OffsetDateTime::now_utc() + Duration::hours(1)),
+            allowed_methods: vec!["submit_place".into()],
+            allowed_ips: vec!["10.0.0.0/8".into()],
+        };
+        let token = super::insert_restricted(
+            1,
+            "",
+            "secret",
+            &[Role::PlacesSource],
+            &["square".into()],
+            &restrictions,
+            &conn,
+        )?;
+        assert_eq!(restrictions.expires_at, token.expires_at);
+        assert_eq!(restrictions.allowed_methods, token.allowed_methods);
+        assert_eq!(restrictions.allowed_ips, token.allowed_ips);
+        assert_eq!(token, super::select_by_secret("secret", &conn)?);
+        Ok(())
+    }
+
+    #[test]
+    fn select_by_secret_skips_expired() -> Result<()> {
+        let conn = conn();
+        let token = super::insert(1, "", "secret", &[], &conn)?;
+        super::set_expires_at(
+            token.id,
+            Some(OffsetDateTime::now_utc() - Duration::seconds(1)),
+            &conn,
+        )?;
+        assert!(super::select_by_secret("secret", &conn).is_err());
+        super::set_expires_at(token.id, None, &conn)?;
+        assert!(super::select_by_secret("secret", &conn).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn set_last_used_at() -> Result<()> {
+        let conn = conn();
+        let token = super::insert(1, "", "secret", &[], &conn)?;
+        assert!(token.last_used_at.is_none());
+        let now = OffsetDateTime::now_utc();
+        super::set_last_used_at(token.id, now, &conn)?;
+        let token = super::select_by_id(token.id, &conn)?;
+        assert_eq!(Some(now), token.last_used_at);
+        // doesn't count as a change of the token
+        assert_eq!(token.created_at, token.updated_at);
+        Ok(())
+    }
 }
@@ -1,4 +1,7 @@
-use super::{blocking_queries, schema::AccessToken, schema::AccessTokenInfo};
+use super::{
+    blocking_queries,
+    schema::{AccessToken, AccessTokenInfo, Restrictions},
+};
 use crate::{db::main::user::schema::Role, Result};
 use deadpool_sqlite::Pool;
 use time::OffsetDateTime;
@@ -40,6 +43,31 @@ pub async fn insert_with_import_origins(
         .await?
 }
 
+pub async fn insert_restricted(
+    user_id: i64,
+    name: String,
+    secret: String,
+    roles: Vec<Role>,
+    import_origins: Vec<String>,
+    restrictions: Restrictions,
+    pool: &Pool,
+) -> Result<AccessToken> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::insert_restricted(
+                user_id,
+                &name,
+                &secret,
+                &roles,
+                &import_origins,
+                &restrictions,
+                conn,
+            )
+        })
+        .await?
+}
+
 pub async fn select_by_secret(secret: String, pool: &Pool) -> Result<AccessToken> {
     pool.get()
         .await?
@@ -47,6 +75,13 @@ pub async fn select_by_secret(secret: String, pool: &Pool) -> Result<AccessToken
         .await?
 }
 
+pub async fn hash_plaintext_secrets(pool: &Pool) -> Result<usize> {
+    pool.get()
+        .await?
+        .interact(|conn| blocking_queries::hash_plaintext_secrets(conn))
+        .await?
+}
+
 pub async fn select_by_user_id(user_id: i64, pool: &Pool) -> Result<Vec<AccessTokenInfo>> {
     pool.get()
         .await?
@@ -71,3 +106,21 @@ pub async fn set_deleted_at(
         .interact(move |conn| blocking_queries::set_deleted_at(id, deleted_at, conn))
         .await?
 }
+
+pub async fn set_expires_at(
+    id: i64,
+    expires_at: Option<OffsetDateTime>,
+    pool: &Pool,
+) -> Result<AccessToken> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_expires_at(id, expires_at, conn))
+        .await?
+}
+
+pub async fn set_last_used_at(id: i64, last_used_at: OffsetDateTime, pool: &Pool) -> Result<()> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_last_used_at(id, last_used_at, conn))
+        .await?
+}
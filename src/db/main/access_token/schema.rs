@@ -1,7 +1,8 @@
 use crate::db::main::user::schema::Role;
+use crate::service::cidr::Cidr;
 use rusqlite::Row;
 use serde_json::Value;
-use std::{str::FromStr, sync::OnceLock};
+use std::{net::IpAddr, str::FromStr, sync::OnceLock};
 use time::OffsetDateTime;
 
 pub const TABLE: &str = "access_token";
@@ -12,12 +13,29 @@ pub enum Columns {
     Id,
     UserId,
     Name,
+    /// Cleared once the secret is hashed, new tokens never store it
     Secret,
     Roles,
     ImportOrigins,
     CreatedAt,
     UpdatedAt,
     DeletedAt,
+    SecretHash,
+    Prefix,
+    ExpiresAt,
+    LastUsedAt,
+    AllowedMethods,
+    AllowedIps,
+}
+
+/// Optional limits on top of the roles of a token
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct Restrictions {
+    pub expires_at: Option<OffsetDateTime>,
+    /// RPC method names, empty means any method the roles allow
+    pub allowed_methods: Vec<String>,
+    /// IP addresses and CIDR ranges, empty means any address
+    pub allowed_ips: Vec<String>,
 }
 
 #[allow(dead_code)]
@@ -26,9 +44,17 @@ pub struct AccessToken {
     pub id: i64,
     pub user_id: i64,
     pub name: Option<String>,
-    pub secret: String,
+    /// See `service::api_token::hash`
+    pub secret_hash: String,
+    /// The start of the secret, which helps to tell tokens apart
+    pub prefix: String,
     pub roles: Vec<Role>,
     pub import_origins: Vec<String>,
+    pub expires_at: Option<OffsetDateTime>,
+    /// Updated at most once a minute
+    pub last_used_at: Option<OffsetDateTime>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_ips: Vec<String>,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
     pub deleted_at: Option<OffsetDateTime>,
@@ -40,7 +66,12 @@ pub struct AccessTokenInfo {
     pub id: i64,
     pub user_id: i64,
     pub label: Option<String>,
+    pub prefix: String,
     pub roles: Vec<Role>,
+    pub expires_at: Option<OffsetDateTime>,
+    pub last_used_at: Option<OffsetDateTime>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_ips: Vec<String>,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
 }
@@ -53,7 +84,12 @@ impl AccessTokenInfo {
                 Columns::Id,
                 Columns::UserId,
                 Columns::Name,
+                Columns::Prefix,
                 Columns::Roles,
+                Columns::ExpiresAt,
+                Columns::LastUsedAt,
+                Columns::AllowedMethods,
+                Columns::AllowedIps,
                 Columns::CreatedAt,
                 Columns::UpdatedAt,
             ]
@@ -70,7 +106,12 @@ impl AccessTokenInfo {
                 id: row.get(Columns::Id.as_ref())?,
                 user_id: row.get(Columns::UserId.as_ref())?,
                 label: row.get(Columns::Name.as_ref())?,
+                prefix: row.get(Columns::Prefix.as_ref())?,
                 roles: parse_roles(row.get(Columns::Roles.as_ref())?)?,
+                expires_at: row.get(Columns::ExpiresAt.as_ref())?,
+                last_used_at: row.get(Columns::LastUsedAt.as_ref())?,
+                allowed_methods: parse_strings(row.get(Columns::AllowedMethods.as_ref())?)?,
+                allowed_ips: parse_strings(row.get(Columns::AllowedIps.as_ref())?)?,
                 created_at: row.get(Columns::CreatedAt.as_ref())?,
                 updated_at: row.get(Columns::UpdatedAt.as_ref())?,
             })
@@ -79,6 +120,32 @@ impl AccessTokenInfo {
 }
 
 impl AccessToken {
+    /// Method restricted tokens are meant for RPC only, REST endpoints pass
+    /// `None` to reject them
+    pub fn allows_method(&self, method: Option<&str>) -> bool {
+        match method {
+            Some(method) => {
+                self.allowed_methods.is_empty()
+                    || self.allowed_methods.iter().any(|it| it == method)
+            }
+            None => self.allowed_methods.is_empty(),
+        }
+    }
+
+    /// Takes the address from `service::client_ip::verified`, never from a header
+    pub fn allows_ip(&self, ip: Option<&IpAddr>) -> bool {
+        if self.allowed_ips.is_empty() {
+            return true;
+        }
+        let Some(ip) = ip else {
+            return false;
+        };
+        self.allowed_ips
+            .iter()
+            .filter_map(|it| it.parse::<Cidr>().ok())
+            .any(|it| it.contains(ip))
+    }
+
     pub fn projection() -> &'static str {
         static PROJECTION: OnceLock<String> = OnceLock::new();
         PROJECTION.get_or_init(|| {
@@ -86,9 +153,14 @@ impl AccessToken {
                 Columns::Id,
                 Columns::UserId,
                 Columns::Name,
-                Columns::Secret,
+                Columns::SecretHash,
+                Columns::Prefix,
                 Columns::Roles,
                 Columns::ImportOrigins,
+                Columns::ExpiresAt,
+                Columns::LastUsedAt,
+                Columns::AllowedMethods,
+                Columns::AllowedIps,
                 Columns::CreatedAt,
                 Columns::UpdatedAt,
                 Columns::DeletedAt,
@@ -106,9 +178,14 @@ impl AccessToken {
                 id: row.get(Columns::Id.as_ref())?,
                 user_id: row.get(Columns::UserId.as_ref())?,
                 name: row.get(Columns::Name.as_ref())?,
-                secret: row.get(Columns::Secret.as_ref())?,
+                secret_hash: row.get(Columns::SecretHash.as_ref())?,
+                prefix: row.get(Columns::Prefix.as_ref())?,
                 roles: parse_roles(row.get(Columns::Roles.as_ref())?)?,
-                import_origins: parse_import_origins(row.get(Columns::ImportOrigins.as_ref())?)?,
+                import_origins: parse_strings(row.get(Columns::ImportOrigins.as_ref())?)?,
+                expires_at: row.get(Columns::ExpiresAt.as_ref())?,
+                last_used_at: row.get(Columns::LastUsedAt.as_ref())?,
+                allowed_methods: parse_strings(row.get(Columns::AllowedMethods.as_ref())?)?,
+                allowed_ips: parse_strings(row.get(Columns::AllowedIps.as_ref())?)?,
                 created_at: row.get(Columns::CreatedAt.as_ref())?,
                 updated_at: row.get(Columns::UpdatedAt.as_ref())?,
                 deleted_at: row.get(Columns::DeletedAt.as_ref())?,
@@ -126,7 +203,7 @@ fn parse_roles(column_value: Value) -> rusqlite::Result<Vec<Role>> {
         .collect())
 }
 
-fn parse_import_origins(column_value: Value) -> rusqlite::Result<Vec<String>> {
+fn parse_strings(column_value: Value) -> rusqlite::Result<Vec<String>> {
     serde_json::from_value(column_value)
         .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
 }
@@ -1,21 +1,32 @@
 use super::schema::{self, Ban, Columns};
 use crate::Result;
-use rusqlite::{named_params, params, Connection, OptionalExtension};
+use rusqlite::{named_params, params, Connection};
 use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
 
-pub fn insert(ip: &str, reason: &str, duration_days: i64, conn: &Connection) -> Result<Ban> {
-    let start_at = OffsetDateTime::now_utc();
-    let end_at = start_at.saturating_add(Duration::days(duration_days));
+pub fn insert(ip: &str, reason: &str, duration: Duration, conn: &Connection) -> Result<Ban> {
+    let end_at = OffsetDateTime::now_utc().saturating_add(duration);
+    insert_rule(Some(ip), None, reason, Some(end_at), conn)
+}
 
+pub fn insert_rule(
+    ip: Option<&str>,
+    user_agent: Option<&str>,
+    reason: &str,
+    end_at: Option<OffsetDateTime>,
+    conn: &Connection,
+) -> Result<Ban> {
+    let start_at = OffsetDateTime::now_utc();
     let sql = format!(
         r#"
             INSERT INTO {table} (
                 {ip},
+                {user_agent},
                 {reason},
                 {start_at},
                 {end_at}
             ) VALUES (
                 :ip,
+                :user_agent,
                 :reason,
                 :start_at,
                 :end_at
@@ -24,6 +35,7 @@ pub fn insert(ip: &str, reason: &str, duration_days: i64, conn: &Connection) ->
         "#,
         table = schema::TABLE_NAME,
         ip = Columns::Ip.as_ref(),
+        user_agent = Columns::UserAgent.as_ref(),
         reason = Columns::Reason.as_ref(),
         start_at = Columns::StartAt.as_ref(),
         end_at = Columns::EndAt.as_ref(),
@@ -31,20 +43,23 @@ pub fn insert(ip: &str, reason: &str, duration_days: i64, conn: &Connection) ->
     );
     let params = named_params! {
         ":ip": ip,
+        ":user_agent": user_agent,
         ":reason": reason,
         ":start_at": start_at.format(&Rfc3339)?,
-        ":end_at": end_at.format(&Rfc3339)?,
+        ":end_at": end_at.map(|it| it.format(&Rfc3339)).transpose()?,
     };
     conn.query_row(&sql, params, Ban::mapper())
         .map_err(Into::into)
 }
 
+#[cfg(test)]
 pub fn select_by_ip(ip: &str, conn: &Connection) -> Result<Option<Ban>> {
+    use rusqlite::OptionalExtension;
     let sql = format!(
         r#"
-            SELECT {projection} 
+            SELECT {projection}
             FROM {table}
-            WHERE {ip} = ?1 AND strftime('%Y-%m-%dT%H:%M:%fZ') > {start_at} AND strftime('%Y-%m-%dT%H:%M:%fZ') < {end_at}
+            WHERE {ip} = ?1 AND strftime('%Y-%m-%dT%H:%M:%fZ') > {start_at} AND ({end_at} IS NULL OR strftime('%Y-%m-%dT%H:%M:%fZ') < {end_at})
         "#,
         projection = Ban::projection(),
         table = schema::TABLE_NAME,
@@ -58,16 +73,184 @@ pub fn select_by_ip(ip: &str, conn: &Connection) -> Result<Option<Ban>> {
         .map_err(Into::into)
 }
 
+pub fn select_by_id(id: i64, conn: &Connection) -> Result<Ban> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {table}
+            WHERE {id} = ?1
+        "#,
+        projection = Ban::projection(),
+        table = schema::TABLE_NAME,
+        id = Columns::Id.as_ref(),
+    );
+    conn.query_row(&sql, params![id], Ban::mapper())
+        .map_err(Into::into)
+}
+
+/// Bans which are in effect, newest first
+pub fn select_active(conn: &Connection) -> Result<Vec<Ban>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {table}
+            WHERE strftime('%Y-%m-%dT%H:%M:%fZ') > {start_at} AND ({end_at} IS NULL OR strftime('%Y-%m-%dT%H:%M:%fZ') < {end_at})
+            ORDER BY {id} DESC
+        "#,
+        projection = Ban::projection(),
+        table = schema::TABLE_NAME,
+        start_at = Columns::StartAt.as_ref(),
+        end_at = Columns::EndAt.as_ref(),
+        id = Columns::Id.as_ref(),
+    );
+    conn.prepare(&sql)?
+        .query_map([], Ban::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+/// All bans, including the expired and lifted ones, newest first
+pub fn select_all(limit: i64, conn: &Connection) -> Result<Vec<Ban>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {table}
+            ORDER BY {id} DESC
+            LIMIT ?1
+        "#,
+        projection = Ban::projection(),
+        table = schema::TABLE_NAME,
+        id = Columns::Id.as_ref(),
+    );
+    conn.prepare(&sql)?
+        .query_map(params![limit], Ban::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+/// Bans which blocked at least one request, the most hit first
+pub fn select_most_hit(limit: i64, conn: &Connection) -> Result<Vec<Ban>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {table}
+            WHERE {hits} > 0
+            ORDER BY {hits} DESC, {id} DESC
+            LIMIT ?1
+        "#,
+        projection = Ban::projection(),
+        table = schema::TABLE_NAME,
+        hits = Columns::Hits.as_ref(),
+        id = Columns::Id.as_ref(),
+    );
+    conn.prepare(&sql)?
+        .query_map(params![limit], Ban::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+pub fn set_end_at(id: i64, end_at: OffsetDateTime, conn: &Connection) -> Result<Ban> {
+    let sql = format!(
+        r#"
+            UPDATE {table}
+            SET {end_at} = ?2
+            WHERE {id} = ?1
+            RETURNING {projection}
+        "#,
+        table = schema::TABLE_NAME,
+        end_at = Columns::EndAt.as_ref(),
+        id = Columns::Id.as_ref(),
+        projection = Ban::projection(),
+    );
+    conn.query_row(&sql, params![id, end_at.format(&Rfc3339)?], Ban::mapper())
+        .map_err(Into::into)
+}
+
+/// Adds up hits counted in memory, as `(id, hits, last_hit_at)`
+pub fn add_hits(hits: &[(i64, i64, OffsetDateTime)], conn: &Connection) -> Result<()> {
+    let sql = format!(
+        r#"
+            UPDATE {table}
+            SET {hits} = {hits} + ?2, {last_hit_at} = ?3
+            WHERE {id} = ?1
+        "#,
+        table = schema::TABLE_NAME,
+        hits = Columns::Hits.as_ref(),
+        last_hit_at = Columns::LastHitAt.as_ref(),
+        id = Columns::Id.as_ref(),
+    );
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(&sql)?;
+        for (id, count, last_hit_at) in hits {
+            stmt.execute(params![id, count, last_hit_at.format(&Rfc3339)?])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use crate::{db::main::test::conn, Result};
+    use time::{Duration, OffsetDateTime};
 
     #[test]
     fn insert() -> Result<()> {
         let conn = conn();
         let ip = "127.0.0.1";
-        let ban = super::insert(ip, "test", 1, &conn)?;
+        let ban = super::insert(ip, "test", Duration::days(1), &conn)?;
         assert_eq!(Some(ban), super::select_by_ip(ip, &conn)?);
         Ok(())
     }
+
+    #[test]
+    fn insert_rule() -> Result<()> {
+        let conn = conn();
+        let ban = super::insert_rule(None, Some("^curl/"), "test", None, &conn)?;
+        assert_eq!(None, ban.ip);
+        assert_eq!(Some("^curl/".into()), ban.user_agent);
+        assert_eq!(None, ban.end_at);
+        assert_eq!(0, ban.hits);
+        assert!(super::insert_rule(None, None, "test", None, &conn).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn select_active() -> Result<()> {
+        let conn = conn();
+        let permanent = super::insert_rule(Some("10.0.0.0/8"), None, "test", None, &conn)?;
+        let temporary = super::insert("10.0.0.1", "test", Duration::days(1), &conn)?;
+        let expired = super::insert("10.0.0.2", "test", Duration::days(1), &conn)?;
+        super::set_end_at(expired.id, OffsetDateTime::now_utc(), &conn)?;
+        let active: Vec<i64> = super::select_active(&conn)?
+            .iter()
+            .map(|it| it.id)
+            .collect();
+        assert_eq!(vec![temporary.id, permanent.id], active);
+        assert_eq!(3, super::select_all(10, &conn)?.len());
+        assert_eq!(1, super::select_all(1, &conn)?.len());
+        Ok(())
+    }
+
+    #[test]
+    fn add_hits() -> Result<()> {
+        let conn = conn();
+        let first = super::insert("10.0.0.1", "test", Duration::days(1), &conn)?;
+        let second = super::insert("10.0.0.2", "test", Duration::days(1), &conn)?;
+        let now = OffsetDateTime::now_utc();
+        super::add_hits(&[(first.id, 2, now), (second.id, 5, now)], &conn)?;
+        super::add_hits(&[(first.id, 1, now)], &conn)?;
+        assert_eq!(3, super::select_by_id(first.id, &conn)?.hits);
+        let most_hit = super::select_most_hit(10, &conn)?;
+        assert_eq!(
+            vec![(second.id, 5), (first.id, 3)],
+            most_hit
+                .iter()
+                .map(|it| (it.id, it.hits))
+                .collect::<Vec<_>>()
+        );
+        assert!(most_hit[0].last_hit_at.is_some());
+        Ok(())
+    }
 }
@@ -1,17 +1,82 @@
 use super::{blocking_queries, schema::Ban};
 use crate::Result;
 use deadpool_sqlite::Pool;
+use time::{Duration, OffsetDateTime};
 
-pub async fn insert(ip: String, reason: String, duration_days: i64, pool: &Pool) -> Result<Ban> {
+pub async fn insert(ip: String, reason: String, duration: Duration, pool: &Pool) -> Result<Ban> {
     pool.get()
         .await?
-        .interact(move |conn| blocking_queries::insert(&ip, &reason, duration_days, conn))
+        .interact(move |conn| blocking_queries::insert(&ip, &reason, duration, conn))
         .await?
 }
 
+pub async fn insert_rule(
+    ip: Option<String>,
+    user_agent: Option<String>,
+    reason: String,
+    end_at: Option<OffsetDateTime>,
+    pool: &Pool,
+) -> Result<Ban> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::insert_rule(
+                ip.as_deref(),
+                user_agent.as_deref(),
+                &reason,
+                end_at,
+                conn,
+            )
+        })
+        .await?
+}
+
+#[cfg(test)]
 pub async fn select_by_ip(ip: String, pool: &Pool) -> Result<Option<Ban>> {
     pool.get()
         .await?
         .interact(move |conn| blocking_queries::select_by_ip(&ip, conn))
         .await?
 }
+
+pub async fn select_by_id(id: i64, pool: &Pool) -> Result<Ban> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_by_id(id, conn))
+        .await?
+}
+
+pub async fn select_active(pool: &Pool) -> Result<Vec<Ban>> {
+    pool.get()
+        .await?
+        .interact(|conn| blocking_queries::select_active(conn))
+        .await?
+}
+
+pub async fn select_all(limit: i64, pool: &Pool) -> Result<Vec<Ban>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_all(limit, conn))
+        .await?
+}
+
+pub async fn select_most_hit(limit: i64, pool: &Pool) -> Result<Vec<Ban>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_most_hit(limit, conn))
+        .await?
+}
+
+pub async fn set_end_at(id: i64, end_at: OffsetDateTime, pool: &Pool) -> Result<Ban> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_end_at(id, end_at, conn))
+        .await?
+}
+
+pub async fn add_hits(hits: Vec<(i64, i64, OffsetDateTime)>, pool: &Pool) -> Result<()> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::add_hits(&hits, conn))
+        .await?
+}
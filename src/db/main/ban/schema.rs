@@ -9,19 +9,29 @@ pub const TABLE_NAME: &str = "ban";
 pub enum Columns {
     Id,
     Ip,
+    UserAgent,
     Reason,
     StartAt,
     EndAt,
+    Hits,
+    LastHitAt,
 }
 
 #[allow(dead_code)]
 #[derive(PartialEq, Eq, Debug)]
 pub struct Ban {
     pub id: i64,
-    pub ip: String,
+    /// An IP address or a CIDR range
+    pub ip: Option<String>,
+    /// A regex, bans with both fields set only match requests which match both
+    pub user_agent: Option<String>,
     pub reason: String,
     pub start_at: OffsetDateTime,
-    pub end_at: OffsetDateTime,
+    /// Permanent if not set
+    pub end_at: Option<OffsetDateTime>,
+    /// Blocked requests, see `service::ban` for how often it's updated
+    pub hits: i64,
+    pub last_hit_at: Option<OffsetDateTime>,
 }
 
 impl Ban {
@@ -31,9 +41,12 @@ impl Ban {
             [
                 Columns::Id,
                 Columns::Ip,
+                Columns::UserAgent,
                 Columns::Reason,
                 Columns::StartAt,
                 Columns::EndAt,
+                Columns::Hits,
+                Columns::LastHitAt,
             ]
             .iter()
             .map(AsRef::as_ref)
@@ -47,9 +60,12 @@ impl Ban {
             Ok(Ban {
                 id: row.get(Columns::Id.as_ref())?,
                 ip: row.get(Columns::Ip.as_ref())?,
+                user_agent: row.get(Columns::UserAgent.as_ref())?,
                 reason: row.get(Columns::Reason.as_ref())?,
                 start_at: row.get(Columns::StartAt.as_ref())?,
                 end_at: row.get(Columns::EndAt.as_ref())?,
+                hits: row.get(Columns::Hits.as_ref())?,
+                last_hit_at: row.get(Columns::LastHitAt.as_ref())?,
             })
         }
     }
@@ -1,8 +1,18 @@
-use super::schema::{self, Conf};
+use super::schema::{self, Columns, Conf};
+use crate::service::conf_crypto::{self, Keys};
 use crate::Result;
-use rusqlite::Connection;
+use rusqlite::{params, params_from_iter, types::Value, Connection};
 
+/// Secrets are decrypted with the keys loaded by `conf_crypto::init`
 pub fn select(conn: &Connection) -> Result<Conf> {
+    select_with_keys(conf_crypto::keys(), conn)
+}
+
+fn select_with_keys(keys: &Keys, conn: &Connection) -> Result<Conf> {
+    decrypt(select_raw(conn)?, keys)
+}
+
+fn select_raw(conn: &Connection) -> Result<Conf> {
     let sql = format!(
         r#"
             SELECT {projection}
@@ -16,9 +26,107 @@ pub fn select(conn: &Connection) -> Result<Conf> {
         .map_err(Into::into)
 }
 
+pub fn select_updated_at(conn: &Connection) -> Result<String> {
+    let sql = format!(
+        r#"
+            SELECT {updated_at}
+            FROM {table}
+        "#,
+        updated_at = Columns::UpdatedAt.as_ref(),
+        table = schema::TABLE_NAME,
+    );
+    conn.query_row(&sql, (), |row| row.get(0))
+        .map_err(Into::into)
+}
+
+/// Writes the given columns of `conf`, the rest stay as they are. RETURNING
+/// doesn't see changes made by triggers, so `updated_at` is bumped right here.
+pub fn update(columns: &[Columns], conf: &Conf, conn: &Connection) -> Result<Conf> {
+    update_with_keys(columns, conf, conf_crypto::keys(), conn)
+}
+
+fn update_with_keys(
+    columns: &[Columns],
+    conf: &Conf,
+    keys: &Keys,
+    conn: &Connection,
+) -> Result<Conf> {
+    if columns.is_empty() {
+        return select_with_keys(keys, conn);
+    }
+    let set = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| format!("{} = ?{}", column.as_ref(), i + 1))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let values = columns
+        .iter()
+        .map(|column| match column {
+            column if Columns::ENCRYPTED.contains(column) => {
+                let Value::Text(plaintext) = conf.column_value(column)? else {
+                    return Err(format!("{column} is not a text column").into());
+                };
+                Ok(keys.encrypt(&plaintext)?.into())
+            }
+            column => conf.column_value(column),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let sql = format!(
+        r#"
+            UPDATE {table}
+            SET {set}, {updated_at} = strftime('%Y-%m-%dT%H:%M:%fZ')
+            RETURNING {projection}
+        "#,
+        table = schema::TABLE_NAME,
+        updated_at = Columns::UpdatedAt.as_ref(),
+        projection = Conf::projection(),
+    );
+    let conf = conn.query_row(&sql, params_from_iter(values), Conf::mapper())?;
+    decrypt(conf, keys)
+}
+
+/// Encrypts the secrets which are in plaintext or encrypted with an old key
+/// under the current key. Returns the names of the rewritten columns.
+pub fn reencrypt(keys: &Keys, conn: &Connection) -> Result<Vec<String>> {
+    let conf = select_raw(conn)?;
+    let mut rewritten = vec![];
+    for column in Columns::ENCRYPTED {
+        let Value::Text(value) = conf.column_value(column)? else {
+            continue;
+        };
+        if keys.is_current(&value) {
+            continue;
+        }
+        let sql = format!(
+            r#"
+                UPDATE {table}
+                SET {column} = ?1
+            "#,
+            table = schema::TABLE_NAME,
+            column = column.as_ref(),
+        );
+        conn.execute(&sql, params![keys.encrypt(&keys.decrypt(&value)?)?])?;
+        rewritten.push(column.to_string());
+    }
+    Ok(rewritten)
+}
+
+fn decrypt(mut conf: Conf, keys: &Keys) -> Result<Conf> {
+    for column in Columns::ENCRYPTED {
+        if let Some(value) = conf.secret_mut(column) {
+            *value = keys
+                .decrypt(value)
+                .map_err(|e| format!("can't decrypt {column}: {e}"))?;
+        }
+    }
+    Ok(conf)
+}
+
 #[cfg(test)]
 mod test {
     use crate::db::main::test::conn;
+    use crate::service::conf_crypto::Keys;
 
     #[test]
     fn select() -> crate::Result<()> {
@@ -27,6 +135,45 @@ mod test {
         assert_eq!(conf.paywall_add_element_comment_price_sat, 500);
         assert_eq!(conf.boost_element_prices, vec![]);
         assert_eq!(conf.cors_origins, Vec::<String>::new());
+        assert_eq!(conf.nostr_secret_key, "");
+        assert_eq!(conf.nostr_relays, Vec::<String>::new());
+        assert_eq!(conf.free_comments_per_day, 5);
+        assert_eq!(conf.free_comment_min_account_age_days, 7);
+        assert_eq!(conf.free_comment_npub_allowlist, Vec::<String>::new());
+        assert_eq!(conf.invoice_backend, "lnd");
+        assert_eq!(conf.lnbits_url, "https://core.btcmap.org");
+        assert_eq!(conf.lnd_url, "https://lnd.btcmap.org");
+        assert_eq!(conf.fiat_currency, "");
+        assert_eq!(conf.paywall_add_element_comment_price_fiat, 0.0);
+        assert_eq!(conf.exchange_rate_provider, "coingecko");
+        assert_eq!(conf.exchange_rate_max_age_secs, 3600);
+        assert_eq!(conf.exchange_rate_fallback, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn update() -> crate::Result<()> {
+        let conn = conn();
+        let mut conf = super::select(&conn)?;
+        conf.cors_origins = vec![
+            "https://a.example.com".into(),
+            "https://b.example.com".into(),
+        ];
+        conf.free_comments_per_day = 3;
+        conf.gitea_api_key = "not written".into();
+        let updated = super::update(
+            &[
+                super::Columns::CorsOrigins,
+                super::Columns::FreeCommentsPerDay,
+            ],
+            &conf,
+            &conn,
+        )?;
+        assert_eq!(conf.cors_origins, updated.cors_origins);
+        assert_eq!(3, updated.free_comments_per_day);
+        assert_eq!("", updated.gitea_api_key);
+        assert_ne!(conf.updated_at, updated.updated_at);
+        assert_eq!(updated.updated_at, super::select_updated_at(&conn)?);
         Ok(())
     }
 
@@ -36,7 +183,7 @@ mod test {
         conn.execute(
             "UPDATE conf SET boost_element_prices = ?1",
             rusqlite::params![
-                r#"[{"days":30,"sats":5000},{"days":90,"sats":10000},{"days":365,"sats":30000}]"#
+                r#"[{"days":30,"sats":5000},{"days":90,"sats":10000},{"days":365,"sats":30000,"fiat":25.5}]"#
             ],
         )?;
         let conf = super::select(&conn)?;
@@ -45,15 +192,18 @@ mod test {
             vec![
                 super::schema::BoostPrice {
                     days: 30,
-                    sats: 5000
+                    sats: 5000,
+                    fiat: None
                 },
                 super::schema::BoostPrice {
                     days: 90,
-                    sats: 10000
+                    sats: 10000,
+                    fiat: None
                 },
                 super::schema::BoostPrice {
                     days: 365,
-                    sats: 30000
+                    sats: 30000,
+                    fiat: Some(25.5),
                 },
             ]
         );
@@ -77,4 +227,70 @@ mod test {
         );
         Ok(())
     }
+
+    #[test]
+    fn select_with_nostr_relays() -> crate::Result<()> {
+        let conn = conn();
+        conn.execute(
+            "UPDATE conf SET nostr_relays = ?1",
+            rusqlite::params!["wss://relay.damus.io, wss://nos.lol"],
+        )?;
+        let conf = super::select(&conn)?;
+        assert_eq!(
+            conf.nostr_relays,
+            vec![
+                "wss://relay.damus.io".to_string(),
+                "wss://nos.lol".to_string(),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn secrets_are_encrypted_at_rest() -> crate::Result<()> {
+        let conn = conn();
+        let keys = Keys::new(&["AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE="])?;
+        let mut conf = super::select_with_keys(&keys, &conn)?;
+        conf.lnd_invoices_macaroon = "macaroon".into();
+        let updated =
+            super::update_with_keys(&[super::Columns::LndInvoicesMacaroon], &conf, &keys, &conn)?;
+        assert_eq!("macaroon", updated.lnd_invoices_macaroon);
+        let raw = super::select_raw(&conn)?;
+        assert!(!raw.lnd_invoices_macaroon.contains("macaroon"));
+        assert_eq!(
+            "macaroon",
+            super::select_with_keys(&keys, &conn)?.lnd_invoices_macaroon
+        );
+        assert!(super::select_with_keys(&Keys::default(), &conn).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn reencrypt() -> crate::Result<()> {
+        let conn = conn();
+        conn.execute(
+            "UPDATE conf SET ppq_key = 'ppq', gitea_api_key = 'gitea'",
+            [],
+        )?;
+        let old = Keys::new(&["AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE="])?;
+        assert_eq!(
+            vec!["gitea_api_key", "ppq_key"],
+            super::reencrypt(&old, &conn)?
+        );
+        assert!(super::reencrypt(&old, &conn)?.is_empty());
+        let raw = super::select_raw(&conn)?;
+        assert!(old.is_current(&raw.ppq_key));
+
+        // rotation, the old key is still needed to read the old values
+        let new = Keys::new(&[
+            "AgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgI=",
+            "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=",
+        ])?;
+        assert_eq!(2, super::reencrypt(&new, &conn)?.len());
+        let conf = super::select_with_keys(&new, &conn)?;
+        assert_eq!("ppq", conf.ppq_key);
+        assert_eq!("gitea", conf.gitea_api_key);
+        assert!(super::select_with_keys(&old, &conn).is_err());
+        Ok(())
+    }
 }
@@ -1,4 +1,8 @@
-use super::{blocking_queries, schema::Conf};
+use super::{
+    blocking_queries,
+    schema::{Columns, Conf},
+};
+use crate::service::conf_crypto;
 use crate::Result;
 use deadpool_sqlite::Pool;
 
@@ -8,3 +12,24 @@ pub async fn select(pool: &Pool) -> Result<Conf> {
         .interact(|conn| blocking_queries::select(conn))
         .await?
 }
+
+pub async fn select_updated_at(pool: &Pool) -> Result<String> {
+    pool.get()
+        .await?
+        .interact(|conn| blocking_queries::select_updated_at(conn))
+        .await?
+}
+
+pub async fn update(columns: Vec<Columns>, conf: Conf, pool: &Pool) -> Result<Conf> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::update(&columns, &conf, conn))
+        .await?
+}
+
+pub async fn reencrypt(pool: &Pool) -> Result<Vec<String>> {
+    pool.get()
+        .await?
+        .interact(|conn| blocking_queries::reencrypt(conf_crypto::keys(), conn))
+        .await?
+}
@@ -1,3 +1,5 @@
+use crate::Result;
+use rusqlite::types::Value;
 use rusqlite::Row;
 use serde::Deserialize;
 use serde::Serialize;
@@ -6,7 +8,9 @@ use std::sync::OnceLock;
 pub const TABLE_NAME: &str = "conf";
 
 #[allow(non_camel_case_types)]
-#[derive(strum::AsRefStr, strum::Display)]
+#[derive(
+    strum::AsRefStr, strum::Display, strum::EnumString, strum::VariantArray, Clone, PartialEq,
+)]
 #[strum(serialize_all = "snake_case")]
 pub enum Columns {
     PaywallAddElementCommentPriceSat,
@@ -18,12 +22,77 @@ pub enum Columns {
     LndReadonlyMacaroon,
     PpqKey,
     CorsOrigins,
+    NostrSecretKey,
+    NostrRelays,
+    FreeCommentsPerDay,
+    FreeCommentMinAccountAgeDays,
+    FreeCommentNpubAllowlist,
+    InvoiceBackend,
+    LnbitsUrl,
+    LndUrl,
+    FiatCurrency,
+    PaywallAddElementCommentPriceFiat,
+    ExchangeRateProvider,
+    ExchangeRateMaxAgeSecs,
+    ExchangeRateFallback,
+    RateLimits,
+    RateLimitBanAfter,
+    RateLimitBanHours,
+    AbuseWindowMinutes,
+    AbuseMaxRequests,
+    AbuseMaxFailedSignins,
+    AbuseMaxInvoices,
+    AbuseBanHours,
+    AbuseAllowlist,
+    HoneypotPlaceIds,
+    HoneypotBanDays,
+    UpdatedAt,
+}
+
+impl Columns {
+    /// Secrets which are encrypted at rest, see `service::conf_crypto`
+    pub const ENCRYPTED: &[Columns] = &[
+        Columns::LnbitsInvoiceKey,
+        Columns::GiteaApiKey,
+        Columns::MatrixBotPassword,
+        Columns::LndInvoicesMacaroon,
+        Columns::LndReadonlyMacaroon,
+        Columns::PpqKey,
+        Columns::NostrSecretKey,
+    ];
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct BoostPrice {
     pub days: i64,
     pub sats: i64,
+    /// Takes precedence over `sats` and gets converted at quote time, in
+    /// `fiat_currency`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<f64>,
+}
+
+/// Requests matched by `service::rate_limit::group`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitGroup {
+    /// GET requests to the v2, v3 and v4 REST APIs
+    Reads,
+    Invoices,
+    /// Sign in, sign up and password changes
+    Auth,
+    Rpc,
+    /// Comment flags, which anyone can file without signing in
+    Flags,
+}
+
+/// A token bucket, which lets clients make `burst` requests at once and then
+/// `per_minute` requests a minute
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub group: RateLimitGroup,
+    pub burst: i64,
+    pub per_minute: i64,
 }
 
 #[allow(dead_code)]
@@ -38,6 +107,51 @@ pub struct Conf {
     pub lnd_readonly_macaroon: String,
     pub ppq_key: String,
     pub cors_origins: Vec<String>,
+    pub nostr_secret_key: String,
+    pub nostr_relays: Vec<String>,
+    /// Daily limit of free comments per identity, zero disables free comments
+    pub free_comments_per_day: i64,
+    pub free_comment_min_account_age_days: i64,
+    /// Only these npubs can comment for free, empty means anyone
+    pub free_comment_npub_allowlist: Vec<String>,
+    /// Where new invoices are created: lnbits, lnd or fake
+    pub invoice_backend: String,
+    pub lnbits_url: String,
+    pub lnd_url: String,
+    /// ISO 4217 code, such as USD. Empty means prices are set in sats only.
+    pub fiat_currency: String,
+    /// Takes precedence over the sat price if not zero
+    pub paywall_add_element_comment_price_fiat: f64,
+    /// Where BTC prices come from: coingecko or fake
+    pub exchange_rate_provider: String,
+    /// Cached rates older than this are not used, even if the provider is down
+    pub exchange_rate_max_age_secs: i64,
+    /// BTC price used when there is no fresh enough rate, zero disables it
+    pub exchange_rate_fallback: f64,
+    /// Groups which aren't listed aren't limited
+    pub rate_limits: Vec<RateLimit>,
+    /// Limited requests an IP can make in an hour before it gets banned, zero
+    /// disables bans
+    pub rate_limit_ban_after: i64,
+    pub rate_limit_ban_hours: i64,
+    /// How often the abuse detector runs, it looks at the requests made since
+    /// the previous run
+    pub abuse_window_minutes: i64,
+    /// Requests an IP can make in a window, zero disables the check
+    pub abuse_max_requests: i64,
+    /// Failed sign ins per IP or username in a window, zero disables the check
+    pub abuse_max_failed_signins: i64,
+    /// Invoices an IP can request in a window, zero disables the check
+    pub abuse_max_invoices: i64,
+    /// Zero means the abuse detector only reports what it finds
+    pub abuse_ban_hours: i64,
+    /// IPs and CIDR ranges which the abuse detector never bans
+    pub abuse_allowlist: Vec<String>,
+    /// Places only bots boost, see `service::ban::check_honeypot`
+    pub honeypot_place_ids: Vec<String>,
+    pub honeypot_ban_days: i64,
+    /// Bumped on every write, the conf service polls it to pick up changes
+    pub updated_at: String,
 }
 
 impl Conf {
@@ -54,6 +168,31 @@ impl Conf {
                 Columns::LndReadonlyMacaroon,
                 Columns::PpqKey,
                 Columns::CorsOrigins,
+                Columns::NostrSecretKey,
+                Columns::NostrRelays,
+                Columns::FreeCommentsPerDay,
+                Columns::FreeCommentMinAccountAgeDays,
+                Columns::FreeCommentNpubAllowlist,
+                Columns::InvoiceBackend,
+                Columns::LnbitsUrl,
+                Columns::LndUrl,
+                Columns::FiatCurrency,
+                Columns::PaywallAddElementCommentPriceFiat,
+                Columns::ExchangeRateProvider,
+                Columns::ExchangeRateMaxAgeSecs,
+                Columns::ExchangeRateFallback,
+                Columns::RateLimits,
+                Columns::RateLimitBanAfter,
+                Columns::RateLimitBanHours,
+                Columns::AbuseWindowMinutes,
+                Columns::AbuseMaxRequests,
+                Columns::AbuseMaxFailedSignins,
+                Columns::AbuseMaxInvoices,
+                Columns::AbuseBanHours,
+                Columns::AbuseAllowlist,
+                Columns::HoneypotPlaceIds,
+                Columns::HoneypotBanDays,
+                Columns::UpdatedAt,
             ]
             .iter()
             .map(AsRef::as_ref)
@@ -74,13 +213,30 @@ impl Conf {
                     )
                 })?;
 
+            let rate_limits: String = row.get(Columns::RateLimits.as_ref())?;
+            let rate_limits = serde_json::from_str(&rate_limits).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    1,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })?;
+
             let cors_origins: String = row.get(Columns::CorsOrigins.as_ref())?;
-            let cors_origins = cors_origins
-                .split(',')
-                .map(str::trim)
-                .filter(|s| !s.is_empty())
-                .map(str::to_string)
-                .collect();
+            let cors_origins = split_list(&cors_origins);
+
+            let nostr_relays: String = row.get(Columns::NostrRelays.as_ref())?;
+            let nostr_relays = split_list(&nostr_relays);
+
+            let free_comment_npub_allowlist: String =
+                row.get(Columns::FreeCommentNpubAllowlist.as_ref())?;
+            let free_comment_npub_allowlist = split_list(&free_comment_npub_allowlist);
+
+            let abuse_allowlist: String = row.get(Columns::AbuseAllowlist.as_ref())?;
+            let abuse_allowlist = split_list(&abuse_allowlist);
+
+            let honeypot_place_ids: String = row.get(Columns::HoneypotPlaceIds.as_ref())?;
+            let honeypot_place_ids = split_list(&honeypot_place_ids);
 
             Ok(Self {
                 paywall_add_element_comment_price_sat: row
@@ -93,7 +249,104 @@ impl Conf {
                 lnd_readonly_macaroon: row.get(Columns::LndReadonlyMacaroon.as_ref())?,
                 ppq_key: row.get(Columns::PpqKey.as_ref())?,
                 cors_origins,
+                nostr_secret_key: row.get(Columns::NostrSecretKey.as_ref())?,
+                nostr_relays,
+                free_comments_per_day: row.get(Columns::FreeCommentsPerDay.as_ref())?,
+                free_comment_min_account_age_days: row
+                    .get(Columns::FreeCommentMinAccountAgeDays.as_ref())?,
+                free_comment_npub_allowlist,
+                invoice_backend: row.get(Columns::InvoiceBackend.as_ref())?,
+                lnbits_url: row.get(Columns::LnbitsUrl.as_ref())?,
+                lnd_url: row.get(Columns::LndUrl.as_ref())?,
+                fiat_currency: row.get(Columns::FiatCurrency.as_ref())?,
+                paywall_add_element_comment_price_fiat: row
+                    .get(Columns::PaywallAddElementCommentPriceFiat.as_ref())?,
+                exchange_rate_provider: row.get(Columns::ExchangeRateProvider.as_ref())?,
+                exchange_rate_max_age_secs: row.get(Columns::ExchangeRateMaxAgeSecs.as_ref())?,
+                exchange_rate_fallback: row.get(Columns::ExchangeRateFallback.as_ref())?,
+                rate_limits,
+                rate_limit_ban_after: row.get(Columns::RateLimitBanAfter.as_ref())?,
+                rate_limit_ban_hours: row.get(Columns::RateLimitBanHours.as_ref())?,
+                abuse_window_minutes: row.get(Columns::AbuseWindowMinutes.as_ref())?,
+                abuse_max_requests: row.get(Columns::AbuseMaxRequests.as_ref())?,
+                abuse_max_failed_signins: row.get(Columns::AbuseMaxFailedSignins.as_ref())?,
+                abuse_max_invoices: row.get(Columns::AbuseMaxInvoices.as_ref())?,
+                abuse_ban_hours: row.get(Columns::AbuseBanHours.as_ref())?,
+                abuse_allowlist,
+                honeypot_place_ids,
+                honeypot_ban_days: row.get(Columns::HoneypotBanDays.as_ref())?,
+                updated_at: row.get(Columns::UpdatedAt.as_ref())?,
             })
         }
     }
+
+    /// The field behind one of the `Columns::ENCRYPTED` columns
+    pub fn secret_mut(&mut self, column: &Columns) -> Option<&mut String> {
+        match column {
+            Columns::LnbitsInvoiceKey => Some(&mut self.lnbits_invoice_key),
+            Columns::GiteaApiKey => Some(&mut self.gitea_api_key),
+            Columns::MatrixBotPassword => Some(&mut self.matrix_bot_password),
+            Columns::LndInvoicesMacaroon => Some(&mut self.lnd_invoices_macaroon),
+            Columns::LndReadonlyMacaroon => Some(&mut self.lnd_readonly_macaroon),
+            Columns::PpqKey => Some(&mut self.ppq_key),
+            Columns::NostrSecretKey => Some(&mut self.nostr_secret_key),
+            _ => None,
+        }
+    }
+
+    /// The value of a column in its DB format, the inverse of `mapper`
+    pub fn column_value(&self, column: &Columns) -> Result<Value> {
+        Ok(match column {
+            Columns::PaywallAddElementCommentPriceSat => {
+                self.paywall_add_element_comment_price_sat.into()
+            }
+            Columns::BoostElementPrices => {
+                serde_json::to_string(&self.boost_element_prices)?.into()
+            }
+            Columns::LnbitsInvoiceKey => self.lnbits_invoice_key.clone().into(),
+            Columns::GiteaApiKey => self.gitea_api_key.clone().into(),
+            Columns::MatrixBotPassword => self.matrix_bot_password.clone().into(),
+            Columns::LndInvoicesMacaroon => self.lnd_invoices_macaroon.clone().into(),
+            Columns::LndReadonlyMacaroon => self.lnd_readonly_macaroon.clone().into(),
+            Columns::PpqKey => self.ppq_key.clone().into(),
+            Columns::CorsOrigins => self.cors_origins.join(",").into(),
+            Columns::NostrSecretKey => self.nostr_secret_key.clone().into(),
+            Columns::NostrRelays => self.nostr_relays.join(",").into(),
+            Columns::FreeCommentsPerDay => self.free_comments_per_day.into(),
+            Columns::FreeCommentMinAccountAgeDays => self.free_comment_min_account_age_days.into(),
+            Columns::FreeCommentNpubAllowlist => self.free_comment_npub_allowlist.join(",").into(),
+            Columns::InvoiceBackend => self.invoice_backend.clone().into(),
+            Columns::LnbitsUrl => self.lnbits_url.clone().into(),
+            Columns::LndUrl => self.lnd_url.clone().into(),
+            Columns::FiatCurrency => self.fiat_currency.clone().into(),
+            Columns::PaywallAddElementCommentPriceFiat => {
+                self.paywall_add_element_comment_price_fiat.into()
+            }
+            Columns::ExchangeRateProvider => self.exchange_rate_provider.clone().into(),
+            Columns::ExchangeRateMaxAgeSecs => self.exchange_rate_max_age_secs.into(),
+            Columns::ExchangeRateFallback => self.exchange_rate_fallback.into(),
+            Columns::RateLimits => serde_json::to_string(&self.rate_limits)?.into(),
+            Columns::RateLimitBanAfter => self.rate_limit_ban_after.into(),
+            Columns::RateLimitBanHours => self.rate_limit_ban_hours.into(),
+            Columns::AbuseWindowMinutes => self.abuse_window_minutes.into(),
+            Columns::AbuseMaxRequests => self.abuse_max_requests.into(),
+            Columns::AbuseMaxFailedSignins => self.abuse_max_failed_signins.into(),
+            Columns::AbuseMaxInvoices => self.abuse_max_invoices.into(),
+            Columns::AbuseBanHours => self.abuse_ban_hours.into(),
+            Columns::AbuseAllowlist => self.abuse_allowlist.join(",").into(),
+            Columns::HoneypotPlaceIds => self.honeypot_place_ids.join(",").into(),
+            Columns::HoneypotBanDays => self.honeypot_ban_days.into(),
+            Columns::UpdatedAt => self.updated_at.clone().into(),
+        })
+    }
+}
+
+/// Parses a comma-separated column into trimmed, non-empty entries.
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
 }
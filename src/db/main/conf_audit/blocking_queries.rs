@@ -0,0 +1,73 @@
+use super::schema::{self, Columns, ConfAudit};
+use crate::Result;
+use rusqlite::{params, Connection};
+use serde_json::{Map, Value};
+
+pub fn insert(user_id: i64, changes: &Map<String, Value>, conn: &Connection) -> Result<ConfAudit> {
+    let sql = format!(
+        r#"
+            INSERT INTO {table} (
+                {user_id},
+                {changes}
+            ) VALUES (
+                ?1,
+                ?2
+            )
+            RETURNING {projection}
+        "#,
+        table = schema::TABLE_NAME,
+        user_id = Columns::UserId.as_ref(),
+        changes = Columns::Changes.as_ref(),
+        projection = ConfAudit::projection(),
+    );
+    conn.query_row(
+        &sql,
+        params![user_id, serde_json::to_string(changes)?],
+        ConfAudit::mapper(),
+    )
+    .map_err(Into::into)
+}
+
+/// Newest first
+#[cfg(test)]
+pub fn select_latest(limit: i64, conn: &Connection) -> Result<Vec<ConfAudit>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {table}
+            ORDER BY {id} DESC
+            LIMIT ?1
+        "#,
+        projection = ConfAudit::projection(),
+        table = schema::TABLE_NAME,
+        id = Columns::Id.as_ref(),
+    );
+    conn.prepare(&sql)?
+        .query_map(params![limit], ConfAudit::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{db::main::test::conn, Result};
+    use serde_json::{json, Map, Value};
+
+    #[test]
+    fn insert_and_select_latest() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let changes: Map<String, Value> = json!({
+            "free_comments_per_day": { "old": 5, "new": 3 }
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+        let first = super::insert(1, &changes, &conn)?;
+        assert_eq!(changes, first.changes);
+        let second = super::insert(2, &Map::new(), &conn)?;
+        assert_eq!(vec![second], super::select_latest(1, &conn)?);
+        assert_eq!(2, super::select_latest(10, &conn)?.len());
+        Ok(())
+    }
+}
@@ -0,0 +1,3 @@
+pub(super) mod blocking_queries;
+pub mod queries;
+pub mod schema;
@@ -0,0 +1,19 @@
+use super::{blocking_queries, schema::ConfAudit};
+use crate::Result;
+use deadpool_sqlite::Pool;
+use serde_json::{Map, Value};
+
+pub async fn insert(user_id: i64, changes: Map<String, Value>, pool: &Pool) -> Result<ConfAudit> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::insert(user_id, &changes, conn))
+        .await?
+}
+
+#[cfg(test)]
+pub async fn select_latest(limit: i64, pool: &Pool) -> Result<Vec<ConfAudit>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_latest(limit, conn))
+        .await?
+}
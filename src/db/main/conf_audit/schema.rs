@@ -0,0 +1,62 @@
+use rusqlite::Row;
+use serde_json::{Map, Value};
+use std::sync::OnceLock;
+use time::OffsetDateTime;
+
+pub const TABLE_NAME: &str = "conf_audit";
+
+#[derive(strum::AsRefStr, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Columns {
+    Id,
+    UserId,
+    Changes,
+    CreatedAt,
+}
+
+/// One write to the `conf` row
+#[derive(Debug, PartialEq)]
+pub struct ConfAudit {
+    pub id: i64,
+    pub user_id: i64,
+    /// Old and new values by column, secrets are redacted
+    pub changes: Map<String, Value>,
+    pub created_at: OffsetDateTime,
+}
+
+impl ConfAudit {
+    pub fn projection() -> &'static str {
+        static PROJECTION: OnceLock<String> = OnceLock::new();
+        PROJECTION.get_or_init(|| {
+            [
+                Columns::Id,
+                Columns::UserId,
+                Columns::Changes,
+                Columns::CreatedAt,
+            ]
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+            .join(", ")
+        })
+    }
+
+    pub const fn mapper() -> fn(&Row) -> rusqlite::Result<ConfAudit> {
+        |row| {
+            let changes: String = row.get(Columns::Changes.as_ref())?;
+            let changes = serde_json::from_str(&changes).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    2,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })?;
+            Ok(ConfAudit {
+                id: row.get(Columns::Id.as_ref())?,
+                user_id: row.get(Columns::UserId.as_ref())?,
+                changes,
+                created_at: row.get(Columns::CreatedAt.as_ref())?,
+            })
+        }
+    }
+}
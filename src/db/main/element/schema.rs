@@ -200,8 +200,28 @@ impl Element {
             .to_string()
     }
 
+    /// OSM survey dates always win, confirmed payments from reviews only fill
+    /// the gap for places which were never surveyed
     pub fn verified_at(&self) -> Option<OffsetDateTime> {
-        self.overpass_data.verification_date()
+        self.overpass_data
+            .verification_date()
+            .or_else(|| self.last_confirmed_payment_at())
+    }
+
+    pub fn rating(&self) -> Option<f64> {
+        self.tags.get("rating")?.as_f64()
+    }
+
+    pub fn rating_count(&self) -> i64 {
+        self.tags
+            .get("rating_count")
+            .and_then(Value::as_i64)
+            .unwrap_or(0)
+    }
+
+    pub fn last_confirmed_payment_at(&self) -> Option<OffsetDateTime> {
+        let date = self.tags.get("last_confirmed_payment_at")?.as_str()?;
+        OffsetDateTime::parse(date, &Rfc3339).ok()
     }
 
     pub fn osm_id(&self) -> String {
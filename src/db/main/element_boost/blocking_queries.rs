@@ -0,0 +1,473 @@
+use crate::{
+    db::main::element_boost::schema::{self, AreaBoostRevenue, BoostHistoryEntry, ElementBoost},
+    Result,
+};
+use rusqlite::{named_params, params, Connection, OptionalExtension};
+use schema::Columns::*;
+use schema::TABLE;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+#[allow(clippy::too_many_arguments)]
+pub fn insert(
+    element_id: i64,
+    invoice_id: Option<i64>,
+    days: i64,
+    area_id: Option<i64>,
+    category: Option<&str>,
+    sponsor_name: Option<&str>,
+    sponsor_message: Option<&str>,
+    conn: &Connection,
+) -> Result<ElementBoost> {
+    let sql = format!(
+        r#"
+            INSERT INTO {TABLE} ({ElementId}, {InvoiceId}, {Days}, {AreaId}, {Category}, {SponsorName}, {SponsorMessage})
+            VALUES (:element_id, :invoice_id, :days, :area_id, :category, :sponsor_name, :sponsor_message)
+            RETURNING {projection}
+        "#,
+        projection = ElementBoost::projection(),
+    );
+    let params = named_params! {
+        ":element_id": element_id,
+        ":invoice_id": invoice_id,
+        ":days": days,
+        ":area_id": area_id,
+        ":category": category,
+        ":sponsor_name": sponsor_name,
+        ":sponsor_message": sponsor_message,
+    };
+    conn.query_row(&sql, params, ElementBoost::mapper())
+        .map_err(Into::into)
+}
+
+pub fn select_by_invoice_id(invoice_id: i64, conn: &Connection) -> Result<Option<ElementBoost>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {TABLE}
+            WHERE {InvoiceId} = ?1
+        "#,
+        projection = ElementBoost::projection(),
+    );
+    conn.query_row(&sql, params![invoice_id], ElementBoost::mapper())
+        .optional()
+        .map_err(Into::into)
+}
+
+/// Renewed invoices take over the boosts of the invoices they replace
+pub fn set_invoice_id(id: i64, invoice_id: i64, conn: &Connection) -> Result<ElementBoost> {
+    let sql = format!(
+        r#"
+            UPDATE {TABLE}
+            SET {InvoiceId} = ?2
+            WHERE {Id} = ?1
+            RETURNING {projection}
+        "#,
+        projection = ElementBoost::projection(),
+    );
+    conn.query_row(&sql, params![id, invoice_id], ElementBoost::mapper())
+        .map_err(Into::into)
+}
+
+/// Returns `None` if the boost has already started
+pub fn activate(
+    id: i64,
+    starts_at: OffsetDateTime,
+    expires_at: OffsetDateTime,
+    conn: &Connection,
+) -> Result<Option<ElementBoost>> {
+    let sql = format!(
+        r#"
+            UPDATE {TABLE}
+            SET {StartsAt} = strftime('%Y-%m-%dT%H:%M:%fZ', :starts_at),
+                {ExpiresAt} = strftime('%Y-%m-%dT%H:%M:%fZ', :expires_at)
+            WHERE {Id} = :id AND {StartsAt} IS NULL
+            RETURNING {projection}
+        "#,
+        projection = ElementBoost::projection(),
+    );
+    let params = named_params! {
+        ":id": id,
+        ":starts_at": starts_at.format(&Rfc3339)?,
+        ":expires_at": expires_at.format(&Rfc3339)?,
+    };
+    conn.query_row(&sql, params, ElementBoost::mapper())
+        .optional()
+        .map_err(Into::into)
+}
+
+/// End of the last started boost with the same scope, new boosts are queued
+/// after it
+pub fn select_max_expires_at(
+    element_id: i64,
+    area_id: Option<i64>,
+    category: Option<&str>,
+    conn: &Connection,
+) -> Result<Option<OffsetDateTime>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {TABLE}
+            WHERE {ElementId} = :element_id
+                AND {AreaId} IS :area_id
+                AND {Category} IS :category
+                AND {ExpiresAt} IS NOT NULL
+            ORDER BY {ExpiresAt} DESC
+            LIMIT 1
+        "#,
+        projection = ElementBoost::projection(),
+    );
+    let params = named_params! {
+        ":element_id": element_id,
+        ":area_id": area_id,
+        ":category": category,
+    };
+    Ok(conn
+        .query_row(&sql, params, ElementBoost::mapper())
+        .optional()?
+        .and_then(|it| it.expires_at))
+}
+
+/// Boosts running at the given time, for a single scope
+pub fn select_active(
+    area_id: Option<i64>,
+    category: Option<&str>,
+    now: OffsetDateTime,
+    conn: &Connection,
+) -> Result<Vec<ElementBoost>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {TABLE}
+            WHERE {AreaId} IS :area_id
+                AND {Category} IS :category
+                AND {StartsAt} <= strftime('%Y-%m-%dT%H:%M:%fZ', :now)
+                AND {ExpiresAt} > strftime('%Y-%m-%dT%H:%M:%fZ', :now)
+            ORDER BY {StartsAt}, {Id}
+        "#,
+        projection = ElementBoost::projection(),
+    );
+    let params = named_params! {
+        ":area_id": area_id,
+        ":category": category,
+        ":now": now.format(&Rfc3339)?,
+    };
+    conn.prepare(&sql)?
+        .query_map(params, ElementBoost::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+/// Paid boosts ordered within the given period, latest first
+pub fn select_paid_created_between(
+    period_start: OffsetDateTime,
+    period_end: OffsetDateTime,
+    conn: &Connection,
+) -> Result<Vec<ElementBoost>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {TABLE}
+            WHERE {CreatedAt} > strftime('%Y-%m-%dT%H:%M:%fZ', :period_start)
+                AND {CreatedAt} < strftime('%Y-%m-%dT%H:%M:%fZ', :period_end)
+                AND {InvoiceId} IS NOT NULL
+                AND {StartsAt} IS NOT NULL
+            ORDER BY {CreatedAt} DESC, {Id} DESC
+        "#,
+        projection = ElementBoost::projection(),
+    );
+    let params = named_params! {
+        ":period_start": period_start.format(&Rfc3339)?,
+        ":period_end": period_end.format(&Rfc3339)?,
+    };
+    conn.prepare(&sql)?
+        .query_map(params, ElementBoost::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+/// Paid boosts of the places within an area, ordered within the given period,
+/// latest first. The area's bounding box narrows down the places before their
+/// area membership is checked.
+pub fn select_paid_created_between_for_area(
+    area_id: i64,
+    period_start: OffsetDateTime,
+    period_end: OffsetDateTime,
+    limit: i64,
+    conn: &Connection,
+) -> Result<Vec<ElementBoost>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {TABLE}
+            WHERE {CreatedAt} > strftime('%Y-%m-%dT%H:%M:%fZ', :period_start)
+                AND {CreatedAt} < strftime('%Y-%m-%dT%H:%M:%fZ', :period_end)
+                AND {InvoiceId} IS NOT NULL
+                AND {StartsAt} IS NOT NULL
+                AND {ElementId} IN (
+                    SELECT e.id
+                    FROM {element_table} e, {area_table} a
+                    WHERE a.id = :area_id
+                        AND e.lat BETWEEN a.bbox_south AND a.bbox_north
+                        AND e.lon BETWEEN a.bbox_west AND a.bbox_east
+                        AND e.id IN (
+                            SELECT element_id FROM {area_element_table}
+                            WHERE area_id = :area_id AND deleted_at IS NULL
+                        )
+                )
+            ORDER BY {CreatedAt} DESC, {Id} DESC
+            LIMIT :limit
+        "#,
+        projection = ElementBoost::projection(),
+        element_table = crate::db::main::element::schema::TABLE_NAME,
+        area_table = crate::db::main::area::schema::TABLE_NAME,
+        area_element_table = crate::db::main::area_element::schema::TABLE_NAME,
+    );
+    let params = named_params! {
+        ":area_id": area_id,
+        ":period_start": period_start.format(&Rfc3339)?,
+        ":period_end": period_end.format(&Rfc3339)?,
+        ":limit": limit,
+    };
+    conn.prepare(&sql)?
+        .query_map(params, ElementBoost::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+/// Started boosts of a place, latest first
+pub fn select_history_by_element_id(
+    element_id: i64,
+    conn: &Connection,
+) -> Result<Vec<BoostHistoryEntry>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}, (
+                SELECT amount_sats FROM {invoice_table} WHERE id = {InvoiceId}
+            ) AS amount_sats
+            FROM {TABLE}
+            WHERE {ElementId} = ?1 AND {StartsAt} IS NOT NULL
+            ORDER BY {StartsAt} DESC, {Id} DESC
+        "#,
+        projection = ElementBoost::projection(),
+        invoice_table = crate::db::main::invoice::schema::TABLE_NAME,
+    );
+    conn.prepare(&sql)?
+        .query_map(params![element_id], BoostHistoryEntry::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+/// Started boosts of the places within an area, and the ones targeting that
+/// area's page, latest first
+pub fn select_history_by_area_id(
+    area_id: i64,
+    conn: &Connection,
+) -> Result<Vec<BoostHistoryEntry>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}, (
+                SELECT amount_sats FROM {invoice_table} WHERE id = {InvoiceId}
+            ) AS amount_sats
+            FROM {TABLE}
+            WHERE ({AreaId} = ?1 OR {ElementId} IN (
+                SELECT element_id FROM {area_element_table}
+                WHERE area_id = ?1 AND deleted_at IS NULL
+            ))
+            AND {StartsAt} IS NOT NULL
+            ORDER BY {StartsAt} DESC, {Id} DESC
+        "#,
+        projection = ElementBoost::projection(),
+        invoice_table = crate::db::main::invoice::schema::TABLE_NAME,
+        area_element_table = crate::db::main::area_element::schema::TABLE_NAME,
+    );
+    conn.prepare(&sql)?
+        .query_map(params![area_id], BoostHistoryEntry::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+/// Paid boosts grouped by the areas their places belong to, a place counts
+/// towards every area it's in. Granted boosts aren't revenue, so they're left
+/// out.
+pub fn select_revenue_by_area(
+    recent_since: OffsetDateTime,
+    conn: &Connection,
+) -> Result<Vec<AreaBoostRevenue>> {
+    let sql = format!(
+        r#"
+            SELECT
+                a.id AS area_id,
+                a.alias AS area_alias,
+                count(*) AS boosts,
+                sum(i.amount_sats) AS total_sats,
+                coalesce(sum(CASE WHEN b.{CreatedAt} >= strftime('%Y-%m-%dT%H:%M:%fZ', :since) THEN i.amount_sats END), 0) AS recent_sats
+            FROM {TABLE} b
+            JOIN {invoice_table} i ON i.id = b.{InvoiceId}
+            JOIN {area_element_table} ae ON ae.element_id = b.{ElementId} AND ae.deleted_at IS NULL
+            JOIN {area_table} a ON a.id = ae.area_id AND a.deleted_at IS NULL
+            WHERE b.{StartsAt} IS NOT NULL
+            GROUP BY a.id
+            ORDER BY total_sats DESC, a.id
+        "#,
+        invoice_table = crate::db::main::invoice::schema::TABLE_NAME,
+        area_element_table = crate::db::main::area_element::schema::TABLE_NAME,
+        area_table = crate::db::main::area::schema::TABLE_NAME,
+    );
+    conn.prepare(&sql)?
+        .query_map(
+            named_params! { ":since": recent_since.format(&Rfc3339)? },
+            AreaBoostRevenue::mapper(),
+        )?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::main::area::schema::Area;
+    use crate::db::main::invoice::schema::InvoiceStatus;
+    use crate::service::overpass::OverpassElement;
+    use crate::{db, db::main::test::conn, Result};
+    use time::{Duration, OffsetDateTime};
+
+    #[test]
+    fn activate_once() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let boost = super::insert(1, Some(1), 30, None, None, Some("Satoshi"), None, &conn)?;
+        assert_eq!(None, boost.starts_at);
+        assert_eq!(Some(boost.clone()), super::select_by_invoice_id(1, &conn)?);
+        assert_eq!(None, super::select_by_invoice_id(2, &conn)?);
+        let boost = super::set_invoice_id(boost.id, 2, &conn)?;
+        assert_eq!(Some(2), boost.invoice_id);
+        let now = OffsetDateTime::now_utc();
+        let activated = super::activate(boost.id, now, now + Duration::days(30), &conn)?.unwrap();
+        assert!(activated.starts_at.is_some());
+        assert_eq!(
+            None,
+            super::activate(boost.id, now, now + Duration::days(30), &conn)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn scopes_are_separate() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let now = OffsetDateTime::now_utc();
+        let global = super::insert(1, None, 30, None, None, None, None, &conn)?;
+        super::activate(global.id, now, now + Duration::days(30), &conn)?;
+        let area = super::insert(1, None, 10, Some(5), None, None, None, &conn)?;
+        super::activate(area.id, now, now + Duration::days(10), &conn)?;
+        let pending = super::insert(1, Some(1), 10, Some(5), Some("cafe"), None, None, &conn)?;
+        assert_eq!(
+            (now + Duration::days(30)).unix_timestamp(),
+            super::select_max_expires_at(1, None, None, &conn)?
+                .unwrap()
+                .unix_timestamp()
+        );
+        assert_eq!(
+            (now + Duration::days(10)).unix_timestamp(),
+            super::select_max_expires_at(1, Some(5), None, &conn)?
+                .unwrap()
+                .unix_timestamp()
+        );
+        assert_eq!(
+            None,
+            super::select_max_expires_at(1, Some(5), Some("cafe"), &conn)?
+        );
+        let active = super::select_active(Some(5), None, now, &conn)?;
+        assert_eq!(
+            vec![area.id],
+            active.iter().map(|it| it.id).collect::<Vec<_>>()
+        );
+        assert!(super::select_active(Some(5), Some("cafe"), now, &conn)?.is_empty());
+        super::activate(pending.id, now, now + Duration::days(10), &conn)?;
+        assert!(super::select_active(Some(5), None, now + Duration::days(11), &conn)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn history_and_revenue() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let area = db::main::area::blocking_queries::insert(Area::mock_tags(), &conn)?;
+        db::main::area_element::blocking_queries::insert(area.id, 1, &conn)?;
+        let invoice = db::main::invoice::blocking_queries::insert(
+            "fake",
+            "element_boost:1:30",
+            5000,
+            "hash",
+            "req",
+            InvoiceStatus::Paid,
+            None,
+            &conn,
+        )?;
+        let now = OffsetDateTime::now_utc();
+        let paid = super::insert(1, Some(invoice.id), 30, None, None, None, None, &conn)?;
+        super::activate(paid.id, now, now + Duration::days(30), &conn)?;
+        let granted = super::insert(1, None, 10, None, None, None, None, &conn)?;
+        super::activate(granted.id, now, now + Duration::days(10), &conn)?;
+        super::insert(1, Some(2), 30, None, None, None, None, &conn)?;
+        let targeted = super::insert(2, None, 5, Some(area.id), None, None, None, &conn)?;
+        super::activate(targeted.id, now, now + Duration::days(5), &conn)?;
+
+        let history = super::select_history_by_element_id(1, &conn)?;
+        assert_eq!(2, history.len());
+        assert_eq!(granted.id, history[0].boost.id);
+        assert_eq!(None, history[0].amount_sats);
+        assert_eq!(Some(5000), history[1].amount_sats);
+        assert_eq!(3, super::select_history_by_area_id(area.id, &conn)?.len());
+
+        let revenue = super::select_revenue_by_area(now - Duration::days(30), &conn)?;
+        assert_eq!(1, revenue.len());
+        assert_eq!(area.id, revenue[0].area_id);
+        assert_eq!(1, revenue[0].boosts);
+        assert_eq!(5000, revenue[0].total_sats);
+        assert_eq!(5000, revenue[0].recent_sats);
+        let revenue = super::select_revenue_by_area(now + Duration::days(1), &conn)?;
+        assert_eq!(0, revenue[0].recent_sats);
+        Ok(())
+    }
+
+    #[test]
+    fn select_paid_created_between() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let area = db::main::area::blocking_queries::insert(Area::mock_tags(), &conn)?;
+        let inside = db::main::element::blocking_queries::insert(&OverpassElement::mock(1), &conn)?;
+        let outside =
+            db::main::element::blocking_queries::insert(&OverpassElement::mock(2), &conn)?;
+        db::main::area_element::blocking_queries::insert(area.id, inside.id, &conn)?;
+        for element in [&inside, &outside] {
+            db::main::element::blocking_queries::set_lat_lon(element.id, 1.0, 1.0, &conn)?;
+        }
+        let now = OffsetDateTime::now_utc();
+        let paid_inside = super::insert(inside.id, Some(1), 30, None, None, None, None, &conn)?;
+        super::activate(paid_inside.id, now, now + Duration::days(30), &conn)?;
+        let paid_outside = super::insert(outside.id, Some(2), 30, None, None, None, None, &conn)?;
+        super::activate(paid_outside.id, now, now + Duration::days(30), &conn)?;
+        let granted = super::insert(inside.id, None, 10, None, None, None, None, &conn)?;
+        super::activate(granted.id, now, now + Duration::days(10), &conn)?;
+        super::insert(inside.id, Some(3), 30, None, None, None, None, &conn)?;
+
+        let start = now - Duration::days(1);
+        let end = now + Duration::days(1);
+        let all = super::select_paid_created_between(start, end, &conn)?;
+        assert_eq!(
+            vec![paid_outside.id, paid_inside.id],
+            all.iter().map(|it| it.id).collect::<Vec<_>>()
+        );
+        assert!(super::select_paid_created_between(end, end, &conn)?.is_empty());
+        let in_area =
+            super::select_paid_created_between_for_area(area.id, start, end, i64::MAX, &conn)?;
+        assert_eq!(
+            vec![paid_inside.id],
+            in_area.iter().map(|it| it.id).collect::<Vec<_>>()
+        );
+        assert!(
+            super::select_paid_created_between_for_area(area.id, start, end, 0, &conn)?.is_empty()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use super::{
+    blocking_queries,
+    schema::{AreaBoostRevenue, BoostHistoryEntry, ElementBoost},
+};
+use crate::Result;
+use deadpool_sqlite::Pool;
+use time::OffsetDateTime;
+
+#[allow(clippy::too_many_arguments)]
+pub async fn insert(
+    element_id: i64,
+    invoice_id: Option<i64>,
+    days: i64,
+    area_id: Option<i64>,
+    category: Option<String>,
+    sponsor_name: Option<String>,
+    sponsor_message: Option<String>,
+    pool: &Pool,
+) -> Result<ElementBoost> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::insert(
+                element_id,
+                invoice_id,
+                days,
+                area_id,
+                category.as_deref(),
+                sponsor_name.as_deref(),
+                sponsor_message.as_deref(),
+                conn,
+            )
+        })
+        .await?
+}
+
+pub async fn select_by_invoice_id(invoice_id: i64, pool: &Pool) -> Result<Option<ElementBoost>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_by_invoice_id(invoice_id, conn))
+        .await?
+}
+
+pub async fn set_invoice_id(id: i64, invoice_id: i64, pool: &Pool) -> Result<ElementBoost> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_invoice_id(id, invoice_id, conn))
+        .await?
+}
+
+pub async fn activate(
+    id: i64,
+    starts_at: OffsetDateTime,
+    expires_at: OffsetDateTime,
+    pool: &Pool,
+) -> Result<Option<ElementBoost>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::activate(id, starts_at, expires_at, conn))
+        .await?
+}
+
+pub async fn select_max_expires_at(
+    element_id: i64,
+    area_id: Option<i64>,
+    category: Option<String>,
+    pool: &Pool,
+) -> Result<Option<OffsetDateTime>> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::select_max_expires_at(element_id, area_id, category.as_deref(), conn)
+        })
+        .await?
+}
+
+pub async fn select_active(
+    area_id: Option<i64>,
+    category: Option<String>,
+    now: OffsetDateTime,
+    pool: &Pool,
+) -> Result<Vec<ElementBoost>> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::select_active(area_id, category.as_deref(), now, conn)
+        })
+        .await?
+}
+
+pub async fn select_paid_created_between(
+    period_start: OffsetDateTime,
+    period_end: OffsetDateTime,
+    pool: &Pool,
+) -> Result<Vec<ElementBoost>> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::select_paid_created_between(period_start, period_end, conn)
+        })
+        .await?
+}
+
+pub async fn select_paid_created_between_for_area(
+    area_id: i64,
+    period_start: OffsetDateTime,
+    period_end: OffsetDateTime,
+    limit: i64,
+    pool: &Pool,
+) -> Result<Vec<ElementBoost>> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::select_paid_created_between_for_area(
+                area_id,
+                period_start,
+                period_end,
+                limit,
+                conn,
+            )
+        })
+        .await?
+}
+
+pub async fn select_history_by_element_id(
+    element_id: i64,
+    pool: &Pool,
+) -> Result<Vec<BoostHistoryEntry>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_history_by_element_id(element_id, conn))
+        .await?
+}
+
+pub async fn select_history_by_area_id(
+    area_id: i64,
+    pool: &Pool,
+) -> Result<Vec<BoostHistoryEntry>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_history_by_area_id(area_id, conn))
+        .await?
+}
+
+pub async fn select_revenue_by_area(
+    recent_since: OffsetDateTime,
+    pool: &Pool,
+) -> Result<Vec<AreaBoostRevenue>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_revenue_by_area(recent_since, conn))
+        .await?
+}
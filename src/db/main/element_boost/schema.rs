@@ -0,0 +1,136 @@
+use rusqlite::Row;
+use std::sync::OnceLock;
+use time::OffsetDateTime;
+
+pub const TABLE: &str = "element_boost";
+
+#[derive(strum::AsRefStr, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Columns {
+    Id,
+    ElementId,
+    InvoiceId,
+    Days,
+    AreaId,
+    Category,
+    SponsorName,
+    SponsorMessage,
+    StartsAt,
+    ExpiresAt,
+    CreatedAt,
+    UpdatedAt,
+}
+
+/// A boost is recorded when it's ordered and starts once its invoice is paid.
+/// Boosts without an area or a category are global, they highlight the place
+/// on the map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElementBoost {
+    pub id: i64,
+    pub element_id: i64,
+    /// `None` for boosts granted by admins
+    pub invoice_id: Option<i64>,
+    pub days: i64,
+    pub area_id: Option<i64>,
+    pub category: Option<String>,
+    /// Public sponsor details, anonymous boosts have none
+    pub sponsor_name: Option<String>,
+    pub sponsor_message: Option<String>,
+    /// `None` until paid
+    pub starts_at: Option<OffsetDateTime>,
+    pub expires_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+impl ElementBoost {
+    pub fn projection() -> &'static str {
+        static PROJECTION: OnceLock<String> = OnceLock::new();
+        PROJECTION.get_or_init(|| {
+            [
+                Columns::Id,
+                Columns::ElementId,
+                Columns::InvoiceId,
+                Columns::Days,
+                Columns::AreaId,
+                Columns::Category,
+                Columns::SponsorName,
+                Columns::SponsorMessage,
+                Columns::StartsAt,
+                Columns::ExpiresAt,
+                Columns::CreatedAt,
+                Columns::UpdatedAt,
+            ]
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+            .join(", ")
+        })
+    }
+
+    pub const fn mapper() -> fn(&Row) -> rusqlite::Result<ElementBoost> {
+        |row| {
+            Ok(ElementBoost {
+                id: row.get(Columns::Id.as_ref())?,
+                element_id: row.get(Columns::ElementId.as_ref())?,
+                invoice_id: row.get(Columns::InvoiceId.as_ref())?,
+                days: row.get(Columns::Days.as_ref())?,
+                area_id: row.get(Columns::AreaId.as_ref())?,
+                category: row.get(Columns::Category.as_ref())?,
+                sponsor_name: row.get(Columns::SponsorName.as_ref())?,
+                sponsor_message: row.get(Columns::SponsorMessage.as_ref())?,
+                starts_at: row.get(Columns::StartsAt.as_ref())?,
+                expires_at: row.get(Columns::ExpiresAt.as_ref())?,
+                created_at: row.get(Columns::CreatedAt.as_ref())?,
+                updated_at: row.get(Columns::UpdatedAt.as_ref())?,
+            })
+        }
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.area_id.is_none() && self.category.is_none()
+    }
+}
+
+/// A started boost and the amount paid for it, granted boosts are free
+#[derive(Debug, PartialEq)]
+pub struct BoostHistoryEntry {
+    pub boost: ElementBoost,
+    pub amount_sats: Option<i64>,
+}
+
+impl BoostHistoryEntry {
+    pub const fn mapper() -> fn(&Row) -> rusqlite::Result<BoostHistoryEntry> {
+        |row| {
+            Ok(BoostHistoryEntry {
+                boost: ElementBoost::mapper()(row)?,
+                amount_sats: row.get("amount_sats")?,
+            })
+        }
+    }
+}
+
+/// Paid boosts of the places within an area
+#[derive(Debug, PartialEq)]
+pub struct AreaBoostRevenue {
+    pub area_id: i64,
+    pub area_alias: String,
+    pub boosts: i64,
+    pub total_sats: i64,
+    /// Boosts ordered since the given date
+    pub recent_sats: i64,
+}
+
+impl AreaBoostRevenue {
+    pub const fn mapper() -> fn(&Row) -> rusqlite::Result<AreaBoostRevenue> {
+        |row| {
+            Ok(AreaBoostRevenue {
+                area_id: row.get("area_id")?,
+                area_alias: row.get("area_alias")?,
+                boosts: row.get("boosts")?,
+                total_sats: row.get("total_sats")?,
+                recent_sats: row.get("recent_sats")?,
+            })
+        }
+    }
+}
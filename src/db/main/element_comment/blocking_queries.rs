@@ -1,37 +1,109 @@
-use super::schema::{self, Columns, ElementComment};
+use super::schema::{self, Columns, ElementComment, HiddenFrom};
+use crate::db::main::element_comment_flag::schema as flag_schema;
 use crate::Result;
-use rusqlite::{named_params, params, Connection};
+use rusqlite::{named_params, params, Connection, OptionalExtension};
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
 pub fn insert(
     element_id: i64,
     comment: impl Into<String>,
     conn: &Connection,
+) -> Result<ElementComment> {
+    insert_threaded(element_id, None, false, None, comment, conn)
+}
+
+pub fn insert_threaded(
+    element_id: i64,
+    parent_id: Option<i64>,
+    official: bool,
+    user_id: Option<i64>,
+    comment: impl Into<String>,
+    conn: &Connection,
 ) -> Result<ElementComment> {
     let sql = format!(
         r#"
             INSERT INTO {table} (
                 {element_id},
+                {parent_id},
+                {official},
+                {user_id},
                 {comment}
             ) VALUES (
                 ?1,
-                ?2
+                ?2,
+                ?3,
+                ?4,
+                ?5
             )
             RETURNING {projection}
         "#,
         table = schema::TABLE_NAME,
         element_id = Columns::ElementId.as_ref(),
+        parent_id = Columns::ParentId.as_ref(),
+        official = Columns::Official.as_ref(),
+        user_id = Columns::UserId.as_ref(),
         comment = Columns::Comment.as_ref(),
         projection = ElementComment::projection(),
     );
     conn.query_row(
         &sql,
-        params![element_id, comment.into()],
+        params![element_id, parent_id, official, user_id, comment.into()],
         ElementComment::mapper(),
     )
     .map_err(Into::into)
 }
 
+/// Inserts an unpublished free comment, unless its author has already posted
+/// `max_posted` comments since the given time. Counting and inserting is a
+/// single statement, so concurrent requests can't both slip under the limit.
+pub fn insert_unpublished_within_limit(
+    element_id: i64,
+    parent_id: Option<i64>,
+    user_id: i64,
+    comment: &str,
+    posted_since: OffsetDateTime,
+    max_posted: i64,
+    conn: &Connection,
+) -> Result<Option<ElementComment>> {
+    let sql = format!(
+        r#"
+            INSERT INTO {table} (
+                {element_id},
+                {parent_id},
+                {user_id},
+                {comment},
+                {deleted_at}
+            )
+            SELECT :element_id, :parent_id, :user_id, :comment, strftime('%Y-%m-%dT%H:%M:%fZ')
+            WHERE (
+                SELECT count(*)
+                FROM {table}
+                WHERE {user_id} = :user_id AND {created_at} > :posted_since
+            ) < :max_posted
+            RETURNING {projection}
+        "#,
+        table = schema::TABLE_NAME,
+        element_id = Columns::ElementId.as_ref(),
+        parent_id = Columns::ParentId.as_ref(),
+        user_id = Columns::UserId.as_ref(),
+        comment = Columns::Comment.as_ref(),
+        deleted_at = Columns::DeletedAt.as_ref(),
+        created_at = Columns::CreatedAt.as_ref(),
+        projection = ElementComment::projection(),
+    );
+    let params = named_params! {
+        ":element_id": element_id,
+        ":parent_id": parent_id,
+        ":user_id": user_id,
+        ":comment": comment,
+        ":posted_since": posted_since.format(&Rfc3339)?,
+        ":max_posted": max_posted,
+    };
+    conn.query_row(&sql, params, ElementComment::mapper())
+        .optional()
+        .map_err(Into::into)
+}
+
 pub fn select_updated_since(
     updated_since: &OffsetDateTime,
     include_deleted: bool,
@@ -281,6 +353,171 @@ pub fn set_deleted_at(
     select_by_id(id, conn)
 }
 
+/// Counts the comments an author posted since the given time, used to rate
+/// limit free comments
+pub fn count_by_user_id_created_since(
+    user_id: i64,
+    created_since: OffsetDateTime,
+    conn: &Connection,
+) -> Result<i64> {
+    let sql = format!(
+        r#"
+            SELECT count(*)
+            FROM {table}
+            WHERE {user_id} = ?1 AND {created_at} > ?2
+        "#,
+        table = schema::TABLE_NAME,
+        user_id = Columns::UserId.as_ref(),
+        created_at = Columns::CreatedAt.as_ref(),
+    );
+    conn.query_row(
+        &sql,
+        params![user_id, created_since.format(&Rfc3339)?],
+        |row| row.get(0),
+    )
+    .map_err(Into::into)
+}
+
+pub fn set_comment(id: i64, comment: &str, conn: &Connection) -> Result<ElementComment> {
+    let sql = format!(
+        r#"
+            UPDATE {table}
+            SET {comment} = ?2
+            WHERE {id} = ?1
+        "#,
+        table = schema::TABLE_NAME,
+        comment = Columns::Comment.as_ref(),
+        id = Columns::Id.as_ref(),
+    );
+    conn.execute(&sql, params![id, comment])?;
+    select_by_id(id, conn)
+}
+
+pub fn set_spam_score(id: i64, spam_score: i64, conn: &Connection) -> Result<ElementComment> {
+    let sql = format!(
+        r#"
+            UPDATE {table}
+            SET {spam_score} = ?2
+            WHERE {id} = ?1
+        "#,
+        table = schema::TABLE_NAME,
+        spam_score = Columns::SpamScore.as_ref(),
+        id = Columns::Id.as_ref(),
+    );
+    conn.execute(&sql, params![id, spam_score])?;
+    select_by_id(id, conn)
+}
+
+/// Links a paid comment to the invoice which publishes it, renewed invoices
+/// take over from the ones they replace
+pub fn set_invoice_id(id: i64, invoice_id: i64, conn: &Connection) -> Result<ElementComment> {
+    let sql = format!(
+        r#"
+            UPDATE {table}
+            SET {invoice_id} = ?2
+            WHERE {id} = ?1
+        "#,
+        table = schema::TABLE_NAME,
+        invoice_id = Columns::InvoiceId.as_ref(),
+        id = Columns::Id.as_ref(),
+    );
+    conn.execute(&sql, params![id, invoice_id])?;
+    select_by_id(id, conn)
+}
+
+/// Pass None to unhide the comment
+pub fn set_hidden_at(
+    id: i64,
+    hidden: Option<(OffsetDateTime, HiddenFrom)>,
+    conn: &Connection,
+) -> Result<ElementComment> {
+    let sql = format!(
+        r#"
+            UPDATE {table}
+            SET {hidden_at} = ?2, {hidden_from} = ?3
+            WHERE {id} = ?1
+        "#,
+        table = schema::TABLE_NAME,
+        hidden_at = Columns::HiddenAt.as_ref(),
+        hidden_from = Columns::HiddenFrom.as_ref(),
+        id = Columns::Id.as_ref(),
+    );
+    let (hidden_at, hidden_from) = match hidden {
+        Some((hidden_at, hidden_from)) => (
+            Some(hidden_at.format(&Rfc3339)?),
+            Some(hidden_from.as_ref().to_string()),
+        ),
+        None => (None, None),
+    };
+    conn.execute(&sql, params![id, hidden_at, hidden_from])?;
+    select_by_id(id, conn)
+}
+
+pub fn set_moderated_at(
+    id: i64,
+    moderated_at: OffsetDateTime,
+    conn: &Connection,
+) -> Result<ElementComment> {
+    let sql = format!(
+        r#"
+            UPDATE {table}
+            SET {moderated_at} = ?2
+            WHERE {id} = ?1
+        "#,
+        table = schema::TABLE_NAME,
+        moderated_at = Columns::ModeratedAt.as_ref(),
+        id = Columns::Id.as_ref(),
+    );
+    conn.execute(&sql, params![id, moderated_at.format(&Rfc3339)?])?;
+    select_by_id(id, conn)
+}
+
+/// Comments which are waiting for a moderator: the ones with unresolved flags
+/// and the ones held back by the spam filter
+pub fn select_moderation_queue(conn: &Connection) -> Result<Vec<ElementComment>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {table}
+            WHERE ({hidden_at} IS NOT NULL AND {moderated_at} IS NULL)
+            OR {id} IN (
+                SELECT {flag_comment_id} FROM {flag_table}
+                WHERE {flag_resolved_at} IS NULL
+            )
+            ORDER BY {created_at}, {id}
+        "#,
+        projection = ElementComment::projection(),
+        table = schema::TABLE_NAME,
+        hidden_at = Columns::HiddenAt.as_ref(),
+        moderated_at = Columns::ModeratedAt.as_ref(),
+        created_at = Columns::CreatedAt.as_ref(),
+        id = Columns::Id.as_ref(),
+        flag_table = flag_schema::TABLE_NAME,
+        flag_comment_id = flag_schema::Columns::ElementCommentId.as_ref(),
+        flag_resolved_at = flag_schema::Columns::ResolvedAt.as_ref(),
+    );
+    conn.prepare(&sql)?
+        .query_map([], ElementComment::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+/// Removes a comment which was never published, such as one waiting for a
+/// payment which never came. Live comments are only ever soft deleted.
+pub fn delete_unpublished(id: i64, conn: &Connection) -> Result<usize> {
+    let sql = format!(
+        r#"
+            DELETE FROM {table}
+            WHERE {id} = ?1 AND {deleted_at} IS NOT NULL AND {moderated_at} IS NULL
+        "#,
+        table = schema::TABLE_NAME,
+        id = Columns::Id.as_ref(),
+        deleted_at = Columns::DeletedAt.as_ref(),
+        moderated_at = Columns::ModeratedAt.as_ref(),
+    );
+    conn.execute(&sql, params![id]).map_err(Into::into)
+}
+
 #[cfg(test)]
 mod test {
     use crate::{db::main::test::conn, Result};
@@ -307,6 +544,58 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn insert_threaded() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let parent = super::insert(1, "Question", &conn)?;
+        assert_eq!(None, parent.parent_id);
+        assert!(!parent.official);
+        let reply = super::insert_threaded(1, Some(parent.id), true, None, "Answer", &conn)?;
+        assert_eq!(Some(parent.id), reply.parent_id);
+        assert!(reply.official);
+        Ok(())
+    }
+
+    #[test]
+    fn insert_unpublished_within_limit() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let hour_ago = OffsetDateTime::now_utc().saturating_sub(Duration::hours(1));
+        let first =
+            super::insert_unpublished_within_limit(1, None, 1, "First", hour_ago, 2, &conn)?
+                .unwrap();
+        assert_eq!(Some(1), first.user_id);
+        assert!(first.deleted_at.is_some());
+        super::insert_unpublished_within_limit(1, None, 1, "Second", hour_ago, 2, &conn)?.unwrap();
+        assert_eq!(
+            None,
+            super::insert_unpublished_within_limit(1, None, 1, "Third", hour_ago, 2, &conn)?
+        );
+        assert!(
+            super::insert_unpublished_within_limit(1, None, 2, "Other", hour_ago, 2, &conn)?
+                .is_some()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn count_by_user_id_created_since() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let hour_ago = OffsetDateTime::now_utc().saturating_sub(Duration::hours(1));
+        let old = super::insert_threaded(1, None, false, Some(1), "Old", &conn)?;
+        super::set_created_at(old.id, hour_ago.saturating_sub(Duration::hours(1)), &conn)?;
+        super::insert_threaded(1, None, false, Some(1), "New", &conn)?;
+        super::insert_threaded(1, None, false, Some(2), "Other", &conn)?;
+        super::insert(1, "Anonymous", &conn)?;
+        assert_eq!(
+            1,
+            super::count_by_user_id_created_since(1, hour_ago, &conn)?
+        );
+        Ok(())
+    }
+
     #[test]
     fn select_updated_since() -> Result<()> {
         let conn = conn();
@@ -418,4 +707,17 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn delete_unpublished() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let live = super::insert(1, "Live", &conn)?;
+        assert_eq!(0, super::delete_unpublished(live.id, &conn)?);
+        let pending = super::insert(1, "Pending", &conn)?;
+        super::set_deleted_at(pending.id, Some(OffsetDateTime::now_utc()), &conn)?;
+        assert_eq!(1, super::delete_unpublished(pending.id, &conn)?);
+        assert!(super::select_by_id(pending.id, &conn).is_err());
+        Ok(())
+    }
 }
@@ -1,4 +1,7 @@
-use super::{blocking_queries, schema::ElementComment};
+use super::{
+    blocking_queries,
+    schema::{ElementComment, HiddenFrom},
+};
 use crate::Result;
 use deadpool_sqlite::Pool;
 use time::OffsetDateTime;
@@ -15,6 +18,51 @@ pub async fn insert(
         .await?
 }
 
+pub async fn insert_threaded(
+    element_id: i64,
+    parent_id: Option<i64>,
+    official: bool,
+    user_id: Option<i64>,
+    comment: impl Into<String>,
+    pool: &Pool,
+) -> Result<ElementComment> {
+    let comment = comment.into();
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::insert_threaded(
+                element_id, parent_id, official, user_id, comment, conn,
+            )
+        })
+        .await?
+}
+
+pub async fn insert_unpublished_within_limit(
+    element_id: i64,
+    parent_id: Option<i64>,
+    user_id: i64,
+    comment: impl Into<String>,
+    posted_since: OffsetDateTime,
+    max_posted: i64,
+    pool: &Pool,
+) -> Result<Option<ElementComment>> {
+    let comment = comment.into();
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::insert_unpublished_within_limit(
+                element_id,
+                parent_id,
+                user_id,
+                &comment,
+                posted_since,
+                max_posted,
+                conn,
+            )
+        })
+        .await?
+}
+
 pub async fn select_updated_since(
     updated_since: OffsetDateTime,
     include_deleted: bool,
@@ -99,3 +147,78 @@ pub async fn set_deleted_at(
         .interact(move |conn| blocking_queries::set_deleted_at(id, deleted_at, conn))
         .await?
 }
+
+pub async fn set_spam_score(id: i64, spam_score: i64, pool: &Pool) -> Result<ElementComment> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_spam_score(id, spam_score, conn))
+        .await?
+}
+
+pub async fn set_invoice_id(id: i64, invoice_id: i64, pool: &Pool) -> Result<ElementComment> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_invoice_id(id, invoice_id, conn))
+        .await?
+}
+
+pub async fn set_hidden_at(
+    id: i64,
+    hidden: Option<(OffsetDateTime, HiddenFrom)>,
+    pool: &Pool,
+) -> Result<ElementComment> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_hidden_at(id, hidden, conn))
+        .await?
+}
+
+pub async fn set_moderated_at(
+    id: i64,
+    moderated_at: OffsetDateTime,
+    pool: &Pool,
+) -> Result<ElementComment> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_moderated_at(id, moderated_at, conn))
+        .await?
+}
+
+pub async fn delete_unpublished(id: i64, pool: &Pool) -> Result<usize> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::delete_unpublished(id, conn))
+        .await?
+}
+
+pub async fn select_moderation_queue(pool: &Pool) -> Result<Vec<ElementComment>> {
+    pool.get()
+        .await?
+        .interact(|conn| blocking_queries::select_moderation_queue(conn))
+        .await?
+}
+
+pub async fn count_by_user_id_created_since(
+    user_id: i64,
+    created_since: OffsetDateTime,
+    pool: &Pool,
+) -> Result<i64> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::count_by_user_id_created_since(user_id, created_since, conn)
+        })
+        .await?
+}
+
+pub async fn set_comment(
+    id: i64,
+    comment: impl Into<String>,
+    pool: &Pool,
+) -> Result<ElementComment> {
+    let comment = comment.into();
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_comment(id, &comment, conn))
+        .await?
+}
@@ -13,6 +13,27 @@ pub enum Columns {
     CreatedAt,
     UpdatedAt,
     DeletedAt,
+    SpamScore,
+    HiddenAt,
+    ModeratedAt,
+    ParentId,
+    Official,
+    UserId,
+    InvoiceId,
+    HiddenFrom,
+}
+
+/// What a hidden comment goes back to once it's restored
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, strum::AsRefStr, strum::EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum HiddenFrom {
+    /// Hidden by a moderator while it was public
+    Public,
+    /// Paid for while hidden or held by the spam filter, so it was never
+    /// published
+    Held,
+    /// Wasn't public at the time, such as unpaid or deleted by its author
+    Unpublished,
 }
 
 #[derive(Debug, Eq, PartialEq, Hash)]
@@ -23,6 +44,23 @@ pub struct ElementComment {
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
     pub deleted_at: Option<OffsetDateTime>,
+    pub spam_score: i64,
+    /// Hidden comments are also marked as deleted, so they disappear from
+    /// public listings, but moderators can restore them
+    pub hidden_at: Option<OffsetDateTime>,
+    /// Set once a moderator has acted on the comment, which removes it from
+    /// the moderation queue
+    pub moderated_at: Option<OffsetDateTime>,
+    /// Set on replies, points to the comment being replied to
+    pub parent_id: Option<i64>,
+    /// Posted by a merchant who proved control of the place
+    pub official: bool,
+    /// Author of a free comment, anonymous paid comments have no user
+    pub user_id: Option<i64>,
+    /// Invoice which publishes a paid comment, the latest one if it was renewed
+    pub invoice_id: Option<i64>,
+    /// Set along with `hidden_at`
+    pub hidden_from: Option<HiddenFrom>,
 }
 
 impl ElementComment {
@@ -36,6 +74,14 @@ impl ElementComment {
                 Columns::CreatedAt,
                 Columns::UpdatedAt,
                 Columns::DeletedAt,
+                Columns::SpamScore,
+                Columns::HiddenAt,
+                Columns::ModeratedAt,
+                Columns::ParentId,
+                Columns::Official,
+                Columns::UserId,
+                Columns::InvoiceId,
+                Columns::HiddenFrom,
             ]
             .iter()
             .map(AsRef::as_ref)
@@ -46,6 +92,14 @@ impl ElementComment {
 
     pub const fn mapper() -> fn(&Row) -> rusqlite::Result<ElementComment> {
         |row| {
+            let hidden_from: Option<String> = row.get(Columns::HiddenFrom.as_ref())?;
+            let hidden_from = hidden_from.map(|it| it.parse()).transpose().map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    13,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })?;
             Ok(ElementComment {
                 id: row.get(Columns::Id.as_ref())?,
                 element_id: row.get(Columns::ElementId.as_ref())?,
@@ -53,6 +107,14 @@ impl ElementComment {
                 created_at: row.get(Columns::CreatedAt.as_ref())?,
                 updated_at: row.get(Columns::UpdatedAt.as_ref())?,
                 deleted_at: row.get(Columns::DeletedAt.as_ref())?,
+                spam_score: row.get(Columns::SpamScore.as_ref())?,
+                hidden_at: row.get(Columns::HiddenAt.as_ref())?,
+                moderated_at: row.get(Columns::ModeratedAt.as_ref())?,
+                parent_id: row.get(Columns::ParentId.as_ref())?,
+                official: row.get(Columns::Official.as_ref())?,
+                user_id: row.get(Columns::UserId.as_ref())?,
+                invoice_id: row.get(Columns::InvoiceId.as_ref())?,
+                hidden_from,
             })
         }
     }
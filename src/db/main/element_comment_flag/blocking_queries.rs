@@ -0,0 +1,115 @@
+use super::schema::{self, Columns, ElementCommentFlag};
+use crate::Result;
+use rusqlite::{params, Connection};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+pub fn insert(
+    element_comment_id: i64,
+    reason: &str,
+    conn: &Connection,
+) -> Result<ElementCommentFlag> {
+    let sql = format!(
+        r#"
+            INSERT INTO {table} (
+                {element_comment_id},
+                {reason}
+            ) VALUES (
+                ?1,
+                ?2
+            )
+            RETURNING {projection}
+        "#,
+        table = schema::TABLE_NAME,
+        element_comment_id = Columns::ElementCommentId.as_ref(),
+        reason = Columns::Reason.as_ref(),
+        projection = ElementCommentFlag::projection(),
+    );
+    conn.query_row(
+        &sql,
+        params![element_comment_id, reason],
+        ElementCommentFlag::mapper(),
+    )
+    .map_err(Into::into)
+}
+
+pub fn select_unresolved_by_element_comment_id(
+    element_comment_id: i64,
+    conn: &Connection,
+) -> Result<Vec<ElementCommentFlag>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {table}
+            WHERE {element_comment_id} = ?1 AND {resolved_at} IS NULL
+            ORDER BY {created_at}, {id}
+        "#,
+        projection = ElementCommentFlag::projection(),
+        table = schema::TABLE_NAME,
+        element_comment_id = Columns::ElementCommentId.as_ref(),
+        resolved_at = Columns::ResolvedAt.as_ref(),
+        created_at = Columns::CreatedAt.as_ref(),
+        id = Columns::Id.as_ref(),
+    );
+    conn.prepare(&sql)?
+        .query_map(params![element_comment_id], ElementCommentFlag::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+/// Marks all open flags of a comment as resolved, returns the number of
+/// affected flags
+pub fn resolve_by_element_comment_id(
+    element_comment_id: i64,
+    resolved_at: OffsetDateTime,
+    conn: &Connection,
+) -> Result<usize> {
+    let sql = format!(
+        r#"
+            UPDATE {table}
+            SET {resolved_at} = ?2, {updated_at} = strftime('%Y-%m-%dT%H:%M:%fZ')
+            WHERE {element_comment_id} = ?1 AND {resolved_at} IS NULL
+        "#,
+        table = schema::TABLE_NAME,
+        resolved_at = Columns::ResolvedAt.as_ref(),
+        updated_at = Columns::UpdatedAt.as_ref(),
+        element_comment_id = Columns::ElementCommentId.as_ref(),
+    );
+    conn.execute(
+        &sql,
+        params![element_comment_id, resolved_at.format(&Rfc3339)?],
+    )
+    .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{db::main::test::conn, Result};
+    use time::OffsetDateTime;
+
+    #[test]
+    fn insert_and_resolve() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let comment = crate::db::main::element_comment::blocking_queries::insert(1, "Spam", &conn)?;
+        let flag = super::insert(comment.id, "spam", &conn)?;
+        assert_eq!("spam", flag.reason);
+        super::insert(comment.id, "offensive", &conn)?;
+        assert_eq!(
+            2,
+            super::select_unresolved_by_element_comment_id(comment.id, &conn)?.len()
+        );
+        let queue =
+            crate::db::main::element_comment::blocking_queries::select_moderation_queue(&conn)?;
+        assert_eq!(1, queue.len());
+        assert_eq!(
+            2,
+            super::resolve_by_element_comment_id(comment.id, OffsetDateTime::now_utc(), &conn)?
+        );
+        assert!(super::select_unresolved_by_element_comment_id(comment.id, &conn)?.is_empty());
+        assert!(
+            crate::db::main::element_comment::blocking_queries::select_moderation_queue(&conn)?
+                .is_empty()
+        );
+        Ok(())
+    }
+}
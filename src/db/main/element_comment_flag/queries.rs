@@ -0,0 +1,41 @@
+use super::{blocking_queries, schema::ElementCommentFlag};
+use crate::Result;
+use deadpool_sqlite::Pool;
+use time::OffsetDateTime;
+
+pub async fn insert(
+    element_comment_id: i64,
+    reason: impl Into<String>,
+    pool: &Pool,
+) -> Result<ElementCommentFlag> {
+    let reason = reason.into();
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::insert(element_comment_id, &reason, conn))
+        .await?
+}
+
+pub async fn select_unresolved_by_element_comment_id(
+    element_comment_id: i64,
+    pool: &Pool,
+) -> Result<Vec<ElementCommentFlag>> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::select_unresolved_by_element_comment_id(element_comment_id, conn)
+        })
+        .await?
+}
+
+pub async fn resolve_by_element_comment_id(
+    element_comment_id: i64,
+    resolved_at: OffsetDateTime,
+    pool: &Pool,
+) -> Result<usize> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::resolve_by_element_comment_id(element_comment_id, resolved_at, conn)
+        })
+        .await?
+}
@@ -0,0 +1,59 @@
+use rusqlite::Row;
+use std::sync::OnceLock;
+use time::OffsetDateTime;
+
+pub const TABLE_NAME: &str = "element_comment_flag";
+
+#[derive(strum::AsRefStr, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Columns {
+    Id,
+    ElementCommentId,
+    Reason,
+    ResolvedAt,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct ElementCommentFlag {
+    pub id: i64,
+    pub element_comment_id: i64,
+    pub reason: String,
+    pub resolved_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+impl ElementCommentFlag {
+    pub fn projection() -> &'static str {
+        static PROJECTION: OnceLock<String> = OnceLock::new();
+        PROJECTION.get_or_init(|| {
+            [
+                Columns::Id,
+                Columns::ElementCommentId,
+                Columns::Reason,
+                Columns::ResolvedAt,
+                Columns::CreatedAt,
+                Columns::UpdatedAt,
+            ]
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+            .join(", ")
+        })
+    }
+
+    pub const fn mapper() -> fn(&Row) -> rusqlite::Result<ElementCommentFlag> {
+        |row| {
+            Ok(ElementCommentFlag {
+                id: row.get(Columns::Id.as_ref())?,
+                element_comment_id: row.get(Columns::ElementCommentId.as_ref())?,
+                reason: row.get(Columns::Reason.as_ref())?,
+                resolved_at: row.get(Columns::ResolvedAt.as_ref())?,
+                created_at: row.get(Columns::CreatedAt.as_ref())?,
+                updated_at: row.get(Columns::UpdatedAt.as_ref())?,
+            })
+        }
+    }
+}
@@ -217,6 +217,44 @@ pub fn select_created_between_for_area(
         .map_err(Into::into)
 }
 
+/// Newest events of a given type for the places mapped to an area. Backed by
+/// the `area_element_area_id` and `element_event_element_id_type` indexes, so
+/// it doesn't have to scan the whole event history.
+pub fn select_by_type_for_area(
+    area_id: i64,
+    r#type: &str,
+    limit: i64,
+    conn: &Connection,
+) -> Result<Vec<ElementEvent>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {table}
+            WHERE {element_id} IN (
+                SELECT element_id FROM {area_element_table}
+                WHERE area_id = :area_id AND deleted_at IS NULL
+            )
+            AND {type} = :type
+            ORDER BY {created_at} DESC, {id} DESC
+            LIMIT :limit
+        "#,
+        projection = ElementEvent::projection(),
+        table = schema::TABLE_NAME,
+        element_id = Columns::ElementId.as_ref(),
+        r#type = Columns::Type.as_ref(),
+        created_at = Columns::CreatedAt.as_ref(),
+        id = Columns::Id.as_ref(),
+        area_element_table = crate::db::main::area_element::schema::TABLE_NAME,
+    );
+    conn.prepare(&sql)?
+        .query_map(
+            named_params! { ":area_id": area_id, ":type": r#type, ":limit": limit },
+            ElementEvent::mapper(),
+        )?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
 pub fn select_by_user(id: i64, limit: i64, conn: &Connection) -> Result<Vec<ElementEvent>> {
     let sql = format!(
         r#"
@@ -395,6 +433,31 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn select_by_type_for_area() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let user = db::main::osm_user::blocking_queries::insert(1, &EditingApiUser::mock(), &conn)?;
+        let inside = db::main::element::blocking_queries::insert(&OverpassElement::mock(1), &conn)?;
+        let outside =
+            db::main::element::blocking_queries::insert(&OverpassElement::mock(2), &conn)?;
+        db::main::area_element::blocking_queries::insert(1, inside.id, &conn)?;
+        db::main::area_element::blocking_queries::insert(2, outside.id, &conn)?;
+        let create = super::insert(user.id, inside.id, "create", &conn)?;
+        super::insert(user.id, inside.id, "update", &conn)?;
+        let delete = super::insert(user.id, inside.id, "delete", &conn)?;
+        super::insert(user.id, outside.id, "create", &conn)?;
+        assert_eq!(
+            vec![create],
+            super::select_by_type_for_area(1, "create", 10, &conn)?
+        );
+        assert_eq!(
+            vec![delete],
+            super::select_by_type_for_area(1, "delete", 10, &conn)?
+        );
+        Ok(())
+    }
+
     #[test]
     fn select_by_id() -> Result<()> {
         let conn = conn();
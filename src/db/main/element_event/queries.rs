@@ -63,6 +63,21 @@ pub async fn select_created_between_for_area(
         .await?
 }
 
+pub async fn select_by_type_for_area(
+    area_id: i64,
+    r#type: impl Into<String>,
+    limit: i64,
+    pool: &Pool,
+) -> Result<Vec<ElementEvent>> {
+    let r#type = r#type.into();
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::select_by_type_for_area(area_id, &r#type, limit, conn)
+        })
+        .await?
+}
+
 pub async fn select_all(
     sort_order: Option<String>,
     limit: Option<i64>,
@@ -50,6 +50,37 @@ pub fn select_by_element_id(element_id: i64, conn: &Connection) -> Result<Vec<El
         .map_err(Into::into)
 }
 
+/// Active issues of the places mapped to an area, newest first
+pub fn select_by_area_id(area_id: i64, limit: i64, conn: &Connection) -> Result<Vec<ElementIssue>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {table}
+            WHERE {element_id} IN (
+                SELECT element_id FROM {area_element_table}
+                WHERE area_id = :area_id AND deleted_at IS NULL
+            )
+            AND {deleted_at} IS NULL
+            ORDER BY {created_at} DESC, {id} DESC
+            LIMIT :limit
+        "#,
+        projection = ElementIssue::projection(),
+        table = schema::TABLE_NAME,
+        element_id = Columns::ElementId.as_ref(),
+        deleted_at = Columns::DeletedAt.as_ref(),
+        created_at = Columns::CreatedAt.as_ref(),
+        id = Columns::Id.as_ref(),
+        area_element_table = db::main::area_element::schema::TABLE_NAME,
+    );
+    conn.prepare(&sql)?
+        .query_map(
+            named_params! { ":area_id": area_id, ":limit": limit },
+            ElementIssue::mapper(),
+        )?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
 pub fn select_ordered_by_severity(
     area_id: i64,
     limit: i64,
@@ -270,6 +301,24 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn select_by_area_id() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let inside =
+            element_queries::insert(&crate::service::overpass::OverpassElement::mock(100), &conn)?;
+        let outside =
+            element_queries::insert(&crate::service::overpass::OverpassElement::mock(200), &conn)?;
+        area_element_queries::insert(1, inside.id, &conn)?;
+        area_element_queries::insert(2, outside.id, &conn)?;
+        let issue = super::insert(inside.id, "outdated", 1, &conn)?;
+        let deleted_issue = super::insert(inside.id, "missing_icon", 1, &conn)?;
+        super::set_deleted_at(deleted_issue.id, Some(OffsetDateTime::now_utc()), &conn)?;
+        super::insert(outside.id, "outdated", 1, &conn)?;
+        assert_eq!(vec![issue], super::select_by_area_id(1, 10, &conn)?);
+        Ok(())
+    }
+
     #[test]
     fn select_count() -> Result<()> {
         let conn = conn();
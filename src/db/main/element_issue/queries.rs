@@ -25,6 +25,13 @@ pub async fn select_by_element_id(element_id: i64, pool: &Pool) -> Result<Vec<El
         .await?
 }
 
+pub async fn select_by_area_id(area_id: i64, limit: i64, pool: &Pool) -> Result<Vec<ElementIssue>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_by_area_id(area_id, limit, conn))
+        .await?
+}
+
 pub async fn select_ordered_by_severity(
     area_id: i64,
     limit: i64,
@@ -0,0 +1,193 @@
+use crate::{
+    db::main::element_review::schema::{self, ElementReview},
+    Result,
+};
+use rusqlite::{named_params, params, Connection};
+use schema::Columns::*;
+use schema::TABLE;
+use std::collections::BTreeMap;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// Inserts a review or replaces the previous one by the same user
+pub fn upsert(
+    element_id: i64,
+    user_id: i64,
+    rating: i64,
+    answers: &BTreeMap<String, bool>,
+    conn: &Connection,
+) -> Result<ElementReview> {
+    let sql = format!(
+        r#"
+            INSERT INTO {TABLE} ({ElementId}, {UserId}, {Rating}, {Answers})
+            VALUES (:element_id, :user_id, :rating, :answers)
+            ON CONFLICT ({ElementId}, {UserId}) DO UPDATE SET
+                {Rating} = excluded.{Rating},
+                {Answers} = excluded.{Answers}
+            RETURNING {projection}
+        "#,
+        projection = ElementReview::projection(),
+    );
+    let params = named_params! {
+        ":element_id": element_id,
+        ":user_id": user_id,
+        ":rating": rating,
+        ":answers": serde_json::to_string(answers)?,
+    };
+    conn.query_row(&sql, params, ElementReview::mapper())
+        .map_err(Into::into)
+}
+
+/// Same as `upsert`, but fails if the user or the place already got the
+/// allowed number of reviews since `since`. Counting and writing happen in one
+/// transaction, so parallel requests can't go over the limits.
+#[allow(clippy::too_many_arguments)]
+pub fn upsert_within_limits(
+    element_id: i64,
+    user_id: i64,
+    rating: i64,
+    answers: &BTreeMap<String, bool>,
+    since: OffsetDateTime,
+    max_per_user: i64,
+    max_per_element: i64,
+    conn: &Connection,
+) -> Result<ElementReview> {
+    let tx = conn.unchecked_transaction()?;
+    if count_by_user_id_updated_since(user_id, since, &tx)? >= max_per_user {
+        return Err(format!("You can post up to {max_per_user} reviews per day").into());
+    }
+    if count_by_element_id_updated_since(element_id, since, &tx)? >= max_per_element {
+        return Err("This place got too many reviews today, try again later".into());
+    }
+    let review = upsert(element_id, user_id, rating, answers, &tx)?;
+    tx.commit()?;
+    Ok(review)
+}
+
+pub fn count_by_user_id_updated_since(
+    user_id: i64,
+    updated_since: OffsetDateTime,
+    conn: &Connection,
+) -> Result<i64> {
+    let sql = format!(
+        r#"
+            SELECT count(*)
+            FROM {TABLE}
+            WHERE {UserId} = ?1 AND {UpdatedAt} > ?2
+        "#,
+    );
+    conn.query_row(
+        &sql,
+        params![user_id, updated_since.format(&Rfc3339)?],
+        |row| row.get(0),
+    )
+    .map_err(Into::into)
+}
+
+pub fn count_by_element_id_updated_since(
+    element_id: i64,
+    updated_since: OffsetDateTime,
+    conn: &Connection,
+) -> Result<i64> {
+    let sql = format!(
+        r#"
+            SELECT count(*)
+            FROM {TABLE}
+            WHERE {ElementId} = ?1 AND {UpdatedAt} > ?2
+        "#,
+    );
+    conn.query_row(
+        &sql,
+        params![element_id, updated_since.format(&Rfc3339)?],
+        |row| row.get(0),
+    )
+    .map_err(Into::into)
+}
+
+pub fn select_by_element_id(element_id: i64, conn: &Connection) -> Result<Vec<ElementReview>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {TABLE}
+            WHERE {ElementId} = ?1
+            ORDER BY {UpdatedAt} DESC, {Id} DESC
+        "#,
+        projection = ElementReview::projection(),
+    );
+    conn.prepare(&sql)?
+        .query_map(params![element_id], ElementReview::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+/// Ids of the places which have at least one review
+pub fn select_element_ids(conn: &Connection) -> Result<Vec<i64>> {
+    let sql = format!(
+        r#"
+            SELECT DISTINCT {ElementId}
+            FROM {TABLE}
+            ORDER BY {ElementId}
+        "#,
+    );
+    conn.prepare(&sql)?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{db::main::test::conn, Result};
+    use std::collections::BTreeMap;
+    use time::{Duration, OffsetDateTime};
+
+    #[test]
+    fn upsert_replaces_previous_review() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let answers = BTreeMap::from([("accepted_lightning".to_string(), true)]);
+        let first = super::upsert(1, 1, 4, &answers, &conn)?;
+        let second = super::upsert(1, 1, 2, &BTreeMap::new(), &conn)?;
+        assert_eq!(first.id, second.id);
+        assert_eq!(2, second.rating);
+        assert!(second.answers.is_empty());
+        super::upsert(1, 2, 5, &answers, &conn)?;
+        super::upsert(2, 2, 5, &answers, &conn)?;
+        assert_eq!(2, super::select_by_element_id(1, &conn)?.len());
+        assert_eq!(vec![1, 2], super::select_element_ids(&conn)?);
+        assert!(super::upsert(1, 3, 6, &answers, &conn).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn upsert_within_limits() -> Result<()> {
+        let conn = conn();
+        conn.pragma_update(None, "foreign_keys", false)?;
+        let since = OffsetDateTime::now_utc() - Duration::days(1);
+        let answers = BTreeMap::new();
+        let upsert = |element_id, user_id| {
+            super::upsert_within_limits(element_id, user_id, 5, &answers, since, 2, 3, &conn)
+        };
+        upsert(1, 1)?;
+        upsert(2, 1)?;
+        // per user
+        assert!(upsert(3, 1).is_err());
+        upsert(1, 2)?;
+        upsert(1, 3)?;
+        // per place
+        assert!(upsert(1, 4).is_err());
+        assert_eq!(2, super::count_by_user_id_updated_since(1, since, &conn)?);
+        assert_eq!(
+            3,
+            super::count_by_element_id_updated_since(1, since, &conn)?
+        );
+        assert_eq!(
+            0,
+            super::count_by_element_id_updated_since(
+                1,
+                OffsetDateTime::now_utc() + Duration::minutes(1),
+                &conn
+            )?
+        );
+        Ok(())
+    }
+}
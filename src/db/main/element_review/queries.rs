@@ -0,0 +1,47 @@
+use super::{blocking_queries, schema::ElementReview};
+use crate::Result;
+use deadpool_sqlite::Pool;
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+
+#[allow(clippy::too_many_arguments)]
+pub async fn upsert_within_limits(
+    element_id: i64,
+    user_id: i64,
+    rating: i64,
+    answers: BTreeMap<String, bool>,
+    since: OffsetDateTime,
+    max_per_user: i64,
+    max_per_element: i64,
+    pool: &Pool,
+) -> Result<ElementReview> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::upsert_within_limits(
+                element_id,
+                user_id,
+                rating,
+                &answers,
+                since,
+                max_per_user,
+                max_per_element,
+                conn,
+            )
+        })
+        .await?
+}
+
+pub async fn select_by_element_id(element_id: i64, pool: &Pool) -> Result<Vec<ElementReview>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_by_element_id(element_id, conn))
+        .await?
+}
+
+pub async fn select_element_ids(pool: &Pool) -> Result<Vec<i64>> {
+    pool.get()
+        .await?
+        .interact(|conn| blocking_queries::select_element_ids(conn))
+        .await?
+}
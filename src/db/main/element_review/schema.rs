@@ -0,0 +1,75 @@
+use rusqlite::Row;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use time::OffsetDateTime;
+
+pub const TABLE: &str = "element_review";
+
+#[derive(strum::AsRefStr, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Columns {
+    Id,
+    ElementId,
+    UserId,
+    Rating,
+    Answers,
+    CreatedAt,
+    UpdatedAt,
+}
+
+/// Each user can have a single review per place, posting again replaces it
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElementReview {
+    pub id: i64,
+    pub element_id: i64,
+    pub user_id: i64,
+    /// 1 to 5 stars
+    pub rating: i64,
+    /// Yes/no answers keyed by question, unanswered questions are omitted
+    pub answers: BTreeMap<String, bool>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+impl ElementReview {
+    pub fn projection() -> &'static str {
+        static PROJECTION: OnceLock<String> = OnceLock::new();
+        PROJECTION.get_or_init(|| {
+            [
+                Columns::Id,
+                Columns::ElementId,
+                Columns::UserId,
+                Columns::Rating,
+                Columns::Answers,
+                Columns::CreatedAt,
+                Columns::UpdatedAt,
+            ]
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+            .join(", ")
+        })
+    }
+
+    pub const fn mapper() -> fn(&Row) -> rusqlite::Result<ElementReview> {
+        |row| {
+            let answers: String = row.get(Columns::Answers.as_ref())?;
+            let answers = serde_json::from_str(&answers).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    4,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })?;
+            Ok(ElementReview {
+                id: row.get(Columns::Id.as_ref())?,
+                element_id: row.get(Columns::ElementId.as_ref())?,
+                user_id: row.get(Columns::UserId.as_ref())?,
+                rating: row.get(Columns::Rating.as_ref())?,
+                answers,
+                created_at: row.get(Columns::CreatedAt.as_ref())?,
+                updated_at: row.get(Columns::UpdatedAt.as_ref())?,
+            })
+        }
+    }
+}
@@ -5,7 +5,7 @@ use crate::{
 use rusqlite::{named_params, params, Connection, ToSql};
 use schema::Columns::*;
 use schema::TABLE;
-use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use time::{format_description::well_known::Rfc3339, Date, OffsetDateTime};
 
 #[allow(clippy::too_many_arguments)]
 pub fn insert(
@@ -55,6 +55,86 @@ pub fn select_all(conn: &Connection) -> Result<Vec<Event>> {
         .map_err(Into::into)
 }
 
+/// Narrows down the events a listing can return, so only those have to be
+/// expanded. Deleted events are only included when updated_since is set.
+/// upcoming_at keeps events which may still happen at that time, past_at
+/// keeps events which may have happened before it, recurring events pass
+/// both unless their series has ended. bbox is west, south, east, north and
+/// area_id keeps events linked to the area or inside its bounding box.
+pub fn select_listed(
+    updated_since: Option<OffsetDateTime>,
+    upcoming_at: Option<OffsetDateTime>,
+    past_at: Option<OffsetDateTime>,
+    bbox: Option<[f64; 4]>,
+    area_id: Option<i64>,
+    conn: &Connection,
+) -> Result<Vec<Event>> {
+    let mut filters: Vec<String> = Vec::new();
+    let mut sql_params: Vec<(&str, &dyn ToSql)> = Vec::new();
+
+    match &updated_since {
+        Some(v) => {
+            filters.push(format!(
+                "julianday({UpdatedAt}) > julianday(:updated_since)"
+            ));
+            sql_params.push((":updated_since", v));
+        }
+        None => filters.push(format!("{DeletedAt} IS NULL")),
+    }
+    if let Some(v) = &upcoming_at {
+        filters.push(format!(
+            r#"(
+                ({CronSchedule} IS NOT NULL AND ({EndsAt} IS NULL OR julianday({EndsAt}) > julianday(:upcoming_at)))
+                OR ({CronSchedule} IS NULL AND ({StartsAt} IS NULL OR julianday(coalesce({EndsAt}, {StartsAt})) > julianday(:upcoming_at)))
+            )"#
+        ));
+        sql_params.push((":upcoming_at", v));
+    }
+    if let Some(v) = &past_at {
+        filters.push(format!(
+            "({CronSchedule} IS NOT NULL OR julianday(coalesce({EndsAt}, {StartsAt})) <= julianday(:past_at))"
+        ));
+        sql_params.push((":past_at", v));
+    }
+    if let Some([west, south, east, north]) = &bbox {
+        filters.push(format!(
+            "{Lat} BETWEEN :south AND :north AND {Lon} BETWEEN :west AND :east"
+        ));
+        sql_params.push((":west", west));
+        sql_params.push((":south", south));
+        sql_params.push((":east", east));
+        sql_params.push((":north", north));
+    }
+    if let Some(v) = &area_id {
+        filters.push(format!(
+            r#"(
+                {AreaId} = :area_id
+                OR EXISTS (
+                    SELECT 1 FROM area a
+                    WHERE a.id = :area_id
+                    AND {Lat} BETWEEN a.bbox_south AND a.bbox_north
+                    AND {Lon} BETWEEN a.bbox_west AND a.bbox_east
+                )
+            )"#
+        ));
+        sql_params.push((":area_id", v));
+    }
+
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {TABLE}
+            WHERE {}
+        "#,
+        filters.join(" AND "),
+        projection = Event::projection(),
+    );
+    conn.prepare(&sql)?
+        .query_map(sql_params.as_slice(), Event::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn update(
     id: i64,
@@ -122,6 +202,22 @@ pub fn update(
         .map_err(Into::into)
 }
 
+pub fn select_by_area_id(area_id: i64, conn: &Connection) -> Result<Vec<Event>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {TABLE}
+            WHERE {AreaId} = ?1 AND {DeletedAt} IS NULL
+            ORDER BY {StartsAt}, {Id}
+        "#,
+        projection = Event::projection(),
+    );
+    conn.prepare(&sql)?
+        .query_map(params![area_id], Event::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
 pub fn select_by_id(id: i64, conn: &Connection) -> Result<Event> {
     let sql = format!(
         r#"
@@ -135,6 +231,42 @@ pub fn select_by_id(id: i64, conn: &Connection) -> Result<Event> {
         .map_err(Into::into)
 }
 
+pub fn set_excluded_dates(id: i64, excluded_dates: &[Date], conn: &Connection) -> Result<Event> {
+    let excluded_dates: Vec<String> = excluded_dates
+        .iter()
+        .map(|it| it.format(schema::DATE_FORMAT))
+        .collect::<Result<_, _>>()?;
+    let sql = format!(
+        r#"
+            UPDATE {TABLE}
+            SET {ExcludedDates} = ?2
+            WHERE {Id} = ?1
+            RETURNING {projection}
+        "#,
+        projection = Event::projection(),
+    );
+    conn.query_row(
+        &sql,
+        params![id, serde_json::to_string(&excluded_dates)?],
+        Event::mapper(),
+    )
+    .map_err(Into::into)
+}
+
+pub fn set_timezone(id: i64, timezone: &str, conn: &Connection) -> Result<Event> {
+    let sql = format!(
+        r#"
+            UPDATE {TABLE}
+            SET {Timezone} = ?2
+            WHERE {Id} = ?1
+            RETURNING {projection}
+        "#,
+        projection = Event::projection(),
+    );
+    conn.query_row(&sql, params![id, timezone], Event::mapper())
+        .map_err(Into::into)
+}
+
 pub fn set_deleted_at(
     id: i64,
     deleted_at: Option<OffsetDateTime>,
@@ -177,10 +309,15 @@ pub fn set_deleted_at(
 #[cfg(test)]
 mod test {
     use crate::{
-        db::main::{area::schema::Area, test::conn},
+        db::main::{
+            area::{self, schema::Area},
+            event::schema::Event,
+            test::conn,
+        },
         Result,
     };
-    use time::OffsetDateTime;
+    use time::macros::{date, datetime};
+    use time::{Duration, OffsetDateTime};
 
     #[test]
     fn insert() -> Result<()> {
@@ -200,6 +337,69 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn set_excluded_dates() -> Result<()> {
+        let conn = conn();
+        let event = super::insert(
+            None,
+            1.23,
+            4.56,
+            "name",
+            "website",
+            None,
+            None,
+            Some("0 0 19 * * Thu"),
+            &conn,
+        )?;
+        assert!(event.excluded_dates.is_empty());
+        let dates = vec![date!(2025 - 01 - 02), date!(2025 - 01 - 09)];
+        let event = super::set_excluded_dates(event.id, &dates, &conn)?;
+        assert_eq!(dates, event.excluded_dates);
+        assert_eq!(event, super::select_by_id(event.id, &conn)?);
+        Ok(())
+    }
+
+    #[test]
+    fn select_by_area_id() -> Result<()> {
+        let conn = conn();
+        let event = super::insert(
+            Some(1),
+            1.23,
+            4.56,
+            "name",
+            "website",
+            None,
+            None,
+            None,
+            &conn,
+        )?;
+        super::insert(
+            Some(2),
+            1.23,
+            4.56,
+            "name",
+            "website",
+            None,
+            None,
+            None,
+            &conn,
+        )?;
+        let deleted = super::insert(
+            Some(1),
+            1.23,
+            4.56,
+            "name",
+            "website",
+            None,
+            None,
+            None,
+            &conn,
+        )?;
+        super::set_deleted_at(deleted.id, Some(OffsetDateTime::now_utc()), &conn)?;
+        assert_eq!(vec![event], super::select_by_area_id(1, &conn)?);
+        Ok(())
+    }
+
     #[test]
     fn update() -> Result<()> {
         let conn = conn();
@@ -415,4 +615,125 @@ mod test {
         );
         Ok(())
     }
+
+    #[test]
+    fn select_listed() -> Result<()> {
+        let conn = conn();
+        let insert = |name: &str, lat, lon, starts_at, ends_at, cron_schedule| {
+            super::insert(
+                None,
+                lat,
+                lon,
+                name,
+                "website",
+                starts_at,
+                ends_at,
+                cron_schedule,
+                &conn,
+            )
+        };
+        insert(
+            "past",
+            1.0,
+            1.0,
+            Some(datetime!(2020-01-01 0:00 UTC)),
+            None,
+            None,
+        )?;
+        insert(
+            "future",
+            1.0,
+            1.0,
+            Some(datetime!(2099-01-01 0:00 UTC)),
+            None,
+            None,
+        )?;
+        insert("tba", 1.0, 1.0, None, None, None)?;
+        insert(
+            "ended_series",
+            1.0,
+            1.0,
+            None,
+            Some(datetime!(2020-01-01 0:00 UTC)),
+            Some("0 0 19 * * Thu"),
+        )?;
+        insert("weekly", 50.0, 50.0, None, None, Some("0 0 19 * * Thu"))?;
+        let deleted = insert("deleted", 1.0, 1.0, None, None, None)?;
+        super::set_deleted_at(deleted.id, Some(OffsetDateTime::now_utc()), &conn)?;
+
+        let names = |events: Vec<Event>| -> Vec<String> {
+            let mut names: Vec<String> = events.into_iter().map(|it| it.name).collect();
+            names.sort();
+            names
+        };
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(
+            vec!["future", "tba", "weekly"],
+            names(super::select_listed(
+                None,
+                Some(now),
+                None,
+                None,
+                None,
+                &conn
+            )?)
+        );
+        assert_eq!(
+            vec!["ended_series", "past", "weekly"],
+            names(super::select_listed(
+                None,
+                None,
+                Some(now),
+                None,
+                None,
+                &conn
+            )?)
+        );
+        assert_eq!(
+            vec!["weekly"],
+            names(super::select_listed(
+                None,
+                None,
+                None,
+                Some([49.0, 49.0, 51.0, 51.0]),
+                None,
+                &conn
+            )?)
+        );
+        assert_eq!(
+            vec!["deleted"],
+            names(
+                super::select_listed(Some(now - Duration::MINUTE), None, None, None, None, &conn)?
+                    .into_iter()
+                    .filter(|it| it.deleted_at.is_some())
+                    .collect()
+            )
+        );
+
+        let area = area::blocking_queries::insert(Area::mock_tags(), &conn)?;
+        area::blocking_queries::set_bbox(area.id, 0.0, 0.0, 2.0, 2.0, &conn)?;
+        super::insert(
+            Some(area.id),
+            -40.0,
+            -40.0,
+            "linked",
+            "website",
+            None,
+            None,
+            None,
+            &conn,
+        )?;
+        assert_eq!(
+            vec!["ended_series", "future", "linked", "past", "tba"],
+            names(super::select_listed(
+                None,
+                None,
+                None,
+                None,
+                Some(area.id),
+                &conn
+            )?)
+        );
+        Ok(())
+    }
 }
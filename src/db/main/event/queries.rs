@@ -3,7 +3,7 @@ use crate::{
     Result,
 };
 use deadpool_sqlite::Pool;
-use time::OffsetDateTime;
+use time::{Date, OffsetDateTime};
 
 #[allow(clippy::too_many_arguments)]
 pub async fn insert(
@@ -42,6 +42,36 @@ pub async fn select_all(pool: &Pool) -> Result<Vec<Event>> {
         .await?
 }
 
+pub async fn select_listed(
+    updated_since: Option<OffsetDateTime>,
+    upcoming_at: Option<OffsetDateTime>,
+    past_at: Option<OffsetDateTime>,
+    bbox: Option<[f64; 4]>,
+    area_id: Option<i64>,
+    pool: &Pool,
+) -> Result<Vec<Event>> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::select_listed(
+                updated_since,
+                upcoming_at,
+                past_at,
+                bbox,
+                area_id,
+                conn,
+            )
+        })
+        .await?
+}
+
+pub async fn select_by_area_id(area_id: i64, pool: &Pool) -> Result<Vec<Event>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_by_area_id(area_id, conn))
+        .await?
+}
+
 pub async fn select_by_id(id: i64, pool: &Pool) -> Result<Event> {
     pool.get()
         .await?
@@ -81,6 +111,20 @@ pub async fn update(
         .await?
 }
 
+pub async fn set_excluded_dates(id: i64, excluded_dates: Vec<Date>, pool: &Pool) -> Result<Event> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_excluded_dates(id, &excluded_dates, conn))
+        .await?
+}
+
+pub async fn set_timezone(id: i64, timezone: String, pool: &Pool) -> Result<Event> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_timezone(id, &timezone, conn))
+        .await?
+}
+
 pub async fn set_deleted_at(
     id: i64,
     deleted_at: Option<OffsetDateTime>,
@@ -1,9 +1,13 @@
 use rusqlite::Row;
 use std::sync::OnceLock;
-use time::OffsetDateTime;
+use time::format_description::BorrowedFormatItem;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime};
 
 pub const TABLE: &str = "event";
 
+pub const DATE_FORMAT: &[BorrowedFormatItem<'_>] = format_description!("[year]-[month]-[day]");
+
 #[derive(strum::AsRefStr, strum::Display)]
 #[strum(serialize_all = "snake_case")]
 pub enum Columns {
@@ -16,6 +20,8 @@ pub enum Columns {
     StartsAt,
     EndsAt,
     CronSchedule,
+    ExcludedDates,
+    Timezone,
     CreatedAt,
     UpdatedAt,
     DeletedAt,
@@ -33,6 +39,10 @@ pub struct Event {
     pub starts_at: Option<OffsetDateTime>,
     pub ends_at: Option<OffsetDateTime>,
     pub cron_schedule: Option<String>,
+    /// Local dates on which a recurring event doesn't take place
+    pub excluded_dates: Vec<Date>,
+    /// IANA timezone of the event location, looked up when the event is saved
+    pub timezone: Option<String>,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
     pub deleted_at: Option<OffsetDateTime>,
@@ -52,6 +62,8 @@ impl Event {
                 Columns::StartsAt,
                 Columns::EndsAt,
                 Columns::CronSchedule,
+                Columns::ExcludedDates,
+                Columns::Timezone,
                 Columns::CreatedAt,
                 Columns::UpdatedAt,
                 Columns::DeletedAt,
@@ -75,6 +87,15 @@ impl Event {
                 starts_at: row.get(Columns::StartsAt.as_ref())?,
                 ends_at: row.get(Columns::EndsAt.as_ref())?,
                 cron_schedule: row.get(Columns::CronSchedule.as_ref())?,
+                excluded_dates: {
+                    let json: String = row.get(Columns::ExcludedDates.as_ref())?;
+                    let dates: Vec<String> = serde_json::from_str(&json).unwrap_or_default();
+                    dates
+                        .iter()
+                        .filter_map(|it| Date::parse(it, DATE_FORMAT).ok())
+                        .collect()
+                },
+                timezone: row.get(Columns::Timezone.as_ref())?,
                 created_at: row.get(Columns::CreatedAt.as_ref())?,
                 updated_at: row.get(Columns::UpdatedAt.as_ref())?,
                 deleted_at: row.get(Columns::DeletedAt.as_ref())?,
@@ -0,0 +1,319 @@
+use crate::{
+    db::main::event::{blocking_queries as event_queries, schema::Event},
+    db::main::event_submission::schema::{self, EventSubmission, EventSubmissionStatus},
+    service, Result,
+};
+use rusqlite::{named_params, params, Connection, OptionalExtension, ToSql};
+use schema::Columns::*;
+use schema::TABLE;
+use time::OffsetDateTime;
+
+#[allow(clippy::too_many_arguments)]
+pub fn insert(
+    user_id: i64,
+    area_id: Option<i64>,
+    lat: f64,
+    lon: f64,
+    name: &str,
+    website: &str,
+    starts_at: Option<OffsetDateTime>,
+    ends_at: Option<OffsetDateTime>,
+    cron_schedule: Option<&str>,
+    conn: &Connection,
+) -> Result<EventSubmission> {
+    let sql = format!(
+        r#"
+            INSERT INTO {TABLE} ({UserId}, {AreaId}, {Lat}, {Lon}, {Name}, {Website}, {StartsAt}, {EndsAt}, {CronSchedule})
+            VALUES (:user_id, :area_id, :lat, :lon, :name, :website, :starts_at, :ends_at, :cron_schedule)
+            RETURNING {projection}
+        "#,
+        projection = EventSubmission::projection(),
+    );
+    let params = named_params! {
+        ":user_id": user_id,
+        ":area_id": area_id,
+        ":lat": lat,
+        ":lon": lon,
+        ":name": name,
+        ":website": website,
+        ":starts_at": starts_at,
+        ":ends_at": ends_at,
+        ":cron_schedule": cron_schedule,
+    };
+    conn.query_row(&sql, params, EventSubmission::mapper())
+        .map_err(Into::into)
+}
+
+pub fn select_by_id(id: i64, conn: &Connection) -> Result<EventSubmission> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {TABLE}
+            WHERE {Id} = ?1
+        "#,
+        projection = EventSubmission::projection(),
+    );
+    conn.query_row(&sql, params![id], EventSubmission::mapper())
+        .map_err(Into::into)
+}
+
+pub fn select_by_status(
+    status: EventSubmissionStatus,
+    conn: &Connection,
+) -> Result<Vec<EventSubmission>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {TABLE}
+            WHERE {Status} = ?1
+            ORDER BY {CreatedAt}, {Id}
+        "#,
+        projection = EventSubmission::projection(),
+    );
+    conn.prepare(&sql)?
+        .query_map(params![status], EventSubmission::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+pub fn select_by_user_id(user_id: i64, conn: &Connection) -> Result<Vec<EventSubmission>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {TABLE}
+            WHERE {UserId} = ?1
+            ORDER BY {CreatedAt} DESC, {Id} DESC
+        "#,
+        projection = EventSubmission::projection(),
+    );
+    conn.prepare(&sql)?
+        .query_map(params![user_id], EventSubmission::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update(
+    id: i64,
+    area_id: Option<Option<i64>>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    name: Option<&str>,
+    website: Option<&str>,
+    starts_at: Option<Option<OffsetDateTime>>,
+    ends_at: Option<Option<OffsetDateTime>>,
+    cron_schedule: Option<Option<&str>>,
+    conn: &Connection,
+) -> Result<EventSubmission> {
+    let mut sets: Vec<String> = Vec::new();
+    let mut sql_params: Vec<(&str, &dyn ToSql)> = vec![(":id", &id)];
+
+    if let Some(v) = &area_id {
+        sets.push(format!("{AreaId} = :area_id"));
+        sql_params.push((":area_id", v));
+    }
+    if let Some(v) = &lat {
+        sets.push(format!("{Lat} = :lat"));
+        sql_params.push((":lat", v));
+    }
+    if let Some(v) = &lon {
+        sets.push(format!("{Lon} = :lon"));
+        sql_params.push((":lon", v));
+    }
+    if let Some(v) = &name {
+        sets.push(format!("{Name} = :name"));
+        sql_params.push((":name", v));
+    }
+    if let Some(v) = &website {
+        sets.push(format!("{Website} = :website"));
+        sql_params.push((":website", v));
+    }
+    if let Some(v) = &starts_at {
+        sets.push(format!("{StartsAt} = :starts_at"));
+        sql_params.push((":starts_at", v));
+    }
+    if let Some(v) = &ends_at {
+        sets.push(format!("{EndsAt} = :ends_at"));
+        sql_params.push((":ends_at", v));
+    }
+    if let Some(v) = &cron_schedule {
+        sets.push(format!("{CronSchedule} = :cron_schedule"));
+        sql_params.push((":cron_schedule", v));
+    }
+
+    if sets.is_empty() {
+        return select_by_id(id, conn);
+    }
+
+    let sql = format!(
+        r#"
+            UPDATE {TABLE}
+            SET {}
+            WHERE {Id} = :id
+            RETURNING {projection}
+        "#,
+        sets.join(", "),
+        projection = EventSubmission::projection(),
+    );
+    conn.query_row(&sql, sql_params.as_slice(), EventSubmission::mapper())
+        .map_err(Into::into)
+}
+
+/// Only pending submissions can be reviewed, so the second of two concurrent
+/// reviews fails instead of overwriting the first one
+pub fn set_review(
+    id: i64,
+    status: EventSubmissionStatus,
+    reviewed_by: i64,
+    review_note: Option<&str>,
+    event_id: Option<i64>,
+    conn: &Connection,
+) -> Result<EventSubmission> {
+    let sql = format!(
+        r#"
+            UPDATE {TABLE}
+            SET {Status} = :status,
+                {ReviewedBy} = :reviewed_by,
+                {ReviewNote} = :review_note,
+                {EventId} = :event_id,
+                {ReviewedAt} = strftime('%Y-%m-%dT%H:%M:%fZ')
+            WHERE {Id} = :id AND {Status} = :pending
+            RETURNING {projection}
+        "#,
+        projection = EventSubmission::projection(),
+    );
+    let params = named_params! {
+        ":id": id,
+        ":status": status,
+        ":reviewed_by": reviewed_by,
+        ":review_note": review_note,
+        ":event_id": event_id,
+        ":pending": EventSubmissionStatus::Pending,
+    };
+    conn.query_row(&sql, params, EventSubmission::mapper())
+        .optional()?
+        .ok_or_else(|| format!("Event submission {id} has already been reviewed").into())
+}
+
+/// Publishes the submission as an event, along with the timezone of its
+/// location. The event is only kept if the submission was still pending.
+pub fn approve(
+    id: i64,
+    reviewed_by: i64,
+    review_note: Option<&str>,
+    conn: &Connection,
+) -> Result<(EventSubmission, Event)> {
+    let tx = conn.unchecked_transaction()?;
+    let submission = select_by_id(id, &tx)?;
+    let event = event_queries::insert(
+        submission.area_id,
+        submission.lat,
+        submission.lon,
+        &submission.name,
+        &submission.website,
+        submission.starts_at,
+        submission.ends_at,
+        submission.cron_schedule.as_deref(),
+        &tx,
+    )?;
+    let timezone = service::event_schedule::lookup_timezone(event.lat, event.lon);
+    let event = event_queries::set_timezone(event.id, timezone.name(), &tx)?;
+    let submission = set_review(
+        id,
+        EventSubmissionStatus::Approved,
+        reviewed_by,
+        review_note,
+        Some(event.id),
+        &tx,
+    )?;
+    tx.commit()?;
+    Ok((submission, event))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        db::main::{
+            event_submission::schema::EventSubmissionStatus, test::conn,
+            user::blocking_queries as user_queries,
+        },
+        Result,
+    };
+
+    fn insert(user_id: i64, conn: &rusqlite::Connection) -> Result<super::EventSubmission> {
+        super::insert(
+            user_id,
+            None,
+            1.23,
+            4.56,
+            "name",
+            "https://example.com",
+            None,
+            None,
+            Some("0 0 19 * * Thu"),
+            conn,
+        )
+    }
+
+    #[test]
+    fn insert_and_select() -> Result<()> {
+        let conn = conn();
+        let user = user_queries::insert("user", "", &conn)?;
+        let submission = insert(user.id, &conn)?;
+        assert_eq!(EventSubmissionStatus::Pending, submission.status);
+        assert_eq!(submission, super::select_by_id(submission.id, &conn)?);
+        assert_eq!(
+            vec![submission.clone()],
+            super::select_by_user_id(user.id, &conn)?
+        );
+        assert_eq!(
+            vec![submission],
+            super::select_by_status(EventSubmissionStatus::Pending, &conn)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn update() -> Result<()> {
+        let conn = conn();
+        let user = user_queries::insert("user", "", &conn)?;
+        let submission = insert(user.id, &conn)?;
+        let updated = super::update(
+            submission.id,
+            None,
+            None,
+            None,
+            Some("renamed"),
+            None,
+            None,
+            None,
+            Some(None),
+            &conn,
+        )?;
+        assert_eq!("renamed", updated.name);
+        assert_eq!(None, updated.cron_schedule);
+        assert_eq!(submission.lat, updated.lat);
+        Ok(())
+    }
+
+    #[test]
+    fn set_review() -> Result<()> {
+        let conn = conn();
+        let user = user_queries::insert("user", "", &conn)?;
+        let submission = insert(user.id, &conn)?;
+        let reviewed = super::set_review(
+            submission.id,
+            EventSubmissionStatus::Rejected,
+            user.id,
+            Some("duplicate"),
+            None,
+            &conn,
+        )?;
+        assert_eq!(EventSubmissionStatus::Rejected, reviewed.status);
+        assert_eq!(Some("duplicate".into()), reviewed.review_note);
+        assert_eq!(Some(user.id), reviewed.reviewed_by);
+        assert!(reviewed.reviewed_at.is_some());
+        assert!(super::select_by_status(EventSubmissionStatus::Pending, &conn)?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{
+    db::main::event::schema::Event,
+    db::main::event_submission::{
+        blocking_queries,
+        schema::{EventSubmission, EventSubmissionStatus},
+    },
+    Result,
+};
+use deadpool_sqlite::Pool;
+use time::OffsetDateTime;
+
+#[allow(clippy::too_many_arguments)]
+pub async fn insert(
+    user_id: i64,
+    area_id: Option<i64>,
+    lat: f64,
+    lon: f64,
+    name: String,
+    website: String,
+    starts_at: Option<OffsetDateTime>,
+    ends_at: Option<OffsetDateTime>,
+    cron_schedule: Option<String>,
+    pool: &Pool,
+) -> Result<EventSubmission> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::insert(
+                user_id,
+                area_id,
+                lat,
+                lon,
+                &name,
+                &website,
+                starts_at,
+                ends_at,
+                cron_schedule.as_deref(),
+                conn,
+            )
+        })
+        .await?
+}
+
+pub async fn select_by_id(id: i64, pool: &Pool) -> Result<EventSubmission> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_by_id(id, conn))
+        .await?
+}
+
+pub async fn select_by_status(
+    status: EventSubmissionStatus,
+    pool: &Pool,
+) -> Result<Vec<EventSubmission>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_by_status(status, conn))
+        .await?
+}
+
+pub async fn select_by_user_id(user_id: i64, pool: &Pool) -> Result<Vec<EventSubmission>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_by_user_id(user_id, conn))
+        .await?
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn update(
+    id: i64,
+    area_id: Option<Option<i64>>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    name: Option<String>,
+    website: Option<String>,
+    starts_at: Option<Option<OffsetDateTime>>,
+    ends_at: Option<Option<OffsetDateTime>>,
+    cron_schedule: Option<Option<String>>,
+    pool: &Pool,
+) -> Result<EventSubmission> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::update(
+                id,
+                area_id,
+                lat,
+                lon,
+                name.as_deref(),
+                website.as_deref(),
+                starts_at,
+                ends_at,
+                cron_schedule.as_ref().map(|it| it.as_deref()),
+                conn,
+            )
+        })
+        .await?
+}
+
+pub async fn approve(
+    id: i64,
+    reviewed_by: i64,
+    review_note: Option<String>,
+    pool: &Pool,
+) -> Result<(EventSubmission, Event)> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::approve(id, reviewed_by, review_note.as_deref(), conn)
+        })
+        .await?
+}
+
+pub async fn set_review(
+    id: i64,
+    status: EventSubmissionStatus,
+    reviewed_by: i64,
+    review_note: Option<String>,
+    event_id: Option<i64>,
+    pool: &Pool,
+) -> Result<EventSubmission> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::set_review(
+                id,
+                status,
+                reviewed_by,
+                review_note.as_deref(),
+                event_id,
+                conn,
+            )
+        })
+        .await?
+}
@@ -0,0 +1,154 @@
+use rusqlite::{
+    types::{FromSql, FromSqlError, ToSqlOutput, ValueRef},
+    Row, ToSql,
+};
+use std::sync::OnceLock;
+use time::OffsetDateTime;
+
+pub const TABLE: &str = "event_submission";
+
+#[derive(strum::AsRefStr, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Columns {
+    Id,
+    UserId,
+    AreaId,
+    Lat,
+    Lon,
+    Name,
+    Website,
+    StartsAt,
+    EndsAt,
+    CronSchedule,
+    Status,
+    ReviewNote,
+    ReviewedBy,
+    ReviewedAt,
+    EventId,
+    CreatedAt,
+    UpdatedAt,
+}
+
+/// Event proposed by a regular user. It stays invisible until a moderator
+/// approves it, which creates the actual event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventSubmission {
+    pub id: i64,
+    pub user_id: i64,
+    pub area_id: Option<i64>,
+    pub lat: f64,
+    pub lon: f64,
+    pub name: String,
+    pub website: String,
+    pub starts_at: Option<OffsetDateTime>,
+    pub ends_at: Option<OffsetDateTime>,
+    pub cron_schedule: Option<String>,
+    pub status: EventSubmissionStatus,
+    /// Moderator comment, shown to the submitter
+    pub review_note: Option<String>,
+    pub reviewed_by: Option<i64>,
+    pub reviewed_at: Option<OffsetDateTime>,
+    /// Event created on approval
+    pub event_id: Option<i64>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+impl EventSubmission {
+    pub fn projection() -> &'static str {
+        static PROJECTION: OnceLock<String> = OnceLock::new();
+        PROJECTION.get_or_init(|| {
+            [
+                Columns::Id,
+                Columns::UserId,
+                Columns::AreaId,
+                Columns::Lat,
+                Columns::Lon,
+                Columns::Name,
+                Columns::Website,
+                Columns::StartsAt,
+                Columns::EndsAt,
+                Columns::CronSchedule,
+                Columns::Status,
+                Columns::ReviewNote,
+                Columns::ReviewedBy,
+                Columns::ReviewedAt,
+                Columns::EventId,
+                Columns::CreatedAt,
+                Columns::UpdatedAt,
+            ]
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+            .join(", ")
+        })
+    }
+
+    pub const fn mapper() -> fn(&Row) -> rusqlite::Result<Self> {
+        |row: &Row| -> rusqlite::Result<Self> {
+            Ok(EventSubmission {
+                id: row.get(Columns::Id.as_ref())?,
+                user_id: row.get(Columns::UserId.as_ref())?,
+                area_id: row.get(Columns::AreaId.as_ref())?,
+                lat: row.get(Columns::Lat.as_ref())?,
+                lon: row.get(Columns::Lon.as_ref())?,
+                name: row.get(Columns::Name.as_ref())?,
+                website: row.get(Columns::Website.as_ref())?,
+                starts_at: row.get(Columns::StartsAt.as_ref())?,
+                ends_at: row.get(Columns::EndsAt.as_ref())?,
+                cron_schedule: row.get(Columns::CronSchedule.as_ref())?,
+                status: row.get(Columns::Status.as_ref())?,
+                review_note: row.get(Columns::ReviewNote.as_ref())?,
+                reviewed_by: row.get(Columns::ReviewedBy.as_ref())?,
+                reviewed_at: row.get(Columns::ReviewedAt.as_ref())?,
+                event_id: row.get(Columns::EventId.as_ref())?,
+                created_at: row.get(Columns::CreatedAt.as_ref())?,
+                updated_at: row.get(Columns::UpdatedAt.as_ref())?,
+            })
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EventSubmissionStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl From<EventSubmissionStatus> for String {
+    fn from(status: EventSubmissionStatus) -> String {
+        match status {
+            EventSubmissionStatus::Pending => "pending".to_string(),
+            EventSubmissionStatus::Approved => "approved".to_string(),
+            EventSubmissionStatus::Rejected => "rejected".to_string(),
+        }
+    }
+}
+
+impl TryFrom<&str> for EventSubmissionStatus {
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "pending" => Ok(EventSubmissionStatus::Pending),
+            "approved" => Ok(EventSubmissionStatus::Approved),
+            "rejected" => Ok(EventSubmissionStatus::Rejected),
+            _ => Err(format!("Unknown event submission status: {}", value).into()),
+        }
+    }
+}
+
+impl FromSql for EventSubmissionStatus {
+    fn column_result(value: ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value
+            .as_str()
+            .and_then(|s| EventSubmissionStatus::try_from(s).map_err(FromSqlError::Other))
+    }
+}
+
+impl ToSql for EventSubmissionStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(String::from(*self)))
+    }
+}
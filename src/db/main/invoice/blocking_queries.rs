@@ -3,6 +3,7 @@ use crate::Result;
 use rusqlite::{named_params, params, Connection};
 use uuid::Uuid;
 
+#[allow(clippy::too_many_arguments)]
 pub fn insert(
     source: impl Into<String>,
     description: impl Into<String>,
@@ -10,6 +11,7 @@ pub fn insert(
     payment_hash: impl Into<String>,
     payment_request: impl Into<String>,
     status: InvoiceStatus,
+    expires_at: Option<String>,
     conn: &Connection,
 ) -> Result<Invoice> {
     let sql = format!(
@@ -21,7 +23,8 @@ pub fn insert(
                 {amount_sats},
                 {payment_hash},
                 {payment_request},
-                {status}
+                {status},
+                {expires_at}
             ) VALUES (
                 :uuid,
                 :source,
@@ -29,7 +32,8 @@ pub fn insert(
                 :amount_sats,
                 :payment_hash,
                 :payment_request,
-                :status
+                :status,
+                :expires_at
             )
             RETURNING {projection}
         "#,
@@ -41,6 +45,7 @@ pub fn insert(
         payment_hash = Columns::PaymentHash.as_ref(),
         payment_request = Columns::PaymentRequest.as_ref(),
         status = Columns::Status.as_ref(),
+        expires_at = Columns::ExpiresAt.as_ref(),
         projection = Invoice::projection(),
     );
     let params = named_params! {
@@ -51,6 +56,7 @@ pub fn insert(
         ":payment_hash": payment_hash.into(),
         ":payment_request": payment_request.into(),
         ":status": status,
+        ":expires_at": expires_at,
     };
     conn.query_row(&sql, params, Invoice::mapper())
         .map_err(Into::into)
@@ -76,6 +82,28 @@ pub fn select_by_status(status: InvoiceStatus, conn: &Connection) -> Result<Vec<
         .map_err(Into::into)
 }
 
+/// Paid invoices which weren't fulfilled and were paid before `paid_before`
+pub fn select_unfulfilled(paid_before: &str, conn: &Connection) -> Result<Vec<Invoice>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {table}
+            WHERE {status} = ?1 AND {fulfilled_at} IS NULL AND {updated_at} < ?2
+            ORDER BY {updated_at}, {id}
+        "#,
+        projection = Invoice::projection(),
+        table = schema::TABLE_NAME,
+        status = Columns::Status.as_ref(),
+        fulfilled_at = Columns::FulfilledAt.as_ref(),
+        updated_at = Columns::UpdatedAt.as_ref(),
+        id = Columns::Id.as_ref(),
+    );
+    conn.prepare(&sql)?
+        .query_map(params![InvoiceStatus::Paid, paid_before], Invoice::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
 pub fn select_by_id(id: i64, conn: &Connection) -> Result<Invoice> {
     let sql = format!(
         r#"
@@ -106,18 +134,88 @@ pub fn select_by_uuid(uuid: &str, conn: &Connection) -> Result<Invoice> {
         .map_err(Into::into)
 }
 
-pub fn set_status(invoice_id: i64, status: InvoiceStatus, conn: &Connection) -> Result<Invoice> {
+pub fn select_by_payment_hash(payment_hash: &str, conn: &Connection) -> Result<Invoice> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {table}
+            WHERE {payment_hash} = ?1
+        "#,
+        projection = Invoice::projection(),
+        table = schema::TABLE_NAME,
+        payment_hash = Columns::PaymentHash.as_ref(),
+    );
+    conn.query_row(&sql, params![payment_hash], Invoice::mapper())
+        .map_err(Into::into)
+}
+
+/// Changes the status only if it's still `from`, so concurrent updates can't
+/// apply the same transition twice. Returns `None` if the invoice wasn't in
+/// the `from` status.
+pub fn transition_status(
+    invoice_id: i64,
+    from: InvoiceStatus,
+    to: InvoiceStatus,
+    conn: &Connection,
+) -> Result<Option<Invoice>> {
     let sql = format!(
         r#"
             UPDATE {table}
-            SET {status} = ?2
-            WHERE {id} = ?1
+            SET {status} = ?3
+            WHERE {id} = ?1 AND {status} = ?2
         "#,
         table = schema::TABLE_NAME,
         status = Columns::Status.as_ref(),
         id = Columns::Id.as_ref(),
     );
-    conn.execute(&sql, params![invoice_id, status])?;
+    if conn.execute(&sql, params![invoice_id, from, to])? == 0 {
+        return Ok(None);
+    }
+    select_by_id(invoice_id, conn).map(Some)
+}
+
+/// Returns false if the invoice was already deleted
+pub fn soft_delete(invoice_id: i64, deleted_at: &str, conn: &Connection) -> Result<bool> {
+    let sql = format!(
+        r#"
+            UPDATE {table}
+            SET {deleted_at} = ?2
+            WHERE {id} = ?1 AND {deleted_at} IS NULL
+        "#,
+        table = schema::TABLE_NAME,
+        deleted_at = Columns::DeletedAt.as_ref(),
+        id = Columns::Id.as_ref(),
+    );
+    Ok(conn.execute(&sql, params![invoice_id, deleted_at])? == 1)
+}
+
+pub fn set_fulfilled_at(invoice_id: i64, fulfilled_at: &str, conn: &Connection) -> Result<()> {
+    let sql = format!(
+        r#"
+            UPDATE {table}
+            SET {fulfilled_at} = ?2
+            WHERE {id} = ?1
+        "#,
+        table = schema::TABLE_NAME,
+        fulfilled_at = Columns::FulfilledAt.as_ref(),
+        id = Columns::Id.as_ref(),
+    );
+    conn.execute(&sql, params![invoice_id, fulfilled_at])?;
+    Ok(())
+}
+
+pub fn set_zap_request(invoice_id: i64, zap_request: &str, conn: &Connection) -> Result<Invoice> {
+    let sql = format!(
+        r#"
+            UPDATE {table}
+            SET {zap_request} = ?2
+            WHERE {id} = ?1
+        "#,
+        table = schema::TABLE_NAME,
+        zap_request = Columns::ZapRequest.as_ref(),
+        id = Columns::Id.as_ref(),
+    );
+    conn.execute(&sql, params![invoice_id, zap_request])?;
     select_by_id(invoice_id, conn)
 }
 
@@ -135,6 +233,7 @@ mod test {
             "hash",
             "req",
             crate::db::main::invoice::schema::InvoiceStatus::Unpaid,
+            None,
             &conn,
         )?;
         assert_eq!(invoice, super::select_by_id(invoice.id, &conn)?);
@@ -151,6 +250,7 @@ mod test {
             "hash1",
             "req1",
             crate::db::main::invoice::schema::InvoiceStatus::Unpaid,
+            None,
             &conn,
         )?;
         let _invoice2 = super::insert(
@@ -160,6 +260,7 @@ mod test {
             "hash2",
             "req2",
             crate::db::main::invoice::schema::InvoiceStatus::Paid,
+            None,
             &conn,
         )?;
         let unpaid = super::select_by_status(
@@ -181,6 +282,7 @@ mod test {
             "hash",
             "req",
             crate::db::main::invoice::schema::InvoiceStatus::Unpaid,
+            None,
             &conn,
         )?;
         let found = super::select_by_uuid(&invoice.uuid, &conn)?;
@@ -189,7 +291,7 @@ mod test {
     }
 
     #[test]
-    fn set_status() -> Result<()> {
+    fn select_by_payment_hash() -> Result<()> {
         let conn = conn();
         let invoice = super::insert(
             "src",
@@ -198,21 +300,94 @@ mod test {
             "hash",
             "req",
             crate::db::main::invoice::schema::InvoiceStatus::Unpaid,
+            None,
             &conn,
         )?;
-        assert_eq!(
-            invoice.status,
-            crate::db::main::invoice::schema::InvoiceStatus::Unpaid
-        );
-        let updated = super::set_status(
+        assert_eq!(invoice, super::select_by_payment_hash("hash", &conn)?);
+        assert!(super::select_by_payment_hash("other", &conn).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn transition_status() -> Result<()> {
+        use crate::db::main::invoice::schema::InvoiceStatus;
+        let conn = conn();
+        let invoice = super::insert(
+            "src",
+            "desc",
+            1,
+            "hash",
+            "req",
+            InvoiceStatus::Unpaid,
+            None,
+            &conn,
+        )?;
+        let updated = super::transition_status(
             invoice.id,
-            crate::db::main::invoice::schema::InvoiceStatus::Paid,
+            InvoiceStatus::Unpaid,
+            InvoiceStatus::Paid,
+            &conn,
+        )?;
+        assert_eq!(InvoiceStatus::Paid, updated.unwrap().status);
+        assert!(super::transition_status(
+            invoice.id,
+            InvoiceStatus::Unpaid,
+            InvoiceStatus::Paid,
+            &conn
+        )?
+        .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn soft_delete() -> Result<()> {
+        let conn = conn();
+        let invoice = super::insert(
+            "src",
+            "desc",
+            1,
+            "hash",
+            "req",
+            crate::db::main::invoice::schema::InvoiceStatus::Unpaid,
+            Some("2025-01-01T01:00:00Z".into()),
             &conn,
         )?;
+        assert_eq!(Some("2025-01-01T01:00:00Z"), invoice.expires_at.as_deref());
+        assert!(super::soft_delete(
+            invoice.id,
+            "2025-01-02T00:00:00Z",
+            &conn
+        )?);
+        assert!(!super::soft_delete(
+            invoice.id,
+            "2025-01-03T00:00:00Z",
+            &conn
+        )?);
         assert_eq!(
-            updated.status,
-            crate::db::main::invoice::schema::InvoiceStatus::Paid
+            Some("2025-01-02T00:00:00Z"),
+            super::select_by_id(invoice.id, &conn)?
+                .deleted_at
+                .as_deref()
         );
         Ok(())
     }
+
+    #[test]
+    fn set_zap_request() -> Result<()> {
+        let conn = conn();
+        let invoice = super::insert(
+            "src",
+            "desc",
+            1,
+            "hash",
+            "req",
+            crate::db::main::invoice::schema::InvoiceStatus::Unpaid,
+            None,
+            &conn,
+        )?;
+        assert_eq!(None, invoice.zap_request);
+        let invoice = super::set_zap_request(invoice.id, "{}", &conn)?;
+        assert_eq!(Some("{}"), invoice.zap_request.as_deref());
+        Ok(())
+    }
 }
@@ -5,6 +5,7 @@ use super::{
 use crate::Result;
 use deadpool_sqlite::Pool;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn insert(
     source: impl Into<String>,
     description: impl Into<String>,
@@ -12,6 +13,7 @@ pub async fn insert(
     payment_hash: impl Into<String>,
     payment_request: impl Into<String>,
     status: InvoiceStatus,
+    expires_at: Option<String>,
     pool: &Pool,
 ) -> Result<Invoice> {
     let source = source.into();
@@ -28,6 +30,7 @@ pub async fn insert(
                 payment_hash,
                 payment_request,
                 status,
+                expires_at,
                 conn,
             )
         })
@@ -41,6 +44,13 @@ pub async fn select_by_status(status: InvoiceStatus, pool: &Pool) -> Result<Vec<
         .await?
 }
 
+pub async fn select_unfulfilled(paid_before: String, pool: &Pool) -> Result<Vec<Invoice>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_unfulfilled(&paid_before, conn))
+        .await?
+}
+
 pub async fn select_by_uuid(uuid: impl Into<String>, pool: &Pool) -> Result<Invoice> {
     let uuid = uuid.into();
     pool.get()
@@ -49,9 +59,46 @@ pub async fn select_by_uuid(uuid: impl Into<String>, pool: &Pool) -> Result<Invo
         .await?
 }
 
-pub async fn set_status(invoice_id: i64, status: InvoiceStatus, pool: &Pool) -> Result<Invoice> {
+pub async fn select_by_payment_hash(
+    payment_hash: impl Into<String>,
+    pool: &Pool,
+) -> Result<Invoice> {
+    let payment_hash = payment_hash.into();
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_by_payment_hash(&payment_hash, conn))
+        .await?
+}
+
+pub async fn transition_status(
+    invoice_id: i64,
+    from: InvoiceStatus,
+    to: InvoiceStatus,
+    pool: &Pool,
+) -> Result<Option<Invoice>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::transition_status(invoice_id, from, to, conn))
+        .await?
+}
+
+pub async fn soft_delete(invoice_id: i64, deleted_at: String, pool: &Pool) -> Result<bool> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::soft_delete(invoice_id, &deleted_at, conn))
+        .await?
+}
+
+pub async fn set_fulfilled_at(invoice_id: i64, fulfilled_at: String, pool: &Pool) -> Result<()> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_fulfilled_at(invoice_id, &fulfilled_at, conn))
+        .await?
+}
+
+pub async fn set_zap_request(invoice_id: i64, zap_request: String, pool: &Pool) -> Result<Invoice> {
     pool.get()
         .await?
-        .interact(move |conn| blocking_queries::set_status(invoice_id, status, conn))
+        .interact(move |conn| blocking_queries::set_zap_request(invoice_id, &zap_request, conn))
         .await?
 }
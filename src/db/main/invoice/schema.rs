@@ -3,6 +3,7 @@ use rusqlite::{
     Row, ToSql,
 };
 use std::sync::OnceLock;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
 pub const TABLE_NAME: &str = "invoice";
 
@@ -20,6 +21,9 @@ pub enum Columns {
     CreatedAt,
     UpdatedAt,
     DeletedAt,
+    ExpiresAt,
+    ZapRequest,
+    FulfilledAt,
 }
 
 #[allow(dead_code)]
@@ -36,6 +40,13 @@ pub struct Invoice {
     pub created_at: String,
     pub updated_at: String,
     pub deleted_at: Option<String>,
+    /// Unpaid invoices can't be paid after this date, `None` means no expiry
+    pub expires_at: Option<String>,
+    /// Signed NIP-57 zap request, if this invoice was requested by a zapper
+    pub zap_request: Option<String>,
+    /// Set once the paid boost or comment went live, paid invoices without it
+    /// are retried
+    pub fulfilled_at: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -100,6 +111,9 @@ impl Invoice {
                 Columns::CreatedAt,
                 Columns::UpdatedAt,
                 Columns::DeletedAt,
+                Columns::ExpiresAt,
+                Columns::ZapRequest,
+                Columns::FulfilledAt,
             ]
             .iter()
             .map(AsRef::as_ref)
@@ -122,6 +136,9 @@ impl Invoice {
                 created_at: row.get(Columns::CreatedAt.as_ref())?,
                 updated_at: row.get(Columns::UpdatedAt.as_ref())?,
                 deleted_at: row.get(Columns::DeletedAt.as_ref())?,
+                expires_at: row.get(Columns::ExpiresAt.as_ref())?,
+                zap_request: row.get(Columns::ZapRequest.as_ref())?,
+                fulfilled_at: row.get(Columns::FulfilledAt.as_ref())?,
             })
         }
     }
@@ -129,12 +146,23 @@ impl Invoice {
     pub fn service(&self) -> InvoicedService {
         InvoicedService::from_description(&self.description)
     }
+
+    pub fn expires_at(&self) -> Option<OffsetDateTime> {
+        OffsetDateTime::parse(self.expires_at.as_deref()?, &Rfc3339).ok()
+    }
+
+    pub fn is_past_expiry(&self, now: OffsetDateTime) -> bool {
+        self.expires_at().is_some_and(|it| it <= now)
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum InvoiceStatus {
     Paid,
     Unpaid,
+    /// Wasn't paid in time, the pending action can be retried with a new
+    /// invoice
+    Expired,
 }
 
 impl From<InvoiceStatus> for String {
@@ -142,6 +170,7 @@ impl From<InvoiceStatus> for String {
         match status {
             InvoiceStatus::Paid => "paid".to_string(),
             InvoiceStatus::Unpaid => "unpaid".to_string(),
+            InvoiceStatus::Expired => "expired".to_string(),
         }
     }
 }
@@ -153,6 +182,7 @@ impl TryFrom<&str> for InvoiceStatus {
         match value {
             "paid" => Ok(InvoiceStatus::Paid),
             "unpaid" => Ok(InvoiceStatus::Unpaid),
+            "expired" => Ok(InvoiceStatus::Expired),
             _ => Err(format!("Unknown invoice status: {}", value).into()),
         }
     }
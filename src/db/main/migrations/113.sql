@@ -0,0 +1,18 @@
+ALTER TABLE conf ADD COLUMN nostr_secret_key TEXT NOT NULL DEFAULT '';
+ALTER TABLE conf ADD COLUMN nostr_relays TEXT NOT NULL DEFAULT '';
+CREATE TABLE nostr_outbox(
+    id INTEGER PRIMARY KEY NOT NULL,
+    relay_url TEXT NOT NULL,
+    event TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    last_error TEXT,
+    sent_at TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ'))
+) STRICT;
+CREATE INDEX nostr_outbox_sent_at_next_attempt_at ON nostr_outbox(sent_at, next_attempt_at);
+CREATE TRIGGER nostr_outbox_updated_at UPDATE OF relay_url, event, attempts, next_attempt_at, last_error, sent_at, created_at ON nostr_outbox
+BEGIN
+    UPDATE nostr_outbox SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ') WHERE id = old.id;
+END;
@@ -0,0 +1,2 @@
+CREATE INDEX element_event_element_id_type ON element_event(element_id, type);
+CREATE INDEX event_area_id ON event(area_id);
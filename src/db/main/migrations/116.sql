@@ -0,0 +1,25 @@
+CREATE TABLE event_submission(
+    id INTEGER PRIMARY KEY NOT NULL,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    area_id INTEGER REFERENCES area(id),
+    lat REAL NOT NULL,
+    lon REAL NOT NULL,
+    name TEXT NOT NULL,
+    website TEXT NOT NULL,
+    starts_at TEXT,
+    ends_at TEXT,
+    cron_schedule TEXT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    review_note TEXT,
+    reviewed_by INTEGER REFERENCES user(id),
+    reviewed_at TEXT,
+    event_id INTEGER REFERENCES event(id),
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ'))
+) STRICT;
+CREATE INDEX event_submission_status ON event_submission(status);
+CREATE INDEX event_submission_user_id ON event_submission(user_id);
+CREATE TRIGGER event_submission_updated_at UPDATE OF user_id, area_id, lat, lon, name, website, starts_at, ends_at, cron_schedule, status, review_note, reviewed_by, reviewed_at, event_id, created_at ON event_submission
+BEGIN
+    UPDATE event_submission SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ') WHERE id = old.id;
+END;
@@ -0,0 +1,14 @@
+ALTER TABLE element_comment ADD COLUMN spam_score INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE element_comment ADD COLUMN hidden_at TEXT;
+ALTER TABLE element_comment ADD COLUMN moderated_at TEXT;
+
+CREATE TABLE element_comment_flag(
+    id INTEGER PRIMARY KEY NOT NULL,
+    element_comment_id INTEGER NOT NULL REFERENCES element_comment(id),
+    reason TEXT NOT NULL,
+    resolved_at TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ'))
+) STRICT;
+
+CREATE INDEX element_comment_flag_element_comment_id ON element_comment_flag(element_comment_id);
@@ -0,0 +1,4 @@
+ALTER TABLE element_comment ADD COLUMN parent_id INTEGER REFERENCES element_comment(id);
+ALTER TABLE element_comment ADD COLUMN official INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX element_comment_parent_id ON element_comment(parent_id);
@@ -0,0 +1,7 @@
+ALTER TABLE element_comment ADD COLUMN user_id INTEGER REFERENCES user(id);
+
+CREATE INDEX element_comment_user_id ON element_comment(user_id);
+
+ALTER TABLE conf ADD COLUMN free_comments_per_day INTEGER NOT NULL DEFAULT 5;
+ALTER TABLE conf ADD COLUMN free_comment_min_account_age_days INTEGER NOT NULL DEFAULT 7;
+ALTER TABLE conf ADD COLUMN free_comment_npub_allowlist TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,15 @@
+CREATE TABLE element_review(
+    id INTEGER PRIMARY KEY NOT NULL,
+    element_id INTEGER NOT NULL REFERENCES element(id),
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    rating INTEGER NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    answers TEXT NOT NULL DEFAULT (json_object()),
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    UNIQUE (element_id, user_id)
+) STRICT;
+
+CREATE TRIGGER element_review_updated_at UPDATE OF rating, answers ON element_review
+BEGIN
+    UPDATE element_review SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ') WHERE id = old.id;
+END;
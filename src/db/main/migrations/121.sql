@@ -0,0 +1,3 @@
+ALTER TABLE conf ADD COLUMN invoice_backend TEXT NOT NULL DEFAULT 'lnd';
+ALTER TABLE conf ADD COLUMN lnbits_url TEXT NOT NULL DEFAULT 'https://core.btcmap.org';
+ALTER TABLE conf ADD COLUMN lnd_url TEXT NOT NULL DEFAULT 'https://lnd.btcmap.org';
@@ -0,0 +1 @@
+CREATE INDEX invoice_payment_hash ON invoice(payment_hash);
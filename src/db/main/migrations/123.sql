@@ -0,0 +1,3 @@
+ALTER TABLE invoice ADD COLUMN expires_at TEXT;
+
+UPDATE invoice SET expires_at = strftime('%Y-%m-%dT%H:%M:%fZ', created_at, '+1 hour');
@@ -0,0 +1 @@
+ALTER TABLE invoice ADD COLUMN zap_request TEXT;
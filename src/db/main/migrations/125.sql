@@ -0,0 +1,48 @@
+CREATE TABLE element_boost(
+    id INTEGER PRIMARY KEY NOT NULL,
+    element_id INTEGER NOT NULL REFERENCES element(id),
+    invoice_id INTEGER UNIQUE REFERENCES invoice(id),
+    days INTEGER NOT NULL CHECK (days > 0),
+    area_id INTEGER REFERENCES area(id),
+    category TEXT,
+    sponsor_name TEXT,
+    sponsor_message TEXT,
+    starts_at TEXT,
+    expires_at TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ'))
+) STRICT;
+
+CREATE INDEX element_boost_element_id ON element_boost(element_id);
+CREATE INDEX element_boost_expires_at ON element_boost(expires_at);
+
+CREATE TRIGGER element_boost_updated_at UPDATE OF invoice_id, starts_at, expires_at ON element_boost
+BEGIN
+    UPDATE element_boost SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ') WHERE id = old.id;
+END;
+
+-- Paid boosts used to be recorded only in invoice descriptions, formatted as
+-- element_boost:{element_id}:{days}. Stacked boosts are approximated, each
+-- one is assumed to start when its invoice was paid.
+INSERT INTO element_boost (element_id, invoice_id, days, starts_at, expires_at, created_at)
+SELECT
+    element_id,
+    id,
+    days,
+    updated_at,
+    strftime('%Y-%m-%dT%H:%M:%fZ', updated_at, '+' || days || ' days'),
+    created_at
+FROM (
+    SELECT
+        id,
+        CAST(substr(rest, 1, instr(rest, ':') - 1) AS INTEGER) AS element_id,
+        CAST(substr(rest, instr(rest, ':') + 1) AS INTEGER) AS days,
+        created_at,
+        updated_at
+    FROM (
+        SELECT id, substr(description, 15) AS rest, created_at, updated_at
+        FROM invoice
+        WHERE status = 'paid' AND description LIKE 'element_boost:%:%'
+    )
+)
+WHERE days > 0 AND element_id IN (SELECT id FROM element);
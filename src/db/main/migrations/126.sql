@@ -0,0 +1,5 @@
+ALTER TABLE conf ADD COLUMN fiat_currency TEXT NOT NULL DEFAULT '';
+ALTER TABLE conf ADD COLUMN paywall_add_element_comment_price_fiat REAL NOT NULL DEFAULT 0;
+ALTER TABLE conf ADD COLUMN exchange_rate_provider TEXT NOT NULL DEFAULT 'coingecko';
+ALTER TABLE conf ADD COLUMN exchange_rate_max_age_secs INTEGER NOT NULL DEFAULT 3600;
+ALTER TABLE conf ADD COLUMN exchange_rate_fallback REAL NOT NULL DEFAULT 0;
@@ -0,0 +1,15 @@
+ALTER TABLE conf ADD COLUMN updated_at TEXT NOT NULL DEFAULT '';
+UPDATE conf SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ');
+
+-- Conf gains columns over time, so the trigger doesn't list them
+CREATE TRIGGER conf_updated_at AFTER UPDATE ON conf WHEN new.updated_at = old.updated_at
+BEGIN
+    UPDATE conf SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ') WHERE id = old.id;
+END;
+
+CREATE TABLE conf_audit(
+    id INTEGER PRIMARY KEY NOT NULL,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    changes TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ'))
+) STRICT;
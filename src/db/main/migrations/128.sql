@@ -0,0 +1,6 @@
+-- Secrets are hashed on startup, since the hash key isn't known to SQLite,
+-- then cleared from the secret column
+ALTER TABLE access_token ADD COLUMN secret_hash TEXT NOT NULL DEFAULT '';
+ALTER TABLE access_token ADD COLUMN prefix TEXT NOT NULL DEFAULT '';
+DROP INDEX access_token_secret;
+CREATE INDEX access_token_secret_hash ON access_token(secret_hash);
@@ -0,0 +1,4 @@
+ALTER TABLE access_token ADD COLUMN expires_at TEXT;
+ALTER TABLE access_token ADD COLUMN last_used_at TEXT;
+ALTER TABLE access_token ADD COLUMN allowed_methods TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE access_token ADD COLUMN allowed_ips TEXT NOT NULL DEFAULT '[]';
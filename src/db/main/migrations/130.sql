@@ -0,0 +1,3 @@
+ALTER TABLE conf ADD COLUMN rate_limits TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE conf ADD COLUMN rate_limit_ban_after INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE conf ADD COLUMN rate_limit_ban_hours INTEGER NOT NULL DEFAULT 24;
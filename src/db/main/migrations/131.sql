@@ -0,0 +1,22 @@
+-- end_at can't be made optional in place
+CREATE TABLE ban_new(
+    id INTEGER PRIMARY KEY NOT NULL,
+    ip TEXT,
+    user_agent TEXT,
+    reason TEXT NOT NULL,
+    start_at TEXT NOT NULL,
+    end_at TEXT,
+    hits INTEGER NOT NULL DEFAULT 0,
+    last_hit_at TEXT,
+    CHECK (ip IS NOT NULL OR user_agent IS NOT NULL)
+) STRICT;
+
+INSERT INTO ban_new (id, ip, reason, start_at, end_at)
+SELECT id, ip, reason, start_at, end_at FROM ban;
+
+DROP TABLE ban;
+
+ALTER TABLE ban_new RENAME TO ban;
+
+CREATE INDEX ban_ip ON ban(ip);
+CREATE INDEX ban_start_at_end_at on ban (start_at, end_at);
@@ -0,0 +1,6 @@
+ALTER TABLE conf ADD COLUMN abuse_window_minutes INTEGER NOT NULL DEFAULT 10;
+ALTER TABLE conf ADD COLUMN abuse_max_requests INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE conf ADD COLUMN abuse_max_failed_signins INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE conf ADD COLUMN abuse_max_invoices INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE conf ADD COLUMN abuse_ban_hours INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE conf ADD COLUMN abuse_allowlist TEXT NOT NULL DEFAULT '';
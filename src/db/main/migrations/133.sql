@@ -0,0 +1,2 @@
+ALTER TABLE invoice ADD COLUMN fulfilled_at TEXT;
+UPDATE invoice SET fulfilled_at = updated_at WHERE status = 'paid';
@@ -0,0 +1,11 @@
+ALTER TABLE element_comment ADD COLUMN invoice_id INTEGER REFERENCES invoice(id);
+
+-- Paid comments used to be linked to their invoices only through invoice
+-- descriptions, formatted as element_comment:{comment_id}:publish. Renewed
+-- invoices keep the description, the latest one is the one that counts.
+UPDATE element_comment SET invoice_id = (
+    SELECT max(id) FROM invoice
+    WHERE description = 'element_comment:' || element_comment.id || ':publish'
+);
+
+CREATE INDEX element_boost_created_at ON element_boost(created_at);
@@ -0,0 +1,2 @@
+-- Filled in when events are saved, older events are looked up when they're read
+ALTER TABLE event ADD COLUMN timezone TEXT;
@@ -0,0 +1,4 @@
+-- Used to be hardcoded in the place boost endpoint
+ALTER TABLE conf ADD COLUMN honeypot_place_ids TEXT NOT NULL DEFAULT '';
+ALTER TABLE conf ADD COLUMN honeypot_ban_days INTEGER NOT NULL DEFAULT 3650;
+UPDATE conf SET honeypot_place_ids = '23143';
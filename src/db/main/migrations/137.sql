@@ -0,0 +1,2 @@
+-- What a hidden comment goes back to once it's restored
+ALTER TABLE element_comment ADD COLUMN hidden_from TEXT;
@@ -3,13 +3,19 @@ pub mod area;
 pub mod area_element;
 pub mod ban;
 pub mod conf;
+pub mod conf_audit;
 pub mod electrum_server;
 pub mod element;
+pub mod element_boost;
 pub mod element_comment;
+pub mod element_comment_flag;
 pub mod element_event;
 pub mod element_issue;
+pub mod element_review;
 pub mod event;
+pub mod event_submission;
 pub mod invoice;
+pub mod nostr_outbox;
 pub mod osm_user;
 pub mod place_import_origin;
 pub mod place_submission;
@@ -0,0 +1,249 @@
+use super::schema::{self, NostrOutboxItem};
+use crate::Result;
+use rusqlite::{named_params, params, Connection};
+use schema::Columns::*;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+pub fn insert(relay_url: &str, event: &str, conn: &Connection) -> Result<NostrOutboxItem> {
+    let sql = format!(
+        r#"
+            INSERT INTO {TABLE_NAME} ({RelayUrl}, {Event})
+            VALUES (:relay_url, :event)
+            RETURNING {projection}
+        "#,
+        TABLE_NAME = schema::TABLE_NAME,
+        projection = NostrOutboxItem::projection(),
+    );
+    let params = named_params! {
+        ":relay_url": relay_url,
+        ":event": event,
+    };
+    conn.query_row(&sql, params, NostrOutboxItem::mapper())
+        .map_err(Into::into)
+}
+
+pub fn select_by_id(id: i64, conn: &Connection) -> Result<NostrOutboxItem> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {TABLE_NAME}
+            WHERE {Id} = ?1
+        "#,
+        TABLE_NAME = schema::TABLE_NAME,
+        projection = NostrOutboxItem::projection(),
+    );
+    conn.query_row(&sql, params![id], NostrOutboxItem::mapper())
+        .map_err(Into::into)
+}
+
+/// Unsent items whose next attempt is due, oldest first. Items which used up
+/// `max_attempts` are left in the table for inspection but never picked up
+/// again. Timestamps are normalized to the millisecond precision of the column
+/// defaults so they compare correctly as text.
+pub fn select_due(
+    now: OffsetDateTime,
+    max_attempts: i64,
+    limit: i64,
+    conn: &Connection,
+) -> Result<Vec<NostrOutboxItem>> {
+    let sql = format!(
+        r#"
+            SELECT {projection}
+            FROM {TABLE_NAME}
+            WHERE {SentAt} IS NULL AND {NextAttemptAt} <= strftime('%Y-%m-%dT%H:%M:%fZ', :now) AND {Attempts} < :max_attempts
+            ORDER BY {Id} ASC
+            LIMIT :limit
+        "#,
+        TABLE_NAME = schema::TABLE_NAME,
+        projection = NostrOutboxItem::projection(),
+    );
+    let params = named_params! {
+        ":now": now.format(&Rfc3339)?,
+        ":max_attempts": max_attempts,
+        ":limit": limit,
+    };
+    conn.prepare(&sql)?
+        .query_map(params, NostrOutboxItem::mapper())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Into::into)
+}
+
+pub fn set_sent(id: i64, sent_at: OffsetDateTime, conn: &Connection) -> Result<NostrOutboxItem> {
+    let sql = format!(
+        r#"
+            UPDATE {TABLE_NAME}
+            SET {SentAt} = strftime('%Y-%m-%dT%H:%M:%fZ', :sent_at),
+                {Attempts} = {Attempts} + 1,
+                {LastError} = NULL
+            WHERE {Id} = :id
+            RETURNING {projection}
+        "#,
+        TABLE_NAME = schema::TABLE_NAME,
+        projection = NostrOutboxItem::projection(),
+    );
+    let params = named_params! {
+        ":id": id,
+        ":sent_at": sent_at.format(&Rfc3339)?,
+    };
+    conn.query_row(&sql, params, NostrOutboxItem::mapper())
+        .map_err(Into::into)
+}
+
+pub fn set_failed(
+    id: i64,
+    error: &str,
+    next_attempt_at: OffsetDateTime,
+    conn: &Connection,
+) -> Result<NostrOutboxItem> {
+    let sql = format!(
+        r#"
+            UPDATE {TABLE_NAME}
+            SET {Attempts} = {Attempts} + 1,
+                {LastError} = :error,
+                {NextAttemptAt} = strftime('%Y-%m-%dT%H:%M:%fZ', :next_attempt_at)
+            WHERE {Id} = :id
+            RETURNING {projection}
+        "#,
+        TABLE_NAME = schema::TABLE_NAME,
+        projection = NostrOutboxItem::projection(),
+    );
+    let params = named_params! {
+        ":id": id,
+        ":error": error,
+        ":next_attempt_at": next_attempt_at.format(&Rfc3339)?,
+    };
+    conn.query_row(&sql, params, NostrOutboxItem::mapper())
+        .map_err(Into::into)
+}
+
+/// Pushes back every unsent item of a relay which is failing, so the relay
+/// backs off as a whole instead of being retried once per event. Items which
+/// are already scheduled later are left alone.
+pub fn postpone_by_relay_url(
+    relay_url: &str,
+    next_attempt_at: OffsetDateTime,
+    conn: &Connection,
+) -> Result<usize> {
+    let sql = format!(
+        r#"
+            UPDATE {TABLE_NAME}
+            SET {NextAttemptAt} = strftime('%Y-%m-%dT%H:%M:%fZ', :next_attempt_at)
+            WHERE {RelayUrl} = :relay_url
+                AND {SentAt} IS NULL
+                AND {NextAttemptAt} < strftime('%Y-%m-%dT%H:%M:%fZ', :next_attempt_at)
+        "#,
+        TABLE_NAME = schema::TABLE_NAME,
+    );
+    let params = named_params! {
+        ":relay_url": relay_url,
+        ":next_attempt_at": next_attempt_at.format(&Rfc3339)?,
+    };
+    conn.execute(&sql, params).map_err(Into::into)
+}
+
+/// Removes the items of every event which all of its relays have accepted.
+/// Events with an item still pending or given up on are kept in full.
+pub fn delete_delivered(conn: &Connection) -> Result<usize> {
+    let sql = format!(
+        r#"
+            DELETE FROM {TABLE_NAME}
+            WHERE {Event} IN (
+                SELECT {Event}
+                FROM {TABLE_NAME}
+                GROUP BY {Event}
+                HAVING count(*) = count({SentAt})
+            )
+        "#,
+        TABLE_NAME = schema::TABLE_NAME,
+    );
+    conn.execute(&sql, []).map_err(Into::into)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{db::main::test::conn, Result};
+    use time::{Duration, OffsetDateTime};
+
+    #[test]
+    fn insert_and_select_by_id() -> Result<()> {
+        let conn = conn();
+        let item = super::insert("wss://relay.example", "{}", &conn)?;
+        assert_eq!("wss://relay.example", item.relay_url);
+        assert_eq!("{}", item.event);
+        assert_eq!(0, item.attempts);
+        assert!(item.sent_at.is_none());
+        assert_eq!(item, super::select_by_id(item.id, &conn)?);
+        Ok(())
+    }
+
+    #[test]
+    fn select_due_skips_sent_future_and_exhausted() -> Result<()> {
+        let conn = conn();
+        let now = OffsetDateTime::now_utc();
+        let due = super::insert("wss://a", "{}", &conn)?;
+        let sent = super::insert("wss://b", "{}", &conn)?;
+        super::set_sent(sent.id, now, &conn)?;
+        let postponed = super::insert("wss://c", "{}", &conn)?;
+        super::set_failed(postponed.id, "timeout", now + Duration::hours(1), &conn)?;
+        let exhausted = super::insert("wss://d", "{}", &conn)?;
+        super::set_failed(exhausted.id, "timeout", now, &conn)?;
+        super::set_failed(exhausted.id, "timeout", now, &conn)?;
+        let res = super::select_due(now + Duration::seconds(1), 2, 100, &conn)?;
+        assert_eq!(vec![due.id], res.iter().map(|it| it.id).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn set_failed_records_error() -> Result<()> {
+        let conn = conn();
+        let item = super::insert("wss://a", "{}", &conn)?;
+        let next_attempt_at = OffsetDateTime::now_utc() + Duration::minutes(5);
+        let item = super::set_failed(item.id, "rejected", next_attempt_at, &conn)?;
+        assert_eq!(1, item.attempts);
+        assert_eq!(Some("rejected"), item.last_error.as_deref());
+        assert!(item.sent_at.is_none());
+        let item = super::set_sent(item.id, OffsetDateTime::now_utc(), &conn)?;
+        assert_eq!(2, item.attempts);
+        assert!(item.last_error.is_none());
+        assert!(item.sent_at.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn postpone_by_relay_url() -> Result<()> {
+        let conn = conn();
+        let now = OffsetDateTime::now_utc();
+        let a = super::insert("wss://a", "{}", &conn)?;
+        let later = super::insert("wss://a", "{}", &conn)?;
+        super::set_failed(later.id, "timeout", now + Duration::hours(2), &conn)?;
+        let b = super::insert("wss://b", "{}", &conn)?;
+        assert_eq!(
+            1,
+            super::postpone_by_relay_url("wss://a", now + Duration::hours(1), &conn)?
+        );
+        let due = super::select_due(now + Duration::minutes(1), 10, 100, &conn)?;
+        assert_eq!(vec![b.id], due.iter().map(|it| it.id).collect::<Vec<_>>());
+        assert_eq!(0, super::select_by_id(a.id, &conn)?.attempts);
+        Ok(())
+    }
+
+    #[test]
+    fn delete_delivered() -> Result<()> {
+        let conn = conn();
+        let now = OffsetDateTime::now_utc();
+        let delivered_a = super::insert("wss://a", "1", &conn)?;
+        let delivered_b = super::insert("wss://b", "1", &conn)?;
+        let partial_a = super::insert("wss://a", "2", &conn)?;
+        let partial_b = super::insert("wss://b", "2", &conn)?;
+        for item in [&delivered_a, &delivered_b, &partial_a] {
+            super::set_sent(item.id, now, &conn)?;
+        }
+        super::set_failed(partial_b.id, "timeout", now, &conn)?;
+        assert_eq!(2, super::delete_delivered(&conn)?);
+        assert!(super::select_by_id(delivered_a.id, &conn).is_err());
+        assert!(super::select_by_id(delivered_b.id, &conn).is_err());
+        assert!(super::select_by_id(partial_a.id, &conn).is_ok());
+        assert!(super::select_by_id(partial_b.id, &conn).is_ok());
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use super::{blocking_queries, schema::NostrOutboxItem};
+use crate::Result;
+use deadpool_sqlite::Pool;
+use time::OffsetDateTime;
+
+pub async fn insert(relay_url: String, event: String, pool: &Pool) -> Result<NostrOutboxItem> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::insert(&relay_url, &event, conn))
+        .await?
+}
+
+#[allow(dead_code)]
+pub async fn select_by_id(id: i64, pool: &Pool) -> Result<NostrOutboxItem> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_by_id(id, conn))
+        .await?
+}
+
+pub async fn select_due(
+    now: OffsetDateTime,
+    max_attempts: i64,
+    limit: i64,
+    pool: &Pool,
+) -> Result<Vec<NostrOutboxItem>> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::select_due(now, max_attempts, limit, conn))
+        .await?
+}
+
+pub async fn set_sent(id: i64, sent_at: OffsetDateTime, pool: &Pool) -> Result<NostrOutboxItem> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_sent(id, sent_at, conn))
+        .await?
+}
+
+pub async fn set_failed(
+    id: i64,
+    error: String,
+    next_attempt_at: OffsetDateTime,
+    pool: &Pool,
+) -> Result<NostrOutboxItem> {
+    pool.get()
+        .await?
+        .interact(move |conn| blocking_queries::set_failed(id, &error, next_attempt_at, conn))
+        .await?
+}
+
+pub async fn postpone_by_relay_url(
+    relay_url: String,
+    next_attempt_at: OffsetDateTime,
+    pool: &Pool,
+) -> Result<usize> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            blocking_queries::postpone_by_relay_url(&relay_url, next_attempt_at, conn)
+        })
+        .await?
+}
+
+pub async fn delete_delivered(pool: &Pool) -> Result<usize> {
+    pool.get()
+        .await?
+        .interact(|conn| blocking_queries::delete_delivered(conn))
+        .await?
+}
@@ -0,0 +1,75 @@
+use rusqlite::Row;
+use std::sync::OnceLock;
+use time::OffsetDateTime;
+
+pub const TABLE_NAME: &str = "nostr_outbox";
+
+#[derive(strum::AsRefStr, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Columns {
+    Id,
+    RelayUrl,
+    Event,
+    Attempts,
+    NextAttemptAt,
+    LastError,
+    SentAt,
+    CreatedAt,
+    UpdatedAt,
+}
+
+/// A signed Nostr event waiting to be delivered to a single relay. Each
+/// relay gets its own row so a flaky relay can't hold back the others.
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NostrOutboxItem {
+    pub id: i64,
+    pub relay_url: String,
+    /// Signed event, serialized as NIP-01 JSON
+    pub event: String,
+    pub attempts: i64,
+    pub next_attempt_at: OffsetDateTime,
+    pub last_error: Option<String>,
+    pub sent_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+impl NostrOutboxItem {
+    pub fn projection() -> &'static str {
+        static PROJECTION: OnceLock<String> = OnceLock::new();
+        PROJECTION.get_or_init(|| {
+            [
+                Columns::Id,
+                Columns::RelayUrl,
+                Columns::Event,
+                Columns::Attempts,
+                Columns::NextAttemptAt,
+                Columns::LastError,
+                Columns::SentAt,
+                Columns::CreatedAt,
+                Columns::UpdatedAt,
+            ]
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+            .join(", ")
+        })
+    }
+
+    pub const fn mapper() -> fn(&Row) -> rusqlite::Result<Self> {
+        |row: &Row| -> rusqlite::Result<Self> {
+            Ok(NostrOutboxItem {
+                id: row.get(Columns::Id.as_ref())?,
+                relay_url: row.get(Columns::RelayUrl.as_ref())?,
+                event: row.get(Columns::Event.as_ref())?,
+                attempts: row.get(Columns::Attempts.as_ref())?,
+                next_attempt_at: row.get(Columns::NextAttemptAt.as_ref())?,
+                last_error: row.get(Columns::LastError.as_ref())?,
+                sent_at: row.get(Columns::SentAt.as_ref())?,
+                created_at: row.get(Columns::CreatedAt.as_ref())?,
+                updated_at: row.get(Columns::UpdatedAt.as_ref())?,
+            })
+        }
+    }
+}
@@ -49,7 +49,7 @@ CREATE TABLE IF NOT EXISTS "element_comment"(
     created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
     updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
     deleted_at TEXT
-) STRICT;
+, spam_score INTEGER NOT NULL DEFAULT 0, hidden_at TEXT, moderated_at TEXT, parent_id INTEGER REFERENCES element_comment(id), official INTEGER NOT NULL DEFAULT 0, user_id INTEGER REFERENCES user(id), invoice_id INTEGER REFERENCES invoice(id), hidden_from TEXT) STRICT;
 CREATE TABLE area_element(
     id INTEGER PRIMARY KEY NOT NULL,
     area_id INTEGER NOT NULL REFERENCES area(id),
@@ -58,12 +58,16 @@ CREATE TABLE area_element(
     updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
     deleted_at TEXT
 ) STRICT;
-CREATE TABLE ban(
+CREATE TABLE IF NOT EXISTS "ban"(
     id INTEGER PRIMARY KEY NOT NULL,
-    ip TEXT NOT NULL,
-    reason TEXT NOT NULL,    
+    ip TEXT,
+    user_agent TEXT,
+    reason TEXT NOT NULL,
     start_at TEXT NOT NULL,
-    end_at TEXT NOT NULL
+    end_at TEXT,
+    hits INTEGER NOT NULL DEFAULT 0,
+    last_hit_at TEXT,
+    CHECK (ip IS NOT NULL OR user_agent IS NOT NULL)
 ) STRICT;
 CREATE TABLE invoice(
     id INTEGER PRIMARY KEY NOT NULL,
@@ -75,13 +79,13 @@ CREATE TABLE invoice(
     created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
     updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
     deleted_at TEXT
-, uuid TEXT, source TEXT NOT NULL DEFAULT 'lnbits') STRICT;
+, uuid TEXT, source TEXT NOT NULL DEFAULT 'lnbits', expires_at TEXT, zap_request TEXT, fulfilled_at TEXT) STRICT;
 CREATE TABLE conf(
     id INTEGER PRIMARY KEY NOT NULL,
     paywall_add_element_comment_price_sat INTEGER NOT NULL,
     boost_element_prices TEXT NOT NULL DEFAULT '[]'
-, lnbits_invoice_key TEXT NOT NULL DEFAULT '', gitea_api_key TEXT NOT NULL DEFAULT '', matrix_bot_password TEXT NOT NULL DEFAULT '', lnd_invoices_macaroon TEXT NOT NULL DEFAULT '', ppq_key TEXT NOT NULL DEFAULT '', lnd_readonly_macaroon TEXT NOT NULL DEFAULT '', cors_origins TEXT NOT NULL DEFAULT '') STRICT;
-INSERT INTO conf VALUES(1,500,'[]','','','','','','','');
+, lnbits_invoice_key TEXT NOT NULL DEFAULT '', gitea_api_key TEXT NOT NULL DEFAULT '', matrix_bot_password TEXT NOT NULL DEFAULT '', lnd_invoices_macaroon TEXT NOT NULL DEFAULT '', ppq_key TEXT NOT NULL DEFAULT '', lnd_readonly_macaroon TEXT NOT NULL DEFAULT '', cors_origins TEXT NOT NULL DEFAULT '', nostr_secret_key TEXT NOT NULL DEFAULT '', nostr_relays TEXT NOT NULL DEFAULT '', free_comments_per_day INTEGER NOT NULL DEFAULT 5, free_comment_min_account_age_days INTEGER NOT NULL DEFAULT 7, free_comment_npub_allowlist TEXT NOT NULL DEFAULT '', invoice_backend TEXT NOT NULL DEFAULT 'lnd', lnbits_url TEXT NOT NULL DEFAULT 'https://core.btcmap.org', lnd_url TEXT NOT NULL DEFAULT 'https://lnd.btcmap.org', fiat_currency TEXT NOT NULL DEFAULT '', paywall_add_element_comment_price_fiat REAL NOT NULL DEFAULT 0, exchange_rate_provider TEXT NOT NULL DEFAULT 'coingecko', exchange_rate_max_age_secs INTEGER NOT NULL DEFAULT 3600, exchange_rate_fallback REAL NOT NULL DEFAULT 0, updated_at TEXT NOT NULL DEFAULT '', rate_limits TEXT NOT NULL DEFAULT '[]', rate_limit_ban_after INTEGER NOT NULL DEFAULT 0, rate_limit_ban_hours INTEGER NOT NULL DEFAULT 24, abuse_window_minutes INTEGER NOT NULL DEFAULT 10, abuse_max_requests INTEGER NOT NULL DEFAULT 0, abuse_max_failed_signins INTEGER NOT NULL DEFAULT 0, abuse_max_invoices INTEGER NOT NULL DEFAULT 0, abuse_ban_hours INTEGER NOT NULL DEFAULT 0, abuse_allowlist TEXT NOT NULL DEFAULT '', honeypot_place_ids TEXT NOT NULL DEFAULT '', honeypot_ban_days INTEGER NOT NULL DEFAULT 3650) STRICT;
+INSERT INTO conf VALUES(1,500,'[]','','','','','','','','','',5,7,'','lnd','https://core.btcmap.org','https://lnd.btcmap.org','',0.0,'coingecko',3600,0.0,'','[]',0,24,10,0,0,0,0,'','23143',3650);
 CREATE TABLE wallet(
     id INTEGER PRIMARY KEY NOT NULL,
     name TEXT NOT NULL UNIQUE,
@@ -131,7 +135,7 @@ CREATE TABLE access_token(
     created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
     updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
     deleted_at TEXT
-) STRICT;
+, secret_hash TEXT NOT NULL DEFAULT '', prefix TEXT NOT NULL DEFAULT '', expires_at TEXT, last_used_at TEXT, allowed_methods TEXT NOT NULL DEFAULT '[]', allowed_ips TEXT NOT NULL DEFAULT '[]') STRICT;
 CREATE TABLE place_import_origin(
     id INTEGER PRIMARY KEY NOT NULL,
     name TEXT UNIQUE NOT NULL,
@@ -154,7 +158,7 @@ CREATE TABLE event(
     created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
     updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
     deleted_at TEXT
-, starts_at TEXT, area_id INTEGER REFERENCES area(id), cron_schedule TEXT) STRICT;
+, starts_at TEXT, area_id INTEGER REFERENCES area(id), cron_schedule TEXT, excluded_dates TEXT NOT NULL DEFAULT '[]', timezone TEXT) STRICT;
 CREATE TABLE place_submission(
     id INTEGER PRIMARY KEY NOT NULL,
     origin TEXT NOT NULL,
@@ -171,6 +175,74 @@ CREATE TABLE place_submission(
     closed_at TEXT,
     deleted_at TEXT
 ) STRICT;
+CREATE TABLE nostr_outbox(
+    id INTEGER PRIMARY KEY NOT NULL,
+    relay_url TEXT NOT NULL,
+    event TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    last_error TEXT,
+    sent_at TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ'))
+) STRICT;
+CREATE TABLE element_comment_flag(
+    id INTEGER PRIMARY KEY NOT NULL,
+    element_comment_id INTEGER NOT NULL REFERENCES element_comment(id),
+    reason TEXT NOT NULL,
+    resolved_at TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ'))
+) STRICT;
+CREATE TABLE element_review(
+    id INTEGER PRIMARY KEY NOT NULL,
+    element_id INTEGER NOT NULL REFERENCES element(id),
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    rating INTEGER NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    answers TEXT NOT NULL DEFAULT (json_object()),
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    UNIQUE (element_id, user_id)
+) STRICT;
+CREATE TABLE element_boost(
+    id INTEGER PRIMARY KEY NOT NULL,
+    element_id INTEGER NOT NULL REFERENCES element(id),
+    invoice_id INTEGER UNIQUE REFERENCES invoice(id),
+    days INTEGER NOT NULL CHECK (days > 0),
+    area_id INTEGER REFERENCES area(id),
+    category TEXT,
+    sponsor_name TEXT,
+    sponsor_message TEXT,
+    starts_at TEXT,
+    expires_at TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ'))
+) STRICT;
+CREATE TABLE event_submission(
+    id INTEGER PRIMARY KEY NOT NULL,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    area_id INTEGER REFERENCES area(id),
+    lat REAL NOT NULL,
+    lon REAL NOT NULL,
+    name TEXT NOT NULL,
+    website TEXT NOT NULL,
+    starts_at TEXT,
+    ends_at TEXT,
+    cron_schedule TEXT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    review_note TEXT,
+    reviewed_by INTEGER REFERENCES user(id),
+    reviewed_at TEXT,
+    event_id INTEGER REFERENCES event(id),
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ'))
+) STRICT;
+CREATE TABLE conf_audit(
+    id INTEGER PRIMARY KEY NOT NULL,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    changes TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ'))
+) STRICT;
 CREATE TRIGGER report_updated_at UPDATE OF area_id, date, tags, created_at, deleted_at ON report
 BEGIN
     UPDATE report SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ') WHERE id = old.id;
@@ -227,10 +299,30 @@ CREATE TRIGGER user_updated_at UPDATE OF name, password, roles, saved_places, sa
 BEGIN
     UPDATE user SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ') WHERE id = old.id;
 END;
-CREATE TRIGGER event_updated_at UPDATE OF lat, lon, name, website, starts_at, ends_at, cron_schedule, area_id, created_at, deleted_at ON event
+CREATE TRIGGER event_updated_at UPDATE OF lat, lon, name, website, starts_at, ends_at, cron_schedule, excluded_dates, area_id, created_at, deleted_at ON event
 BEGIN
     UPDATE event SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ') WHERE id = old.id;
 END;
+CREATE TRIGGER nostr_outbox_updated_at UPDATE OF relay_url, event, attempts, next_attempt_at, last_error, sent_at, created_at ON nostr_outbox
+BEGIN
+    UPDATE nostr_outbox SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ') WHERE id = old.id;
+END;
+CREATE TRIGGER element_review_updated_at UPDATE OF rating, answers ON element_review
+BEGIN
+    UPDATE element_review SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ') WHERE id = old.id;
+END;
+CREATE TRIGGER element_boost_updated_at UPDATE OF invoice_id, starts_at, expires_at ON element_boost
+BEGIN
+    UPDATE element_boost SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ') WHERE id = old.id;
+END;
+CREATE TRIGGER event_submission_updated_at UPDATE OF user_id, area_id, lat, lon, name, website, starts_at, ends_at, cron_schedule, status, review_note, reviewed_by, reviewed_at, event_id, created_at ON event_submission
+BEGIN
+    UPDATE event_submission SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ') WHERE id = old.id;
+END;
+CREATE TRIGGER conf_updated_at AFTER UPDATE ON conf WHEN new.updated_at = old.updated_at
+BEGIN
+    UPDATE conf SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ') WHERE id = old.id;
+END;
 CREATE INDEX idx_user_updated_at ON "osm_user"(updated_at);
 CREATE INDEX area_updated_at ON area(updated_at);
 CREATE INDEX report_updated_at ON report(updated_at);
@@ -248,7 +340,6 @@ CREATE INDEX element_issue_updated_at ON element_issue(updated_at);
 CREATE INDEX element_issue_deleted_at ON element_issue(deleted_at);
 CREATE INDEX admin_name ON "user"(name);
 CREATE INDEX admin_updated_at ON "user"(updated_at);
-CREATE INDEX access_token_secret ON access_token(secret);
 CREATE UNIQUE INDEX area_element_area_id_element_id ON area_element(area_id, element_id);
 CREATE INDEX element_event_updated_at ON element_event(updated_at);
 CREATE INDEX idx_area_bbox_west ON area(bbox_west);
@@ -262,4 +353,17 @@ CREATE INDEX element_deleted_at ON element(deleted_at);
 CREATE INDEX element_event_user_created_type ON element_event(user_id, created_at, type);
 CREATE INDEX element_event_type_created_at ON element_event(type, created_at);
 CREATE INDEX area_type ON area(json_extract(tags, '$.type'));
+CREATE INDEX nostr_outbox_sent_at_next_attempt_at ON nostr_outbox(sent_at, next_attempt_at);
+CREATE INDEX element_event_element_id_type ON element_event(element_id, type);
+CREATE INDEX element_comment_parent_id ON element_comment(parent_id);
+CREATE INDEX element_comment_user_id ON element_comment(user_id);
+CREATE INDEX element_comment_flag_element_comment_id ON element_comment_flag(element_comment_id);
+CREATE INDEX event_area_id ON event(area_id);
+CREATE INDEX event_submission_status ON event_submission(status);
+CREATE INDEX event_submission_user_id ON event_submission(user_id);
+CREATE INDEX invoice_payment_hash ON invoice(payment_hash);
+CREATE INDEX element_boost_element_id ON element_boost(element_id);
+CREATE INDEX element_boost_expires_at ON element_boost(expires_at);
+CREATE INDEX element_boost_created_at ON element_boost(created_at);
+CREATE INDEX access_token_secret_hash ON access_token(secret_hash);
 COMMIT;
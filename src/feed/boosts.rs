@@ -0,0 +1,108 @@
+use super::{feed_url, place_url, Feed, FeedItem, MAX_ITEMS};
+use crate::db::main::MainPool;
+use crate::rest::nostr_auth::ApiBaseUrl;
+use crate::{db, Result};
+use actix_web::{
+    get,
+    web::{Data, Path},
+    HttpRequest, Responder,
+};
+use time::OffsetDateTime;
+
+#[get("/boosts/{area}")]
+pub async fn boosts_for_area(
+    req: HttpRequest,
+    area: Path<String>,
+    base_url: Data<ApiBaseUrl>,
+    pool: Data<MainPool>,
+) -> Result<impl Responder> {
+    let area = db::main::area::queries::select_by_id_or_alias(area.to_string(), &pool).await?;
+    let boosts = db::main::element_boost::queries::select_paid_created_between_for_area(
+        area.id,
+        OffsetDateTime::UNIX_EPOCH,
+        OffsetDateTime::now_utc(),
+        MAX_ITEMS as i64,
+        &pool,
+    )
+    .await?;
+    let mut items = Vec::with_capacity(boosts.len());
+    for boost in boosts {
+        let element = db::main::element::queries::select_by_id(boost.element_id, &pool).await?;
+        items.push(FeedItem {
+            // paid boosts always have an invoice
+            id: format!(
+                "https://btcmap.org/boost/{}",
+                boost.invoice_id.unwrap_or_default()
+            ),
+            title: element.name(Some("en")),
+            summary: format!("Boosted for {} days", boost.days),
+            url: place_url(boost.element_id),
+            date: boost.created_at,
+        });
+    }
+    Ok(Feed {
+        url: feed_url(&base_url, &format!("boosts/{}", area.id)),
+        title: format!("BTC Map - Boosts in {}", area.name()),
+        items,
+    }
+    .respond(&req))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::main::area::schema::Area;
+    use crate::db::main::invoice::schema::InvoiceStatus;
+    use crate::db::main::test::pool;
+    use crate::rest::nostr_auth::ApiBaseUrl;
+    use crate::service::overpass::OverpassElement;
+    use crate::{db, Result};
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use actix_web::{test, App};
+    use serde_json::Value;
+
+    #[test]
+    async fn boosts_for_area() -> Result<()> {
+        let pool = pool();
+        let area = db::main::area::queries::insert(Area::mock_tags(), &pool).await?;
+        let inside = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let outside = db::main::element::queries::insert(OverpassElement::mock(2), &pool).await?;
+        db::main::area_element::queries::insert(area.id, inside.id, &pool).await?;
+        for (element_id, status) in [
+            (inside.id, InvoiceStatus::Paid),
+            (inside.id, InvoiceStatus::Unpaid),
+            (outside.id, InvoiceStatus::Paid),
+        ] {
+            db::main::element::queries::set_lat_lon(element_id, 1.0, 1.0, &pool).await?;
+            let invoice = db::main::invoice::queries::insert(
+                "src",
+                format!("element_boost:{element_id}:30"),
+                0,
+                "",
+                "",
+                status,
+                None,
+                &pool,
+            )
+            .await?;
+            if status == InvoiceStatus::Paid {
+                crate::service::boost::on_paid(&invoice, element_id, 30, &pool).await?;
+            }
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .app_data(Data::new(ApiBaseUrl("https://api.example.com".into())))
+                .service(super::boosts_for_area),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri(&format!("/boosts/{}?format=json", area.id))
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        let items = res["items"].as_array().unwrap();
+        assert_eq!(1, items.len());
+        assert_eq!("Boosted for 30 days", items[0]["content_text"]);
+        Ok(())
+    }
+}
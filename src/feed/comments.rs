@@ -0,0 +1,147 @@
+use super::{feed_url, place_url, Feed, FeedItem, MAX_ITEMS};
+use crate::db::main::element_comment::schema::ElementComment;
+use crate::db::main::MainPool;
+use crate::rest::nostr_auth::ApiBaseUrl;
+use crate::{db, service, Result};
+use actix_web::{
+    get,
+    web::{Data, Path},
+    HttpRequest, Responder,
+};
+
+#[get("/new-comments")]
+pub async fn new_comments(
+    req: HttpRequest,
+    base_url: Data<ApiBaseUrl>,
+    pool: Data<MainPool>,
+) -> Result<impl Responder> {
+    let comments =
+        db::main::element_comment::queries::select_latest(MAX_ITEMS as i64, &pool).await?;
+    let items = comments
+        .into_iter()
+        .filter(|it| it.deleted_at.is_none())
+        .map(|it| FeedItem {
+            id: format!("https://btcmap.org/comment/{}", it.id),
+            title: title(&it),
+            summary: it.comment,
+            url: place_url(it.element_id),
+            date: it.created_at,
+        })
+        .collect();
+    Ok(Feed {
+        url: feed_url(&base_url, "new-comments"),
+        title: "BTC Map - New Comments".into(),
+        items,
+    }
+    .respond(&req))
+}
+
+#[get("/new-comments/{area}")]
+pub async fn new_comments_for_area(
+    req: HttpRequest,
+    area: Path<String>,
+    base_url: Data<ApiBaseUrl>,
+    pool: Data<MainPool>,
+) -> Result<impl Responder> {
+    let area = db::main::area::queries::select_by_id_or_alias(area.to_string(), &pool).await?;
+    let mut comments = service::area::get_comments(&area, false, &pool).await?;
+    comments.sort_by_key(|it| std::cmp::Reverse(it.created_at));
+    comments.truncate(MAX_ITEMS);
+    let mut items = vec![];
+    for comment in comments {
+        let element = db::main::element::queries::select_by_id(comment.element_id, &pool).await?;
+        if element.deleted_at.is_none() {
+            items.push(FeedItem {
+                id: format!("https://btcmap.org/comment/{}", comment.id),
+                title: title(&comment),
+                summary: comment.comment,
+                url: place_url(element.id),
+                date: comment.created_at,
+            });
+        }
+    }
+    Ok(Feed {
+        url: feed_url(&base_url, &format!("new-comments/{}", area.id)),
+        title: format!("BTC Map - New Comments in {}", area.name()),
+        items,
+    }
+    .respond(&req))
+}
+
+/// Replies and merchant responses are marked so they make sense out of the
+/// thread context
+fn title(comment: &ElementComment) -> String {
+    if comment.official {
+        format!("Official response: {}", comment.comment)
+    } else if comment.parent_id.is_some() {
+        format!("Reply: {}", comment.comment)
+    } else {
+        comment.comment.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::main::test::pool;
+    use crate::rest::nostr_auth::ApiBaseUrl;
+    use crate::service::overpass::OverpassElement;
+    use crate::{db, Result};
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use actix_web::{test, App};
+    use serde_json::Value;
+
+    #[test]
+    async fn new_comments_marks_replies() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let question =
+            db::main::element_comment::queries::insert(element.id, "Open on Sunday?", &pool)
+                .await?;
+        db::main::element_comment::queries::insert_threaded(
+            element.id,
+            Some(question.id),
+            false,
+            None,
+            "I think so",
+            &pool,
+        )
+        .await?;
+        db::main::element_comment::queries::insert_threaded(
+            element.id,
+            Some(question.id),
+            true,
+            None,
+            "Yes, 10-14",
+            &pool,
+        )
+        .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .app_data(Data::new(ApiBaseUrl("https://api.example.com".into())))
+                .service(super::new_comments),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/new-comments?format=json")
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        let mut titles: Vec<&str> = res["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|it| it["title"].as_str().unwrap())
+            .collect();
+        titles.sort();
+        assert_eq!(
+            vec![
+                "Official response: Yes, 10-14",
+                "Open on Sunday?",
+                "Reply: I think so"
+            ],
+            titles
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use super::{feed_url, Feed, FeedItem, MAX_ITEMS};
+use crate::db::main::MainPool;
+use crate::rest::nostr_auth::ApiBaseUrl;
+use crate::service::event_schedule::{self, Timezones, EXPANSION_WINDOW};
+use crate::{db, Result};
+use actix_web::{
+    get,
+    web::{Data, Path},
+    HttpRequest, Responder,
+};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+#[get("/upcoming-events/{area}")]
+pub async fn upcoming_events_for_area(
+    req: HttpRequest,
+    area: Path<String>,
+    base_url: Data<ApiBaseUrl>,
+    pool: Data<MainPool>,
+) -> Result<impl Responder> {
+    let area = db::main::area::queries::select_by_id_or_alias(area.to_string(), &pool).await?;
+    let now = OffsetDateTime::now_utc();
+    let api_base_url = base_url.0.trim_end_matches('/');
+    let mut timezones = Timezones::default();
+    let mut items = vec![];
+    for event in db::main::event::queries::select_by_area_id(area.id, &pool).await? {
+        // Same rule as GET /v4/events: recurring events are listed while they
+        // have upcoming occurrences, events without a start date are TBA
+        let next_occurrence = match &event.cron_schedule {
+            Some(_) => {
+                let tz = timezones.get(&event, &pool).await?;
+                event_schedule::occurrences(&event, tz, now, now + EXPANSION_WINDOW)
+                    .ok()
+                    .map(|it| it.into_iter().next())
+            }
+            None => None,
+        };
+        let starts_at = match next_occurrence {
+            Some(Some(occurrence)) => Some(occurrence.starts_at),
+            Some(None) => continue,
+            None => event.starts_at,
+        };
+        if starts_at.is_some_and(|it| it <= now) {
+            continue;
+        }
+        let summary = match (starts_at, &event.cron_schedule) {
+            (Some(starts_at), Some(_)) => format!("Next on {}", starts_at.format(&Rfc3339)?),
+            (Some(starts_at), None) => format!("Starts at {}", starts_at.format(&Rfc3339)?),
+            (None, _) => "Check the event website for the schedule".into(),
+        };
+        items.push(FeedItem {
+            id: format!("{api_base_url}/v4/events/{}", event.id),
+            title: event.name,
+            summary,
+            url: event.website,
+            date: event.starts_at.unwrap_or(event.created_at),
+        });
+    }
+    items.truncate(MAX_ITEMS);
+    Ok(Feed {
+        url: feed_url(&base_url, &format!("upcoming-events/{}", area.id)),
+        title: format!("BTC Map - Upcoming Events in {}", area.name()),
+        items,
+    }
+    .respond(&req))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::main::area::schema::Area;
+    use crate::db::main::test::pool;
+    use crate::rest::nostr_auth::ApiBaseUrl;
+    use crate::{db, Result};
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use actix_web::{test, App};
+    use time::macros::datetime;
+
+    #[test]
+    async fn upcoming_events_for_area() -> Result<()> {
+        let pool = pool();
+        let area = db::main::area::queries::insert(Area::mock_tags(), &pool).await?;
+        for (area_id, name, starts_at) in [
+            (
+                Some(area.id),
+                "Upcoming",
+                Some(datetime!(2099-01-01 0:00 UTC)),
+            ),
+            (Some(area.id), "Past", Some(datetime!(2000-01-01 0:00 UTC))),
+            (None, "Elsewhere", Some(datetime!(2099-01-01 0:00 UTC))),
+        ] {
+            db::main::event::queries::insert(
+                area_id,
+                1.23,
+                4.56,
+                name.into(),
+                "https://example.com".into(),
+                starts_at,
+                None,
+                None,
+                &pool,
+            )
+            .await?;
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .app_data(Data::new(ApiBaseUrl("https://api.example.com".into())))
+                .service(super::upcoming_events_for_area),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri(&format!("/upcoming-events/{}", area.id))
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(1, body.matches("<entry>").count());
+        assert!(body.contains("<title>Upcoming</title>"));
+        assert!(body.contains("<id>https://api.example.com/v4/events/1</id>"));
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use super::{Feed, HOME_PAGE_URL};
+use serde_json::json;
+use time::format_description::well_known::Rfc3339;
+
+/// Renders a JSON Feed 1.1 document, see https://www.jsonfeed.org/version/1.1/
+pub fn render(feed: &Feed) -> String {
+    let items: Vec<_> = feed
+        .items
+        .iter()
+        .map(|item| {
+            json!({
+                "id": item.id,
+                "url": item.url,
+                "title": item.title,
+                "content_text": item.summary,
+                "date_published": item.date.format(&Rfc3339).unwrap(),
+                "authors": [{ "name": "BTC Map" }],
+            })
+        })
+        .collect();
+    json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": feed.title,
+        "home_page_url": HOME_PAGE_URL,
+        "feed_url": feed.url,
+        "items": items,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::test;
+    use serde_json::Value;
+
+    #[test]
+    async fn render() -> crate::Result<()> {
+        let res: Value = serde_json::from_str(&super::render(&crate::feed::test::feed()))?;
+        assert_eq!("https://jsonfeed.org/version/1.1", res["version"]);
+        assert_eq!("Test & <Feed>", res["title"]);
+        assert_eq!("https://api.example.com/feeds/test", res["feed_url"]);
+        assert_eq!("Bob's \"Cafe\"", res["items"][0]["title"]);
+        assert_eq!("2025-01-02T03:04:05Z", res["items"][0]["date_published"]);
+        Ok(())
+    }
+}
@@ -1 +1,194 @@
 pub mod atom;
+pub mod boosts;
+pub mod comments;
+pub mod events;
+pub mod json;
+pub mod place_issues;
+pub mod places;
+pub mod rss;
+
+use crate::rest::nostr_auth::ApiBaseUrl;
+use actix_web::{
+    http::header::{ACCEPT, VARY},
+    web::Query,
+    HttpRequest, HttpResponse,
+};
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+pub const HOME_PAGE_URL: &str = "https://btcmap.org";
+
+/// Max number of items served by a single feed
+pub const MAX_ITEMS: usize = 100;
+
+/// Format-neutral feed, rendered as Atom, RSS 2.0 or JSON Feed 1.1 depending
+/// on what the client asked for.
+pub struct Feed {
+    /// Canonical URL of this feed, also used as its id
+    pub url: String,
+    pub title: String,
+    pub items: Vec<FeedItem>,
+}
+
+pub struct FeedItem {
+    /// Stable, globally unique id. Feed readers use it to detect new items.
+    pub id: String,
+    pub title: String,
+    pub summary: String,
+    pub url: String,
+    pub date: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+    Json,
+}
+
+#[derive(Deserialize)]
+struct FeedArgs {
+    format: Option<String>,
+}
+
+impl FeedFormat {
+    /// Explicit `?format=atom|rss|json` wins over the `Accept` header. Atom is
+    /// the default so existing subscribers keep getting what they used to.
+    pub fn negotiate(req: &HttpRequest) -> Self {
+        let format = Query::<FeedArgs>::from_query(req.query_string())
+            .ok()
+            .and_then(|it| it.into_inner().format);
+        match format.as_deref() {
+            Some("atom") => return FeedFormat::Atom,
+            Some("rss") => return FeedFormat::Rss,
+            Some("json") => return FeedFormat::Json,
+            _ => {}
+        }
+        let accept = req
+            .headers()
+            .get(ACCEPT)
+            .and_then(|it| it.to_str().ok())
+            .unwrap_or_default();
+        if accept.contains("application/feed+json") || accept.contains("application/json") {
+            FeedFormat::Json
+        } else if accept.contains("application/rss+xml") {
+            FeedFormat::Rss
+        } else {
+            FeedFormat::Atom
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8",
+            FeedFormat::Json => "application/feed+json; charset=utf-8",
+        }
+    }
+}
+
+impl Feed {
+    pub fn respond(&self, req: &HttpRequest) -> HttpResponse {
+        let format = FeedFormat::negotiate(req);
+        let body = match format {
+            FeedFormat::Atom => atom::render(self),
+            FeedFormat::Rss => rss::render(self),
+            FeedFormat::Json => json::render(self),
+        };
+        HttpResponse::Ok()
+            .insert_header(("content-type", format.content_type()))
+            .insert_header((VARY, "Accept"))
+            .body(body)
+    }
+}
+
+pub fn feed_url(base_url: &ApiBaseUrl, path: &str) -> String {
+    format!("{}/feeds/{path}", base_url.0.trim_end_matches('/'))
+}
+
+pub fn place_url(element_id: i64) -> String {
+    format!("{HOME_PAGE_URL}/merchant/{element_id}")
+}
+
+fn xml_escape(str: &str) -> String {
+    str.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Feed, FeedFormat, FeedItem};
+    use crate::rest::nostr_auth::ApiBaseUrl;
+    use actix_web::test;
+    use actix_web::test::TestRequest;
+    use time::macros::datetime;
+
+    pub fn feed() -> Feed {
+        Feed {
+            url: "https://api.example.com/feeds/test".into(),
+            title: "Test & <Feed>".into(),
+            items: vec![FeedItem {
+                id: "https://btcmap.org/event/1".into(),
+                title: "Bob's \"Cafe\"".into(),
+                summary: "Summary".into(),
+                url: "https://btcmap.org/merchant/1".into(),
+                date: datetime!(2025-01-02 03:04:05 UTC),
+            }],
+        }
+    }
+
+    #[test]
+    async fn negotiate_defaults_to_atom() {
+        let req = TestRequest::get().to_http_request();
+        assert_eq!(FeedFormat::Atom, FeedFormat::negotiate(&req));
+        let req = TestRequest::get()
+            .insert_header(("accept", "text/html,*/*"))
+            .to_http_request();
+        assert_eq!(FeedFormat::Atom, FeedFormat::negotiate(&req));
+    }
+
+    #[test]
+    async fn negotiate_by_accept_header() {
+        let req = TestRequest::get()
+            .insert_header(("accept", "application/feed+json"))
+            .to_http_request();
+        assert_eq!(FeedFormat::Json, FeedFormat::negotiate(&req));
+        let req = TestRequest::get()
+            .insert_header(("accept", "application/rss+xml, application/xml;q=0.9"))
+            .to_http_request();
+        assert_eq!(FeedFormat::Rss, FeedFormat::negotiate(&req));
+    }
+
+    #[test]
+    async fn negotiate_by_query_overrides_accept() {
+        let req = TestRequest::get()
+            .uri("/feeds/new-places?format=rss")
+            .insert_header(("accept", "application/feed+json"))
+            .to_http_request();
+        assert_eq!(FeedFormat::Rss, FeedFormat::negotiate(&req));
+        let req = TestRequest::get()
+            .uri("/feeds/new-places?format=json")
+            .to_http_request();
+        assert_eq!(FeedFormat::Json, FeedFormat::negotiate(&req));
+    }
+
+    #[test]
+    async fn feed_url() {
+        let base_url = ApiBaseUrl("https://api.example.com/".into());
+        assert_eq!(
+            "https://api.example.com/feeds/new-places/1",
+            super::feed_url(&base_url, "new-places/1"),
+        );
+    }
+
+    #[test]
+    async fn xml_escape() {
+        assert_eq!(
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;",
+            super::xml_escape(r#"<a href="x">Tom & Jerry's</a>"#),
+        );
+    }
+}
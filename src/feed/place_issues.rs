@@ -0,0 +1,43 @@
+use super::{feed_url, place_url, Feed, FeedItem, MAX_ITEMS};
+use crate::db::main::MainPool;
+use crate::rest::nostr_auth::ApiBaseUrl;
+use crate::{db, Result};
+use actix_web::{
+    get,
+    web::{Data, Path},
+    HttpRequest, Responder,
+};
+
+#[get("/place-issues/{area}")]
+pub async fn place_issues_for_area(
+    req: HttpRequest,
+    area: Path<String>,
+    base_url: Data<ApiBaseUrl>,
+    pool: Data<MainPool>,
+) -> Result<impl Responder> {
+    let area = db::main::area::queries::select_by_id_or_alias(area.to_string(), &pool).await?;
+    let issues =
+        db::main::element_issue::queries::select_by_area_id(area.id, MAX_ITEMS as i64, &pool)
+            .await?;
+    let mut items = Vec::with_capacity(issues.len());
+    for issue in issues {
+        let element = db::main::element::queries::select_by_id(issue.element_id, &pool).await?;
+        items.push(FeedItem {
+            id: format!("https://btcmap.org/issue/{}", issue.id),
+            title: format!(
+                "{}: {}",
+                element.name(Some("en")),
+                issue.code.replace('_', " ")
+            ),
+            summary: "This place needs some attention, check BTC Map for more details".into(),
+            url: place_url(element.id),
+            date: issue.created_at,
+        });
+    }
+    Ok(Feed {
+        url: feed_url(&base_url, &format!("place-issues/{}", area.id)),
+        title: format!("BTC Map - Place Issues in {}", area.name()),
+        items,
+    }
+    .respond(&req))
+}
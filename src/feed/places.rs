@@ -0,0 +1,166 @@
+use super::{feed_url, place_url, Feed, FeedItem, MAX_ITEMS};
+use crate::db::main::element_event::schema::ElementEvent;
+use crate::db::main::MainPool;
+use crate::rest::nostr_auth::ApiBaseUrl;
+use crate::{db, Result};
+use actix_web::{
+    get,
+    web::{Data, Path},
+    HttpRequest, Responder,
+};
+
+#[get("/new-places")]
+pub async fn new_places(
+    req: HttpRequest,
+    base_url: Data<ApiBaseUrl>,
+    pool: Data<MainPool>,
+) -> Result<impl Responder> {
+    let events = db::main::element_event::queries::select_by_type(
+        "create".into(),
+        Some("DESC".into()),
+        Some(MAX_ITEMS as i64),
+        &pool,
+    )
+    .await?;
+    Ok(Feed {
+        url: feed_url(&base_url, "new-places"),
+        title: "BTC Map - New Places".into(),
+        items: events_to_items(events, "Check BTC Map for more details", &pool).await?,
+    }
+    .respond(&req))
+}
+
+#[get("/new-places/{area}")]
+pub async fn new_places_for_area(
+    req: HttpRequest,
+    area: Path<String>,
+    base_url: Data<ApiBaseUrl>,
+    pool: Data<MainPool>,
+) -> Result<impl Responder> {
+    let area = db::main::area::queries::select_by_id_or_alias(area.to_string(), &pool).await?;
+    let events = db::main::element_event::queries::select_by_type_for_area(
+        area.id,
+        "create",
+        MAX_ITEMS as i64,
+        &pool,
+    )
+    .await?;
+    Ok(Feed {
+        url: feed_url(&base_url, &format!("new-places/{}", area.id)),
+        title: format!("BTC Map - New Places in {}", area.name()),
+        items: events_to_items(events, "Check BTC Map for more details", &pool).await?,
+    }
+    .respond(&req))
+}
+
+#[get("/removed-places/{area}")]
+pub async fn removed_places_for_area(
+    req: HttpRequest,
+    area: Path<String>,
+    base_url: Data<ApiBaseUrl>,
+    pool: Data<MainPool>,
+) -> Result<impl Responder> {
+    let area = db::main::area::queries::select_by_id_or_alias(area.to_string(), &pool).await?;
+    let events = db::main::element_event::queries::select_by_type_for_area(
+        area.id,
+        "delete",
+        MAX_ITEMS as i64,
+        &pool,
+    )
+    .await?;
+    Ok(Feed {
+        url: feed_url(&base_url, &format!("removed-places/{}", area.id)),
+        title: format!("BTC Map - Removed Places in {}", area.name()),
+        items: events_to_items(events, "This place has been removed from the map", &pool).await?,
+    }
+    .respond(&req))
+}
+
+async fn events_to_items(
+    events: Vec<ElementEvent>,
+    summary: &str,
+    pool: &MainPool,
+) -> Result<Vec<FeedItem>> {
+    let mut items = Vec::with_capacity(events.len());
+    for event in events {
+        let element = db::main::element::queries::select_by_id(event.element_id, pool).await?;
+        items.push(FeedItem {
+            id: format!("https://btcmap.org/event/{}", event.id),
+            title: element.name(None),
+            summary: summary.into(),
+            url: place_url(element.id),
+            date: event.created_at,
+        });
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::main::test::pool;
+    use crate::rest::nostr_auth::ApiBaseUrl;
+    use crate::service::osm::EditingApiUser;
+    use crate::service::overpass::OverpassElement;
+    use crate::{db, Result};
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use actix_web::{test, App};
+    use serde_json::Value;
+
+    #[test]
+    async fn new_places_for_area() -> Result<()> {
+        let pool = pool();
+        let area = db::main::area::queries::insert(
+            crate::db::main::area::schema::Area::mock_tags(),
+            &pool,
+        )
+        .await?;
+        db::main::osm_user::queries::insert(1, EditingApiUser::mock(), &pool).await?;
+        let inside = db::main::element::queries::insert(
+            OverpassElement::mock_with_tag(1, "name", "Inside"),
+            &pool,
+        )
+        .await?;
+        let outside = db::main::element::queries::insert(OverpassElement::mock(2), &pool).await?;
+        db::main::area_element::queries::insert(area.id, inside.id, &pool).await?;
+        db::main::element_event::queries::insert(1, inside.id, "create", &pool).await?;
+        db::main::element_event::queries::insert(1, inside.id, "delete", &pool).await?;
+        db::main::element_event::queries::insert(1, outside.id, "create", &pool).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .app_data(Data::new(ApiBaseUrl("https://api.example.com".into())))
+                .service(super::new_places_for_area)
+                .service(super::removed_places_for_area),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri(&format!("/new-places/{}", area.id))
+            .insert_header(("accept", "application/feed+json"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(
+            "application/feed+json; charset=utf-8",
+            res.headers().get("content-type").unwrap()
+        );
+        let res: Value = test::read_body_json(res).await;
+        assert_eq!(
+            format!("https://api.example.com/feeds/new-places/{}", area.id),
+            res["feed_url"]
+        );
+        let items = res["items"].as_array().unwrap();
+        assert_eq!(1, items.len());
+        assert_eq!("Inside", items[0]["title"]);
+        let req = TestRequest::get()
+            .uri(&format!("/removed-places/{}?format=rss", area.id))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(
+            "application/rss+xml; charset=utf-8",
+            res.headers().get("content-type").unwrap()
+        );
+        let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+        assert_eq!(1, body.matches("<item>").count());
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use super::{xml_escape, Feed, FeedItem, HOME_PAGE_URL};
+use time::format_description::well_known::Rfc2822;
+use time::OffsetDateTime;
+
+pub fn render(feed: &Feed) -> String {
+    let feed_url = xml_escape(&feed.url);
+    let feed_title = xml_escape(&feed.title);
+    let mut res = String::new();
+    res.push_str(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+    res.push_str(r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">"#);
+    res.push_str("<channel>");
+    res.push_str(&format!("<title>{feed_title}</title>"));
+    res.push_str(&format!("<link>{HOME_PAGE_URL}</link>"));
+    res.push_str(&format!("<description>{feed_title}</description>"));
+    res.push_str(&format!(
+        r#"<atom:link href="{feed_url}" rel="self" type="application/rss+xml"/>"#
+    ));
+    res.push_str(&format!(
+        "<lastBuildDate>{}</lastBuildDate>",
+        OffsetDateTime::now_utc().format(&Rfc2822).unwrap()
+    ));
+    for item in &feed.items {
+        res.push_str(&render_item(item));
+    }
+    res.push_str("</channel>");
+    res.push_str("</rss>");
+    res
+}
+
+fn render_item(item: &FeedItem) -> String {
+    let guid = xml_escape(&item.id);
+    let title = xml_escape(&item.title);
+    let description = xml_escape(&item.summary);
+    let url = xml_escape(&item.url);
+    let pub_date = item.date.format(&Rfc2822).unwrap();
+    format!(
+        r#"
+            <item>
+                <title>{title}</title>
+                <link>{url}</link>
+                <description>{description}</description>
+                <guid isPermaLink="false">{guid}</guid>
+                <pubDate>{pub_date}</pubDate>
+            </item>
+        "#
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::test;
+
+    #[test]
+    async fn render() {
+        let res = super::render(&crate::feed::test::feed());
+        assert!(res.contains(r#"<rss version="2.0""#));
+        assert!(res.contains("<title>Test &amp; &lt;Feed&gt;</title>"));
+        assert!(res.contains(r#"<guid isPermaLink="false">https://btcmap.org/event/1</guid>"#));
+        assert!(res.contains("<pubDate>Thu, 02 Jan 2025 03:04:05 +0000</pubDate>"));
+    }
+}
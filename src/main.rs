@@ -14,7 +14,7 @@ use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 mod feed;
 mod rpc;
-use crate::db::main::conf::schema::Conf;
+use crate::service::conf::SharedConf;
 use crate::service::log::Log;
 use actix_web::web::{scope, Data};
 mod db;
@@ -31,35 +31,35 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 /// - empty (the default): every origin is allowed
 /// - one or more entries: only those origins are allowed
 ///
+/// Origins are checked against the current conf on every request, so edits
+/// apply without a restart.
+///
 /// The middleware always allows every method and every header, and caches
 /// preflight responses for 1 hour, which is enough for any other browser
 /// client to use the API without CORS errors.
-fn build_cors(conf: &Conf) -> Cors {
-    let mut cors = Cors::default()
+fn build_cors(conf: &SharedConf) -> Cors {
+    let conf = conf.clone();
+    Cors::default()
         .allow_any_method()
         .allow_any_header()
-        .max_age(3600);
-
-    if conf.cors_origins.is_empty() {
-        cors = cors.allow_any_origin();
-    } else {
-        for origin in &conf.cors_origins {
-            cors = cors.allowed_origin(origin);
-        }
-    }
-
-    cors
+        .max_age(3600)
+        .allowed_origin_fn(move |origin, _| {
+            let origins = &conf.load().cors_origins;
+            origins.is_empty() || origins.iter().any(|it| origin == it.as_str())
+        })
 }
 
 #[actix_web::main]
 async fn main() -> Result<()> {
     init_env();
+    service::conf_crypto::init()?;
+    service::client_ip::init()?;
 
     let main_pool = db::main::pool()?;
     let image_pool = db::image::pool()?;
     let log_pool = db::log::pool()?;
 
-    let conf = db::main::conf::queries::select(&main_pool).await?;
+    service::api_token::init(&main_pool).await?;
 
     // Trusted external base URL of this API. Used by the NIP-98 NostrAuth
     // extractor to reconstruct the URL the signed event must bind to.
@@ -74,10 +74,19 @@ async fn main() -> Result<()> {
     // can break out of their loops before the actix runtime drops on SIGTERM.
     // See the note in `service::wallet_cache::init` for why this matters.
     let shutdown = CancellationToken::new();
+    let conf = service::conf::init(&main_pool, shutdown.clone()).await?;
     service::wallet_cache::init(&main_pool, shutdown.clone());
+    service::nostr::init(&main_pool, shutdown.clone());
+    service::invoice_stream::init(&main_pool, shutdown.clone());
+    service::abuse::init(&main_pool, &log_pool, &conf, shutdown.clone());
+    // shared by all workers, so it's created outside of the app factory
+    let rate_limiter = Data::new(service::rate_limit::RateLimiter::default());
+    let bans = Data::new(service::ban::BanCache::default());
 
     HttpServer::new(move || {
         App::new()
+            // runs within Log, so limited requests are logged too
+            .wrap(from_fn(service::rate_limit::limit))
             .wrap(Log)
             .wrap(NormalizePath::trim())
             .wrap(Compress::default())
@@ -87,11 +96,14 @@ async fn main() -> Result<()> {
             .app_data(Data::new(image_pool.clone()))
             .app_data(Data::new(log_pool.clone()))
             .app_data(Data::new(conf.clone()))
+            .app_data(rate_limiter.clone())
+            .app_data(bans.clone())
             .app_data(web::PayloadConfig::new(64 * 1024 * 1024))
             .app_data(Data::new(rest::nostr_auth::ApiBaseUrl(
                 api_base_url.clone(),
             )))
             .service(og::element::get_element)
+            .service(rest::lnurl::get_lightning_address)
             .service(
                 scope("rpc")
                     .app_data(Data::new(log_pool.clone()))
@@ -100,10 +112,14 @@ async fn main() -> Result<()> {
             )
             .service(
                 scope("feeds")
-                    .service(feed::atom::new_places)
-                    .service(feed::atom::new_places_for_area)
-                    .service(feed::atom::new_comments)
-                    .service(feed::atom::new_comments_for_area),
+                    .service(feed::places::new_places)
+                    .service(feed::places::new_places_for_area)
+                    .service(feed::places::removed_places_for_area)
+                    .service(feed::comments::new_comments)
+                    .service(feed::comments::new_comments_for_area)
+                    .service(feed::boosts::boosts_for_area)
+                    .service(feed::place_issues::place_issues_for_area)
+                    .service(feed::events::upcoming_events_for_area),
             )
             .service(
                 scope("v2")
@@ -196,13 +212,24 @@ async fn main() -> Result<()> {
                             .service(rest::v4::places::search)
                             .service(rest::v4::places::get_by_id)
                             .service(rest::v4::places::get_by_id_comments)
+                            .service(rest::v4::places::get_by_id_reviews)
+                            .service(rest::v4::places::post_by_id_review)
                             .service(rest::v4::places::get_by_id_areas)
+                            .service(rest::v4::places::get_by_id_boosts)
                             .service(rest::v4::places::get_by_id_activity),
                     )
-                    .service(scope("invoices").service(rest::v4::invoices::get_by_id))
+                    .service(
+                        scope("invoices")
+                            .service(rest::v4::invoices::get_by_id_wait)
+                            .service(rest::v4::invoices::get_by_id)
+                            .service(rest::v4::invoices::post_renew),
+                    )
+                    .service(rest::v4::events::get_ics)
                     .service(
                         scope("events")
                             .service(rest::v4::events::get)
+                            .service(rest::v4::event_submissions::post)
+                            .service(rest::v4::event_submissions::get)
                             .service(rest::v4::events::get_by_id),
                     )
                     .service(
@@ -215,11 +242,19 @@ async fn main() -> Result<()> {
                             .service(rest::v4::place_comments::get)
                             .service(rest::v4::place_comments::get_quote)
                             .service(rest::v4::place_comments::get_by_id)
-                            .service(rest::v4::place_comments::post),
+                            .service(rest::v4::place_comments::post)
+                            .service(rest::v4::place_comments::post_official)
+                            .service(rest::v4::place_comments::post_free)
+                            .service(rest::v4::place_comments::put)
+                            .service(rest::v4::place_comments::delete)
+                            .service(rest::v4::place_comments::post_flag),
                     )
                     .service(
                         scope("place-boosts")
                             .service(rest::v4::place_boosts::get_quote)
+                            .service(rest::lnurl::get_boost_pay_request)
+                            .service(rest::lnurl::get_boost_callback)
+                            .service(rest::v4::place_boosts::get)
                             .service(rest::v4::place_boosts::post),
                     )
                     .service(scope("search").service(rest::v4::search::get))
@@ -231,6 +266,8 @@ async fn main() -> Result<()> {
                             .service(rest::v4::areas::delete_saved)
                             .service(rest::v4::areas::get_by_id_top_editors)
                             .service(rest::v4::areas::get_by_id_image)
+                            .service(rest::v4::areas::get_by_id_events_ics)
+                            .service(rest::v4::areas::get_by_id_boosts)
                             .service(rest::v4::areas::get_by_id)
                             .service(rest::v4::areas::get),
                     )
@@ -282,16 +319,17 @@ fn init_env() {
 mod test {
     use super::build_cors;
     use crate::db::main::conf::schema::Conf;
+    use crate::service::conf::SharedConf;
     use actix_web::http::header::HeaderValue;
     use actix_web::http::StatusCode;
     use actix_web::test::TestRequest;
     use actix_web::{test, App};
 
-    fn conf_with(origins: &[&str]) -> Conf {
-        Conf {
+    fn conf_with(origins: &[&str]) -> SharedConf {
+        SharedConf::new(Conf {
             cors_origins: origins.iter().map(|s| s.to_string()).collect(),
             ..Default::default()
-        }
+        })
     }
 
     #[test]
@@ -317,8 +355,8 @@ mod test {
             .headers()
             .get(actix_web::http::header::ACCESS_CONTROL_ALLOW_ORIGIN)
             .expect("missing Access-Control-Allow-Origin");
-        // actix-cors echoes the request Origin back when allow_any_origin()
-        // is set, instead of writing a literal "*". Both forms are valid CORS
+        // actix-cors echoes the request Origin back for origins accepted by
+        // allowed_origin_fn(), instead of writing a literal "*". Both forms are valid CORS
         // responses for a non-credentialed request; the browser only checks
         // that the value is present and matches the Origin.
         assert_eq!(
@@ -379,4 +417,28 @@ mod test {
             .get(actix_web::http::header::ACCESS_CONTROL_ALLOW_ORIGIN)
             .is_none());
     }
+
+    #[test]
+    async fn cors_follows_conf_reloads() {
+        let conf = conf_with(&["https://allowed.example.com"]);
+
+        let app = test::init_service(App::new().wrap(build_cors(&conf))).await;
+        let preflight = || {
+            TestRequest::default()
+                .method(actix_web::http::Method::OPTIONS)
+                .uri("/rpc")
+                .insert_header(("Origin", "https://new.example.com"))
+                .insert_header(("Access-Control-Request-Method", "POST"))
+                .to_request()
+        };
+        let res = test::call_service(&app, preflight()).await;
+        assert_ne!(StatusCode::OK, res.status());
+
+        conf.store(Conf {
+            cors_origins: vec!["https://new.example.com".into()],
+            ..Default::default()
+        });
+        let res = test::call_service(&app, preflight()).await;
+        assert_eq!(StatusCode::OK, res.status());
+    }
 }
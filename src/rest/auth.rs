@@ -2,6 +2,7 @@ use crate::db;
 use crate::db::main::access_token::schema::AccessToken;
 use crate::db::main::user::schema::User;
 use crate::db::main::MainPool;
+use crate::service;
 use actix_web::{dev::Payload, http::header, web::Data, FromRequest, HttpRequest};
 use std::future::Future;
 use std::pin::Pin;
@@ -66,6 +67,26 @@ impl FromRequest for Auth {
                 });
             };
 
+            // method restricted tokens are for RPC only
+            if !access_token.allows_method(None)
+                || !access_token.allows_ip(service::client_ip::verified(&req).as_ref())
+            {
+                return Ok(Auth {
+                    user: None,
+                    token: None,
+                });
+            }
+
+            if service::api_token::touch(&access_token, &pool)
+                .await
+                .is_err()
+            {
+                return Ok(Auth {
+                    user: None,
+                    token: None,
+                });
+            }
+
             let Ok(user) = db::main::user::queries::select_by_id(access_token.user_id, &pool).await
             else {
                 return Ok(Auth {
@@ -45,6 +45,21 @@ impl RestApiError {
             "Authentication required".to_string(),
         )
     }
+
+    pub fn too_many_requests(message: impl Into<String>) -> Self {
+        Self::new(RestApiErrorCode::TooManyRequests, message.into())
+    }
+
+    pub fn service_unavailable(message: impl Into<String>) -> Self {
+        Self::new(RestApiErrorCode::ServiceUnavailable, message.into())
+    }
+
+    pub fn forbidden() -> Self {
+        Self::new(
+            RestApiErrorCode::Forbidden,
+            "You are not allowed to perform this action".to_string(),
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -53,6 +68,9 @@ pub enum RestApiErrorCode {
     NotFound,
     Database,
     Unauthorized,
+    Forbidden,
+    TooManyRequests,
+    ServiceUnavailable,
 }
 
 impl fmt::Display for RestApiError {
@@ -68,6 +86,9 @@ impl std::fmt::Display for RestApiErrorCode {
             RestApiErrorCode::NotFound => write!(f, "not_found"),
             RestApiErrorCode::Database => write!(f, "database"),
             RestApiErrorCode::Unauthorized => write!(f, "unauthorized"),
+            RestApiErrorCode::Forbidden => write!(f, "forbidden"),
+            RestApiErrorCode::TooManyRequests => write!(f, "too_many_requests"),
+            RestApiErrorCode::ServiceUnavailable => write!(f, "service_unavailable"),
         }
     }
 }
@@ -79,6 +100,9 @@ impl RestApiErrorCode {
             Self::NotFound => StatusCode::NOT_FOUND,
             Self::Database => StatusCode::INTERNAL_SERVER_ERROR,
             Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            Self::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
         }
     }
 }
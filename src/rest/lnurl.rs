@@ -0,0 +1,397 @@
+use crate::db;
+use crate::db::main::conf::schema::Conf;
+use crate::db::main::element::schema::Element;
+use crate::db::main::MainPool;
+use crate::rest::nostr_auth::ApiBaseUrl;
+use crate::service;
+use crate::service::conf::SharedConf;
+use crate::service::lnurl::BOOST_USERNAME_PREFIX;
+use actix_web::get;
+use actix_web::web::Data;
+use actix_web::web::Path;
+use actix_web::web::Query;
+use actix_web::HttpResponse;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+
+// LNURL wallets expect their own response format (LUD-06), errors included,
+// so these handlers don't use `RestApiError`
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PayRequest {
+    callback: String,
+    /// Millisatoshis
+    min_sendable: i64,
+    /// Millisatoshis
+    max_sendable: i64,
+    metadata: String,
+    tag: &'static str,
+    /// NIP-57, set when zap receipts can be published
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allows_nostr: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nostr_pubkey: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PayResponse {
+    pr: String,
+    routes: Vec<()>,
+}
+
+fn error(reason: impl Into<String>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "status": "ERROR",
+        "reason": reason.into(),
+    }))
+}
+
+/// `boost-{place_id}@{host}`
+fn boost_identifier(place_id: &str, base_url: &ApiBaseUrl) -> String {
+    let host = url::Url::parse(&base_url.0)
+        .ok()
+        .and_then(|it| it.host_str().map(str::to_string))
+        .unwrap_or_default();
+    format!("{BOOST_USERNAME_PREFIX}{place_id}@{host}")
+}
+
+async fn select_element(place_id: &str, pool: &MainPool) -> Result<Element, HttpResponse> {
+    match db::main::element::queries::select_by_id_or_osm_id(place_id, pool).await {
+        Ok(element) if element.deleted_at.is_none() => Ok(element),
+        Ok(_) | Err(crate::Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows)) => {
+            Err(error("place not found"))
+        }
+        Err(_) => Err(error("database error")),
+    }
+}
+
+async fn pay_request(
+    place_id: &str,
+    base_url: &ApiBaseUrl,
+    conf: &Conf,
+    pool: &MainPool,
+) -> HttpResponse {
+    let element = match select_element(place_id, pool).await {
+        Ok(element) => element,
+        Err(res) => return res,
+    };
+    let Ok(prices) = service::exchange_rate::current_boost_prices(conf).await else {
+        return error("boosts are not available");
+    };
+    let prices = &prices;
+    let Some(max_days) = service::boost::max_days(prices) else {
+        return error("boosts are not available");
+    };
+    let (Ok(min), Ok(max)) = (
+        service::boost::price(service::boost::MIN_DAYS, prices),
+        service::boost::price(max_days, prices),
+    ) else {
+        return error("boosts are not available");
+    };
+    let nostr_pubkey = service::nostr::signer(conf).map(|it| it.public_key().to_hex());
+    HttpResponse::Ok().json(PayRequest {
+        allows_nostr: nostr_pubkey.as_ref().map(|_| true),
+        nostr_pubkey,
+        callback: format!(
+            "{}/v4/place-boosts/lnurlp/{place_id}/callback",
+            base_url.0.trim_end_matches('/')
+        ),
+        min_sendable: min * 1000,
+        max_sendable: max * 1000,
+        metadata: service::lnurl::boost_metadata(&element, &boost_identifier(place_id, base_url)),
+        tag: "payRequest",
+    })
+}
+
+/// Lightning Address (LUD-16) entry point, `boost-{place_id}@{host}` resolves
+/// to this URL
+#[get("/.well-known/lnurlp/{username}")]
+pub async fn get_lightning_address(
+    username: Path<String>,
+    base_url: Data<ApiBaseUrl>,
+    conf: Data<SharedConf>,
+    pool: Data<MainPool>,
+) -> HttpResponse {
+    let conf = conf.load();
+    let Some(place_id) = username.strip_prefix(BOOST_USERNAME_PREFIX) else {
+        return error("unknown lightning address");
+    };
+    pay_request(place_id, &base_url, &conf, &pool).await
+}
+
+/// Plain LNURL-pay entry point, meant to be bech32 encoded into a QR code
+#[get("/lnurlp/{place_id}")]
+pub async fn get_boost_pay_request(
+    place_id: Path<String>,
+    base_url: Data<ApiBaseUrl>,
+    conf: Data<SharedConf>,
+    pool: Data<MainPool>,
+) -> HttpResponse {
+    let conf = conf.load();
+    pay_request(&place_id, &base_url, &conf, &pool).await
+}
+
+#[derive(Deserialize)]
+pub struct CallbackArgs {
+    /// Millisatoshis
+    amount: i64,
+    /// NIP-57 zap request
+    nostr: Option<String>,
+}
+
+#[get("/lnurlp/{place_id}/callback")]
+pub async fn get_boost_callback(
+    place_id: Path<String>,
+    args: Query<CallbackArgs>,
+    base_url: Data<ApiBaseUrl>,
+    conf: Data<SharedConf>,
+    pool: Data<MainPool>,
+) -> HttpResponse {
+    let conf = conf.load();
+    let element = match select_element(&place_id, &pool).await {
+        Ok(element) => element,
+        Err(res) => return res,
+    };
+    if args.amount % 1000 != 0 {
+        return error("amount must be a whole number of sats");
+    }
+    let amount_sats = args.amount / 1000;
+    let Ok(prices) = service::exchange_rate::current_boost_prices(&conf).await else {
+        return error("boosts are not available");
+    };
+    let days = match service::boost::days_for_amount(amount_sats, &prices) {
+        Ok(days) => days,
+        Err(e) => return error(e.to_string()),
+    };
+    // zap invoices commit to the zap request instead of the metadata
+    let description = match &args.nostr {
+        Some(zap_request) => {
+            let Some(keys) = service::nostr::signer(&conf) else {
+                return error("zaps are not supported");
+            };
+            if let Err(e) =
+                service::zap::verify_request(zap_request, args.amount, &keys.public_key())
+            {
+                return error(e.to_string());
+            }
+            zap_request.clone()
+        }
+        None => service::lnurl::boost_metadata(&element, &boost_identifier(&place_id, &base_url)),
+    };
+    let invoice = match service::invoice::create_with_description_hash(
+        &conf.invoice_backend,
+        format!("element_boost:{}:{}", element.id, days),
+        Some(service::lnurl::description_hash(&description)),
+        amount_sats,
+        &pool,
+    )
+    .await
+    {
+        Ok(invoice) => invoice,
+        Err(_) => return error("failed to create invoice"),
+    };
+    let invoice = match &args.nostr {
+        Some(zap_request) => {
+            match db::main::invoice::queries::set_zap_request(
+                invoice.id,
+                zap_request.clone(),
+                &pool,
+            )
+            .await
+            {
+                Ok(invoice) => invoice,
+                Err(_) => return error("database error"),
+            }
+        }
+        None => invoice,
+    };
+    HttpResponse::Ok().json(PayResponse {
+        pr: invoice.payment_request,
+        routes: vec![],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::main::conf::schema::{BoostPrice, Conf};
+    use crate::db::main::invoice::schema::InvoiceStatus;
+    use crate::db::main::test::pool;
+    use crate::rest::nostr_auth::ApiBaseUrl;
+    use crate::service::conf::SharedConf;
+    use crate::service::invoice_backend::FakeBackend;
+    use crate::service::overpass::OverpassElement;
+    use crate::{db, service, Result};
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use actix_web::{test, App};
+    use nostr::event::EventBuilder;
+    use nostr::key::Keys;
+    use nostr::{JsonUtil, Kind, Tag};
+    use serde_json::Value;
+
+    #[test]
+    async fn boost_via_lightning_address() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let conf = Conf {
+            invoice_backend: "fake".into(),
+            boost_element_prices: vec![
+                BoostPrice {
+                    days: 30,
+                    sats: 5000,
+                    fiat: None,
+                },
+                BoostPrice {
+                    days: 90,
+                    sats: 12000,
+                    fiat: None,
+                },
+            ],
+            ..Conf::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool.clone()))
+                .app_data(Data::new(SharedConf::new(conf)))
+                .app_data(Data::new(ApiBaseUrl("https://api.btcmap.org".into())))
+                .service(super::get_lightning_address)
+                .service(super::get_boost_pay_request)
+                .service(super::get_boost_callback),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri(&format!("/.well-known/lnurlp/boost-{}", element.id))
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("payRequest", res["tag"]);
+        assert_eq!(167_000, res["minSendable"]);
+        assert_eq!(12_000_000, res["maxSendable"]);
+        assert_eq!(
+            format!(
+                "https://api.btcmap.org/v4/place-boosts/lnurlp/{}/callback",
+                element.id
+            ),
+            res["callback"]
+        );
+        let metadata: Value = serde_json::from_str(res["metadata"].as_str().unwrap())?;
+        assert_eq!(
+            format!("boost-{}@api.btcmap.org", element.id),
+            metadata[1][1]
+        );
+
+        let req = TestRequest::get()
+            .uri(&format!("/lnurlp/{}/callback?amount=100000", element.id))
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("ERROR", res["status"]);
+
+        let req = TestRequest::get()
+            .uri(&format!("/lnurlp/{}/callback?amount=6000000", element.id))
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(res["pr"].as_str().unwrap().starts_with("lnfake6000"));
+        let invoices =
+            db::main::invoice::queries::select_by_status(InvoiceStatus::Unpaid, &pool).await?;
+        assert_eq!(1, invoices.len());
+        assert_eq!(
+            format!("element_boost:{}:39", element.id),
+            invoices[0].description
+        );
+
+        let req = TestRequest::get()
+            .uri("/.well-known/lnurlp/satoshi")
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("ERROR", res["status"]);
+        Ok(())
+    }
+
+    #[test]
+    async fn zap_boosts_place_and_publishes_receipt() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let btcmap = Keys::generate();
+        let secret_key = btcmap.secret_key().to_secret_hex();
+        pool.get()
+            .await?
+            .interact(move |conn| {
+                conn.execute(
+                    "UPDATE conf SET nostr_secret_key = ?1, nostr_relays = 'wss://btcmap.example.com'",
+                    [secret_key],
+                )
+            })
+            .await??;
+        let conf = Conf {
+            invoice_backend: "fake".into(),
+            boost_element_prices: vec![BoostPrice {
+                days: 30,
+                sats: 5000,
+                fiat: None,
+            }],
+            ..db::main::conf::queries::select(&pool).await?
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool.clone()))
+                .app_data(Data::new(SharedConf::new(conf)))
+                .app_data(Data::new(ApiBaseUrl("https://api.btcmap.org".into())))
+                .service(super::get_boost_pay_request)
+                .service(super::get_boost_callback),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri(&format!("/lnurlp/{}", element.id))
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(true, res["allowsNostr"]);
+        assert_eq!(btcmap.public_key().to_hex(), res["nostrPubkey"]);
+
+        let zap_request = EventBuilder::new(Kind::ZapRequest, "")
+            .tags([
+                Tag::parse(["p", &btcmap.public_key().to_hex()]).unwrap(),
+                Tag::parse(["amount", "5000000"]).unwrap(),
+                Tag::parse(["relays", "wss://zapper.example.com"]).unwrap(),
+            ])
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+            .as_json();
+        let req = TestRequest::get()
+            .uri(&format!(
+                "/lnurlp/{}/callback?amount=5000000&nostr={}",
+                element.id,
+                url::form_urlencoded::byte_serialize(zap_request.as_bytes()).collect::<String>(),
+            ))
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        let payment_hash = res["pr"].as_str().unwrap().split(':').nth(1).unwrap();
+        let invoice =
+            db::main::invoice::queries::select_by_payment_hash(payment_hash, &pool).await?;
+        assert_eq!(Some(zap_request), invoice.zap_request);
+
+        assert!(FakeBackend::pay(payment_hash));
+        assert!(service::invoice::mark_paid(&invoice, &pool, &None).await?);
+        let element = db::main::element::queries::select_by_id(element.id, &pool).await?;
+        assert!(element.boosted_until().is_some());
+        let outbox = db::main::nostr_outbox::queries::select_due(
+            time::OffsetDateTime::now_utc(),
+            10,
+            100,
+            &pool,
+        )
+        .await?;
+        // the receipt goes straight to the zapper's relays, only our own
+        // relays are served from the outbox
+        assert!(!outbox.is_empty());
+        assert!(outbox
+            .iter()
+            .all(|it| it.relay_url == "wss://btcmap.example.com"));
+        assert!(outbox
+            .iter()
+            .all(|it| nostr::Event::from_json(&it.event).unwrap().kind != Kind::ZapReceipt));
+        Ok(())
+    }
+}
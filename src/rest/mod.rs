@@ -1,5 +1,6 @@
 pub mod auth;
 pub mod error;
+pub mod lnurl;
 pub mod nostr_auth;
 pub mod v2;
 pub mod v3;
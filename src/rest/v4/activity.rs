@@ -1,7 +1,7 @@
 use crate::db;
+use crate::db::main::element_boost::schema::ElementBoost;
 use crate::db::main::element_comment::schema::ElementComment;
 use crate::db::main::element_event::schema::ElementEvent;
-use crate::db::main::invoice::schema::{InvoiceStatus, InvoicedService};
 use crate::db::main::MainPool;
 use crate::rest::error::RestApiError;
 use crate::rest::error::RestResult;
@@ -12,7 +12,7 @@ use actix_web::web::Query;
 use regex::Regex;
 use serde::Deserialize;
 use serde::Serialize;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::LazyLock;
 use time::Duration;
 use time::OffsetDateTime;
@@ -57,6 +57,14 @@ pub struct ActivityItem {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[ts(optional)]
     pub comment: Option<String>,
+    /// Set when the comment is a reply to another comment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub reply_to: Option<i64>,
+    /// Set when the comment is an official response from the merchant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub official: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     #[ts(optional, type = "number")]
     pub duration_days: Option<i64>,
@@ -216,6 +224,8 @@ pub async fn get(
             osm_user_name: Some(osm_user.osm_data.display_name),
             osm_user_tip: user_tip,
             comment: None,
+            reply_to: None,
+            official: None,
             duration_days: None,
             image: format!("https://api.btcmap.org/og/element/{}", event.element_id),
             created_at: event.created_at,
@@ -225,9 +235,9 @@ pub async fn get(
     // Fetch comments — area-scoped (optimized), global, or global + post-filter
     let comments = if !areas.is_empty() && places.is_empty() {
         let mut combined: HashSet<ElementComment> = HashSet::new();
-        for area in areas {
+        for area in &areas {
             let comments = db::main::element_comment::queries::select_created_between_for_area(
-                area, day_ago, period_end, &pool,
+                *area, day_ago, period_end, &pool,
             )
             .await
             .map_err(|_| RestApiError::database())?;
@@ -264,43 +274,46 @@ pub async fn get(
             osm_user_name: None,
             osm_user_tip: None,
             comment: Some(comment.comment),
+            reply_to: comment.parent_id,
+            official: comment.official.then_some(true),
             duration_days: None,
             image: format!("https://api.btcmap.org/og/element/{}", comment.element_id),
             created_at: comment.created_at,
         });
     }
 
-    // Fetch boosts — invoices store element_id in a description string,
-    // not a JOINable column, so we use the in_filter() helper for filtering
-    let paid_invoices = db::main::invoice::queries::select_by_status(InvoiceStatus::Paid, &pool)
-        .await
-        .map_err(|_| RestApiError::database())?;
-
-    for invoice in paid_invoices {
-        let service = InvoicedService::from_description(&invoice.description);
-        let InvoicedService::Boost {
-            element_id,
-            duration_days,
-        } = service
-        else {
-            continue;
-        };
-
-        if !in_filter(element_id) {
-            continue;
+    // Fetch boosts — area-scoped (optimized), global, or global + post-filter
+    let boosts = if !areas.is_empty() && places.is_empty() {
+        let mut combined: HashMap<i64, ElementBoost> = HashMap::new();
+        for area in &areas {
+            let area_boosts =
+                db::main::element_boost::queries::select_paid_created_between_for_area(
+                    *area,
+                    day_ago,
+                    period_end,
+                    i64::MAX,
+                    &pool,
+                )
+                .await
+                .map_err(|_| RestApiError::database())?;
+            for boost in area_boosts {
+                combined.insert(boost.id, boost);
+            }
         }
+        combined.into_values().collect()
+    } else {
+        db::main::element_boost::queries::select_paid_created_between(day_ago, period_end, &pool)
+            .await
+            .map_err(|_| RestApiError::database())?
+    };
 
-        let created_at = OffsetDateTime::parse(
-            &invoice.created_at,
-            &time::format_description::well_known::Rfc3339,
-        )
-        .map_err(|_| RestApiError::database())?;
-
-        if created_at < day_ago || created_at > now {
+    for boost in boosts {
+        if !in_filter(boost.element_id) {
             continue;
         }
 
-        let element = match db::main::element::queries::select_by_id(element_id, &pool).await {
+        let element = match db::main::element::queries::select_by_id(boost.element_id, &pool).await
+        {
             Ok(e) => e,
             Err(_) => continue,
         };
@@ -309,15 +322,17 @@ pub async fn get(
 
         items.push(ActivityItem {
             r#type: EVENT_TYPE_BOOST.to_string(),
-            place_id: element_id,
+            place_id: boost.element_id,
             place_name: Some(element_name),
             osm_user_id: None,
             osm_user_name: None,
             osm_user_tip: None,
             comment: None,
-            duration_days: Some(duration_days),
-            image: format!("https://api.btcmap.org/og/element/{element_id}"),
-            created_at,
+            reply_to: None,
+            official: None,
+            duration_days: Some(boost.days),
+            image: format!("https://api.btcmap.org/og/element/{}", boost.element_id),
+            created_at: boost.created_at,
         });
     }
 
@@ -477,13 +492,38 @@ mod test {
     async fn get_with_boosts() -> Result<()> {
         let pool = pool();
         let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
-        let _invoice = db::main::invoice::queries::insert(
+        let invoice = db::main::invoice::queries::insert(
             "src",
             format!("element_boost:{}:30", element.id),
             1000,
             "hash",
             "req",
             db::main::invoice::schema::InvoiceStatus::Paid,
+            None,
+            &pool,
+        )
+        .await?;
+        crate::service::boost::on_paid(&invoice, element.id, 30, &pool).await?;
+        // unpaid orders don't show up
+        let unpaid = db::main::invoice::queries::insert(
+            "src",
+            format!("element_boost:{}:30", element.id),
+            1000,
+            "hash2",
+            "req2",
+            db::main::invoice::schema::InvoiceStatus::Unpaid,
+            None,
+            &pool,
+        )
+        .await?;
+        db::main::element_boost::queries::insert(
+            element.id,
+            Some(unpaid.id),
+            30,
+            None,
+            None,
+            None,
+            None,
             &pool,
         )
         .await?;
@@ -603,26 +643,32 @@ mod test {
                 .await?;
         db::main::area_element::queries::insert(area.id, element_in_area.id, &pool).await?;
 
-        db::main::invoice::queries::insert(
+        db::main::element::queries::set_lat_lon(element_in_area.id, 1.0, 1.0, &pool).await?;
+        let invoice = db::main::invoice::queries::insert(
             "src",
             format!("element_boost:{}:30", element_in_area.id),
             1000,
             "hash1",
             "req1",
             db::main::invoice::schema::InvoiceStatus::Paid,
+            None,
             &pool,
         )
         .await?;
-        db::main::invoice::queries::insert(
+        crate::service::boost::on_paid(&invoice, element_in_area.id, 30, &pool).await?;
+        db::main::element::queries::set_lat_lon(element_outside.id, 1.0, 1.0, &pool).await?;
+        let invoice = db::main::invoice::queries::insert(
             "src",
             format!("element_boost:{}:30", element_outside.id),
             1000,
             "hash2",
             "req2",
             db::main::invoice::schema::InvoiceStatus::Paid,
+            None,
             &pool,
         )
         .await?;
+        crate::service::boost::on_paid(&invoice, element_outside.id, 30, &pool).await?;
 
         let app = test::init_service(
             App::new()
@@ -4,6 +4,7 @@ use crate::db::main::MainPool;
 use crate::rest::auth::Auth;
 use crate::rest::error::RestResult as Res;
 use crate::rest::error::{RestApiError, RestApiErrorCode};
+use crate::rest::v4::places::Boost;
 use crate::rest::v4::top_editors::{
     extract_tip_url, far_future, parse_date, validate_limit, TopEditor, EXCLUDED_USER_IDS,
 };
@@ -295,6 +296,48 @@ pub async fn get_by_id_top_editors(
     Ok(Json(editors))
 }
 
+/// Boosts of the places within the area and the ones targeting the area
+/// page, latest first
+#[get("{id}/boosts")]
+pub async fn get_by_id_boosts(id: Path<String>, pool: Data<MainPool>) -> Res<Vec<Boost>> {
+    if id.len() > 128 {
+        return Err(RestApiError::invalid_input("id too long"));
+    }
+    let area = db::main::area::queries::select_by_id_or_alias(id.into_inner(), &pool)
+        .await
+        .map_err(|e| match e {
+            Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
+            _ => RestApiError::database(),
+        })?;
+    db::main::element_boost::queries::select_history_by_area_id(area.id, &pool)
+        .await
+        .map(|it| Json(it.into_iter().map(Boost::from).collect()))
+        .map_err(|_| RestApiError::database())
+}
+
+#[get("{id}/events.ics")]
+pub async fn get_by_id_events_ics(
+    id: Path<String>,
+    pool: Data<MainPool>,
+) -> Result<HttpResponse, RestApiError> {
+    let area = db::main::area::queries::select_by_id_or_alias(id.into_inner(), &pool)
+        .await
+        .map_err(|e| match e {
+            Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
+            _ => RestApiError::database(),
+        })?;
+    let events = db::main::event::queries::select_by_area_id(area.id, &pool)
+        .await
+        .map_err(|_| RestApiError::database())?;
+    let body =
+        service::ical::calendar(&format!("BTC Map Events in {}", area.name()), events, &pool)
+            .await
+            .map_err(|_| RestApiError::database())?;
+    Ok(HttpResponse::Ok()
+        .content_type(service::ical::CONTENT_TYPE)
+        .body(body))
+}
+
 #[derive(Deserialize)]
 pub struct GetImageArgs {
     pub r#type: String,
@@ -1148,4 +1191,39 @@ mod test {
             super::fit_dimensions(50, 50, Some(200), Some(200))
         );
     }
+
+    #[test]
+    async fn get_by_id_events_ics() -> Result<()> {
+        let pool = pool();
+        let mut tags = Area::mock_tags();
+        tags.insert("timezone".into(), json!("Asia/Bangkok"));
+        let area = db::main::area::queries::insert(tags, &pool).await?;
+        db::main::event::queries::insert(
+            Some(area.id),
+            18.78,
+            98.99,
+            "Weekly Bitcoin Mixer".into(),
+            "https://example.com".into(),
+            Some(time::macros::datetime!(2025-01-01 0:00 UTC)),
+            None,
+            Some("0 0 19 * * Thu".into()),
+            &pool,
+        )
+        .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .service(super::get_by_id_events_ics),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri(&format!("/{}/events.ics", area.id))
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("BEGIN:VTIMEZONE\r\nTZID:Asia/Bangkok\r\n"));
+        assert!(body.contains("DTSTART;TZID=Asia/Bangkok:20250102T190000\r\n"));
+        assert!(body.contains("RRULE:FREQ=WEEKLY;BYDAY=TH;BYHOUR=19;BYMINUTE=0;BYSECOND=0\r\n"));
+        Ok(())
+    }
 }
@@ -0,0 +1,247 @@
+use crate::db;
+use crate::db::main::event_submission::schema::EventSubmission;
+use crate::db::main::MainPool;
+use crate::rest::auth::Auth;
+use crate::rest::error::{RestApiError, RestResult};
+use crate::rest::nostr_auth::NostrAuth;
+use crate::rest::v4::nostr::user_from_auth;
+use crate::service;
+use actix_web::web::{Data, Json};
+use actix_web::{get, post};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+#[derive(Deserialize)]
+pub struct PostArgs {
+    lat: f64,
+    lon: f64,
+    name: String,
+    website: String,
+    #[serde(default)]
+    #[serde(with = "time::serde::rfc3339::option")]
+    starts_at: Option<OffsetDateTime>,
+    #[serde(default)]
+    #[serde(with = "time::serde::rfc3339::option")]
+    ends_at: Option<OffsetDateTime>,
+    #[serde(default)]
+    cron_schedule: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Item {
+    pub id: i64,
+    pub lat: f64,
+    pub lon: f64,
+    pub name: String,
+    pub website: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub starts_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub ends_at: Option<OffsetDateTime>,
+    pub cron_schedule: Option<String>,
+    /// pending, approved or rejected
+    pub status: String,
+    pub review_note: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub reviewed_at: Option<OffsetDateTime>,
+    /// Published event, set once the submission is approved
+    pub event_id: Option<i64>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl From<EventSubmission> for Item {
+    fn from(val: EventSubmission) -> Self {
+        Item {
+            id: val.id,
+            lat: val.lat,
+            lon: val.lon,
+            name: val.name,
+            website: val.website,
+            starts_at: val.starts_at,
+            ends_at: val.ends_at,
+            cron_schedule: val.cron_schedule,
+            status: val.status.into(),
+            review_note: val.review_note,
+            reviewed_at: val.reviewed_at,
+            event_id: val.event_id,
+            created_at: val.created_at,
+        }
+    }
+}
+
+/// `POST /v4/events/submissions`
+///
+/// Anyone with a Bearer token or a NIP-98 signature can propose an event.
+/// Proposals stay pending until a moderator reviews them.
+#[post("submissions")]
+pub async fn post(
+    auth: Auth,
+    nostr: NostrAuth,
+    args: Json<PostArgs>,
+    pool: Data<MainPool>,
+) -> RestResult<Item> {
+    let user = user_from_auth(auth, nostr, &pool).await?;
+    let args = args.into_inner();
+    service::event_submission::submit(
+        &user,
+        args.lat,
+        args.lon,
+        args.name,
+        args.website,
+        args.starts_at,
+        args.ends_at,
+        args.cron_schedule,
+        &pool,
+    )
+    .await
+    .map(|it| Json(it.into()))
+    .map_err(|e| match e {
+        crate::Error::Other(message) => RestApiError::invalid_input(message),
+        _ => RestApiError::database(),
+    })
+}
+
+/// `GET /v4/events/submissions`
+///
+/// Lists the caller's own submissions, newest first, so submitters can
+/// follow the review outcome.
+#[get("submissions")]
+pub async fn get(auth: Auth, nostr: NostrAuth, pool: Data<MainPool>) -> RestResult<Vec<Item>> {
+    let user = user_from_auth(auth, nostr, &pool).await?;
+    let submissions = db::main::event_submission::queries::select_by_user_id(user.id, &pool)
+        .await
+        .map_err(|_| RestApiError::database())?;
+    Ok(Json(submissions.into_iter().map(Into::into).collect()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db;
+    use crate::db::main::test::pool;
+    use crate::rest::nostr_auth::ApiBaseUrl;
+    use crate::Result;
+    use actix_web::http::header;
+    use actix_web::test::TestRequest;
+    use actix_web::web::{scope, Data};
+    use actix_web::{test, App};
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use nostr::event::EventBuilder;
+    use nostr::key::Keys;
+    use nostr::nips::nip19::ToBech32;
+    use nostr::{JsonUtil, Kind, Tag, Timestamp};
+    use serde_json::{json, Value};
+
+    const BASE_URL: &str = "https://api.example.com";
+
+    fn nip98(keys: &Keys, url: &str, method: &str) -> String {
+        let event = EventBuilder::new(Kind::from_u16(27235), "")
+            .tags(vec![
+                Tag::parse(["u", url]).unwrap(),
+                Tag::parse(["method", method]).unwrap(),
+            ])
+            .custom_created_at(Timestamp::now())
+            .sign_with_keys(keys)
+            .unwrap();
+        format!("Nostr {}", BASE64.encode(event.as_json().as_bytes()))
+    }
+
+    #[test]
+    async fn post_requires_auth() -> Result<()> {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool()))
+                .app_data(Data::new(ApiBaseUrl(BASE_URL.into())))
+                .service(scope("/events").service(super::post)),
+        )
+        .await;
+        let req = TestRequest::post()
+            .uri("/events/submissions")
+            .set_json(
+                json!({"lat": 1.0, "lon": 2.0, "name": "Meetup", "website": "https://example.com"}),
+            )
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 401);
+        Ok(())
+    }
+
+    #[test]
+    async fn post_with_bearer_token() -> Result<()> {
+        let pool = pool();
+        let user = db::main::user::queries::insert("user", "", &pool).await?;
+        db::main::access_token::queries::insert(user.id, "".into(), "secret".into(), vec![], &pool)
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .app_data(Data::new(ApiBaseUrl(BASE_URL.into())))
+                .service(scope("/events").service(super::post).service(super::get)),
+        )
+        .await;
+        let req = TestRequest::post()
+            .uri("/events/submissions")
+            .insert_header((header::AUTHORIZATION, "Bearer secret"))
+            .set_json(json!({
+                "lat": 1.0,
+                "lon": 2.0,
+                "name": "Meetup",
+                "website": "https://example.com",
+                "cron_schedule": "0 0 19 * * Thu",
+            }))
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("pending", res["status"]);
+        let req = TestRequest::get()
+            .uri("/events/submissions")
+            .insert_header((header::AUTHORIZATION, "Bearer secret"))
+            .to_request();
+        let res: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        let req = TestRequest::post()
+            .uri("/events/submissions")
+            .insert_header((header::AUTHORIZATION, "Bearer secret"))
+            .set_json(json!({"lat": 1.0, "lon": 2.0, "name": "", "website": "https://example.com"}))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 400);
+        Ok(())
+    }
+
+    #[test]
+    async fn post_with_nip98_creates_account() -> Result<()> {
+        let pool = pool();
+        let keys = Keys::generate();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool.clone()))
+                .app_data(Data::new(ApiBaseUrl(BASE_URL.into())))
+                .service(scope("/events").service(super::post)),
+        )
+        .await;
+        let req = TestRequest::post()
+            .uri("/events/submissions")
+            .insert_header((
+                header::AUTHORIZATION,
+                nip98(&keys, &format!("{BASE_URL}/events/submissions"), "POST"),
+            ))
+            .set_json(
+                json!({"lat": 1.0, "lon": 2.0, "name": "Meetup", "website": "https://example.com"}),
+            )
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 200);
+        let user =
+            db::main::user::queries::select_by_npub(keys.public_key().to_bech32().unwrap(), &pool)
+                .await?
+                .unwrap();
+        assert_eq!(
+            1,
+            db::main::event_submission::queries::select_by_user_id(user.id, &pool)
+                .await?
+                .len()
+        );
+        Ok(())
+    }
+}
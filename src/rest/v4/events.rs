@@ -1,16 +1,43 @@
 use crate::db;
-use crate::db::main::event::schema::Event;
+use crate::db::main::event::schema::{Event, DATE_FORMAT};
 use crate::db::main::MainPool;
 use crate::rest::error::RestApiError;
 use crate::rest::error::RestResult;
+use crate::service;
+use crate::service::event_schedule::{self, Occurrence, Timezones, EXPANSION_WINDOW};
+use crate::service::ical;
 use crate::Error;
 use actix_web::get;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
-use serde::Serialize;
+use actix_web::web::Query;
+use actix_web::HttpResponse;
+use geo::{Distance, Haversine, Point};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
 use time::OffsetDateTime;
 
+#[derive(Deserialize)]
+pub struct GetListArgs {
+    /// upcoming (default), past or all
+    when: Option<String>,
+    min_lat: Option<f64>,
+    min_lon: Option<f64>,
+    max_lat: Option<f64>,
+    max_lon: Option<f64>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    radius_km: Option<f64>,
+    /// Area id or alias
+    area: Option<String>,
+    #[serde(default)]
+    #[serde(with = "time::serde::rfc3339::option")]
+    updated_since: Option<OffsetDateTime>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
 #[derive(Serialize)]
 pub struct Item {
     pub id: i64,
@@ -24,6 +51,14 @@ pub struct Item {
     #[serde(with = "time::serde::rfc3339::option")]
     pub ends_at: Option<OffsetDateTime>,
     pub cron_schedule: Option<String>,
+    pub excluded_dates: Vec<String>,
+    /// Upcoming occurrences of a recurring event, empty for one-off events
+    pub occurrences: Vec<Occurrence>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub deleted_at: Option<OffsetDateTime>,
 }
 
 impl From<Event> for Item {
@@ -38,44 +73,296 @@ impl From<Event> for Item {
             starts_at: val.starts_at.unwrap_or(OffsetDateTime::UNIX_EPOCH),
             ends_at: val.ends_at,
             cron_schedule: val.cron_schedule,
+            excluded_dates: val
+                .excluded_dates
+                .iter()
+                .filter_map(|it| it.format(DATE_FORMAT).ok())
+                .collect(),
+            occurrences: vec![],
+            updated_at: val.updated_at,
+            deleted_at: val.deleted_at,
         }
     }
 }
 
-#[get("")]
-pub async fn get(pool: Data<MainPool>) -> RestResult<Vec<Item>> {
-    let items = db::main::event::queries::select_all(&pool)
+/// Expands recurring events within the default window. Returns None for
+/// one-off events and for schedules we can't parse.
+async fn upcoming_occurrences(
+    event: &Event,
+    now: OffsetDateTime,
+    timezones: &mut Timezones,
+    pool: &MainPool,
+) -> Result<Option<Vec<Occurrence>>, RestApiError> {
+    if event.cron_schedule.is_none() {
+        return Ok(None);
+    }
+    let tz = timezones
+        .get(event, pool)
         .await
         .map_err(|_| RestApiError::database())?;
-    let items: Vec<Event> = items
-        .into_iter()
-        .filter(|it| {
-            it.deleted_at.is_none()
-                && (it.starts_at.is_none() || it.starts_at > Some(OffsetDateTime::now_utc()))
-        })
-        .collect();
-    Ok(Json(items.into_iter().map(|it| it.into()).collect()))
+    Ok(event_schedule::occurrences(event, tz, now, now + EXPANSION_WINDOW).ok())
+}
+
+/// Mean earth radius, same as the one used for Haversine distances
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+#[derive(PartialEq)]
+enum When {
+    Upcoming,
+    Past,
+    All,
+}
+
+enum Location {
+    Bbox {
+        min_lat: f64,
+        min_lon: f64,
+        max_lat: f64,
+        max_lon: f64,
+    },
+    Radius {
+        center: Point,
+        radius_km: f64,
+    },
+}
+
+impl Location {
+    fn from_args(args: &GetListArgs) -> Result<Option<Self>, RestApiError> {
+        let bbox = [args.min_lat, args.min_lon, args.max_lat, args.max_lon];
+        let radius = [args.lat, args.lon, args.radius_km];
+        match (bbox, radius) {
+            ([None, None, None, None], [None, None, None]) => Ok(None),
+            ([Some(min_lat), Some(min_lon), Some(max_lat), Some(max_lon)], [None, None, None]) => {
+                if min_lat > max_lat || min_lon > max_lon {
+                    return Err(RestApiError::invalid_input(
+                        "min_lat and min_lon must not exceed max_lat and max_lon",
+                    ));
+                }
+                Ok(Some(Location::Bbox {
+                    min_lat,
+                    min_lon,
+                    max_lat,
+                    max_lon,
+                }))
+            }
+            ([None, None, None, None], [Some(lat), Some(lon), Some(radius_km)]) => {
+                if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                    return Err(RestApiError::invalid_input("lat or lon out of range"));
+                }
+                if radius_km <= 0.0 {
+                    return Err(RestApiError::invalid_input(
+                        "radius_km must be greater than 0",
+                    ));
+                }
+                Ok(Some(Location::Radius {
+                    center: Point::new(lon, lat),
+                    radius_km,
+                }))
+            }
+            _ => Err(RestApiError::invalid_input(
+                "Pass either min_lat, min_lon, max_lat and max_lon or lat, lon and radius_km",
+            )),
+        }
+    }
+
+    /// West, south, east and north bounds which hold every matching event
+    fn bbox(&self) -> [f64; 4] {
+        match self {
+            Location::Bbox {
+                min_lat,
+                min_lon,
+                max_lat,
+                max_lon,
+            } => [*min_lon, *min_lat, *max_lon, *max_lat],
+            Location::Radius { center, radius_km } => {
+                let radius = radius_km / EARTH_RADIUS_KM;
+                let (lat, lon) = (center.y(), center.x());
+                let min_lat = lat - radius.to_degrees();
+                let max_lat = lat + radius.to_degrees();
+                // Circles around a pole or across the antimeridian span all
+                // longitudes
+                let lon_delta = (radius.sin() / lat.to_radians().cos()).asin().to_degrees();
+                if min_lat <= -90.0
+                    || max_lat >= 90.0
+                    || lon_delta.is_nan()
+                    || lon - lon_delta < -180.0
+                    || lon + lon_delta > 180.0
+                {
+                    [-180.0, min_lat.max(-90.0), 180.0, max_lat.min(90.0)]
+                } else {
+                    [lon - lon_delta, min_lat, lon + lon_delta, max_lat]
+                }
+            }
+        }
+    }
+
+    fn contains(&self, event: &Event) -> bool {
+        match self {
+            Location::Bbox {
+                min_lat,
+                min_lon,
+                max_lat,
+                max_lon,
+            } => {
+                (*min_lat..=*max_lat).contains(&event.lat)
+                    && (*min_lon..=*max_lon).contains(&event.lon)
+            }
+            Location::Radius { center, radius_km } => {
+                Haversine.distance(*center, Point::new(event.lon, event.lat)) / 1000.0 <= *radius_km
+            }
+        }
+    }
+}
+
+#[get("")]
+pub async fn get(args: Query<GetListArgs>, pool: Data<MainPool>) -> RestResult<Vec<Item>> {
+    // Incremental sync needs every change, including past and deleted events
+    let when = match args.when.as_deref() {
+        None if args.updated_since.is_some() => When::All,
+        None | Some("upcoming") => When::Upcoming,
+        Some("past") => When::Past,
+        Some("all") => When::All,
+        Some(_) => {
+            return Err(RestApiError::invalid_input(
+                "when must be upcoming, past or all",
+            ))
+        }
+    };
+    let location = Location::from_args(&args)?;
+    if args.limit.is_some_and(|it| it < 0) || args.offset.is_some_and(|it| it < 0) {
+        return Err(RestApiError::invalid_input(
+            "limit and offset must not be negative",
+        ));
+    }
+    let area = match &args.area {
+        Some(area) => Some(
+            db::main::area::queries::select_by_id_or_alias(area.clone(), &pool)
+                .await
+                .map_err(|e| match e {
+                    Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => {
+                        RestApiError::not_found()
+                    }
+                    _ => RestApiError::database(),
+                })?,
+        ),
+        None => None,
+    };
+    let now = OffsetDateTime::now_utc();
+    // Cheap checks go to the database, occurrences are only expanded for the
+    // events which can match
+    let events = db::main::event::queries::select_listed(
+        args.updated_since,
+        Some(now).filter(|_| when == When::Upcoming),
+        Some(now).filter(|_| when == When::Past),
+        location.as_ref().map(Location::bbox),
+        area.as_ref().map(|it| it.id),
+        &pool,
+    )
+    .await
+    .map_err(|_| RestApiError::database())?;
+    let mut timezones = Timezones::default();
+    let mut items = vec![];
+    for event in events {
+        if location.as_ref().is_some_and(|it| !it.contains(&event)) {
+            continue;
+        }
+        if let Some(area) = &area {
+            // Events are often linked to a parent or sibling area, so the
+            // area geometry is the source of truth
+            let inside = event.area_id == Some(area.id)
+                || service::area::point_inside_area(area, geo::coord!(x: event.lon, y: event.lat))
+                    .unwrap_or(false);
+            if !inside {
+                continue;
+            }
+        }
+        let occurrences = upcoming_occurrences(&event, now, &mut timezones, &pool).await?;
+        // Recurring events stay upcoming for as long as they have upcoming
+        // occurrences, events without a start date are TBA
+        let upcoming = match &occurrences {
+            Some(occurrences) => !occurrences.is_empty(),
+            None => event
+                .starts_at
+                .is_none_or(|it| event.ends_at.unwrap_or(it) > now),
+        };
+        let past = !upcoming && (event.starts_at.is_some() || event.cron_schedule.is_some());
+        let matches = match when {
+            When::Upcoming => upcoming,
+            When::Past => past,
+            When::All => true,
+        };
+        if !matches {
+            continue;
+        }
+        let mut item: Item = event.into();
+        item.occurrences = occurrences.unwrap_or_default();
+        items.push(item);
+    }
+    if args.updated_since.is_some() {
+        items.sort_by_key(|it| (it.updated_at, it.id));
+    } else if when == When::Upcoming {
+        // Soonest first, TBA events go last
+        items.sort_by_key(|it| {
+            let starts_at = it
+                .occurrences
+                .first()
+                .map(|it| it.starts_at)
+                .or(Some(it.starts_at).filter(|it| *it != OffsetDateTime::UNIX_EPOCH));
+            (starts_at.is_none(), starts_at, it.id)
+        });
+    } else if when == When::Past {
+        items.sort_by_key(|it| (Reverse(it.ends_at.unwrap_or(it.starts_at)), it.id));
+    }
+    let offset = args.offset.unwrap_or(0) as usize;
+    let limit = args.limit.map(|it| it as usize).unwrap_or(usize::MAX);
+    Ok(Json(items.into_iter().skip(offset).take(limit).collect()))
 }
 
 #[get("{id}")]
 pub async fn get_by_id(id: Path<i64>, pool: Data<MainPool>) -> RestResult<Item> {
-    db::main::event::queries::select_by_id(id.into_inner(), &pool)
+    let event = db::main::event::queries::select_by_id(id.into_inner(), &pool)
         .await
-        .map(|it| Json(it.into()))
         .map_err(|e| match e {
             Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
             _ => RestApiError::database(),
-        })
+        })?;
+    let occurrences = upcoming_occurrences(
+        &event,
+        OffsetDateTime::now_utc(),
+        &mut Timezones::default(),
+        &pool,
+    )
+    .await?;
+    let mut item: Item = event.into();
+    item.occurrences = occurrences.unwrap_or_default();
+    Ok(Json(item))
+}
+
+/// Registered in the v4 scope, "/events.ics" doesn't match the events scope
+#[get("events.ics")]
+pub async fn get_ics(pool: Data<MainPool>) -> Result<HttpResponse, RestApiError> {
+    let events = db::main::event::queries::select_all(&pool)
+        .await
+        .map_err(|_| RestApiError::database())?;
+    let body = ical::calendar("BTC Map Events", events, &pool)
+        .await
+        .map_err(|_| RestApiError::database())?;
+    Ok(HttpResponse::Ok()
+        .content_type(ical::CONTENT_TYPE)
+        .body(body))
 }
 
 #[cfg(test)]
 mod test {
+    use crate::db::main::event::schema::Event;
     use crate::db::main::test::pool;
+    use crate::db::main::MainPool;
     use crate::{db, Result};
     use actix_web::test::TestRequest;
     use actix_web::web::{scope, Data};
     use actix_web::{test, App};
     use geojson::JsonObject;
+    use serde_json::{json, Map};
     use time::macros::datetime;
     use time::OffsetDateTime;
 
@@ -194,6 +481,287 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    async fn get_includes_recurring_events_with_upcoming_occurrences() -> Result<()> {
+        let pool = pool();
+        let event = db::main::event::queries::insert(
+            None,
+            1.23,
+            4.56,
+            "weekly".to_string(),
+            "https://example.com".to_string(),
+            Some(datetime!(2020-01-01 0:00 UTC)),
+            None,
+            Some("0 0 19 * * Thu".to_string()),
+            &pool,
+        )
+        .await?;
+        db::main::event::queries::insert(
+            None,
+            1.23,
+            4.56,
+            "ended".to_string(),
+            "https://example.com".to_string(),
+            Some(datetime!(2020-01-01 0:00 UTC)),
+            Some(datetime!(2020-12-31 0:00 UTC)),
+            Some("0 0 19 * * Thu".to_string()),
+            &pool,
+        )
+        .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get().uri("/").to_request();
+        let res: Vec<JsonObject> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        assert_eq!(event.id, res[0]["id"].as_i64().unwrap());
+        let occurrences = res[0]["occurrences"].as_array().unwrap();
+        assert!(occurrences.len() >= 12);
+        Ok(())
+    }
+
+    async fn insert_event(
+        name: &str,
+        lat: f64,
+        lon: f64,
+        starts_at: Option<OffsetDateTime>,
+        pool: &MainPool,
+    ) -> Result<Event> {
+        db::main::event::queries::insert(
+            None,
+            lat,
+            lon,
+            name.to_string(),
+            "https://example.com".to_string(),
+            starts_at,
+            None,
+            None,
+            pool,
+        )
+        .await
+    }
+
+    async fn get_names(uri: &str, pool: MainPool) -> Vec<String> {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get().uri(uri).to_request();
+        let res: Vec<JsonObject> = test::call_and_read_body_json(&app, req).await;
+        res.iter()
+            .map(|it| it["name"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    async fn get_past_events() -> Result<()> {
+        let pool = pool();
+        insert_event("old", 0.0, 0.0, Some(datetime!(2020-01-01 0:00 UTC)), &pool).await?;
+        insert_event(
+            "older",
+            0.0,
+            0.0,
+            Some(datetime!(2019-01-01 0:00 UTC)),
+            &pool,
+        )
+        .await?;
+        insert_event(
+            "future",
+            0.0,
+            0.0,
+            Some(datetime!(2099-01-01 0:00 UTC)),
+            &pool,
+        )
+        .await?;
+        insert_event("tba", 0.0, 0.0, None, &pool).await?;
+        assert_eq!(vec!["old", "older"], get_names("/?when=past", pool).await);
+        Ok(())
+    }
+
+    #[test]
+    async fn get_sorted_by_start_with_pagination() -> Result<()> {
+        let pool = pool();
+        insert_event("tba", 0.0, 0.0, None, &pool).await?;
+        insert_event(
+            "third",
+            0.0,
+            0.0,
+            Some(datetime!(2099-03-01 0:00 UTC)),
+            &pool,
+        )
+        .await?;
+        insert_event(
+            "first",
+            0.0,
+            0.0,
+            Some(datetime!(2099-01-01 0:00 UTC)),
+            &pool,
+        )
+        .await?;
+        insert_event(
+            "second",
+            0.0,
+            0.0,
+            Some(datetime!(2099-02-01 0:00 UTC)),
+            &pool,
+        )
+        .await?;
+        assert_eq!(
+            vec!["second", "third"],
+            get_names("/?limit=2&offset=1", pool).await
+        );
+        Ok(())
+    }
+
+    #[test]
+    async fn get_by_bbox() -> Result<()> {
+        let pool = pool();
+        insert_event("inside", 7.98, 98.33, None, &pool).await?;
+        insert_event("outside", 51.5, -0.1, None, &pool).await?;
+        assert_eq!(
+            vec!["inside"],
+            get_names("/?min_lat=7&min_lon=98&max_lat=9&max_lon=99", pool).await
+        );
+        Ok(())
+    }
+
+    #[test]
+    async fn get_by_radius() -> Result<()> {
+        let pool = pool();
+        insert_event("near", 7.89, 98.39, None, &pool).await?;
+        insert_event("far", 18.78, 98.99, None, &pool).await?;
+        assert_eq!(
+            vec!["near"],
+            get_names("/?lat=7.98&lon=98.33&radius_km=25", pool).await
+        );
+        Ok(())
+    }
+
+    #[test]
+    async fn get_by_radius_across_antimeridian() -> Result<()> {
+        let pool = pool();
+        insert_event("east", -16.5, 179.9, None, &pool).await?;
+        insert_event("west", -16.5, -179.9, None, &pool).await?;
+        insert_event("far", -16.5, 170.0, None, &pool).await?;
+        assert_eq!(
+            vec!["east", "west"],
+            get_names("/?lat=-16.5&lon=179.95&radius_km=25", pool).await
+        );
+        Ok(())
+    }
+
+    #[test]
+    async fn get_by_area_polygon() -> Result<()> {
+        let pool = pool();
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), json!("phuket"));
+        tags.insert(
+            "geo_json".into(),
+            json!({
+                "type": "Polygon",
+                "coordinates": [[[98.2, 7.7], [98.5, 7.7], [98.5, 8.2], [98.2, 8.2], [98.2, 7.7]]]
+            }),
+        );
+        db::main::area::queries::insert(tags, &pool).await?;
+        insert_event("inside", 7.98, 98.33, None, &pool).await?;
+        insert_event("outside", 51.5, -0.1, None, &pool).await?;
+        assert_eq!(vec!["inside"], get_names("/?area=phuket", pool).await);
+        Ok(())
+    }
+
+    #[test]
+    async fn get_updated_since_includes_deleted() -> Result<()> {
+        let pool = pool();
+        let past = insert_event(
+            "past",
+            0.0,
+            0.0,
+            Some(datetime!(2020-01-01 0:00 UTC)),
+            &pool,
+        )
+        .await?;
+        db::main::event::queries::set_deleted_at(past.id, Some(OffsetDateTime::now_utc()), &pool)
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/?updated_since=2020-01-01T00:00:00Z")
+            .to_request();
+        let res: Vec<JsonObject> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        assert!(res[0]["deleted_at"].is_string());
+        let req = TestRequest::get()
+            .uri("/?updated_since=2099-01-01T00:00:00Z")
+            .to_request();
+        let res: Vec<JsonObject> = test::call_and_read_body_json(&app, req).await;
+        assert!(res.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_invalid_args() -> Result<()> {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool()))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        for uri in [
+            "/?when=tomorrow",
+            "/?lat=1&lon=2",
+            "/?min_lat=1&min_lon=1&max_lat=0&max_lon=2",
+            "/?lat=1&lon=2&radius_km=-1",
+            "/?limit=-1",
+        ] {
+            let req = TestRequest::get().uri(uri).to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), 400, "{uri}");
+        }
+        let req = TestRequest::get().uri("/?area=unknown").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 404);
+        Ok(())
+    }
+
+    #[test]
+    async fn get_ics() -> Result<()> {
+        let pool = pool();
+        db::main::event::queries::insert(
+            None,
+            1.23,
+            4.56,
+            "name".to_string(),
+            "https://example.com".to_string(),
+            Some(datetime!(2099-01-01 0:00 UTC)),
+            None,
+            None,
+            &pool,
+        )
+        .await?;
+        let app =
+            test::init_service(App::new().app_data(Data::new(pool)).service(super::get_ics)).await;
+        let req = TestRequest::get().uri("/events.ics").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(
+            "text/calendar; charset=utf-8",
+            res.headers().get("content-type").unwrap()
+        );
+        let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+        assert!(body.contains("BEGIN:VEVENT\r\nUID:event-1@btcmap.org\r\n"));
+        assert!(body.contains("DTSTART:20990101T000000Z\r\n"));
+        Ok(())
+    }
+
     #[test]
     async fn get_by_id() -> Result<()> {
         let pool = pool();
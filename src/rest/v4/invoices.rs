@@ -1,21 +1,73 @@
 use crate::db;
-use crate::db::main::invoice::schema::InvoiceStatus;
+use crate::db::main::invoice::schema::{Invoice, InvoiceStatus};
 use crate::db::main::MainPool;
 use crate::rest::error::RestApiError;
 use crate::rest::error::RestResult as Res;
 use crate::service;
 use crate::Error;
 use actix_web::get;
+use actix_web::post;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
+use actix_web::web::Query;
+use actix_web::HttpRequest;
+use serde::Deserialize;
 use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+const DEFAULT_WAIT_TIMEOUT_SECS: u64 = 30;
+const MAX_WAIT_TIMEOUT_SECS: u64 = 60;
+
+/// Each waiter holds a connection open, so a single client can't have many.
+/// Clients without a known address are not limited.
+const MAX_WAITS_PER_IP: usize = 4;
+
+static ACTIVE_WAITS: LazyLock<Mutex<HashMap<String, usize>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Counts towards `MAX_WAITS_PER_IP` until dropped
+struct WaitSlot {
+    ip: String,
+}
+
+impl WaitSlot {
+    fn acquire(ip: &str) -> Option<WaitSlot> {
+        let mut active = ACTIVE_WAITS.lock().unwrap();
+        let count = active.entry(ip.to_string()).or_default();
+        if *count >= MAX_WAITS_PER_IP {
+            return None;
+        }
+        *count += 1;
+        Some(WaitSlot { ip: ip.to_string() })
+    }
+}
+
+impl Drop for WaitSlot {
+    fn drop(&mut self) {
+        let mut active = ACTIVE_WAITS.lock().unwrap();
+        if let Some(count) = active.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&self.ip);
+            }
+        }
+    }
+}
 
 #[derive(Serialize, ts_rs::TS)]
 #[ts(export, rename = "Invoice")]
 pub struct GetByIdRes {
     id: String,
     status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    expires_at: Option<String>,
+    /// Expired invoices can be replaced with a fresh one via `POST {id}/renew`
+    renewable: bool,
 }
 
 #[get("{id}")]
@@ -28,11 +80,11 @@ pub async fn get_by_id(uuid: Path<String>, pool: Data<MainPool>) -> Res<GetByIdR
             _ => RestApiError::database(),
         })?;
 
-    if invoice.status == InvoiceStatus::Unpaid
-        && crate::service::invoice::sync_unpaid_invoice(&invoice, &pool, &matrix_client)
+    if invoice.status == InvoiceStatus::Unpaid {
+        // syncing can also move the invoice to expired
+        crate::service::invoice::sync_unpaid_invoice(&invoice, &pool, &matrix_client)
             .await
-            .map_err(|_| RestApiError::database())?
-    {
+            .map_err(|_| RestApiError::database())?;
         invoice = db::main::invoice::queries::select_by_uuid(uuid.as_str(), &pool)
             .await
             .map_err(|e| match e {
@@ -41,8 +93,225 @@ pub async fn get_by_id(uuid: Path<String>, pool: Data<MainPool>) -> Res<GetByIdR
             })?;
     }
 
-    Ok(Json(GetByIdRes {
-        id: invoice.uuid,
-        status: invoice.status.into(),
+    Ok(Json(invoice.into()))
+}
+
+impl From<Invoice> for GetByIdRes {
+    fn from(invoice: Invoice) -> Self {
+        GetByIdRes {
+            renewable: service::invoice::is_renewable(&invoice, OffsetDateTime::now_utc()),
+            id: invoice.uuid,
+            status: invoice.status.into(),
+            expires_at: invoice.expires_at,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WaitArgs {
+    /// Seconds, capped at `MAX_WAIT_TIMEOUT_SECS`
+    timeout: Option<u64>,
+}
+
+/// Long-poll alternative to `get_by_id`. Responds as soon as the invoice gets
+/// paid or expires, or with the unchanged invoice once the timeout passes.
+#[get("{id}/wait")]
+pub async fn get_by_id_wait(
+    req: HttpRequest,
+    uuid: Path<String>,
+    args: Query<WaitArgs>,
+    pool: Data<MainPool>,
+) -> Res<GetByIdRes> {
+    let timeout = args.timeout.unwrap_or(DEFAULT_WAIT_TIMEOUT_SECS);
+    if !(1..=MAX_WAIT_TIMEOUT_SECS).contains(&timeout) {
+        return Err(RestApiError::invalid_input(format!(
+            "timeout must be between 1 and {MAX_WAIT_TIMEOUT_SECS} seconds"
+        )));
+    }
+    let _slot = match service::client_ip::get(&req) {
+        Some(ip) => Some(WaitSlot::acquire(&ip.to_string()).ok_or_else(|| {
+            RestApiError::too_many_requests(format!(
+                "no more than {MAX_WAITS_PER_IP} invoices can be awaited at once"
+            ))
+        })?),
+        None => None,
+    };
+    let matrix_client = service::matrix::try_client(&pool);
+    let invoice = service::invoice::wait(
+        uuid.as_str(),
+        Duration::from_secs(timeout),
+        &pool,
+        &matrix_client,
+    )
+    .await
+    .map_err(|e| match e {
+        Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
+        _ => RestApiError::database(),
+    })?;
+    Ok(Json(invoice.into()))
+}
+
+#[derive(Serialize, ts_rs::TS)]
+#[ts(export, rename = "RenewInvoiceResponse")]
+pub struct RenewRes {
+    pub invoice_id: String,
+    pub invoice: String,
+}
+
+/// Replaces an expired boost or comment invoice with a fresh one
+#[post("{id}/renew")]
+pub async fn post_renew(uuid: Path<String>, pool: Data<MainPool>) -> Res<RenewRes> {
+    let invoice = db::main::invoice::queries::select_by_uuid(uuid.as_str(), &pool)
+        .await
+        .map_err(|e| match e {
+            Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
+            _ => RestApiError::database(),
+        })?;
+    let invoice = service::invoice::renew(&invoice, &pool)
+        .await
+        .map_err(|e| match e {
+            Error::Other(message) => RestApiError::invalid_input(message),
+            Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
+            _ => RestApiError::database(),
+        })?;
+    Ok(Json(RenewRes {
+        invoice_id: invoice.uuid,
+        invoice: invoice.payment_request,
     }))
 }
+
+#[cfg(test)]
+mod test {
+    use crate::db::main::invoice::schema::InvoiceStatus;
+    use crate::db::main::test::pool;
+    use crate::service::invoice_backend::{self, FakeBackend};
+    use crate::service::overpass::OverpassElement;
+    use crate::{db, service, Result};
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use actix_web::{test, App};
+    use serde_json::Value;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    async fn wait_returns_once_paid() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let conf = db::main::conf::queries::select(&pool).await?;
+        let new_invoice = invoice_backend::backend("fake", &conf)?
+            .create("boost", None, 100)
+            .await?;
+        let invoice = db::main::invoice::queries::insert(
+            "fake",
+            format!("element_boost:{}:30", element.id),
+            100,
+            new_invoice.payment_hash,
+            new_invoice.payment_request,
+            InvoiceStatus::Unpaid,
+            None,
+            &pool,
+        )
+        .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool.clone()))
+                .service(super::get_by_id_wait),
+        )
+        .await;
+
+        let started_at = Instant::now();
+        let req = TestRequest::get()
+            .uri(&format!("/{}/wait?timeout=1", invoice.uuid))
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("unpaid", res["status"]);
+        assert!(started_at.elapsed() >= Duration::from_secs(1));
+
+        let paid_invoice = db::main::invoice::queries::select_by_uuid(&invoice.uuid, &pool).await?;
+        let paid_pool = pool.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(FakeBackend::pay(&paid_invoice.payment_hash));
+            service::invoice::mark_paid(&paid_invoice, &paid_pool, &None)
+                .await
+                .unwrap();
+        });
+        let started_at = Instant::now();
+        let req = TestRequest::get()
+            .uri(&format!("/{}/wait?timeout=30", invoice.uuid))
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("paid", res["status"]);
+        assert!(started_at.elapsed() < Duration::from_secs(5));
+        Ok(())
+    }
+
+    #[test]
+    async fn wait_validates_timeout() -> Result<()> {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool()))
+                .service(super::get_by_id_wait),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/unknown/wait?timeout=3600")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        let req = TestRequest::get().uri("/unknown/wait").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(404, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn wait_slots_are_limited_per_ip() {
+        let slots: Vec<_> = (0..super::MAX_WAITS_PER_IP)
+            .map(|_| super::WaitSlot::acquire("192.0.2.1").unwrap())
+            .collect();
+        assert!(super::WaitSlot::acquire("192.0.2.1").is_none());
+        assert!(super::WaitSlot::acquire("192.0.2.2").is_some());
+        drop(slots);
+        assert!(super::WaitSlot::acquire("192.0.2.1").is_some());
+    }
+
+    #[test]
+    async fn wait_slots_are_keyed_on_client_ip() -> Result<()> {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool()))
+                .service(super::get_by_id_wait),
+        )
+        .await;
+        let _slots: Vec<_> = (0..super::MAX_WAITS_PER_IP)
+            .map(|_| super::WaitSlot::acquire("198.51.100.1").unwrap())
+            .collect();
+        // without trusted proxies the forwarded address is the client
+        let req = TestRequest::get()
+            .uri("/unknown/wait")
+            .peer_addr("192.0.2.3:1234".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "198.51.100.1"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(429, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn clients_without_ip_dont_share_wait_slots() -> Result<()> {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool()))
+                .service(super::get_by_id_wait),
+        )
+        .await;
+        let _slots: Vec<_> = (0..super::MAX_WAITS_PER_IP)
+            .map(|_| super::WaitSlot::acquire("").unwrap())
+            .collect();
+        let req = TestRequest::get().uri("/unknown/wait").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(404, res.status().as_u16());
+        Ok(())
+    }
+}
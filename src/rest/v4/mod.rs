@@ -3,6 +3,7 @@ pub mod areas;
 pub mod communities;
 pub mod countries;
 pub mod dashboard;
+pub mod event_submissions;
 pub mod events;
 pub mod invoices;
 pub mod nostr;
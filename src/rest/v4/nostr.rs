@@ -1,15 +1,16 @@
 use crate::db;
 use crate::db::main::user::schema::{Role, User};
 use crate::db::main::MainPool;
+use crate::rest::auth::Auth;
 use crate::rest::error::{RestApiError, RestResult};
 use crate::rest::nostr_auth::NostrAuth;
+use crate::service;
 use actix_web::post;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use names::Generator;
 use names::Name;
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, ts_rs::TS)]
 #[ts(export)]
@@ -57,7 +58,7 @@ pub async fn auth_nostr(auth: NostrAuth, pool: Data<MainPool>) -> RestResult<Aut
     // the user's current roles (see rpc::handler — non-empty token roles
     // override the user's). Otherwise role revocations on the user would
     // not take effect for already-issued Nostr tokens.
-    let secret = Uuid::new_v4().to_string();
+    let secret = service::api_token::new_secret();
     db::main::access_token::queries::insert(user.id, String::new(), secret.clone(), vec![], &pool)
         .await
         .map_err(|_| RestApiError::database())?;
@@ -69,6 +70,27 @@ pub async fn auth_nostr(auth: NostrAuth, pool: Data<MainPool>) -> RestResult<Aut
     }))
 }
 
+/// Resolves the caller of endpoints which accept either a Bearer token or
+/// a NIP-98 signature in the `Authorization` header. Unknown pubkeys get a
+/// fresh account, same as on `POST /v4/auth/nostr`.
+pub(crate) async fn user_from_auth(
+    auth: Auth,
+    nostr: NostrAuth,
+    pool: &MainPool,
+) -> Result<User, RestApiError> {
+    if let Some(user) = auth.user {
+        return Ok(user);
+    }
+    let npub = nostr.npub.ok_or_else(RestApiError::unauthorized)?;
+    match db::main::user::queries::select_by_npub(npub.clone(), pool)
+        .await
+        .map_err(|_| RestApiError::database())?
+    {
+        Some(user) => Ok(user),
+        None => create_or_recover(&npub, pool).await,
+    }
+}
+
 /// Auto-create a user for an unknown npub. The insert sets `roles`
 /// atomically alongside `npub`. Two distinct UNIQUE failures are possible
 /// here, and they're handled differently:
@@ -1,18 +1,33 @@
 use crate::db;
 use crate::db::main::conf::schema::BoostPrice;
-use crate::db::main::conf::schema::Conf;
 use crate::db::main::MainPool;
 use crate::rest::error::RestApiError;
 use crate::rest::error::RestResult;
 use crate::service;
+use crate::service::conf::SharedConf;
 use crate::Error;
 use actix_web::get;
 use actix_web::post;
 use actix_web::web::Data;
 use actix_web::web::Json;
+use actix_web::web::Query;
 use actix_web::HttpRequest;
 use serde::Deserialize;
 use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+#[derive(Serialize, ts_rs::TS)]
+#[ts(export, rename = "PlaceBoostPricePoint")]
+pub struct PricePoint {
+    #[ts(type = "number")]
+    pub days: i64,
+    #[ts(type = "number")]
+    pub sats: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub fiat: Option<f64>,
+}
 
 #[derive(Serialize, ts_rs::TS)]
 #[ts(export, rename = "PlaceBoostQuote")]
@@ -23,30 +38,172 @@ pub struct Quote {
     pub quote_90d_sat: i64,
     #[ts(type = "number")]
     pub quote_365d_sat: i64,
+    #[ts(type = "number")]
+    pub min_days: i64,
+    #[ts(type = "number")]
+    pub max_days: i64,
+    /// Price curve, other durations are interpolated between these points
+    pub prices: Vec<PricePoint>,
+    /// Price of the requested duration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub quote_sat: Option<i64>,
+    #[ts(type = "number")]
+    pub max_sponsor_name_length: usize,
+    #[ts(type = "number")]
+    pub max_sponsor_message_length: usize,
+    /// Set if fiat pricing is enabled, the `_fiat` fields are in this currency
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub fiat_currency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub quote_30d_fiat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub quote_90d_fiat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub quote_365d_fiat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub quote_fiat: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct GetQuoteArgs {
+    pub days: Option<i64>,
 }
 
 #[get("/quote")]
-pub async fn get_quote(conf: Data<Conf>) -> RestResult<Quote> {
-    let price = |days: i64| -> i64 {
-        conf.boost_element_prices
-            .iter()
-            .find(|p| p.days == days)
-            .map(|p: &BoostPrice| p.sats)
-            .unwrap_or_default()
-    };
+pub async fn get_quote(args: Query<GetQuoteArgs>, conf: Data<SharedConf>) -> RestResult<Quote> {
+    let conf = conf.load();
+    // quotes stay available in sats if the rate provider is down
+    let rate = service::exchange_rate::rate(&conf).await.ok().flatten();
+    let prices = service::exchange_rate::boost_prices(&conf.boost_element_prices, rate.as_ref())
+        .map_err(|_| RestApiError::service_unavailable("no exchange rate available"))?;
+    let prices = &prices;
+    let price = |days: i64| -> i64 { service::boost::price(days, prices).unwrap_or_default() };
+    let fiat = |sats: i64| -> Option<f64> { rate.as_ref().map(|it| it.to_fiat(sats)) };
+    let quote_sat = args
+        .days
+        .map(|days| service::boost::price(days, prices))
+        .transpose()
+        .map_err(|e| RestApiError::invalid_input(e.to_string()))?;
+    let mut points: Vec<PricePoint> = prices
+        .iter()
+        .map(|it: &BoostPrice| PricePoint {
+            days: it.days,
+            sats: it.sats,
+            fiat: fiat(it.sats),
+        })
+        .collect();
+    points.sort_by_key(|it| it.days);
     Ok(Json(Quote {
         quote_30d_sat: price(30),
         quote_90d_sat: price(90),
         quote_365d_sat: price(365),
+        min_days: service::boost::MIN_DAYS,
+        max_days: service::boost::max_days(prices).unwrap_or_default(),
+        prices: points,
+        quote_sat,
+        max_sponsor_name_length: service::boost::MAX_SPONSOR_NAME_LEN,
+        max_sponsor_message_length: service::boost::MAX_SPONSOR_MESSAGE_LEN,
+        fiat_currency: rate.as_ref().map(|it| it.currency.clone()),
+        quote_30d_fiat: fiat(price(30)),
+        quote_90d_fiat: fiat(price(90)),
+        quote_365d_fiat: fiat(price(365)),
+        quote_fiat: quote_sat.and_then(fiat),
     }))
 }
 
+#[derive(Deserialize)]
+pub struct GetArgs {
+    /// Area ID or alias
+    pub area: Option<String>,
+    pub category: Option<String>,
+}
+
+#[derive(Serialize, ts_rs::TS)]
+#[ts(export, rename = "PlaceBoost")]
+pub struct ActiveBoost {
+    #[ts(type = "number")]
+    pub place_id: i64,
+    pub starts_at: String,
+    pub expires_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub sponsor_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub sponsor_message: Option<String>,
+}
+
+/// Places boosted on an area or a category page, or both. Without filters,
+/// returns the global boosts.
+#[get("")]
+pub async fn get(args: Query<GetArgs>, pool: Data<MainPool>) -> RestResult<Vec<ActiveBoost>> {
+    let area_id = match &args.area {
+        Some(area) => Some(
+            db::main::area::queries::select_by_id_or_alias(area, &pool)
+                .await
+                .map_err(|e| match e {
+                    Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => {
+                        RestApiError::not_found()
+                    }
+                    _ => RestApiError::database(),
+                })?
+                .id,
+        ),
+        None => None,
+    };
+    let boosts = db::main::element_boost::queries::select_active(
+        area_id,
+        args.category.clone(),
+        OffsetDateTime::now_utc(),
+        &pool,
+    )
+    .await
+    .map_err(|_| RestApiError::database())?;
+    let format = |date: Option<OffsetDateTime>| -> String {
+        date.and_then(|it| it.format(&Rfc3339).ok())
+            .unwrap_or_default()
+    };
+    Ok(Json(
+        boosts
+            .into_iter()
+            .map(|it| ActiveBoost {
+                place_id: it.element_id,
+                starts_at: format(it.starts_at),
+                expires_at: format(it.expires_at),
+                sponsor_name: it.sponsor_name,
+                sponsor_message: it.sponsor_message,
+            })
+            .collect(),
+    ))
+}
+
 #[derive(Deserialize, ts_rs::TS)]
 #[ts(export, rename = "PostPlaceBoostArgs")]
 pub struct PostArgs {
     pub place_id: String,
     #[ts(type = "number")]
     pub days: i64,
+    /// Area ID or alias, boosts the place on that area's page only
+    #[serde(default)]
+    #[ts(optional)]
+    pub area: Option<String>,
+    /// Boosts the place on its category page only
+    #[serde(default)]
+    #[ts(optional)]
+    pub category: Option<String>,
+    /// Shown publicly, leave out for an anonymous boost
+    #[serde(default)]
+    #[ts(optional)]
+    pub sponsor_name: Option<String>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub sponsor_message: Option<String>,
 }
 
 #[derive(Serialize, ts_rs::TS)]
@@ -60,54 +217,213 @@ pub struct PostResponse {
 pub async fn post(
     req: HttpRequest,
     args: Json<PostArgs>,
-    conf: Data<Conf>,
+    conf: Data<SharedConf>,
     pool: Data<MainPool>,
 ) -> RestResult<PostResponse> {
-    if args.place_id == "23143" {
-        let ip = req
-            .connection_info()
-            .realip_remote_addr()
-            .map(|s| s.to_owned());
-        if let Some(ip) = ip {
-            db::main::ban::queries::insert(ip, "spam".to_string(), 3650, &pool)
-                .await
-                .map_err(|_| RestApiError::database())?;
-            return Err(RestApiError::new(
-                crate::rest::error::RestApiErrorCode::InvalidInput,
-                "invalid request",
-            ));
-        }
-    }
-
+    let conf = conf.load();
     let element = db::main::element::queries::select_by_id_or_osm_id(&args.place_id, &pool)
         .await
         .map_err(|e| match e {
             Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
             _ => RestApiError::database(),
         })?;
-    let sats = match args.days {
-        30 => conf.boost_element_prices.iter().find(|p| p.days == 30),
-        90 => conf.boost_element_prices.iter().find(|p| p.days == 90),
-        365 => conf.boost_element_prices.iter().find(|p| p.days == 365),
-        _ => None,
+    let client_ip = service::client_ip::get(&req);
+    if service::ban::check_honeypot(element.id, client_ip, &conf, &pool)
+        .await
+        .map_err(|_| RestApiError::database())?
+    {
+        return Err(RestApiError::invalid_input("invalid request"));
     }
-    .ok_or_else(|| {
-        RestApiError::new(
-            crate::rest::error::RestApiErrorCode::InvalidInput,
-            "invalid duration",
-        )
-    })?
-    .sats;
-    let invoice = service::invoice::create(
-        "lnd",
-        format!("element_boost:{}:{}", element.id, args.days),
-        sats,
-        &pool,
-    )
-    .await
-    .map_err(|_| RestApiError::database())?;
+    let area_id = match &args.area {
+        Some(area) => Some(
+            db::main::area::queries::select_by_id_or_alias(area, &pool)
+                .await
+                .map_err(|e| match e {
+                    Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => {
+                        RestApiError::not_found()
+                    }
+                    _ => RestApiError::database(),
+                })?
+                .id,
+        ),
+        None => None,
+    };
+    let args = args.into_inner();
+    let order = service::boost::BoostOrder {
+        days: args.days,
+        area_id,
+        category: args.category,
+        sponsor_name: args.sponsor_name,
+        sponsor_message: args.sponsor_message,
+    };
+    let invoice = service::boost::order(&element, order, &conf.invoice_backend, &pool)
+        .await
+        .map_err(|e| match e {
+            Error::Other(message) => RestApiError::invalid_input(message),
+            _ => RestApiError::database(),
+        })?;
     Ok(Json(PostResponse {
         invoice_id: invoice.uuid,
         invoice: invoice.payment_request,
     }))
 }
+
+#[cfg(test)]
+mod test {
+    use crate::db::main::area::schema::Area;
+    use crate::db::main::conf::schema::{BoostPrice, Conf};
+    use crate::db::main::test::pool;
+    use crate::service::conf::SharedConf;
+    use crate::service::exchange_rate::FakeProvider;
+    use crate::service::overpass::OverpassElement;
+    use crate::{db, service, Result};
+    use actix_web::test::TestRequest;
+    use actix_web::web::{scope, Data};
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
+
+    #[test]
+    async fn order_area_boost() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let area = db::main::area::queries::insert(Area::mock_tags(), &pool).await?;
+        db::main::area_element::queries::insert(area.id, element.id, &pool).await?;
+        let prices = vec![
+            BoostPrice {
+                days: 30,
+                sats: 5000,
+                fiat: None,
+            },
+            BoostPrice {
+                days: 90,
+                sats: 12000,
+                fiat: None,
+            },
+        ];
+        let prices_json = serde_json::to_string(&prices)?;
+        pool.get()
+            .await?
+            .interact(move |conn| {
+                conn.execute("UPDATE conf SET boost_element_prices = ?1", [prices_json])
+            })
+            .await??;
+        let conf = Conf {
+            invoice_backend: "fake".into(),
+            boost_element_prices: prices,
+            ..Conf::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool.clone()))
+                .app_data(Data::new(SharedConf::new(conf)))
+                .service(super::get_quote)
+                .service(scope("/").service(super::get).service(super::post)),
+        )
+        .await;
+
+        let req = TestRequest::get().uri("/quote?days=45").to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(6750, res["quote_sat"]);
+        assert_eq!(90, res["max_days"]);
+        assert_eq!(0, res["quote_365d_sat"]);
+
+        let req = TestRequest::post()
+            .uri("/")
+            .set_json(json!({"place_id": element.id.to_string(), "days": 91}))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+
+        let req = TestRequest::post()
+            .uri("/")
+            .set_json(json!({
+                "place_id": element.id.to_string(),
+                "days": 45,
+                "area": "alias",
+                "sponsor_name": "Satoshi",
+            }))
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        let invoice =
+            db::main::invoice::queries::select_by_uuid(res["invoice_id"].as_str().unwrap(), &pool)
+                .await?;
+        assert_eq!(6750, invoice.amount_sats);
+        assert!(service::invoice::mark_paid(&invoice, &pool, &None).await?);
+
+        let req = TestRequest::get().uri("/?area=alias").to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(element.id, res[0]["place_id"]);
+        assert_eq!("Satoshi", res[0]["sponsor_name"]);
+        let req = TestRequest::get().uri("/").to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(json!([]), res);
+        Ok(())
+    }
+
+    #[test]
+    async fn quote_in_fiat() -> Result<()> {
+        FakeProvider::set("EUR", Some(100_000.0));
+        let conf = Conf {
+            boost_element_prices: vec![
+                BoostPrice {
+                    days: 30,
+                    sats: 5000,
+                    fiat: Some(10.0),
+                },
+                BoostPrice {
+                    days: 90,
+                    sats: 12000,
+                    fiat: None,
+                },
+            ],
+            fiat_currency: "EUR".into(),
+            exchange_rate_provider: "fake".into(),
+            ..Conf::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(SharedConf::new(conf)))
+                .service(super::get_quote),
+        )
+        .await;
+        let req = TestRequest::get().uri("/quote?days=60").to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("EUR", res["fiat_currency"]);
+        assert_eq!(10000, res["quote_30d_sat"]);
+        assert_eq!(10.0, res["quote_30d_fiat"]);
+        assert_eq!(12000, res["quote_90d_sat"]);
+        assert_eq!(11000, res["quote_sat"]);
+        assert_eq!(11.0, res["quote_fiat"]);
+        assert_eq!(10.0, res["prices"][0]["fiat"]);
+        Ok(())
+    }
+
+    #[test]
+    async fn honeypot_bans_client() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let conf = Conf {
+            invoice_backend: "fake".into(),
+            honeypot_place_ids: vec![element.id.to_string()],
+            honeypot_ban_days: 3650,
+            ..Conf::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool.clone()))
+                .app_data(Data::new(SharedConf::new(conf)))
+                .service(scope("/").service(super::post)),
+        )
+        .await;
+        let req = TestRequest::post()
+            .uri("/")
+            .peer_addr("192.0.2.1:1234".parse().unwrap())
+            .set_json(json!({"place_id": element.id.to_string(), "days": 30}))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        let ban = db::main::ban::queries::select_by_ip("192.0.2.1".into(), &pool).await?;
+        assert_eq!("spam", ban.unwrap().reason);
+        Ok(())
+    }
+}
@@ -1,13 +1,20 @@
 use crate::db;
-use crate::db::main::conf::schema::Conf;
 use crate::db::main::element_comment::schema::ElementComment;
 use crate::db::main::MainPool;
+use crate::rest::auth::Auth;
 use crate::rest::error::RestApiError;
+use crate::rest::error::RestApiErrorCode;
 use crate::rest::error::RestResult;
+use crate::rest::nostr_auth::NostrAuth;
+use crate::rest::v4::nostr::user_from_auth;
 use crate::service;
+use crate::service::comment::FreeCommentDenial;
+use crate::service::conf::SharedConf;
 use crate::Error;
+use actix_web::delete;
 use actix_web::get;
 use actix_web::post;
+use actix_web::put;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
@@ -47,6 +54,10 @@ pub struct Item {
     #[ts(type = "number")]
     pub place_id: i64,
     pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub parent_id: Option<i64>,
+    pub official: bool,
     #[serde(with = "time::serde::rfc3339")]
     #[ts(type = "string")]
     pub created_at: OffsetDateTime,
@@ -65,6 +76,8 @@ impl From<ElementComment> for Item {
             id: val.id,
             place_id: val.element_id,
             text: val.comment,
+            parent_id: val.parent_id,
+            official: val.official,
             created_at: val.created_at,
             updated_at: val.updated_at,
             deleted_at: val.deleted_at,
@@ -102,12 +115,25 @@ pub async fn get_by_id(id: Path<i64>, pool: Data<MainPool>) -> Result<Json<Item>
 pub struct Quote {
     #[ts(type = "number")]
     pub quote_sat: i64,
+    /// Set if fiat pricing is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub fiat_currency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub quote_fiat: Option<f64>,
 }
 
 #[get("/quote")]
-pub async fn get_quote(conf: Data<Conf>) -> RestResult<Quote> {
+pub async fn get_quote(conf: Data<SharedConf>) -> RestResult<Quote> {
+    let conf = conf.load();
+    let rate = service::exchange_rate::rate(&conf).await.ok().flatten();
+    let quote_sat = service::exchange_rate::comment_price_sat(&conf, rate.as_ref())
+        .map_err(|_| RestApiError::service_unavailable("no exchange rate available"))?;
     Ok(Json(Quote {
-        quote_sat: conf.paywall_add_element_comment_price_sat,
+        quote_sat,
+        fiat_currency: rate.as_ref().map(|it| it.currency.clone()),
+        quote_fiat: rate.as_ref().map(|it| it.to_fiat(quote_sat)),
     }))
 }
 
@@ -116,6 +142,9 @@ pub async fn get_quote(conf: Data<Conf>) -> RestResult<Quote> {
 pub struct PostArgs {
     pub place_id: String,
     pub comment: String,
+    #[serde(default)]
+    #[ts(optional, type = "number")]
+    pub parent_id: Option<i64>,
 }
 
 #[derive(Serialize, ts_rs::TS)]
@@ -128,9 +157,10 @@ pub struct PostResponse {
 #[post("")]
 pub async fn post(
     args: Json<PostArgs>,
-    conf: Data<Conf>,
+    conf: Data<SharedConf>,
     pool: Data<MainPool>,
 ) -> RestResult<PostResponse> {
+    let conf = conf.load();
     if args.comment.trim().is_empty() {
         return Err(RestApiError::invalid_input("Comment cannot be empty"));
     }
@@ -141,9 +171,21 @@ pub async fn post(
             Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
             _ => RestApiError::database(),
         })?;
-    let comment = db::main::element_comment::queries::insert(element.id, &args.comment, &pool)
-        .await
-        .map_err(|_| RestApiError::database())?;
+    if let Some(parent_id) = args.parent_id {
+        service::comment::validate_parent(&element, parent_id, &pool)
+            .await
+            .map_err(|e| RestApiError::invalid_input(e.to_string()))?;
+    }
+    let comment = db::main::element_comment::queries::insert_threaded(
+        element.id,
+        args.parent_id,
+        false,
+        None,
+        &args.comment,
+        &pool,
+    )
+    .await
+    .map_err(|_| RestApiError::database())?;
     db::main::element_comment::queries::set_deleted_at(
         comment.id,
         Some(OffsetDateTime::now_utc()),
@@ -151,24 +193,203 @@ pub async fn post(
     )
     .await
     .map_err(|_| RestApiError::database())?;
+    let price_sat = service::exchange_rate::current_comment_price_sat(&conf)
+        .await
+        .map_err(|_| RestApiError::service_unavailable("no exchange rate available"))?;
     let invoice = service::invoice::create(
-        "lnd",
+        &conf.invoice_backend,
         format!("element_comment:{}:publish", comment.id),
-        conf.paywall_add_element_comment_price_sat,
+        price_sat,
         &pool,
     )
     .await
     .map_err(|_| RestApiError::database())?;
+    db::main::element_comment::queries::set_invoice_id(comment.id, invoice.id, &pool)
+        .await
+        .map_err(|_| RestApiError::database())?;
     Ok(Json(PostResponse {
         invoice_id: invoice.uuid,
         invoice: invoice.payment_request,
     }))
 }
 
+/// Merchants reply to their customers for free, but they have to sign the
+/// request with the npub linked to the place
+#[post("official")]
+pub async fn post_official(
+    nostr: NostrAuth,
+    args: Json<PostArgs>,
+    pool: Data<MainPool>,
+) -> RestResult<Item> {
+    let Some(npub) = nostr.npub else {
+        return Err(RestApiError::unauthorized());
+    };
+    if args.comment.trim().is_empty() {
+        return Err(RestApiError::invalid_input("Comment cannot be empty"));
+    }
+    let element = db::main::element::queries::select_by_id_or_osm_id(&args.place_id, &pool)
+        .await
+        .map_err(|e| match e {
+            Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
+            _ => RestApiError::database(),
+        })?;
+    if !service::comment::is_owner(&element, &npub) {
+        return Err(RestApiError::forbidden());
+    }
+    service::comment::post_official(&element, args.parent_id, &args.comment, &npub, &pool)
+        .await
+        .map(Into::into)
+        .map_err(|e| match e {
+            Error::Other(message) => RestApiError::invalid_input(message),
+            _ => RestApiError::database(),
+        })
+}
+
+/// Signed in users can skip the paywall, within the limits set in conf
+#[post("free")]
+pub async fn post_free(
+    auth: Auth,
+    nostr: NostrAuth,
+    args: Json<PostArgs>,
+    conf: Data<SharedConf>,
+    pool: Data<MainPool>,
+) -> RestResult<Item> {
+    let conf = conf.load();
+    let user = user_from_auth(auth, nostr, &pool).await?;
+    if args.comment.trim().is_empty() {
+        return Err(RestApiError::invalid_input("Comment cannot be empty"));
+    }
+    let element = db::main::element::queries::select_by_id_or_osm_id(&args.place_id, &pool)
+        .await
+        .map_err(|e| match e {
+            Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
+            _ => RestApiError::database(),
+        })?;
+    match service::comment::free_comment_denial(&user, &conf, &pool)
+        .await
+        .map_err(|_| RestApiError::database())?
+    {
+        Some(denial @ FreeCommentDenial::RateLimited { .. }) => {
+            return Err(RestApiError::too_many_requests(denial.to_string()))
+        }
+        Some(denial) => {
+            return Err(RestApiError::new(
+                RestApiErrorCode::Forbidden,
+                denial.to_string(),
+            ))
+        }
+        None => {}
+    }
+    service::comment::post_free(
+        &user,
+        &element,
+        args.parent_id,
+        &args.comment,
+        conf.free_comments_per_day,
+        &pool,
+    )
+    .await
+    .map_err(|e| match e {
+        Error::Other(message) => RestApiError::invalid_input(message),
+        _ => RestApiError::database(),
+    })?
+    .map(Into::into)
+    .ok_or_else(|| {
+        RestApiError::too_many_requests(
+            FreeCommentDenial::RateLimited {
+                per_day: conf.free_comments_per_day,
+            }
+            .to_string(),
+        )
+    })
+}
+
+#[derive(Deserialize, ts_rs::TS)]
+#[ts(export, rename = "PutPlaceCommentArgs")]
+pub struct PutArgs {
+    pub comment: String,
+}
+
+/// Authors can edit their own free comments
+#[put("{id}")]
+pub async fn put(
+    id: Path<i64>,
+    auth: Auth,
+    nostr: NostrAuth,
+    args: Json<PutArgs>,
+    pool: Data<MainPool>,
+) -> RestResult<Item> {
+    let user = user_from_auth(auth, nostr, &pool).await?;
+    if args.comment.trim().is_empty() {
+        return Err(RestApiError::invalid_input("Comment cannot be empty"));
+    }
+    service::comment::edit_own(&user, *id, &args.comment, &pool)
+        .await
+        .map(Into::into)
+        .map_err(own_comment_error)
+}
+
+/// Authors can delete their own free comments
+#[delete("{id}")]
+pub async fn delete(
+    id: Path<i64>,
+    auth: Auth,
+    nostr: NostrAuth,
+    pool: Data<MainPool>,
+) -> RestResult<Item> {
+    let user = user_from_auth(auth, nostr, &pool).await?;
+    service::comment::delete_own(&user, *id, &pool)
+        .await
+        .map(Into::into)
+        .map_err(own_comment_error)
+}
+
+/// Other people's comments are reported as missing, so their existence
+/// doesn't leak
+fn own_comment_error(e: Error) -> RestApiError {
+    match e {
+        Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
+        Error::Other(message) => RestApiError::invalid_input(message),
+        _ => RestApiError::database(),
+    }
+}
+
+#[derive(Deserialize, ts_rs::TS)]
+#[ts(export, rename = "FlagPlaceCommentArgs")]
+pub struct FlagArgs {
+    pub reason: String,
+}
+
+#[derive(Serialize, ts_rs::TS)]
+#[ts(export, rename = "FlagPlaceCommentResponse")]
+pub struct FlagResponse {
+    #[ts(type = "number")]
+    pub id: i64,
+}
+
+/// Reports a comment to the moderators, the comment stays visible until
+/// they decide to hide it
+#[post("{id}/flags")]
+pub async fn post_flag(
+    id: Path<i64>,
+    args: Json<FlagArgs>,
+    pool: Data<MainPool>,
+) -> RestResult<FlagResponse> {
+    service::comment::flag(*id, &args.reason, &pool)
+        .await
+        .map(|it| Json(FlagResponse { id: it.id }))
+        .map_err(|e| match e {
+            Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
+            Error::Other(message) => RestApiError::invalid_input(message),
+            _ => RestApiError::database(),
+        })
+}
+
 #[cfg(test)]
 mod test {
     use crate::rest::error::RestApiError;
     use crate::rest::error::RestResult;
+    use crate::service::conf::SharedConf;
     use actix_web::post;
     use actix_web::test::TestRequest;
     use actix_web::web::scope;
@@ -209,4 +430,199 @@ mod test {
         let res = test::call_service(&app, req).await;
         assert_eq!(res.status(), 400);
     }
+
+    #[test]
+    async fn post_flag() -> crate::Result<()> {
+        let pool = crate::db::main::test::pool();
+        let element = crate::db::main::element::queries::insert(
+            crate::service::overpass::OverpassElement::mock(1),
+            &pool,
+        )
+        .await?;
+        let comment =
+            crate::db::main::element_comment::queries::insert(element.id, "Rude", &pool).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(actix_web::web::Data::new(pool))
+                .service(scope("/comments").service(super::post_flag)),
+        )
+        .await;
+        let req = TestRequest::post()
+            .uri(&format!("/comments/{}/flags", comment.id))
+            .set_json(serde_json::json!({"reason": "offensive"}))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 200);
+        let req = TestRequest::post()
+            .uri(&format!("/comments/{}/flags", comment.id))
+            .set_json(serde_json::json!({"reason": ""}))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 400);
+        let req = TestRequest::post()
+            .uri("/comments/1000/flags")
+            .set_json(serde_json::json!({"reason": "spam"}))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 404);
+        Ok(())
+    }
+
+    #[test]
+    async fn post_official_requires_owner() -> crate::Result<()> {
+        use base64::Engine;
+        use nostr::nips::nip19::ToBech32;
+        use nostr::JsonUtil;
+        let base_url = "https://api.example.com";
+        let pool = crate::db::main::test::pool();
+        let keys = nostr::Keys::generate();
+        let npub = keys.public_key().to_bech32().unwrap();
+        let element = crate::db::main::element::queries::insert(
+            crate::service::overpass::OverpassElement::mock(1),
+            &pool,
+        )
+        .await?;
+        let question =
+            crate::db::main::element_comment::queries::insert(element.id, "Open?", &pool).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(actix_web::web::Data::new(pool.clone()))
+                .app_data(actix_web::web::Data::new(
+                    crate::rest::nostr_auth::ApiBaseUrl(base_url.into()),
+                ))
+                .service(scope("/comments").service(super::post_official)),
+        )
+        .await;
+        let auth = || {
+            let event = nostr::EventBuilder::new(nostr::Kind::from_u16(27235), "")
+                .tags(vec![
+                    nostr::Tag::parse(["u", &format!("{base_url}/comments/official")]).unwrap(),
+                    nostr::Tag::parse(["method", "POST"]).unwrap(),
+                ])
+                .sign_with_keys(&keys)
+                .unwrap();
+            format!(
+                "Nostr {}",
+                base64::engine::general_purpose::STANDARD.encode(event.as_json())
+            )
+        };
+        let body = serde_json::json!({
+            "place_id": element.id.to_string(),
+            "comment": "Yes",
+            "parent_id": question.id,
+        });
+        let req = TestRequest::post()
+            .uri("/comments/official")
+            .set_json(&body)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+        let req = TestRequest::post()
+            .uri("/comments/official")
+            .insert_header((actix_web::http::header::AUTHORIZATION, auth()))
+            .set_json(&body)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 403);
+        crate::db::main::element::queries::set_tag(
+            element.id,
+            crate::service::comment::OWNER_NPUB_TAG,
+            &serde_json::Value::from(npub),
+            &pool,
+        )
+        .await?;
+        let req = TestRequest::post()
+            .uri("/comments/official")
+            .insert_header((actix_web::http::header::AUTHORIZATION, auth()))
+            .set_json(&body)
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(true, res["official"]);
+        assert_eq!(question.id, res["parent_id"]);
+        Ok(())
+    }
+
+    #[test]
+    async fn post_free_edit_and_delete() -> crate::Result<()> {
+        let pool = crate::db::main::test::pool();
+        let element = crate::db::main::element::queries::insert(
+            crate::service::overpass::OverpassElement::mock(1),
+            &pool,
+        )
+        .await?;
+        let user = crate::db::main::user::queries::insert("user", "", &pool).await?;
+        crate::db::main::access_token::queries::insert(
+            user.id,
+            "".into(),
+            "secret".into(),
+            vec![],
+            &pool,
+        )
+        .await?;
+        let mut conf = crate::db::main::conf::queries::select(&pool).await?;
+        let new_accounts = test::init_service(
+            App::new()
+                .app_data(actix_web::web::Data::new(pool.clone()))
+                .app_data(actix_web::web::Data::new(SharedConf::new(conf.clone())))
+                .service(scope("/comments").service(super::post_free)),
+        )
+        .await;
+        let body = serde_json::json!({"place_id": element.id.to_string(), "comment": "Nice"});
+        let req = TestRequest::post()
+            .uri("/comments/free")
+            .insert_header(("Authorization", "Bearer secret"))
+            .set_json(&body)
+            .to_request();
+        assert_eq!(test::call_service(&new_accounts, req).await.status(), 403);
+
+        conf.free_comment_min_account_age_days = 0;
+        conf.free_comments_per_day = 1;
+        let app = test::init_service(
+            App::new()
+                .app_data(actix_web::web::Data::new(pool.clone()))
+                .app_data(actix_web::web::Data::new(SharedConf::new(conf)))
+                .service(
+                    scope("/comments")
+                        .service(super::post_free)
+                        .service(super::put)
+                        .service(super::delete),
+                ),
+        )
+        .await;
+        let req = TestRequest::post()
+            .uri("/comments/free")
+            .set_json(&body)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+        let req = TestRequest::post()
+            .uri("/comments/free")
+            .insert_header(("Authorization", "Bearer secret"))
+            .set_json(&body)
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let id = res["id"].as_i64().unwrap();
+        let req = TestRequest::post()
+            .uri("/comments/free")
+            .insert_header(("Authorization", "Bearer secret"))
+            .set_json(&body)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 429);
+
+        let req = TestRequest::put()
+            .uri(&format!("/comments/{id}"))
+            .insert_header(("Authorization", "Bearer secret"))
+            .set_json(serde_json::json!({"comment": "Very nice"}))
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("Very nice", res["text"]);
+        let req = TestRequest::delete()
+            .uri(&format!("/comments/{id}"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+        let req = TestRequest::delete()
+            .uri(&format!("/comments/{id}"))
+            .insert_header(("Authorization", "Bearer secret"))
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert!(res["deleted_at"].is_string());
+        Ok(())
+    }
 }
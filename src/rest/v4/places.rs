@@ -1,11 +1,15 @@
 use crate::db;
 use crate::db::main::element::schema::Element;
+use crate::db::main::element_boost::schema::BoostHistoryEntry;
 use crate::db::main::element_comment::schema::ElementComment;
 use crate::db::main::element_event::queries::ElementEventWithUser;
+use crate::db::main::element_review::schema::ElementReview;
 use crate::db::main::MainPool;
 use crate::rest::auth::Auth;
 use crate::rest::error::RestApiError;
 use crate::rest::error::RestResult as Res;
+use crate::rest::nostr_auth::NostrAuth;
+use crate::rest::v4::nostr::user_from_auth;
 use crate::service;
 use crate::Error;
 use actix_web::delete;
@@ -21,6 +25,7 @@ use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Map;
 use serde_json::Value;
+use std::collections::BTreeMap;
 use time::OffsetDateTime;
 use tracing::warn;
 
@@ -207,6 +212,12 @@ pub struct Place {
     #[ts(optional, type = "number")]
     pub comments: Option<i64>,
     #[ts(optional)]
+    pub rating: Option<f64>,
+    #[ts(optional, type = "number")]
+    pub rating_count: Option<i64>,
+    #[ts(optional)]
+    pub last_confirmed_payment_at: Option<String>,
+    #[ts(optional)]
     pub description: Option<String>,
     #[ts(optional)]
     pub image: Option<String>,
@@ -379,6 +390,10 @@ pub struct Comment {
     #[ts(type = "number")]
     pub id: i64,
     pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub parent_id: Option<i64>,
+    pub official: bool,
     #[serde(with = "time::serde::rfc3339")]
     #[ts(type = "string")]
     pub created_at: OffsetDateTime,
@@ -389,6 +404,8 @@ impl From<ElementComment> for Comment {
         Comment {
             id: val.id,
             text: val.comment,
+            parent_id: val.parent_id,
+            official: val.official,
             created_at: val.created_at,
         }
     }
@@ -408,6 +425,83 @@ pub async fn get_by_id_comments(id: Path<String>, pool: Data<MainPool>) -> Res<V
         .map_err(|_| RestApiError::database())
 }
 
+#[derive(Serialize, ts_rs::TS)]
+#[ts(export, rename = "PlaceReview")]
+pub struct Review {
+    #[ts(type = "number")]
+    pub id: i64,
+    #[ts(type = "number")]
+    pub rating: i64,
+    pub answers: BTreeMap<String, bool>,
+    #[serde(with = "time::serde::rfc3339")]
+    #[ts(type = "string")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    #[ts(type = "string")]
+    pub updated_at: OffsetDateTime,
+}
+
+impl From<ElementReview> for Review {
+    fn from(val: ElementReview) -> Self {
+        Review {
+            id: val.id,
+            rating: val.rating,
+            answers: val.answers,
+            created_at: val.created_at,
+            updated_at: val.updated_at,
+        }
+    }
+}
+
+#[get("{id}/reviews")]
+pub async fn get_by_id_reviews(id: Path<String>, pool: Data<MainPool>) -> Res<Vec<Review>> {
+    let element = db::main::element::queries::select_by_id_or_osm_id(id.as_str(), &pool)
+        .await
+        .map_err(|e| match e {
+            Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
+            _ => RestApiError::database(),
+        })?;
+    db::main::element_review::queries::select_by_element_id(element.id, &pool)
+        .await
+        .map(|it| Json(it.into_iter().map(Review::from).collect()))
+        .map_err(|_| RestApiError::database())
+}
+
+#[derive(Deserialize, ts_rs::TS)]
+#[ts(export, rename = "PostPlaceReviewArgs")]
+pub struct PostReviewArgs {
+    #[ts(type = "number")]
+    pub rating: i64,
+    #[serde(default)]
+    pub answers: BTreeMap<String, bool>,
+}
+
+/// Creates or replaces the caller's review of a place
+#[post("{id}/reviews")]
+pub async fn post_by_id_review(
+    id: Path<String>,
+    auth: Auth,
+    nostr: NostrAuth,
+    args: Json<PostReviewArgs>,
+    pool: Data<MainPool>,
+) -> Res<Review> {
+    let user = user_from_auth(auth, nostr, &pool).await?;
+    let element = db::main::element::queries::select_by_id_or_osm_id(id.as_str(), &pool)
+        .await
+        .map_err(|e| match e {
+            Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
+            _ => RestApiError::database(),
+        })?;
+    let args = args.into_inner();
+    service::review::submit(&element, user.id, args.rating, args.answers, &pool)
+        .await
+        .map(|it| Json(it.into()))
+        .map_err(|e| match e {
+            Error::Other(message) => RestApiError::invalid_input(message),
+            _ => RestApiError::database(),
+        })
+}
+
 #[derive(Serialize, ts_rs::TS)]
 #[ts(export, rename = "PlaceActivity")]
 pub struct Activity {
@@ -461,6 +555,77 @@ pub async fn get_by_id_activity(id: Path<String>, pool: Data<MainPool>) -> Res<V
         .map_err(|_| RestApiError::database())
 }
 
+#[derive(Serialize, ts_rs::TS)]
+#[ts(export, rename = "PlaceBoostHistoryEntry")]
+pub struct Boost {
+    #[ts(type = "number")]
+    pub id: i64,
+    #[ts(type = "number")]
+    pub place_id: i64,
+    #[ts(type = "number")]
+    pub days: i64,
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[ts(type = "string")]
+    pub starts_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[ts(type = "string")]
+    pub expires_at: Option<OffsetDateTime>,
+    /// Boosts granted by admins are free
+    #[ts(type = "number")]
+    pub amount_sats: i64,
+    /// Set on boosts which target an area page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub area_id: Option<i64>,
+    /// Set on boosts which target a category page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub sponsor_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub sponsor_message: Option<String>,
+    pub active: bool,
+}
+
+impl From<BoostHistoryEntry> for Boost {
+    fn from(val: BoostHistoryEntry) -> Self {
+        let now = OffsetDateTime::now_utc();
+        let boost = val.boost;
+        Boost {
+            id: boost.id,
+            place_id: boost.element_id,
+            days: boost.days,
+            active: boost.starts_at.is_some_and(|it| it <= now)
+                && boost.expires_at.is_some_and(|it| it > now),
+            starts_at: boost.starts_at,
+            expires_at: boost.expires_at,
+            amount_sats: val.amount_sats.unwrap_or_default(),
+            area_id: boost.area_id,
+            category: boost.category,
+            sponsor_name: boost.sponsor_name,
+            sponsor_message: boost.sponsor_message,
+        }
+    }
+}
+
+/// Past, active and queued boosts, latest first
+#[get("{id}/boosts")]
+pub async fn get_by_id_boosts(id: Path<String>, pool: Data<MainPool>) -> Res<Vec<Boost>> {
+    let element = db::main::element::queries::select_by_id_or_osm_id(id.as_str(), &pool)
+        .await
+        .map_err(|e| match e {
+            Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => RestApiError::not_found(),
+            _ => RestApiError::database(),
+        })?;
+    db::main::element_boost::queries::select_history_by_element_id(element.id, &pool)
+        .await
+        .map(|it| Json(it.into_iter().map(Boost::from).collect()))
+        .map_err(|_| RestApiError::database())
+}
+
 #[derive(Serialize, ts_rs::TS)]
 #[ts(export, rename = "PlaceArea")]
 pub struct AreaResponse {
@@ -604,13 +769,14 @@ mod place_type_test {
 mod test {
     use crate::db::main::area::schema::Area;
     use crate::db::main::test::pool;
+    use crate::db::main::user::schema::Role;
     use crate::service::overpass::OverpassElement;
     use crate::{db, Result};
     use actix_web::test::TestRequest;
     use actix_web::web::{scope, Data};
     use actix_web::{test, App};
     use geojson::JsonObject;
-    use serde_json::{Map, Value};
+    use serde_json::{json, Map, Value};
     use time::macros::datetime;
     use time::OffsetDateTime;
 
@@ -734,6 +900,40 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    async fn get_by_id_boosts() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let area = db::main::area::queries::insert(Area::mock_tags(), &pool).await?;
+        db::main::area_element::queries::insert(area.id, element.id, &pool).await?;
+        crate::service::boost::grant(element.id, 10, &pool).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool.clone()))
+                .service(super::get_by_id_boosts),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri(&format!("/{}/boosts", element.id))
+            .to_request();
+        let res: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        assert_eq!(10, res[0]["days"]);
+        assert_eq!(0, res[0]["amount_sats"]);
+        assert_eq!(true, res[0]["active"]);
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .service(crate::rest::v4::areas::get_by_id_boosts),
+        )
+        .await;
+        let req = TestRequest::get().uri("/alias/boosts").to_request();
+        let res: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        assert_eq!(element.id, res[0]["place_id"]);
+        Ok(())
+    }
+
     #[test]
     async fn get_by_id_areas_includes_deleted_when_requested() -> Result<()> {
         let pool = pool();
@@ -757,4 +957,73 @@ mod test {
         assert_eq!(area.id, res[0]["id"].as_i64().unwrap());
         Ok(())
     }
+
+    #[test]
+    async fn post_review_replaces_previous_and_aggregates() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let user = db::main::user::queries::insert("user", "", &pool).await?;
+        db::main::access_token::queries::insert(user.id, "".into(), "secret".into(), vec![], &pool)
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool.clone()))
+                .service(super::get_by_id_reviews)
+                .service(super::post_by_id_review),
+        )
+        .await;
+        let uri = format!("/{}/reviews", element.id);
+        let req = TestRequest::post()
+            .uri(&uri)
+            .set_json(json!({"rating": 5}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+        let req = TestRequest::post()
+            .uri(&uri)
+            .insert_header(("Authorization", "Bearer secret"))
+            .set_json(json!({"rating": 6}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+        for rating in [2, 4] {
+            let req = TestRequest::post()
+                .uri(&uri)
+                .insert_header(("Authorization", "Bearer secret"))
+                .set_json(json!({"rating": rating, "answers": {"accepted_lightning": true}}))
+                .to_request();
+            assert!(test::call_service(&app, req).await.status().is_success());
+        }
+        let req = TestRequest::get().uri(&uri).to_request();
+        let res: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        assert_eq!(4, res[0]["rating"]);
+        assert_eq!(true, res[0]["answers"]["accepted_lightning"]);
+
+        let tags = || async {
+            crate::rpc::element::generate_element_ratings::run(&pool).await?;
+            let element = db::main::element::queries::select_by_id(element.id, &pool).await?;
+            Ok::<_, crate::Error>(crate::service::element::generate_tags(
+                &element,
+                &[
+                    "rating",
+                    "rating_count",
+                    "last_confirmed_payment_at",
+                    "verified_at",
+                ],
+                None,
+            ))
+        };
+        // a fresh account can rate, but it can't verify the place
+        let res = tags().await?;
+        assert_eq!(4.0, res["rating"]);
+        assert_eq!(1, res["rating_count"]);
+        assert!(!res.contains_key("last_confirmed_payment_at"));
+        assert!(!res.contains_key("verified_at"));
+
+        db::main::user::queries::set_roles(user.id, &[Role::Admin], &pool).await?;
+        let res = tags().await?;
+        assert!(res.contains_key("last_confirmed_payment_at"));
+        // the mock has no survey date, so the confirmed payment fills the gap
+        assert!(res.contains_key("verified_at"));
+        Ok(())
+    }
 }
@@ -4,6 +4,7 @@ use crate::db::{self, main::user::schema::Role};
 use crate::rest::auth::Auth;
 use crate::rest::error::RestApiError;
 use crate::rest::nostr_auth::NostrProof;
+use crate::service;
 use actix_web::delete;
 use actix_web::get;
 use actix_web::http::header;
@@ -22,7 +23,6 @@ use names::Generator;
 use names::Name;
 use serde::Deserialize;
 use serde::Serialize;
-use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, ts_rs::TS)]
 #[ts(export)]
@@ -347,7 +347,7 @@ pub async fn create_token(
         .verify_password(password.as_bytes(), &password_hash)
         .map_err(|_| RestApiError::invalid_input("Invalid credentials"))?;
 
-    let token = Uuid::new_v4().to_string();
+    let token = service::api_token::new_secret();
     db::main::access_token::queries::insert(
         user.id,
         args.label.clone().unwrap_or_default(),
@@ -1,6 +1,7 @@
 use crate::db::log::request::queries as log_request_queries;
 use crate::db::log::sync::queries as log_sync_queries;
 use crate::db::log::LogPool;
+use crate::db::main::element_boost::queries as element_boost_queries;
 use crate::db::main::element_event::queries as element_event_queries;
 use crate::db::main::place_submission::queries as place_submission_queries;
 use crate::db::main::place_submission::schema::OriginSubmissionCounts;
@@ -35,6 +36,7 @@ pub struct Res {
     pub lnd: Option<NodeStats>,
     pub sync_runs: Vec<SyncRun>,
     pub wallets: Wallets,
+    pub boost_revenue: Vec<AreaRevenue>,
 }
 
 #[derive(Serialize)]
@@ -122,6 +124,17 @@ pub struct TxSummary {
     pub delta: i64,
 }
 
+/// Paid boosts of the places within an area, places count towards every area
+/// they belong to
+#[derive(Serialize)]
+pub struct AreaRevenue {
+    pub area_id: i64,
+    pub area_alias: String,
+    pub boosts: i64,
+    pub total_sats: i64,
+    pub sats_30d: i64,
+}
+
 #[derive(Serialize, Debug, PartialEq)]
 pub struct PlatformUniqueIps24h {
     pub web: i64,
@@ -247,6 +260,18 @@ pub async fn run(pool: &MainPool, log_pool: &LogPool) -> Result<Res> {
             }
         }
     };
+    let boost_revenue =
+        element_boost_queries::select_revenue_by_area(started_at - Duration::days(30), pool)
+            .await?
+            .into_iter()
+            .map(|it| AreaRevenue {
+                area_id: it.area_id,
+                area_alias: it.area_alias,
+                boosts: it.boosts,
+                total_sats: it.total_sats,
+                sats_30d: it.recent_sats,
+            })
+            .collect();
     let raw_unique_ips = log_request_queries::select_platform_unique_ips_24h(log_pool).await?;
     let unique_ips_24h = PlatformUniqueIps24h {
         web: raw_unique_ips.web,
@@ -269,6 +294,7 @@ pub async fn run(pool: &MainPool, log_pool: &LogPool) -> Result<Res> {
         lnd,
         sync_runs,
         wallets,
+        boost_revenue,
     })
 }
 
@@ -432,6 +458,7 @@ mod test {
         assert!(res.sync_runs.is_empty());
         assert!(res.lnd.is_none());
         assert!(res.wallets.wallets.is_empty());
+        assert!(res.boost_revenue.is_empty());
         assert_eq!(0, res.unique_ips_24h.web);
         assert_eq!(0, res.unique_ips_24h.android);
         assert_eq!(0, res.unique_ips_24h.ios);
@@ -162,6 +162,9 @@ mod test {
                     password: "ihsotasatoshi123".into(),
                     label: None,
                     roles: vec![],
+                    expires_in_secs: None,
+                    allowed_methods: vec![],
+                    allowed_ips: vec![],
                 },
                 &pool,
             )
@@ -175,6 +178,9 @@ mod test {
                     password: "newpassfoobarbaz".into(),
                     label: None,
                     roles: vec![],
+                    expires_in_secs: None,
+                    allowed_methods: vec![],
+                    allowed_ips: vec![],
                 },
                 &pool,
             )
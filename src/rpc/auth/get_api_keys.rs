@@ -10,7 +10,15 @@ use time::OffsetDateTime;
 pub struct Res {
     pub id: i64,
     pub label: Option<String>,
+    /// The start of the secret, the rest of it is never stored
+    pub prefix: String,
     pub roles: Vec<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used_at: Option<OffsetDateTime>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_ips: Vec<String>,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
     #[serde(with = "time::serde::rfc3339")]
@@ -23,7 +31,12 @@ impl From<AccessTokenInfo> for Res {
         Self {
             id: val.id,
             label: val.label,
+            prefix: val.prefix,
             roles,
+            expires_at: val.expires_at,
+            last_used_at: val.last_used_at,
+            allowed_methods: val.allowed_methods,
+            allowed_ips: val.allowed_ips,
             created_at: val.created_at,
             updated_at: val.updated_at,
         }
@@ -48,7 +61,12 @@ mod test {
             id,
             user_id: 1,
             label: label.map(|s| s.to_string()),
+            prefix: "btcmap_1a2b3c4d".into(),
             roles,
+            expires_at: None,
+            last_used_at: None,
+            allowed_methods: vec![],
+            allowed_ips: vec![],
             created_at: OffsetDateTime::UNIX_EPOCH,
             updated_at: OffsetDateTime::UNIX_EPOCH,
         }
@@ -75,6 +93,7 @@ mod test {
         let res: super::Res = info(42, Some("my laptop"), vec![Role::User]).into();
         assert_eq!(res.id, 42);
         assert_eq!(res.label.as_deref(), Some("my laptop"));
+        assert_eq!(res.prefix, "btcmap_1a2b3c4d");
         assert_eq!(res.roles, vec!["user".to_string()]);
         Ok(())
     }
@@ -1,6 +1,7 @@
 pub mod change_password;
 pub mod get_api_keys;
 pub mod revoke_api_key;
+pub mod rotate_api_key;
 pub mod signin;
 pub mod signout;
 pub mod signup;
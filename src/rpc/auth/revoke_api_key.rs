@@ -80,9 +80,14 @@ mod test {
             id: 5,
             user_id: 1,
             name: Some("laptop".into()),
-            secret: "secret".into(),
+            secret_hash: String::new(),
+            prefix: String::new(),
             roles: vec![],
             import_origins: vec![],
+            expires_at: None,
+            last_used_at: None,
+            allowed_methods: vec![],
+            allowed_ips: vec![],
             created_at: OffsetDateTime::UNIX_EPOCH,
             updated_at: OffsetDateTime::UNIX_EPOCH,
             deleted_at: Some(OffsetDateTime::UNIX_EPOCH),
@@ -100,9 +105,14 @@ mod test {
             id: 5,
             user_id: 1,
             name: Some("laptop".into()),
-            secret: "secret".into(),
+            secret_hash: String::new(),
+            prefix: String::new(),
             roles: vec![],
             import_origins: vec![],
+            expires_at: None,
+            last_used_at: None,
+            allowed_methods: vec![],
+            allowed_ips: vec![],
             created_at: OffsetDateTime::UNIX_EPOCH,
             updated_at: OffsetDateTime::UNIX_EPOCH,
             deleted_at: None,
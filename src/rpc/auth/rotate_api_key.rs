@@ -0,0 +1,287 @@
+use crate::db;
+use crate::db::main::access_token::schema::{AccessToken, Restrictions};
+use crate::db::main::user::schema::User;
+use crate::service;
+use crate::Result;
+use deadpool_sqlite::Pool;
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+
+const DEFAULT_OVERLAP_SECS: i64 = 60 * 60;
+
+const MAX_OVERLAP_SECS: i64 = 7 * 24 * 60 * 60;
+
+#[derive(Deserialize)]
+pub struct Params {
+    /// Defaults to the key used to call this method
+    pub id: Option<i64>,
+    /// How long the old key keeps working
+    pub overlap_secs: Option<i64>,
+    /// Defaults to the lifetime of the old key, if it had one
+    pub expires_in_secs: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct Res {
+    pub id: i64,
+    pub api_key: String,
+    pub prefix: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub previous_key_expires_at: Option<OffsetDateTime>,
+}
+
+impl std::fmt::Debug for Res {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Res")
+            .field("id", &self.id)
+            .field("api_key", &"<redacted>")
+            .field("prefix", &self.prefix)
+            .field("expires_at", &self.expires_at)
+            .field("previous_key_expires_at", &self.previous_key_expires_at)
+            .finish()
+    }
+}
+
+pub async fn run(params: Params, caller: &AccessToken, user: &User, pool: &Pool) -> Result<Res> {
+    let error_mask = "Key rotation failed";
+    let old = match params.id {
+        Some(id) => db::main::access_token::queries::select_by_id(id, pool)
+            .await
+            .map_err(|_| error_mask)?,
+        None => db::main::access_token::queries::select_by_id(caller.id, pool).await?,
+    };
+    if old.user_id != user.id || old.deleted_at.is_some() {
+        return Err(error_mask.into());
+    }
+    let overlap_secs = params.overlap_secs.unwrap_or(DEFAULT_OVERLAP_SECS);
+    if !(0..=MAX_OVERLAP_SECS).contains(&overlap_secs) {
+        return Err(format!("overlap_secs must be between 0 and {MAX_OVERLAP_SECS}").into());
+    }
+
+    let now = OffsetDateTime::now_utc();
+    let expires_at = match params.expires_in_secs {
+        Some(secs) => Some(service::api_token::expires_at(now, secs)?),
+        // keys issued before lifetimes were capped keep working, but their
+        // replacements are capped
+        None => old
+            .expires_at
+            .map(|it| {
+                let secs = (it - old.created_at).whole_seconds();
+                service::api_token::expires_at(
+                    now,
+                    secs.clamp(1, service::api_token::MAX_LIFETIME_SECS),
+                )
+            })
+            .transpose()?,
+    };
+    let api_key = service::api_token::new_secret();
+    let new = db::main::access_token::queries::insert_restricted(
+        user.id,
+        old.name.clone().unwrap_or_default(),
+        api_key.clone(),
+        old.roles.clone(),
+        old.import_origins.clone(),
+        Restrictions {
+            expires_at,
+            allowed_methods: old.allowed_methods.clone(),
+            allowed_ips: old.allowed_ips.clone(),
+        },
+        pool,
+    )
+    .await?;
+
+    let overlap_end = now + Duration::seconds(overlap_secs);
+    let previous_key_expires_at = match old.expires_at {
+        Some(expires_at) if expires_at < overlap_end => expires_at,
+        _ => overlap_end,
+    };
+    db::main::access_token::queries::set_expires_at(old.id, Some(previous_key_expires_at), pool)
+        .await?;
+
+    Ok(Res {
+        id: new.id,
+        api_key,
+        prefix: new.prefix,
+        expires_at: new.expires_at,
+        previous_key_expires_at: Some(previous_key_expires_at),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::Params;
+    use crate::db::main::access_token::{queries, schema::Restrictions};
+    use crate::db::main::test::pool;
+    use crate::db::main::user::schema::{Role, User};
+    use crate::Result;
+    use actix_web::test;
+    use time::{Duration, OffsetDateTime};
+
+    fn user(id: i64) -> User {
+        User {
+            id,
+            name: format!("user_{id}"),
+            password: String::new(),
+            roles: vec![Role::User],
+            saved_places: vec![],
+            saved_areas: vec![],
+            npub: None,
+            geofence: vec![],
+            created_at: String::new(),
+            updated_at: String::new(),
+            deleted_at: None,
+        }
+    }
+
+    fn params() -> Params {
+        Params {
+            id: None,
+            overlap_secs: None,
+            expires_in_secs: None,
+        }
+    }
+
+    #[test]
+    async fn rotates_calling_key() -> Result<()> {
+        let pool = pool();
+        let old = queries::insert_restricted(
+            1,
+            "importer".into(),
+            "old".into(),
+            vec![Role::PlacesSource],
+            vec!["https://example.com".into()],
+            Restrictions {
+                expires_at: None,
+                allowed_methods: vec!["import_places".into()],
+                allowed_ips: vec!["10.0.0.0/8".into()],
+            },
+            &pool,
+        )
+        .await?;
+
+        let res = super::run(params(), &old, &user(1), &pool).await?;
+        assert!(res.api_key.starts_with(&res.prefix));
+        assert!(res.expires_at.is_none());
+        let overlap = res.previous_key_expires_at.unwrap() - OffsetDateTime::now_utc();
+        assert!(overlap > Duration::minutes(59) && overlap <= Duration::hours(1));
+
+        let new = queries::select_by_secret(res.api_key, &pool).await?;
+        assert_eq!(res.id, new.id);
+        assert_eq!(old.name, new.name);
+        assert_eq!(old.roles, new.roles);
+        assert_eq!(old.import_origins, new.import_origins);
+        assert_eq!(old.allowed_methods, new.allowed_methods);
+        assert_eq!(old.allowed_ips, new.allowed_ips);
+
+        // the old key keeps working until the overlap window ends
+        let old = queries::select_by_secret("old".into(), &pool).await?;
+        assert_eq!(res.previous_key_expires_at, old.expires_at);
+        Ok(())
+    }
+
+    #[test]
+    async fn keeps_lifetime_of_expiring_keys() -> Result<()> {
+        let pool = pool();
+        let old = queries::insert_restricted(
+            1,
+            "importer".into(),
+            "old".into(),
+            vec![],
+            vec![],
+            Restrictions {
+                expires_at: Some(OffsetDateTime::now_utc() + Duration::minutes(10)),
+                ..Default::default()
+            },
+            &pool,
+        )
+        .await?;
+
+        let res = super::run(params(), &old, &user(1), &pool).await?;
+        let lifetime = res.expires_at.unwrap() - OffsetDateTime::now_utc();
+        assert!(lifetime > Duration::minutes(9) && lifetime <= Duration::minutes(10));
+        // the overlap can't extend the old key
+        assert_eq!(old.expires_at, res.previous_key_expires_at);
+
+        let res = super::run(
+            Params {
+                expires_in_secs: Some(60),
+                ..params()
+            },
+            &old,
+            &user(1),
+            &pool,
+        )
+        .await?;
+        assert!(res.expires_at.unwrap() <= OffsetDateTime::now_utc() + Duration::minutes(1));
+        Ok(())
+    }
+
+    #[test]
+    async fn rejects_key_of_another_user() -> Result<()> {
+        let pool = pool();
+        let caller = queries::insert(2, "".into(), "caller".into(), vec![], &pool).await?;
+        let other = queries::insert(1, "".into(), "other".into(), vec![], &pool).await?;
+
+        let res = super::run(
+            Params {
+                id: Some(other.id),
+                ..params()
+            },
+            &caller,
+            &user(2),
+            &pool,
+        )
+        .await;
+        assert_eq!("Key rotation failed", res.unwrap_err().to_string());
+        assert!(queries::select_by_id(other.id, &pool)
+            .await?
+            .expires_at
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    async fn rejects_invalid_lifetime() -> Result<()> {
+        let pool = pool();
+        let caller = queries::insert(1, "".into(), "caller".into(), vec![], &pool).await?;
+        for expires_in_secs in [0, -1, i64::MIN, i64::MAX] {
+            let res = super::run(
+                Params {
+                    expires_in_secs: Some(expires_in_secs),
+                    ..params()
+                },
+                &caller,
+                &user(1),
+                &pool,
+            )
+            .await;
+            assert!(res.is_err(), "{expires_in_secs}");
+        }
+        // nothing was rotated
+        assert!(queries::select_by_id(caller.id, &pool)
+            .await?
+            .expires_at
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    async fn rejects_invalid_overlap() -> Result<()> {
+        let pool = pool();
+        let caller = queries::insert(1, "".into(), "caller".into(), vec![], &pool).await?;
+        let res = super::run(
+            Params {
+                overlap_secs: Some(-1),
+                ..params()
+            },
+            &caller,
+            &user(1),
+            &pool,
+        )
+        .await;
+        assert!(res.is_err());
+        Ok(())
+    }
+}
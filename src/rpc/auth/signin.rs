@@ -1,13 +1,17 @@
 use crate::db;
+use crate::db::main::access_token::schema::Restrictions;
 use crate::db::main::user::schema::Role;
-use crate::rpc::handler::allowed_methods;
+use crate::rpc::handler::{allowed_methods, RpcMethod};
+use crate::service;
+use crate::service::cidr::Cidr;
 use crate::{Error, Result};
 use argon2::PasswordVerifier;
 use argon2::{Argon2, PasswordHash};
 use deadpool_sqlite::Pool;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::str::FromStr;
-use uuid::Uuid;
+use time::OffsetDateTime;
 
 #[derive(Deserialize, Clone)]
 pub struct Params {
@@ -16,6 +20,11 @@ pub struct Params {
     pub label: Option<String>,
     #[serde(default)]
     pub roles: Vec<String>,
+    pub expires_in_secs: Option<i64>,
+    #[serde(default)]
+    pub allowed_methods: Vec<String>,
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
 }
 
 #[derive(Serialize)]
@@ -35,6 +44,7 @@ impl std::fmt::Debug for Res {
 
 pub async fn run(params: Params, pool: &Pool) -> Result<Res> {
     let error_cause_mask = "Invalid credentials";
+    let restrictions = parse_restrictions(&params)?;
     let user = db::main::user::queries::select_by_name(params.username, pool)
         .await
         .map_err(|_| error_cause_mask)?;
@@ -43,12 +53,14 @@ pub async fn run(params: Params, pool: &Pool) -> Result<Res> {
         .verify_password(params.password.as_bytes(), &password_hash)
         .map_err(|_| error_cause_mask)?;
     let token_roles = parse_and_validate_token_roles(&params.roles, &user.roles)?;
-    let api_key = Uuid::new_v4().to_string();
-    db::main::access_token::queries::insert(
+    let api_key = service::api_token::new_secret();
+    db::main::access_token::queries::insert_restricted(
         user.id,
         params.label.unwrap_or_default(),
         api_key.clone(),
         token_roles,
+        vec![],
+        restrictions,
         pool,
     )
     .await?;
@@ -58,6 +70,27 @@ pub async fn run(params: Params, pool: &Pool) -> Result<Res> {
     })
 }
 
+fn parse_restrictions(params: &Params) -> Result<Restrictions> {
+    for method in &params.allowed_methods {
+        serde_json::from_value::<RpcMethod>(Value::String(method.clone()))
+            .map_err(|_| Error::Other(format!("'{}' is not a valid method", method)))?;
+    }
+    let allowed_ips = params
+        .allowed_ips
+        .iter()
+        .map(|it| it.parse::<Cidr>().map(|it| it.to_string()))
+        .collect::<Result<Vec<_>>>()?;
+    let expires_at = params
+        .expires_in_secs
+        .map(|it| service::api_token::expires_at(OffsetDateTime::now_utc(), it))
+        .transpose()?;
+    Ok(Restrictions {
+        expires_at,
+        allowed_methods: params.allowed_methods.clone(),
+        allowed_ips,
+    })
+}
+
 fn parse_and_validate_token_roles(requested: &[String], user_roles: &[Role]) -> Result<Vec<Role>> {
     let mut parsed = Vec::with_capacity(requested.len());
     for name in requested {
@@ -126,6 +159,18 @@ mod test {
             .unwrap();
     }
 
+    fn params() -> Params {
+        Params {
+            username: String::new(),
+            password: String::new(),
+            label: None,
+            roles: vec![],
+            expires_in_secs: None,
+            allowed_methods: vec![],
+            allowed_ips: vec![],
+        }
+    }
+
     fn run_test<F>(future: F) -> Result<()>
     where
         F: std::future::Future<Output = Result<()>>,
@@ -149,6 +194,7 @@ mod test {
                     password: "ihsotasatoshi123".into(),
                     label: None,
                     roles: vec![],
+                    ..params()
                 },
                 &pool,
             )
@@ -171,6 +217,7 @@ mod test {
                     password: "wrong-password".into(),
                     label: None,
                     roles: vec![],
+                    ..params()
                 },
                 &pool,
             )
@@ -192,6 +239,7 @@ mod test {
                     password: "ihsotasatoshi123".into(),
                     label: None,
                     roles: vec![],
+                    ..params()
                 },
                 &pool,
             )
@@ -215,6 +263,7 @@ mod test {
                     password: "ihsotasatoshi123".into(),
                     label: None,
                     roles: vec![],
+                    ..params()
                 },
                 &pool,
             )
@@ -237,6 +286,7 @@ mod test {
                     password: "ihsotasatoshi123".into(),
                     label: Some("my laptop".into()),
                     roles: vec![],
+                    ..params()
                 },
                 &pool,
             )
@@ -262,6 +312,7 @@ mod test {
                     password: "ihsotasatoshi123".into(),
                     label: Some("dashboard".into()),
                     roles: vec!["dashboard".into()],
+                    ..params()
                 },
                 &pool,
             )
@@ -289,6 +340,7 @@ mod test {
                     password: "ihsotasatoshi123".into(),
                     label: Some("dashboard".into()),
                     roles: vec!["dashboard".into()],
+                    ..params()
                 },
                 &pool,
             )
@@ -312,6 +364,7 @@ mod test {
                     password: "ihsotasatoshi123".into(),
                     label: Some("dashboard".into()),
                     roles: vec!["dashboard".into()],
+                    ..params()
                 },
                 &pool,
             )
@@ -337,6 +390,7 @@ mod test {
                     password: "ihsotasatoshi123".into(),
                     label: Some("admin".into()),
                     roles: vec!["admin".into()],
+                    ..params()
                 },
                 &pool,
             )
@@ -363,6 +417,7 @@ mod test {
                     password: "ihsotasatoshi123".into(),
                     label: Some("root".into()),
                     roles: vec!["root".into()],
+                    ..params()
                 },
                 &pool,
             )
@@ -388,6 +443,7 @@ mod test {
                     password: "ihsotasatoshi123".into(),
                     label: None,
                     roles: vec!["superuser".into()],
+                    ..params()
                 },
                 &pool,
             )
@@ -415,4 +471,81 @@ mod test {
             Ok(())
         })
     }
+
+    #[test]
+    fn restricted_token() -> Result<()> {
+        run_test(async {
+            let pool = pool();
+            insert_user("alice", "ihsotasatoshi123", &pool).await;
+
+            let res = super::run(
+                Params {
+                    username: "alice".into(),
+                    password: "ihsotasatoshi123".into(),
+                    expires_in_secs: Some(3600),
+                    allowed_methods: vec!["whoami".into()],
+                    allowed_ips: vec!["10.0.0.1".into()],
+                    ..params()
+                },
+                &pool,
+            )
+            .await?;
+
+            let token =
+                db::main::access_token::queries::select_by_secret(res.api_key, &pool).await?;
+            assert!(token.expires_at.is_some());
+            assert_eq!(token.allowed_methods, vec!["whoami".to_string()]);
+            assert_eq!(token.allowed_ips, vec!["10.0.0.1/32".to_string()]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn invalid_restrictions_are_rejected() -> Result<()> {
+        run_test(async {
+            let pool = pool();
+            insert_user("alice", "ihsotasatoshi123", &pool).await;
+
+            let err = super::run(
+                Params {
+                    username: "alice".into(),
+                    password: "ihsotasatoshi123".into(),
+                    allowed_methods: vec!["launch_rockets".into()],
+                    ..params()
+                },
+                &pool,
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(err.to_string(), "'launch_rockets' is not a valid method");
+
+            let err = super::run(
+                Params {
+                    username: "alice".into(),
+                    password: "ihsotasatoshi123".into(),
+                    allowed_ips: vec!["10.0.0.0/99".into()],
+                    ..params()
+                },
+                &pool,
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(err.to_string(), "invalid prefix length: 99");
+
+            for expires_in_secs in [0, -1, i64::MAX] {
+                let res = super::run(
+                    Params {
+                        username: "alice".into(),
+                        password: "ihsotasatoshi123".into(),
+                        expires_in_secs: Some(expires_in_secs),
+                        ..params()
+                    },
+                    &pool,
+                )
+                .await;
+                assert!(res.is_err(), "{expires_in_secs}");
+            }
+            Ok(())
+        })
+    }
 }
@@ -52,9 +52,14 @@ mod test {
             id,
             user_id: 1,
             name: Some("laptop".into()),
-            secret: "secret".into(),
+            secret_hash: String::new(),
+            prefix: String::new(),
             roles: vec![],
             import_origins: vec![],
+            expires_at: None,
+            last_used_at: None,
+            allowed_methods: vec![],
+            allowed_ips: vec![],
             created_at: OffsetDateTime::UNIX_EPOCH,
             updated_at: OffsetDateTime::UNIX_EPOCH,
             deleted_at: None,
@@ -1,6 +1,9 @@
 use crate::{
     db::{self, main::user::schema::Role},
-    service::auth::{MAX_PASSWORD_LENGTH, MIN_PASSWORD_LENGTH},
+    service::{
+        self,
+        auth::{MAX_PASSWORD_LENGTH, MIN_PASSWORD_LENGTH},
+    },
     Error, Result,
 };
 use argon2::PasswordHasher;
@@ -13,7 +16,6 @@ use names::Generator;
 use names::Name;
 use rusqlite::ffi::ErrorCode;
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
 
 #[derive(Deserialize)]
 pub struct Params {
@@ -81,7 +83,7 @@ pub async fn run(params: Params, pool: &Pool) -> Result<Res> {
         }
     };
     let user = db::main::user::queries::set_roles(user.id, &[Role::User], pool).await?;
-    let token = Uuid::new_v4().to_string();
+    let token = service::api_token::new_secret();
     db::main::access_token::queries::insert(
         user.id,
         params.label.unwrap_or_default(),
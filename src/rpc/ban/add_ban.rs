@@ -0,0 +1,147 @@
+use super::Res;
+use crate::{db, service, Result};
+use deadpool_sqlite::Pool;
+use serde::Deserialize;
+use time::{Duration, OffsetDateTime};
+
+#[derive(Deserialize)]
+pub struct Params {
+    /// An IP address or a CIDR range
+    pub ip: Option<String>,
+    /// A regex matched against the User-Agent header
+    pub user_agent: Option<String>,
+    pub reason: String,
+    /// Permanent if not set
+    pub duration_hours: Option<i64>,
+}
+
+pub async fn run(params: Params, pool: &Pool) -> Result<Res> {
+    let ip = params.ip.map(|it| it.trim().to_string());
+    if let Some(ip) = &ip {
+        service::ban::validate_ip(ip)?;
+    }
+    if let Some(user_agent) = &params.user_agent {
+        if user_agent.is_empty() {
+            return Err("user_agent can't be empty".into());
+        }
+        service::ban::validate_user_agent(user_agent)?;
+    }
+    if ip.is_none() && params.user_agent.is_none() {
+        return Err("either ip or user_agent is required".into());
+    }
+    if params.reason.trim().is_empty() {
+        return Err("reason can't be empty".into());
+    }
+    let end_at = match params.duration_hours {
+        Some(hours) if hours <= 0 => return Err("duration_hours must be positive".into()),
+        Some(hours) => Some(OffsetDateTime::now_utc().saturating_add(Duration::hours(hours))),
+        None => None,
+    };
+    let ban =
+        db::main::ban::queries::insert_rule(ip, params.user_agent, params.reason, end_at, pool)
+            .await?;
+    service::ban::invalidate();
+    Ok(ban.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Params;
+    use crate::{db::main::test::pool, Result};
+
+    fn params() -> Params {
+        Params {
+            ip: None,
+            user_agent: None,
+            reason: "spam".into(),
+            duration_hours: None,
+        }
+    }
+
+    #[actix_web::test]
+    async fn add_ban() -> Result<()> {
+        let pool = pool();
+        let res = super::run(
+            Params {
+                ip: Some("2001:db8::/32".into()),
+                user_agent: Some("^python-requests/".into()),
+                duration_hours: Some(24),
+                ..params()
+            },
+            &pool,
+        )
+        .await?;
+        assert_eq!(Some("2001:db8::/32".into()), res.ip);
+        assert_eq!(Some("^python-requests/".into()), res.user_agent);
+        assert!(res.end_at.is_some());
+        assert_eq!(0, res.hits);
+
+        let res = super::run(
+            Params {
+                ip: Some("10.0.0.1".into()),
+                ..params()
+            },
+            &pool,
+        )
+        .await?;
+        assert_eq!(None, res.end_at);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn add_ban_rejects_invalid_input() -> Result<()> {
+        let pool = pool();
+        for params in [
+            params(),
+            Params {
+                ip: Some("10.0.0.0/33".into()),
+                ..params()
+            },
+            Params {
+                user_agent: Some("(".into()),
+                ..params()
+            },
+            Params {
+                user_agent: Some("".into()),
+                ..params()
+            },
+            Params {
+                ip: Some("0.0.0.0/0".into()),
+                ..params()
+            },
+            Params {
+                ip: Some("10.0.0.0/7".into()),
+                ..params()
+            },
+            Params {
+                ip: Some("2001::/31".into()),
+                ..params()
+            },
+            Params {
+                user_agent: Some(".*".into()),
+                ..params()
+            },
+            Params {
+                user_agent: Some("^".into()),
+                ..params()
+            },
+            Params {
+                user_agent: Some("a|".into()),
+                ..params()
+            },
+            Params {
+                ip: Some("10.0.0.1".into()),
+                reason: " ".into(),
+                ..params()
+            },
+            Params {
+                ip: Some("10.0.0.1".into()),
+                duration_hours: Some(0),
+                ..params()
+            },
+        ] {
+            assert!(super::run(params, &pool).await.is_err());
+        }
+        Ok(())
+    }
+}
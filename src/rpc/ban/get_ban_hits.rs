@@ -0,0 +1,47 @@
+use super::Res;
+use crate::{db, Result};
+use deadpool_sqlite::Pool;
+use serde::Deserialize;
+
+const DEFAULT_LIMIT: i64 = 100;
+
+#[derive(Deserialize, Default)]
+pub struct Params {
+    pub limit: Option<i64>,
+}
+
+/// Hits are counted in memory and written every 30 seconds, see `service::ban`
+pub async fn run(params: Params, pool: &Pool) -> Result<Vec<Res>> {
+    let bans = db::main::ban::queries::select_most_hit(params.limit.unwrap_or(DEFAULT_LIMIT), pool)
+        .await?;
+    Ok(bans.into_iter().map(Into::into).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Params;
+    use crate::{db, db::main::test::pool, Result};
+    use time::{Duration, OffsetDateTime};
+
+    #[actix_web::test]
+    async fn get_ban_hits() -> Result<()> {
+        let pool = pool();
+        let hit = db::main::ban::queries::insert(
+            "10.0.0.1".into(),
+            "spam".into(),
+            Duration::days(1),
+            &pool,
+        )
+        .await?;
+        db::main::ban::queries::insert("10.0.0.2".into(), "spam".into(), Duration::days(1), &pool)
+            .await?;
+        db::main::ban::queries::add_hits(vec![(hit.id, 3, OffsetDateTime::now_utc())], &pool)
+            .await?;
+
+        let res = super::run(Params::default(), &pool).await?;
+        assert_eq!(1, res.len());
+        assert_eq!(hit.id, res[0].id);
+        assert_eq!(3, res[0].hits);
+        Ok(())
+    }
+}
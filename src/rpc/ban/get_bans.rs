@@ -0,0 +1,68 @@
+use super::Res;
+use crate::{db, Result};
+use deadpool_sqlite::Pool;
+use serde::Deserialize;
+
+const DEFAULT_LIMIT: i64 = 100;
+
+#[derive(Deserialize, Default)]
+pub struct Params {
+    /// Include expired and lifted bans
+    #[serde(default)]
+    pub include_inactive: bool,
+    /// Only applies with `include_inactive`, active bans are always returned in full
+    pub limit: Option<i64>,
+}
+
+pub async fn run(params: Params, pool: &Pool) -> Result<Vec<Res>> {
+    let bans = if params.include_inactive {
+        db::main::ban::queries::select_all(params.limit.unwrap_or(DEFAULT_LIMIT), pool).await?
+    } else {
+        db::main::ban::queries::select_active(pool).await?
+    };
+    Ok(bans.into_iter().map(Into::into).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Params;
+    use crate::{db, db::main::test::pool, Result};
+    use time::{Duration, OffsetDateTime};
+
+    #[actix_web::test]
+    async fn get_bans() -> Result<()> {
+        let pool = pool();
+        let active = db::main::ban::queries::insert(
+            "10.0.0.1".into(),
+            "spam".into(),
+            Duration::days(1),
+            &pool,
+        )
+        .await?;
+        let lifted = db::main::ban::queries::insert(
+            "10.0.0.2".into(),
+            "spam".into(),
+            Duration::days(1),
+            &pool,
+        )
+        .await?;
+        db::main::ban::queries::set_end_at(lifted.id, OffsetDateTime::now_utc(), &pool).await?;
+
+        let res = super::run(Params::default(), &pool).await?;
+        assert_eq!(
+            vec![active.id],
+            res.iter().map(|it| it.id).collect::<Vec<_>>()
+        );
+
+        let res = super::run(
+            Params {
+                include_inactive: true,
+                limit: None,
+            },
+            &pool,
+        )
+        .await?;
+        assert_eq!(2, res.len());
+        Ok(())
+    }
+}
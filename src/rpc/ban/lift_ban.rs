@@ -0,0 +1,54 @@
+use super::Res;
+use crate::{db, service, Result};
+use deadpool_sqlite::Pool;
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub id: i64,
+}
+
+/// Ends the ban right away, lifting an inactive ban does nothing
+pub async fn run(params: Params, pool: &Pool) -> Result<Res> {
+    let ban = db::main::ban::queries::select_by_id(params.id, pool)
+        .await
+        .map_err(|_| format!("there is no ban with id {}", params.id))?;
+    let now = OffsetDateTime::now_utc();
+    if ban.end_at.is_some_and(|it| it <= now) {
+        return Ok(ban.into());
+    }
+    let ban = db::main::ban::queries::set_end_at(ban.id, now, pool).await?;
+    service::ban::invalidate();
+    Ok(ban.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Params;
+    use crate::{db, db::main::test::pool, Result};
+    use time::Duration;
+
+    #[actix_web::test]
+    async fn lift_ban() -> Result<()> {
+        let pool = pool();
+        let ban = db::main::ban::queries::insert(
+            "10.0.0.1".into(),
+            "spam".into(),
+            Duration::days(1),
+            &pool,
+        )
+        .await?;
+        let lifted = super::run(Params { id: ban.id }, &pool).await?;
+        assert!(lifted.end_at.unwrap() < ban.end_at.unwrap());
+        assert!(db::main::ban::queries::select_active(&pool)
+            .await?
+            .is_empty());
+
+        let again = super::run(Params { id: ban.id }, &pool).await?;
+        assert_eq!(lifted.end_at, again.end_at);
+
+        assert!(super::run(Params { id: 42 }, &pool).await.is_err());
+        Ok(())
+    }
+}
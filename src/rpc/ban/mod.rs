@@ -0,0 +1,38 @@
+use crate::db::main::ban::schema::Ban;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+pub mod add_ban;
+pub mod get_ban_hits;
+pub mod get_bans;
+pub mod lift_ban;
+
+#[derive(Serialize)]
+pub struct Res {
+    pub id: i64,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub reason: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub start_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub end_at: Option<OffsetDateTime>,
+    pub hits: i64,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_hit_at: Option<OffsetDateTime>,
+}
+
+impl From<Ban> for Res {
+    fn from(ban: Ban) -> Self {
+        Res {
+            id: ban.id,
+            ip: ban.ip,
+            user_agent: ban.user_agent,
+            reason: ban.reason,
+            start_at: ban.start_at,
+            end_at: ban.end_at,
+            hits: ban.hits,
+            last_hit_at: ban.last_hit_at,
+        }
+    }
+}
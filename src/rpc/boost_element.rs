@@ -1,12 +1,7 @@
-use crate::{
-    db::{self, main::element::schema::Element},
-    Result,
-};
+use crate::{db, service, Result};
 use deadpool_sqlite::Pool;
 use geojson::JsonObject;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use time::{format_description::well_known::Iso8601, Duration, OffsetDateTime};
 
 #[derive(Deserialize)]
 pub struct Params {
@@ -21,34 +16,14 @@ pub struct Res {
 }
 
 pub async fn run(params: Params, pool: &Pool) -> Result<Res> {
-    let element = boost(&params.id, params.days, pool).await?;
+    if params.days < service::boost::MIN_DAYS {
+        return Err(format!("days must be at least {}", service::boost::MIN_DAYS).into());
+    }
+    let element = db::main::element::queries::select_by_id_or_osm_id(&params.id, pool).await?;
+    service::boost::grant(element.id, params.days, pool).await?;
+    let element = db::main::element::queries::select_by_id(element.id, pool).await?;
     Ok(Res {
         id: element.id,
         tags: element.tags,
     })
 }
-
-async fn boost(id_or_osm_id: &str, days: i64, pool: &Pool) -> Result<Element> {
-    let element = db::main::element::queries::select_by_id_or_osm_id(id_or_osm_id, pool).await?;
-    let boost_expires = element.tag("boost:expires");
-    let boost_expires = match boost_expires {
-        Value::String(v) => {
-            OffsetDateTime::parse(v, &Iso8601::DEFAULT).unwrap_or(OffsetDateTime::now_utc())
-        }
-        _ => OffsetDateTime::now_utc(),
-    };
-    let boost_expires = if boost_expires < OffsetDateTime::now_utc() {
-        OffsetDateTime::now_utc()
-    } else {
-        boost_expires
-    };
-    let boost_expires = boost_expires.checked_add(Duration::days(days)).unwrap();
-    let element = db::main::element::queries::set_tag(
-        element.id,
-        "boost:expires",
-        &Value::String(boost_expires.format(&Iso8601::DEFAULT)?),
-        pool,
-    )
-    .await?;
-    Ok(element)
-}
@@ -0,0 +1,34 @@
+use super::redacted;
+use crate::{db, Result};
+use deadpool_sqlite::Pool;
+use serde_json::{Map, Value};
+
+pub async fn run(pool: &Pool) -> Result<Map<String, Value>> {
+    let conf = db::main::conf::queries::select(pool).await?;
+    Ok(redacted(&conf))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::main::test::pool;
+    use crate::Result;
+    use actix_web::test;
+    use serde_json::json;
+
+    #[test]
+    async fn run() -> Result<()> {
+        let pool = pool();
+        pool.get()
+            .await?
+            .interact(|conn| conn.execute("UPDATE conf SET ppq_key = 'secret'", []))
+            .await??;
+        let res = super::run(&pool).await?;
+        assert_eq!(
+            Some(&json!(500)),
+            res.get("paywall_add_element_comment_price_sat")
+        );
+        assert_eq!(Some(&json!(super::super::REDACTED)), res.get("ppq_key"));
+        assert_eq!(Some(&json!("")), res.get("gitea_api_key"));
+        Ok(())
+    }
+}
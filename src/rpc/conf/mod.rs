@@ -0,0 +1,69 @@
+use crate::db::main::conf::schema::{Columns, Conf};
+use serde_json::{json, Map, Value};
+use strum::VariantArray;
+
+pub mod get_conf;
+pub mod set_conf;
+
+/// Replaces secret values, the response only tells whether they are set
+pub const REDACTED: &str = "[redacted]";
+
+/// The JSON value of a column. Keys, passwords and macaroons never leave the
+/// server through RPC, so they are redacted.
+pub fn value(conf: &Conf, column: &Columns) -> Value {
+    let secret = |value: &str| {
+        if value.is_empty() {
+            json!("")
+        } else {
+            json!(REDACTED)
+        }
+    };
+    match column {
+        Columns::PaywallAddElementCommentPriceSat => {
+            json!(conf.paywall_add_element_comment_price_sat)
+        }
+        Columns::BoostElementPrices => json!(conf.boost_element_prices),
+        Columns::LnbitsInvoiceKey => secret(&conf.lnbits_invoice_key),
+        Columns::GiteaApiKey => secret(&conf.gitea_api_key),
+        Columns::MatrixBotPassword => secret(&conf.matrix_bot_password),
+        Columns::LndInvoicesMacaroon => secret(&conf.lnd_invoices_macaroon),
+        Columns::LndReadonlyMacaroon => secret(&conf.lnd_readonly_macaroon),
+        Columns::PpqKey => secret(&conf.ppq_key),
+        Columns::CorsOrigins => json!(conf.cors_origins),
+        Columns::NostrSecretKey => secret(&conf.nostr_secret_key),
+        Columns::NostrRelays => json!(conf.nostr_relays),
+        Columns::FreeCommentsPerDay => json!(conf.free_comments_per_day),
+        Columns::FreeCommentMinAccountAgeDays => json!(conf.free_comment_min_account_age_days),
+        Columns::FreeCommentNpubAllowlist => json!(conf.free_comment_npub_allowlist),
+        Columns::InvoiceBackend => json!(conf.invoice_backend),
+        Columns::LnbitsUrl => json!(conf.lnbits_url),
+        Columns::LndUrl => json!(conf.lnd_url),
+        Columns::FiatCurrency => json!(conf.fiat_currency),
+        Columns::PaywallAddElementCommentPriceFiat => {
+            json!(conf.paywall_add_element_comment_price_fiat)
+        }
+        Columns::ExchangeRateProvider => json!(conf.exchange_rate_provider),
+        Columns::ExchangeRateMaxAgeSecs => json!(conf.exchange_rate_max_age_secs),
+        Columns::ExchangeRateFallback => json!(conf.exchange_rate_fallback),
+        Columns::RateLimits => json!(conf.rate_limits),
+        Columns::RateLimitBanAfter => json!(conf.rate_limit_ban_after),
+        Columns::RateLimitBanHours => json!(conf.rate_limit_ban_hours),
+        Columns::AbuseWindowMinutes => json!(conf.abuse_window_minutes),
+        Columns::AbuseMaxRequests => json!(conf.abuse_max_requests),
+        Columns::AbuseMaxFailedSignins => json!(conf.abuse_max_failed_signins),
+        Columns::AbuseMaxInvoices => json!(conf.abuse_max_invoices),
+        Columns::AbuseBanHours => json!(conf.abuse_ban_hours),
+        Columns::AbuseAllowlist => json!(conf.abuse_allowlist),
+        Columns::HoneypotPlaceIds => json!(conf.honeypot_place_ids),
+        Columns::HoneypotBanDays => json!(conf.honeypot_ban_days),
+        Columns::UpdatedAt => json!(conf.updated_at),
+    }
+}
+
+/// All columns by name, with secrets redacted
+pub fn redacted(conf: &Conf) -> Map<String, Value> {
+    Columns::VARIANTS
+        .iter()
+        .map(|column| (column.to_string(), value(conf, column)))
+        .collect()
+}
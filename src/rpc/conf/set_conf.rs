@@ -0,0 +1,328 @@
+use super::{redacted, value};
+use crate::{
+    db::{
+        self,
+        main::conf::schema::{BoostPrice, Columns, Conf, RateLimit},
+        main::user::schema::User,
+    },
+    service,
+    service::cidr::Cidr,
+    Result,
+};
+use deadpool_sqlite::Pool;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Map, Value};
+use std::str::FromStr;
+use url::Url;
+
+/// The detector reads the whole window from the log in one go
+const MAX_ABUSE_WINDOW_MINUTES: i64 = 24 * 60;
+
+#[derive(Deserialize)]
+pub struct Params {
+    /// New values by conf field name, fields which aren't listed stay as they are
+    pub changes: Map<String, Value>,
+}
+
+pub async fn run(params: Params, user: &User, pool: &Pool) -> Result<Map<String, Value>> {
+    if params.changes.is_empty() {
+        return Err("changes can't be empty".into());
+    }
+    let old = db::main::conf::queries::select(pool).await?;
+    let mut new = old.clone();
+    let mut columns = vec![];
+    for (key, value) in params.changes {
+        let column = Columns::from_str(&key).map_err(|_| format!("unknown conf field: {key}"))?;
+        apply(&mut new, &column, value).map_err(|e| format!("invalid {key}: {e}"))?;
+        columns.push(column);
+    }
+    if columns.contains(&Columns::InvoiceBackend) {
+        // refuse to switch to a backend which can't create invoices
+        service::invoice_backend::backend(&new.invoice_backend, &new)?;
+    }
+    let new = db::main::conf::queries::update(columns.clone(), new, pool).await?;
+    let changes: Map<String, Value> = columns
+        .iter()
+        .map(|column| {
+            (
+                column.to_string(),
+                json!({ "old": value(&old, column), "new": value(&new, column) }),
+            )
+        })
+        .collect();
+    db::main::conf_audit::queries::insert(user.id, changes, pool).await?;
+    service::conf::notify_changed();
+    Ok(redacted(&new))
+}
+
+fn apply(conf: &mut Conf, column: &Columns, value: Value) -> Result<()> {
+    match column {
+        Columns::PaywallAddElementCommentPriceSat => {
+            conf.paywall_add_element_comment_price_sat = non_negative(parse(value)?)?
+        }
+        Columns::BoostElementPrices => {
+            let prices: Vec<BoostPrice> = parse(value)?;
+            for price in &prices {
+                if price.days <= 0 {
+                    return Err("days must be positive".into());
+                }
+                non_negative(price.sats)?;
+                if let Some(fiat) = price.fiat {
+                    non_negative_f64(fiat)?;
+                }
+            }
+            conf.boost_element_prices = prices
+        }
+        Columns::LnbitsInvoiceKey => conf.lnbits_invoice_key = parse(value)?,
+        Columns::GiteaApiKey => conf.gitea_api_key = parse(value)?,
+        Columns::MatrixBotPassword => conf.matrix_bot_password = parse(value)?,
+        Columns::LndInvoicesMacaroon => conf.lnd_invoices_macaroon = parse(value)?,
+        Columns::LndReadonlyMacaroon => conf.lnd_readonly_macaroon = parse(value)?,
+        Columns::PpqKey => conf.ppq_key = parse(value)?,
+        Columns::CorsOrigins => {
+            let origins = list(value)?;
+            for origin in &origins {
+                let url = Url::parse(origin).map_err(|e| format!("{origin}: {e}"))?;
+                // browsers send origins without a path, so those would never match
+                if url.origin().ascii_serialization() != *origin {
+                    return Err(format!("{origin} is not an origin").into());
+                }
+            }
+            conf.cors_origins = origins
+        }
+        Columns::NostrSecretKey => conf.nostr_secret_key = parse(value)?,
+        Columns::NostrRelays => {
+            let relays = list(value)?;
+            for relay in &relays {
+                url(relay, &["wss", "ws"])?;
+            }
+            conf.nostr_relays = relays
+        }
+        Columns::FreeCommentsPerDay => conf.free_comments_per_day = non_negative(parse(value)?)?,
+        Columns::FreeCommentMinAccountAgeDays => {
+            conf.free_comment_min_account_age_days = non_negative(parse(value)?)?
+        }
+        Columns::FreeCommentNpubAllowlist => {
+            let npubs = list(value)?;
+            for npub in &npubs {
+                if !npub.starts_with("npub1") {
+                    return Err(format!("{npub} is not an npub").into());
+                }
+            }
+            conf.free_comment_npub_allowlist = npubs
+        }
+        Columns::InvoiceBackend => conf.invoice_backend = parse(value)?,
+        Columns::LnbitsUrl => conf.lnbits_url = url(&parse::<String>(value)?, &["https", "http"])?,
+        Columns::LndUrl => conf.lnd_url = url(&parse::<String>(value)?, &["https", "http"])?,
+        Columns::FiatCurrency => {
+            let currency: String = parse(value)?;
+            if !currency.is_empty()
+                && (currency.len() != 3 || !currency.chars().all(|it| it.is_ascii_uppercase()))
+            {
+                return Err("expected an ISO 4217 code, such as USD".into());
+            }
+            conf.fiat_currency = currency
+        }
+        Columns::PaywallAddElementCommentPriceFiat => {
+            conf.paywall_add_element_comment_price_fiat = non_negative_f64(parse(value)?)?
+        }
+        Columns::ExchangeRateProvider => {
+            let provider: String = parse(value)?;
+            service::exchange_rate::provider(&provider)?;
+            conf.exchange_rate_provider = provider
+        }
+        Columns::ExchangeRateMaxAgeSecs => {
+            let secs = parse(value)?;
+            if secs <= 0 {
+                return Err("must be positive".into());
+            }
+            conf.exchange_rate_max_age_secs = secs
+        }
+        Columns::ExchangeRateFallback => {
+            conf.exchange_rate_fallback = non_negative_f64(parse(value)?)?
+        }
+        Columns::RateLimits => {
+            let limits: Vec<RateLimit> = parse(value)?;
+            for (i, limit) in limits.iter().enumerate() {
+                if limit.burst <= 0 || limit.per_minute <= 0 {
+                    return Err("burst and per_minute must be positive".into());
+                }
+                if limits[..i].iter().any(|it| it.group == limit.group) {
+                    return Err(format!("{:?} is listed more than once", limit.group).into());
+                }
+            }
+            conf.rate_limits = limits
+        }
+        Columns::RateLimitBanAfter => conf.rate_limit_ban_after = non_negative(parse(value)?)?,
+        Columns::RateLimitBanHours => {
+            let hours = parse(value)?;
+            if hours <= 0 {
+                return Err("must be positive".into());
+            }
+            conf.rate_limit_ban_hours = hours
+        }
+        Columns::AbuseWindowMinutes => {
+            let minutes = parse(value)?;
+            if !(1..=MAX_ABUSE_WINDOW_MINUTES).contains(&minutes) {
+                return Err(format!("must be between 1 and {MAX_ABUSE_WINDOW_MINUTES}").into());
+            }
+            conf.abuse_window_minutes = minutes
+        }
+        Columns::AbuseMaxRequests => conf.abuse_max_requests = non_negative(parse(value)?)?,
+        Columns::AbuseMaxFailedSignins => {
+            conf.abuse_max_failed_signins = non_negative(parse(value)?)?
+        }
+        Columns::AbuseMaxInvoices => conf.abuse_max_invoices = non_negative(parse(value)?)?,
+        Columns::AbuseBanHours => conf.abuse_ban_hours = non_negative(parse(value)?)?,
+        Columns::AbuseAllowlist => {
+            let ranges = list(value)?;
+            for range in &ranges {
+                range.parse::<Cidr>()?;
+            }
+            conf.abuse_allowlist = ranges
+        }
+        Columns::HoneypotPlaceIds => {
+            let ids = list(value)?;
+            if ids.iter().any(|it| it.parse::<i64>().is_err()) {
+                return Err("must be place ids".into());
+            }
+            conf.honeypot_place_ids = ids
+        }
+        Columns::HoneypotBanDays => {
+            let days = parse(value)?;
+            if days <= 0 {
+                return Err("must be positive".into());
+            }
+            conf.honeypot_ban_days = days
+        }
+        Columns::UpdatedAt => return Err("set automatically on every write".into()),
+    }
+    Ok(())
+}
+
+fn parse<T: DeserializeOwned>(value: Value) -> Result<T> {
+    serde_json::from_value(value).map_err(Into::into)
+}
+
+/// Accepts both a JSON array and a comma-separated string, like the DB column
+fn list(value: Value) -> Result<Vec<String>> {
+    let items = match value {
+        Value::String(value) => value.split(',').map(str::to_string).collect(),
+        value => parse::<Vec<String>>(value)?,
+    };
+    Ok(items
+        .iter()
+        .map(|it| it.trim())
+        .filter(|it| !it.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+fn url(value: &str, schemes: &[&str]) -> Result<String> {
+    let url = Url::parse(value).map_err(|e| format!("{value}: {e}"))?;
+    if !schemes.contains(&url.scheme()) {
+        return Err(format!("{value}: expected one of {}", schemes.join(", ")).into());
+    }
+    Ok(value.to_string())
+}
+
+fn non_negative(value: i64) -> Result<i64> {
+    if value < 0 {
+        return Err("can't be negative".into());
+    }
+    Ok(value)
+}
+
+fn non_negative_f64(value: f64) -> Result<f64> {
+    if !value.is_finite() || value < 0.0 {
+        return Err("can't be negative".into());
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Params;
+    use crate::db::main::test::pool;
+    use crate::{db, Result};
+    use actix_web::test;
+    use serde_json::{json, Value};
+
+    fn params(changes: Value) -> Params {
+        Params {
+            changes: changes.as_object().unwrap().clone(),
+        }
+    }
+
+    #[test]
+    async fn run() -> Result<()> {
+        let pool = pool();
+        let user = db::main::user::queries::insert("root", "", &pool).await?;
+        let res = super::run(
+            params(json!({
+                "cors_origins": ["https://btcmap.org"],
+                "free_comments_per_day": 3,
+                "ppq_key": "secret",
+            })),
+            &user,
+            &pool,
+        )
+        .await?;
+        assert_eq!(
+            Some(&json!(["https://btcmap.org"])),
+            res.get("cors_origins")
+        );
+        assert_eq!(Some(&json!(super::super::REDACTED)), res.get("ppq_key"));
+
+        let conf = db::main::conf::queries::select(&pool).await?;
+        assert_eq!(3, conf.free_comments_per_day);
+        assert_eq!("secret", conf.ppq_key);
+
+        let audit = db::main::conf_audit::queries::select_latest(1, &pool).await?;
+        let changes = &audit[0].changes;
+        assert_eq!(user.id, audit[0].user_id);
+        assert_eq!(
+            Some(&json!({ "old": 5, "new": 3 })),
+            changes.get("free_comments_per_day")
+        );
+        // secrets never end up in the audit log
+        assert_eq!(
+            Some(&json!({ "old": "", "new": super::super::REDACTED })),
+            changes.get("ppq_key")
+        );
+        Ok(())
+    }
+
+    #[test]
+    async fn run_rejects_invalid_input() -> Result<()> {
+        let pool = pool();
+        let user = db::main::user::queries::insert("root", "", &pool).await?;
+        for changes in [
+            json!({}),
+            json!({ "unknown": 1 }),
+            json!({ "updated_at": "2020-01-01T00:00:00Z" }),
+            json!({ "free_comments_per_day": -1 }),
+            json!({ "free_comments_per_day": "5" }),
+            json!({ "cors_origins": ["https://btcmap.org/"] }),
+            json!({ "boost_element_prices": [{ "days": 0, "sats": 100 }] }),
+            json!({ "invoice_backend": "lnbits" }),
+            json!({ "exchange_rate_provider": "unknown" }),
+            json!({ "fiat_currency": "usd" }),
+            json!({ "rate_limits": [{ "group": "writes", "burst": 1, "per_minute": 1 }] }),
+            json!({ "rate_limits": [{ "group": "reads", "burst": 0, "per_minute": 1 }] }),
+            json!({ "rate_limits": [
+                { "group": "rpc", "burst": 1, "per_minute": 1 },
+                { "group": "rpc", "burst": 2, "per_minute": 2 },
+            ] }),
+            json!({ "rate_limit_ban_hours": 0 }),
+            json!({ "abuse_window_minutes": 0 }),
+            json!({ "abuse_max_requests": -1 }),
+            json!({ "abuse_allowlist": "10.0.0.0/33" }),
+        ] {
+            assert!(super::run(params(changes), &user, &pool).await.is_err());
+        }
+        let audit = db::main::conf_audit::queries::select_latest(1, &pool).await?;
+        assert!(audit.is_empty());
+        Ok(())
+    }
+}
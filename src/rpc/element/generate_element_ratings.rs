@@ -0,0 +1,32 @@
+use crate::{db, service, Result};
+use deadpool_sqlite::Pool;
+use serde::Serialize;
+use time::OffsetDateTime;
+use tracing::info;
+
+#[derive(Serialize)]
+pub struct Res {
+    pub elements_affected: i64,
+    pub time_sec: f64,
+}
+
+pub async fn run(pool: &Pool) -> Result<Res> {
+    let started_at = OffsetDateTime::now_utc();
+    let element_ids = db::main::element_review::queries::select_element_ids(pool).await?;
+    let mut elements_affected = 0;
+    for element_id in element_ids {
+        let element = db::main::element::queries::select_by_id(element_id, pool).await?;
+        if service::review::refresh_rating_tags(&element, pool).await? {
+            info!(
+                element.id,
+                element.name = element.name(None),
+                "updated rating tags"
+            );
+            elements_affected += 1;
+        }
+    }
+    Ok(Res {
+        elements_affected,
+        time_sec: (OffsetDateTime::now_utc() - started_at).as_seconds_f64(),
+    })
+}
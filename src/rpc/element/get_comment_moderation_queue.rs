@@ -0,0 +1,39 @@
+use crate::{db, Result};
+use deadpool_sqlite::Pool;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+#[derive(Serialize)]
+pub struct Res {
+    pub id: i64,
+    pub element_id: i64,
+    pub comment: String,
+    pub spam_score: i64,
+    /// Reasons given by the users who flagged the comment
+    pub flags: Vec<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub hidden_at: Option<OffsetDateTime>,
+}
+
+pub async fn run(pool: &Pool) -> Result<Vec<Res>> {
+    let mut res = vec![];
+    for comment in db::main::element_comment::queries::select_moderation_queue(pool).await? {
+        let flags =
+            db::main::element_comment_flag::queries::select_unresolved_by_element_comment_id(
+                comment.id, pool,
+            )
+            .await?;
+        res.push(Res {
+            id: comment.id,
+            element_id: comment.element_id,
+            comment: comment.comment,
+            spam_score: comment.spam_score,
+            flags: flags.into_iter().map(|it| it.reason).collect(),
+            created_at: comment.created_at,
+            hidden_at: comment.hidden_at,
+        });
+    }
+    Ok(res)
+}
@@ -0,0 +1,15 @@
+use super::CommentRes;
+use crate::{service, Result};
+use deadpool_sqlite::Pool;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub id: i64,
+}
+
+pub async fn run(params: Params, pool: &Pool) -> Result<CommentRes> {
+    service::comment::hide(params.id, pool)
+        .await
+        .map(Into::into)
+}
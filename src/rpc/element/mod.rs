@@ -1,2 +1,31 @@
+use crate::db::main::element_comment::schema::ElementComment;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+pub mod delete_element_comment;
 pub mod generate_element_comment_counts;
+pub mod generate_element_ratings;
+pub mod get_comment_moderation_queue;
 pub mod get_element;
+pub mod hide_element_comment;
+pub mod restore_element_comment;
+
+/// Returned by the comment moderation methods
+#[derive(Serialize)]
+pub struct CommentRes {
+    pub id: i64,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub deleted_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub hidden_at: Option<OffsetDateTime>,
+}
+
+impl From<ElementComment> for CommentRes {
+    fn from(val: ElementComment) -> Self {
+        CommentRes {
+            id: val.id,
+            deleted_at: val.deleted_at,
+            hidden_at: val.hidden_at,
+        }
+    }
+}
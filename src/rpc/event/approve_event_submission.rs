@@ -0,0 +1,22 @@
+use super::get_event_submissions::Res;
+use crate::{
+    db::{self, main::user::schema::User},
+    service, Result,
+};
+use deadpool_sqlite::Pool;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct Params {
+    id: i64,
+    #[serde(default)]
+    note: Option<String>,
+}
+
+pub async fn run(params: Params, user: &User, pool: &Pool) -> Result<Res> {
+    let submission = db::main::event_submission::queries::select_by_id(params.id, pool).await?;
+    super::geofence::check(user, submission.lat, submission.lon, pool).await?;
+    service::event_submission::approve(&submission, user, params.note, pool)
+        .await
+        .map(|(submission, _)| submission.into())
+}
@@ -1,6 +1,6 @@
 use crate::{
     db::{self, main::event::schema::Event, main::user::schema::User},
-    Result,
+    service, Result,
 };
 use deadpool_sqlite::Pool;
 use serde::{Deserialize, Serialize};
@@ -33,7 +33,10 @@ impl From<Event> for Res {
 
 pub async fn run(params: Params, user: &User, pool: &Pool) -> Result<Res> {
     super::geofence::check(user, params.lat, params.lon, pool).await?;
-    db::main::event::queries::insert(
+    if let Some(cron_schedule) = &params.cron_schedule {
+        service::event_schedule::parse_schedule(cron_schedule)?;
+    }
+    let event = db::main::event::queries::insert(
         params.area_id,
         params.lat,
         params.lon,
@@ -44,8 +47,10 @@ pub async fn run(params: Params, user: &User, pool: &Pool) -> Result<Res> {
         params.cron_schedule,
         pool,
     )
-    .await
-    .map(Into::into)
+    .await?;
+    service::event_schedule::store_timezone(&event, pool)
+        .await
+        .map(Into::into)
 }
 
 #[cfg(test)]
@@ -146,6 +151,8 @@ mod test {
             let user = em_user(vec![phuket.id]);
             let res = run(params(Some(phuket.id), 7.98, 98.33), &user, &pool).await?;
             assert!(res.id > 0);
+            let event = db::main::event::queries::select_by_id(res.id, &pool).await?;
+            assert_eq!(Some("Asia/Bangkok".into()), event.timezone);
             Ok::<(), crate::Error>(())
         })
     }
@@ -1,12 +1,6 @@
-use crate::{
-    db::{self, main::area::schema::Area},
-    Result,
-};
+use crate::service::area::{area_inside_area, point_inside_area};
+use crate::{db, Result};
 use deadpool_sqlite::Pool;
-use geo::Contains;
-use geo::LineString;
-use geo::MultiPolygon;
-use geo::Polygon;
 
 /// Verify that the (lat, lon) the caller wants to operate on is inside the
 /// caller's geofence when the geofence is non-empty.
@@ -46,32 +40,37 @@ pub(crate) async fn check(
     .into())
 }
 
-fn point_inside_area(area: &Area, coord: geo::Coord) -> Result<bool> {
-    let geometries = area.geo_json_geometries()?;
-    for geometry in &geometries {
-        match &geometry.value {
-            geojson::GeometryValue::Polygon { coordinates: _ } => {
-                let poly: Polygon = (&geometry.value).try_into().unwrap();
-                if poly.contains(&coord) {
-                    return Ok(true);
-                }
-            }
-            geojson::GeometryValue::MultiPolygon { coordinates: _ } => {
-                let multi_poly: MultiPolygon = (&geometry.value).try_into().unwrap();
-                if multi_poly.contains(&coord) {
-                    return Ok(true);
-                }
-            }
-            geojson::GeometryValue::LineString { coordinates: _ } => {
-                let line_string: LineString = (&geometry.value).try_into().unwrap();
-                if line_string.contains(&coord) {
-                    return Ok(true);
-                }
-            }
-            _ => continue,
+/// Verify that the caller may assign `area_id` to something located at
+/// (lat, lon): the point has to be inside the area and the area has to be
+/// covered by the caller's geofence, either by being one of the fenced areas
+/// or by lying inside one of them.
+pub(crate) async fn check_area(
+    user: &crate::db::main::user::schema::User,
+    area_id: i64,
+    lat: f64,
+    lon: f64,
+    pool: &Pool,
+) -> Result<()> {
+    let area = db::main::area::queries::select_by_id(area_id, pool).await?;
+    if area.deleted_at.is_some() {
+        return Err(format!("Area {area_id} is deleted").into());
+    }
+    if !point_inside_area(&area, geo::coord!(x: lon, y: lat))? {
+        return Err(format!("Location ({lat}, {lon}) is outside area {area_id}").into());
+    }
+    if user.geofence.is_empty() || user.geofence.contains(&area_id) {
+        return Ok(());
+    }
+    for fence in db::main::area::queries::select_by_ids(&user.geofence, pool).await? {
+        if area_inside_area(&area, &fence)? {
+            return Ok(());
         }
     }
-    Ok(false)
+    Err(format!(
+        "Area {area_id} is outside your geofence (allowed areas: {:?})",
+        user.geofence
+    )
+    .into())
 }
 
 /// Convenience wrapper used by `delete_event`: load the existing event
@@ -0,0 +1,84 @@
+use crate::{
+    db::{
+        self,
+        main::event_submission::schema::{EventSubmission, EventSubmissionStatus},
+        main::user::schema::User,
+    },
+    Result,
+};
+use deadpool_sqlite::Pool;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+#[derive(Deserialize)]
+pub struct Params {
+    /// pending (default), approved or rejected
+    #[serde(default)]
+    status: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Res {
+    pub id: i64,
+    pub user_id: i64,
+    pub area_id: Option<i64>,
+    pub lat: f64,
+    pub lon: f64,
+    pub name: String,
+    pub website: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub starts_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub ends_at: Option<OffsetDateTime>,
+    pub cron_schedule: Option<String>,
+    pub status: String,
+    pub review_note: Option<String>,
+    pub reviewed_by: Option<i64>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub reviewed_at: Option<OffsetDateTime>,
+    pub event_id: Option<i64>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl From<EventSubmission> for Res {
+    fn from(val: EventSubmission) -> Self {
+        Res {
+            id: val.id,
+            user_id: val.user_id,
+            area_id: val.area_id,
+            lat: val.lat,
+            lon: val.lon,
+            name: val.name,
+            website: val.website,
+            starts_at: val.starts_at,
+            ends_at: val.ends_at,
+            cron_schedule: val.cron_schedule,
+            status: val.status.into(),
+            review_note: val.review_note,
+            reviewed_by: val.reviewed_by,
+            reviewed_at: val.reviewed_at,
+            event_id: val.event_id,
+            created_at: val.created_at,
+        }
+    }
+}
+
+/// Moderators only see the submissions located inside their geofence
+pub async fn run(params: Params, user: &User, pool: &Pool) -> Result<Vec<Res>> {
+    let status = match params.status {
+        Some(status) => EventSubmissionStatus::try_from(status.as_str())
+            .map_err(|_| format!("Unknown submission status: {status}"))?,
+        None => EventSubmissionStatus::Pending,
+    };
+    let mut res = vec![];
+    for submission in db::main::event_submission::queries::select_by_status(status, pool).await? {
+        if super::geofence::check(user, submission.lat, submission.lon, pool)
+            .await
+            .is_ok()
+        {
+            res.push(submission.into());
+        }
+    }
+    Ok(res)
+}
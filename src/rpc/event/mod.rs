@@ -1,6 +1,10 @@
+pub mod approve_event_submission;
 pub mod create_event;
 pub mod delete_event;
 pub(crate) mod geofence;
 pub mod get_event;
+pub mod get_event_submissions;
 pub mod get_events;
+pub mod reject_event_submission;
 pub mod update_event;
+pub mod update_event_submission;
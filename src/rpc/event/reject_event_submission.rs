@@ -0,0 +1,25 @@
+use super::get_event_submissions::Res;
+use crate::{
+    db::{self, main::user::schema::User},
+    service, Result,
+};
+use deadpool_sqlite::Pool;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct Params {
+    id: i64,
+    /// Shared with the submitter
+    reason: String,
+}
+
+pub async fn run(params: Params, user: &User, pool: &Pool) -> Result<Res> {
+    if params.reason.trim().is_empty() {
+        return Err("reason can't be empty".into());
+    }
+    let submission = db::main::event_submission::queries::select_by_id(params.id, pool).await?;
+    super::geofence::check(user, submission.lat, submission.lon, pool).await?;
+    service::event_submission::reject(&submission, user, params.reason, pool)
+        .await
+        .map(Into::into)
+}
@@ -1,12 +1,16 @@
 use crate::{
-    db::{self, main::event::schema::Event, main::user::schema::User},
-    Result,
+    db::{
+        self,
+        main::event::schema::{Event, DATE_FORMAT},
+        main::user::schema::User,
+    },
+    service, Result,
 };
 use deadpool_sqlite::Pool;
 use serde::{Deserialize, Serialize};
-use time::OffsetDateTime;
+use time::{Date, OffsetDateTime};
 
-mod optional_rfc3339 {
+pub(super) mod optional_rfc3339 {
     use serde::{de::Error, Deserialize, Deserializer};
     use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
@@ -41,6 +45,10 @@ pub struct Params {
     ends_at: Option<Option<OffsetDateTime>>,
     #[serde(default)]
     cron_schedule: Option<Option<String>>,
+    /// Local dates (YYYY-MM-DD) on which a recurring event is skipped,
+    /// replaces the existing list
+    #[serde(default)]
+    excluded_dates: Option<Vec<String>>,
 }
 
 #[derive(Serialize)]
@@ -55,6 +63,7 @@ pub struct Res {
     #[serde(with = "time::serde::rfc3339::option")]
     ends_at: Option<OffsetDateTime>,
     cron_schedule: Option<String>,
+    excluded_dates: Vec<String>,
     pub area_id: Option<i64>,
 }
 
@@ -69,6 +78,11 @@ impl From<Event> for Res {
             starts_at: event.starts_at,
             ends_at: event.ends_at,
             cron_schedule: event.cron_schedule,
+            excluded_dates: event
+                .excluded_dates
+                .iter()
+                .filter_map(|it| it.format(DATE_FORMAT).ok())
+                .collect(),
             area_id: event.area_id,
         }
     }
@@ -79,7 +93,23 @@ pub async fn run(params: Params, user: &User, pool: &Pool) -> Result<Res> {
     let lat = params.lat.unwrap_or(event.lat);
     let lon = params.lon.unwrap_or(event.lon);
     super::geofence::check(user, lat, lon, pool).await?;
-    db::main::event::queries::update(
+    if let Some(Some(cron_schedule)) = &params.cron_schedule {
+        service::event_schedule::parse_schedule(cron_schedule)?;
+    }
+    let excluded_dates = match params.excluded_dates {
+        Some(dates) => Some(
+            dates
+                .iter()
+                .map(|it| {
+                    Date::parse(it, DATE_FORMAT)
+                        .map_err(|_| format!("Invalid excluded date {it:?}, expected YYYY-MM-DD"))
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?,
+        ),
+        None => None,
+    };
+    let moved = params.lat.is_some() || params.lon.is_some();
+    let event = db::main::event::queries::update(
         params.id,
         params.area_id,
         params.lat,
@@ -91,8 +121,18 @@ pub async fn run(params: Params, user: &User, pool: &Pool) -> Result<Res> {
         params.cron_schedule,
         pool,
     )
-    .await
-    .map(Into::into)
+    .await?;
+    let event = if moved {
+        service::event_schedule::store_timezone(&event, pool).await?
+    } else {
+        event
+    };
+    match excluded_dates {
+        Some(dates) => db::main::event::queries::set_excluded_dates(event.id, dates, pool)
+            .await
+            .map(Into::into),
+        None => Ok(event.into()),
+    }
 }
 
 #[cfg(test)]
@@ -189,6 +229,7 @@ mod test {
                     starts_at: None,
                     ends_at: None,
                     cron_schedule: None,
+                    excluded_dates: None,
                 },
                 &user,
                 &pool,
@@ -245,6 +286,7 @@ mod test {
                     starts_at: None,
                     ends_at: None,
                     cron_schedule: None,
+                    excluded_dates: None,
                 },
                 &user,
                 &pool,
@@ -290,6 +332,7 @@ mod test {
                     starts_at: None,
                     ends_at: None,
                     cron_schedule: None,
+                    excluded_dates: None,
                 },
                 &user,
                 &pool,
@@ -308,6 +351,47 @@ mod test {
         })
     }
 
+    #[test]
+    fn sets_excluded_dates_and_rejects_invalid_cron_schedule() -> Result<()> {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        rt.block_on(async {
+            let pool = pool();
+            let phuket =
+                insert_area("phuket", serde_json::from_str(PHUKET).unwrap(), &pool).await?;
+            let event = event_queries::insert(
+                Some(phuket),
+                7.98,
+                98.33,
+                "meetup".into(),
+                "https://example.com".into(),
+                None,
+                None,
+                Some("0 0 19 * * Thu".into()),
+                &pool,
+            )
+            .await?;
+            let user = em_user(vec![phuket]);
+            let params = json!({ "id": event.id, "excluded_dates": ["2025-12-25"] });
+            let res = super::run(serde_json::from_value(params).unwrap(), &user, &pool).await?;
+            assert_eq!(vec!["2025-12-25".to_string()], res.excluded_dates);
+            let params = json!({ "id": event.id, "cron_schedule": "every thursday" });
+            assert!(
+                super::run(serde_json::from_value(params).unwrap(), &user, &pool)
+                    .await
+                    .is_err()
+            );
+            let params = json!({ "id": event.id, "excluded_dates": ["25.12.2025"] });
+            assert!(
+                super::run(serde_json::from_value(params).unwrap(), &user, &pool)
+                    .await
+                    .is_err()
+            );
+            Ok::<(), crate::Error>(())
+        })
+    }
+
     #[test]
     fn parses_rfc3339_string() {
         let v = json!({
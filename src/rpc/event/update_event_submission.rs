@@ -0,0 +1,285 @@
+use super::get_event_submissions::Res;
+use super::update_event::optional_rfc3339;
+use crate::{
+    db::{self, main::user::schema::User},
+    service, Result,
+};
+use deadpool_sqlite::Pool;
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+/// Lets moderators fix up a pending submission before approving it
+#[derive(Deserialize)]
+pub struct Params {
+    pub id: i64,
+    #[serde(default)]
+    pub area_id: Option<Option<i64>>,
+    #[serde(default)]
+    lat: Option<f64>,
+    #[serde(default)]
+    lon: Option<f64>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    website: Option<String>,
+    #[serde(default, deserialize_with = "optional_rfc3339::deserialize")]
+    starts_at: Option<Option<OffsetDateTime>>,
+    #[serde(default, deserialize_with = "optional_rfc3339::deserialize")]
+    ends_at: Option<Option<OffsetDateTime>>,
+    #[serde(default)]
+    cron_schedule: Option<Option<String>>,
+}
+
+pub async fn run(params: Params, user: &User, pool: &Pool) -> Result<Res> {
+    let submission = db::main::event_submission::queries::select_by_id(params.id, pool).await?;
+    service::event_submission::ensure_pending(&submission)?;
+    super::geofence::check(user, submission.lat, submission.lon, pool).await?;
+    let lat = params.lat.unwrap_or(submission.lat);
+    let lon = params.lon.unwrap_or(submission.lon);
+    super::geofence::check(user, lat, lon, pool).await?;
+    let area_id = params.area_id.unwrap_or(submission.area_id);
+    if let Some(area_id) = area_id {
+        if params.area_id.is_some() || params.lat.is_some() || params.lon.is_some() {
+            super::geofence::check_area(user, area_id, lat, lon, pool).await?;
+        }
+    }
+    service::event_submission::validate(
+        lat,
+        lon,
+        params.name.as_deref().unwrap_or(&submission.name),
+        params.website.as_deref().unwrap_or(&submission.website),
+        params.starts_at.unwrap_or(submission.starts_at),
+        params.ends_at.unwrap_or(submission.ends_at),
+        params
+            .cron_schedule
+            .as_ref()
+            .map(|it| it.as_deref())
+            .unwrap_or(submission.cron_schedule.as_deref()),
+    )?;
+    db::main::event_submission::queries::update(
+        params.id,
+        params.area_id,
+        params.lat,
+        params.lon,
+        params.name,
+        params.website,
+        params.starts_at,
+        params.ends_at,
+        params.cron_schedule,
+        pool,
+    )
+    .await
+    .map(Into::into)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        db::{
+            self,
+            main::{
+                event_submission::schema::EventSubmissionStatus,
+                test::pool,
+                user::schema::{Role, User},
+            },
+        },
+        service, Result,
+    };
+    use deadpool_sqlite::Pool;
+    use serde_json::{json, Map};
+
+    const PHUKET: &str = r#"{
+        "type":"Feature",
+        "properties":{},
+        "geometry":{
+            "type":"Polygon",
+            "coordinates":[[
+                [98.2181205776469, 8.20412838698085],
+                [98.2181205776469, 7.74024270965898],
+                [98.4806081271279, 7.74024270965898],
+                [98.4806085771279, 8.20412838698085],
+                [98.2181205776469, 8.20412838698085]
+            ]]
+        }
+    }"#;
+
+    async fn insert_area(name: &str, coordinates: serde_json::Value, pool: &Pool) -> Result<i64> {
+        let mut tags = Map::new();
+        tags.insert("name".into(), json!(name));
+        tags.insert(
+            "geo_json".into(),
+            json!({"type": "Polygon", "coordinates": coordinates}),
+        );
+        tags.insert("url_alias".into(), json!(name));
+        Ok(db::main::area::queries::insert(tags, pool).await?.id)
+    }
+
+    fn em_user(id: i64, geofence: Vec<i64>) -> User {
+        User {
+            id,
+            name: "em".into(),
+            password: String::new(),
+            roles: vec![Role::EventManager],
+            saved_places: vec![],
+            saved_areas: vec![],
+            npub: None,
+            geofence,
+            created_at: String::new(),
+            updated_at: String::new(),
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn moderation_queue_respects_geofence() -> Result<()> {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        rt.block_on(async {
+            let pool = pool();
+            let mut tags = Map::new();
+            tags.insert("name".into(), json!("phuket"));
+            tags.insert("geo_json".into(), serde_json::from_str(PHUKET).unwrap());
+            tags.insert("url_alias".into(), json!("phuket"));
+            let phuket = db::main::area::queries::insert(tags, &pool).await?.id;
+            let submitter = db::main::user::queries::insert("submitter", "", &pool).await?;
+            let moderator = em_user(submitter.id, vec![phuket]);
+            let inside = service::event_submission::submit(
+                &submitter,
+                7.98,
+                98.33,
+                "Phuket Meetup".into(),
+                "https://example.com".into(),
+                None,
+                None,
+                None,
+                &pool,
+            )
+            .await?;
+            let outside = service::event_submission::submit(
+                &submitter,
+                51.5,
+                -0.1,
+                "London Meetup".into(),
+                "https://example.com".into(),
+                None,
+                None,
+                None,
+                &pool,
+            )
+            .await?;
+
+            let queue = super::super::get_event_submissions::run(
+                serde_json::from_value(json!({}))?,
+                &moderator,
+                &pool,
+            )
+            .await?;
+            assert_eq!(1, queue.len());
+            assert_eq!(inside.id, queue[0].id);
+
+            // Moving a submission out of the fence is not allowed
+            assert!(super::run(
+                serde_json::from_value(json!({"id": inside.id, "lat": 51.5, "lon": -0.1}))?,
+                &moderator,
+                &pool,
+            )
+            .await
+            .is_err());
+            let res = super::run(
+                serde_json::from_value(json!({"id": inside.id, "name": "Phuket Bitcoin Meetup"}))?,
+                &moderator,
+                &pool,
+            )
+            .await?;
+            assert_eq!("Phuket Bitcoin Meetup", res.name);
+
+            // Assigned areas have to contain the submission and be covered by
+            // the moderator's geofence
+            let kathu = insert_area(
+                "kathu",
+                json!([[
+                    [98.30, 8.00],
+                    [98.30, 7.90],
+                    [98.40, 7.90],
+                    [98.40, 8.00],
+                    [98.30, 8.00]
+                ]]),
+                &pool,
+            )
+            .await?;
+            let patong = insert_area(
+                "patong",
+                json!([[
+                    [98.25, 7.92],
+                    [98.25, 7.85],
+                    [98.29, 7.85],
+                    [98.29, 7.92],
+                    [98.25, 7.92]
+                ]]),
+                &pool,
+            )
+            .await?;
+            let thailand = insert_area(
+                "thailand",
+                json!([[
+                    [97.0, 21.0],
+                    [97.0, 5.0],
+                    [106.0, 5.0],
+                    [106.0, 21.0],
+                    [97.0, 21.0]
+                ]]),
+                &pool,
+            )
+            .await?;
+            for (area_id, err) in [
+                (patong, "is outside area"),
+                (thailand, "is outside your geofence"),
+            ] {
+                let res = super::run(
+                    serde_json::from_value(json!({"id": inside.id, "area_id": area_id}))?,
+                    &moderator,
+                    &pool,
+                )
+                .await;
+                assert!(res.is_err_and(|it| it.to_string().contains(err)));
+            }
+            for area_id in [phuket, kathu] {
+                let res = super::run(
+                    serde_json::from_value(json!({"id": inside.id, "area_id": area_id}))?,
+                    &moderator,
+                    &pool,
+                )
+                .await?;
+                assert_eq!(Some(area_id), res.area_id);
+            }
+            // Moving the submission out of its area is not allowed either
+            assert!(super::run(
+                serde_json::from_value(json!({"id": inside.id, "lat": 7.80, "lon": 98.33}))?,
+                &moderator,
+                &pool,
+            )
+            .await
+            .is_err());
+
+            assert!(super::super::reject_event_submission::run(
+                serde_json::from_value(json!({"id": outside.id, "reason": "spam"}))?,
+                &moderator,
+                &pool,
+            )
+            .await
+            .is_err());
+            let res = super::super::approve_event_submission::run(
+                serde_json::from_value(json!({"id": inside.id}))?,
+                &moderator,
+                &pool,
+            )
+            .await?;
+            assert_eq!(String::from(EventSubmissionStatus::Approved), res.status);
+            let event =
+                db::main::event::queries::select_by_id(res.event_id.unwrap(), &pool).await?;
+            assert_eq!("Phuket Bitcoin Meetup", event.name);
+            Ok::<(), crate::Error>(())
+        })
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     db::{self, image::ImagePool, log::LogPool, main::user::schema::Role, main::MainPool},
-    Result,
+    service, Result,
 };
 use actix_web::{
     dev::ServiceResponse,
@@ -11,7 +11,7 @@ use actix_web::{
     middleware::ErrorHandlerResponse,
     post,
     web::{Data, Json},
-    HttpRequest, HttpResponseBuilder,
+    HttpMessage, HttpRequest, HttpResponseBuilder,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{json, Map, Value};
@@ -40,6 +40,7 @@ pub enum RpcMethod {
     Whoami,
     GetApiKeys,
     RevokeApiKey,
+    RotateApiKey,
     // element
     GetElement,
     SetElementTag,
@@ -53,6 +54,11 @@ pub enum RpcMethod {
     HumanizeOpeningHours,
     GetElementIssues,
     GenerateElementCommentCounts,
+    GenerateElementRatings,
+    GetCommentModerationQueue,
+    HideElementComment,
+    RestoreElementComment,
+    DeleteElementComment,
     // area
     AddArea,
     GetArea,
@@ -76,6 +82,7 @@ pub enum RpcMethod {
     // invoice
     CreateInvoice,
     GetInvoice,
+    PayFakeInvoice,
     SyncUnpaidInvoices,
     // search
     Search,
@@ -87,12 +94,21 @@ pub enum RpcMethod {
     GetEvent,
     UpdateEvent,
     DeleteEvent,
+    GetEventSubmissions,
+    UpdateEventSubmission,
+    ApproveEventSubmission,
+    RejectEventSubmission,
     // Import
     SubmitPlace,
     GetSubmittedPlace,
     RevokeSubmittedPlace,
     SyncSubmittedPlaces,
     GetPlaceImportOrigins,
+    // Ban
+    AddBan,
+    GetBans,
+    LiftBan,
+    GetBanHits,
     // Electrum server
     GetElectrumServers,
     AddElectrumServer,
@@ -105,6 +121,9 @@ pub enum RpcMethod {
     RemoveWallet,
     // Matrix
     SendMatrixMessage,
+    // Conf
+    GetConf,
+    SetConf,
     // Debug
     GetRequestLog,
     GetDailyInfraReport,
@@ -126,6 +145,7 @@ impl Role {
         RpcMethod::ChangePassword,
         RpcMethod::GetApiKeys,
         RpcMethod::RevokeApiKey,
+        RpcMethod::RotateApiKey,
         RpcMethod::Signout,
     ];
 
@@ -136,6 +156,13 @@ impl Role {
         RpcMethod::SetElementTag,
         // Admins can remove custom place tags
         RpcMethod::RemoveElementTag,
+        // Admins can review flagged comments and the ones held by the spam filter
+        RpcMethod::GetCommentModerationQueue,
+        // Admins can hide comments, hidden comments can be restored later
+        RpcMethod::HideElementComment,
+        RpcMethod::RestoreElementComment,
+        // Admins can delete comments
+        RpcMethod::DeleteElementComment,
         // Admins can create new areas
         RpcMethod::AddArea,
         // Admins can look up any area
@@ -168,6 +195,11 @@ impl Role {
         // Admins can update events
         RpcMethod::UpdateEvent,
         RpcMethod::DeleteEvent,
+        // Admins can review events submitted by the community
+        RpcMethod::GetEventSubmissions,
+        RpcMethod::UpdateEventSubmission,
+        RpcMethod::ApproveEventSubmission,
+        RpcMethod::RejectEventSubmission,
         // Admins can import places
         RpcMethod::SubmitPlace,
         // Admins can revoke imported places
@@ -190,6 +222,12 @@ impl Role {
         RpcMethod::UpdateWallet,
         // Admins can soft-delete wallets
         RpcMethod::RemoveWallet,
+        // Admins can ban IP ranges and user agents, and lift bans
+        RpcMethod::AddBan,
+        RpcMethod::GetBans,
+        RpcMethod::LiftBan,
+        // Admins can see which bans block the most requests
+        RpcMethod::GetBanHits,
         // Admins can list electrum servers configured for wallet balance lookups
         RpcMethod::GetElectrumServers,
         // Admins can add electrum servers
@@ -212,6 +250,10 @@ impl Role {
         RpcMethod::GetEvent,
         RpcMethod::UpdateEvent,
         RpcMethod::DeleteEvent,
+        RpcMethod::GetEventSubmissions,
+        RpcMethod::UpdateEventSubmission,
+        RpcMethod::ApproveEventSubmission,
+        RpcMethod::RejectEventSubmission,
         RpcMethod::Search,
     ];
 
@@ -222,6 +264,10 @@ impl Role {
         RpcMethod::RemoveAreaTag,
         RpcMethod::SetAreaImage,
         RpcMethod::RemoveArea,
+        RpcMethod::GetEventSubmissions,
+        RpcMethod::UpdateEventSubmission,
+        RpcMethod::ApproveEventSubmission,
+        RpcMethod::RejectEventSubmission,
         RpcMethod::Search,
     ];
 
@@ -359,6 +405,7 @@ pub async fn handle(
     image_pool: Data<ImagePool>,
     log_pool: Data<LogPool>,
 ) -> Result<Json<RpcResponse>> {
+    let client_ip = service::client_ip::verified(&req);
     let headers = req.headers();
     let Ok(req) = serde_json::from_str::<Map<String, Value>>(&req_body) else {
         let error_data = json!("Request body is not a valid JSON object");
@@ -372,7 +419,7 @@ pub async fn handle(
             error_data,
         )))));
     };
-    let Some(_) = method.as_str() else {
+    let Some(method_name) = method.as_str().map(str::to_string) else {
         let error_data = json!("Field method is not a string");
         return Ok(Json(RpcResponse::error(RpcError::parse_error(Some(
             error_data,
@@ -405,6 +452,16 @@ pub async fn handle(
         Some(bearer_token) => {
             let bearer_token =
                 db::main::access_token::queries::select_by_secret(bearer_token, &main_pool).await?;
+            if !bearer_token.allows_method(Some(&method_name))
+                || !bearer_token.allows_ip(client_ip.as_ref())
+            {
+                return Ok(Json(RpcResponse::error(RpcError {
+                    code: 1,
+                    message: "This API key can't be used to call this method".to_string(),
+                    data: None,
+                })));
+            }
+            service::api_token::touch(&bearer_token, &main_pool).await?;
             let user =
                 db::main::user::queries::select_by_id(bearer_token.user_id, &main_pool).await?;
             if bearer_token.roles.is_empty() {
@@ -458,6 +515,16 @@ pub async fn handle(
             super::auth::revoke_api_key::run(params(req.params)?, user.unwrap(), &main_pool)
                 .await?,
         ),
+        RpcMethod::RotateApiKey => RpcResponse::from(
+            req.id.clone(),
+            super::auth::rotate_api_key::run(
+                params(req.params)?,
+                &auth_token.as_ref().unwrap().0,
+                user.unwrap(),
+                &main_pool,
+            )
+            .await?,
+        ),
         RpcMethod::Signout => RpcResponse::from(
             req.id.clone(),
             super::auth::signout::run(&auth_token.as_ref().unwrap().0, &main_pool).await?,
@@ -511,6 +578,26 @@ pub async fn handle(
             req.id.clone(),
             super::element::generate_element_comment_counts::run(&main_pool).await?,
         ),
+        RpcMethod::GenerateElementRatings => RpcResponse::from(
+            req.id.clone(),
+            super::element::generate_element_ratings::run(&main_pool).await?,
+        ),
+        RpcMethod::GetCommentModerationQueue => RpcResponse::from(
+            req.id.clone(),
+            super::element::get_comment_moderation_queue::run(&main_pool).await?,
+        ),
+        RpcMethod::HideElementComment => RpcResponse::from(
+            req.id.clone(),
+            super::element::hide_element_comment::run(params(req.params)?, &main_pool).await?,
+        ),
+        RpcMethod::RestoreElementComment => RpcResponse::from(
+            req.id.clone(),
+            super::element::restore_element_comment::run(params(req.params)?, &main_pool).await?,
+        ),
+        RpcMethod::DeleteElementComment => RpcResponse::from(
+            req.id.clone(),
+            super::element::delete_element_comment::run(params(req.params)?, &main_pool).await?,
+        ),
         // area
         RpcMethod::AddArea => RpcResponse::from(
             req.id.clone(),
@@ -624,6 +711,10 @@ pub async fn handle(
             req.id.clone(),
             super::invoice::create_invoice::run(params(req.params)?, &main_pool).await?,
         ),
+        RpcMethod::PayFakeInvoice => RpcResponse::from(
+            req.id.clone(),
+            super::invoice::pay_fake_invoice::run(params(req.params)?, &main_pool).await?,
+        ),
         RpcMethod::SyncUnpaidInvoices => RpcResponse::from(
             req.id.clone(),
             super::sync_unpaid_invoices::run(&main_pool).await?,
@@ -656,6 +747,42 @@ pub async fn handle(
             req.id.clone(),
             super::event::delete_event::run(params(req.params)?, user.unwrap(), &main_pool).await?,
         ),
+        RpcMethod::GetEventSubmissions => RpcResponse::from(
+            req.id.clone(),
+            super::event::get_event_submissions::run(
+                params(req.params)?,
+                user.unwrap(),
+                &main_pool,
+            )
+            .await?,
+        ),
+        RpcMethod::UpdateEventSubmission => RpcResponse::from(
+            req.id.clone(),
+            super::event::update_event_submission::run(
+                params(req.params)?,
+                user.unwrap(),
+                &main_pool,
+            )
+            .await?,
+        ),
+        RpcMethod::ApproveEventSubmission => RpcResponse::from(
+            req.id.clone(),
+            super::event::approve_event_submission::run(
+                params(req.params)?,
+                user.unwrap(),
+                &main_pool,
+            )
+            .await?,
+        ),
+        RpcMethod::RejectEventSubmission => RpcResponse::from(
+            req.id.clone(),
+            super::event::reject_event_submission::run(
+                params(req.params)?,
+                user.unwrap(),
+                &main_pool,
+            )
+            .await?,
+        ),
         RpcMethod::SubmitPlace => {
             let params: super::import::submit_place::Params = params(req.params)?;
             let token = &auth_token.as_ref().unwrap().0;
@@ -696,6 +823,30 @@ pub async fn handle(
             req.id.clone(),
             super::import::get_place_import_origins::run(&main_pool).await?,
         ),
+        RpcMethod::GetConf => RpcResponse::from(
+            req.id.clone(),
+            super::conf::get_conf::run(&main_pool).await?,
+        ),
+        RpcMethod::SetConf => RpcResponse::from(
+            req.id.clone(),
+            super::conf::set_conf::run(params(req.params)?, user.unwrap(), &main_pool).await?,
+        ),
+        RpcMethod::AddBan => RpcResponse::from(
+            req.id.clone(),
+            super::ban::add_ban::run(params(req.params)?, &main_pool).await?,
+        ),
+        RpcMethod::GetBans => RpcResponse::from(
+            req.id.clone(),
+            super::ban::get_bans::run(params(req.params)?, &main_pool).await?,
+        ),
+        RpcMethod::LiftBan => RpcResponse::from(
+            req.id.clone(),
+            super::ban::lift_ban::run(params(req.params)?, &main_pool).await?,
+        ),
+        RpcMethod::GetBanHits => RpcResponse::from(
+            req.id.clone(),
+            super::ban::get_ban_hits::run(params(req.params)?, &main_pool).await?,
+        ),
         RpcMethod::GetElectrumServers => RpcResponse::from(
             req.id.clone(),
             super::electrum::get_electrum_servers::run(params(req.params)?, &main_pool).await?,
@@ -765,6 +916,7 @@ where
 
 pub fn handle_rpc_error<B>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
     let (req, res) = res.into_parts();
+    req.extensions_mut().insert(service::log::RpcFailed);
     let error_message = res.error().unwrap().to_string();
     let body = RpcResponse::error(RpcError::server_error(Some(Value::String(error_message))));
     let body = serde_json::to_string(&body).unwrap();
@@ -1001,6 +1153,91 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    async fn ip_restricted_key_ignores_forwarded_for() -> Result<()> {
+        let pool = pool();
+        let user = db::main::user::queries::insert("root", "", &pool).await?;
+        db::main::access_token::queries::insert_restricted(
+            user.id,
+            "".into(),
+            "secret".into(),
+            vec![Role::Root],
+            vec![],
+            db::main::access_token::schema::Restrictions {
+                allowed_ips: vec!["10.0.0.1".into()],
+                ..Default::default()
+            },
+            &pool,
+        )
+        .await?;
+        let client: Option<Client> = None;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .app_data(Data::new(client))
+                .app_data(Data::new(log_pool()))
+                .app_data(Data::new(image_pool()))
+                .service(scope("/").service(super::handle)),
+        )
+        .await;
+        let req = |peer: &str| {
+            test::TestRequest::post()
+                .uri("/")
+                .peer_addr(peer.parse().unwrap())
+                .insert_header((header::AUTHORIZATION, "Bearer secret"))
+                .insert_header(("X-Forwarded-For", "10.0.0.1"))
+                .set_json(json!({ "jsonrpc": "2.0", "method": "whoami", "id": 1 }))
+                .to_request()
+        };
+
+        let res: RpcResponse = test::call_and_read_body_json(&app, req("6.6.6.6:1234")).await;
+        assert_eq!(
+            "This API key can't be used to call this method",
+            res.error.unwrap().message
+        );
+        let res: RpcResponse = test::call_and_read_body_json(&app, req("10.0.0.1:1234")).await;
+        assert!(res.error.is_none());
+        Ok(())
+    }
+
+    #[test]
+    async fn failed_calls_are_logged() -> Result<()> {
+        let pool = pool();
+        let client: Option<Client> = None;
+        let log_pool = log_pool();
+        let image_pool = image_pool();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .app_data(Data::new(client))
+                .app_data(Data::new(log_pool.clone()))
+                .app_data(Data::new(image_pool))
+                .wrap(ErrorHandlers::new().default_handler(super::handle_rpc_error))
+                .wrap(service::log::Log)
+                .service(scope("/rpc").service(super::handle)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/rpc")
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .set_json(json!({
+                "jsonrpc": "2.0",
+                "method": "signin",
+                "params": { "username": "satoshi", "password": "wrong" },
+                "id": 1
+            }))
+            .to_request();
+        let res: RpcResponse = test::call_and_read_body_json(&app, req).await;
+        assert!(res.error.is_some());
+
+        let since = time::OffsetDateTime::now_utc() - time::Duration::minutes(1);
+        let ips = db::log::request::queries::select_failed_signin_ips(since, 1, &log_pool).await?;
+        assert_eq!(1, ips.len());
+        assert_eq!("10.0.0.1", ips[0].ip);
+        Ok(())
+    }
+
     #[test]
     async fn valid_request_with_auth() -> Result<()> {
         let pool = pool();
@@ -1377,6 +1614,7 @@ mod test {
             RpcMethod::ChangePassword,
             RpcMethod::GetApiKeys,
             RpcMethod::RevokeApiKey,
+            RpcMethod::RotateApiKey,
             RpcMethod::Signout,
         ] {
             assert!(
@@ -45,9 +45,14 @@ mod test {
             id: 1,
             user_id: 1,
             name: Some("source".to_string()),
-            secret: "secret".to_string(),
+            secret_hash: String::new(),
+            prefix: String::new(),
             roles,
             import_origins,
+            expires_at: None,
+            last_used_at: None,
+            allowed_methods: vec![],
+            allowed_ips: vec![],
             created_at: OffsetDateTime::UNIX_EPOCH,
             updated_at: OffsetDateTime::UNIX_EPOCH,
             deleted_at: None,
@@ -98,9 +98,14 @@ mod test {
             id: 1,
             user_id: 1,
             name: None,
-            secret: "secret".to_string(),
+            secret_hash: String::new(),
+            prefix: String::new(),
             roles: vec![Role::Admin],
             import_origins: vec![],
+            expires_at: None,
+            last_used_at: None,
+            allowed_methods: vec![],
+            allowed_ips: vec![],
             created_at: time::OffsetDateTime::UNIX_EPOCH,
             updated_at: time::OffsetDateTime::UNIX_EPOCH,
             deleted_at: None,
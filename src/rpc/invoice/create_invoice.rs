@@ -1,5 +1,5 @@
 use crate::{
-    db::main::invoice::schema::Invoice,
+    db::{self, main::invoice::schema::Invoice},
     service::{self},
     Result,
 };
@@ -28,8 +28,9 @@ impl From<Invoice> for Res {
 }
 
 pub async fn run(params: Params, pool: &Pool) -> Result<Res> {
+    let conf = db::main::conf::queries::select(pool).await?;
     let invoice = service::invoice::create(
-        "lnd",
+        &conf.invoice_backend,
         params.description.unwrap_or_default(),
         params.amount_sats,
         pool,
@@ -1,2 +1,3 @@
 pub mod create_invoice;
 pub mod get_invoice;
+pub mod pay_fake_invoice;
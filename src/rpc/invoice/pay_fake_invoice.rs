@@ -0,0 +1,46 @@
+use crate::{
+    db::{self, main::invoice::schema::Invoice},
+    service::{self, invoice_backend::FakeBackend, matrix},
+    Result,
+};
+use deadpool_sqlite::Pool;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub uuid: String,
+}
+
+#[derive(Serialize)]
+pub struct Res {
+    pub uuid: String,
+    pub status: String,
+}
+
+impl From<Invoice> for Res {
+    fn from(val: Invoice) -> Self {
+        Res {
+            uuid: val.uuid,
+            status: val.status.into(),
+        }
+    }
+}
+
+/// Settles an invoice issued by the fake backend, so paid flows can be
+/// exercised locally without a Lightning node
+pub async fn run(params: Params, pool: &Pool) -> Result<Res> {
+    let invoice = db::main::invoice::queries::select_by_uuid(params.uuid.clone(), pool).await?;
+    if invoice.source != "fake" {
+        return Err("only fake invoices can be paid this way".into());
+    }
+    if !FakeBackend::pay(&invoice.payment_hash) {
+        return Err("invoice is not payable".into());
+    }
+    let matrix_client = matrix::try_client(pool);
+    service::invoice::sync_unpaid_invoice(&invoice, pool, &matrix_client).await?;
+    Ok(
+        db::main::invoice::queries::select_by_uuid(params.uuid, pool)
+            .await?
+            .into(),
+    )
+}
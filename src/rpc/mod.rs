@@ -3,7 +3,9 @@ pub mod admin;
 pub mod analytics;
 pub mod area;
 pub mod auth;
+pub mod ban;
 pub mod boost_element;
+pub mod conf;
 pub mod electrum;
 pub mod element;
 pub mod event;
@@ -0,0 +1,363 @@
+//! Looks through the request log for scraping bursts, sign in brute force and
+//! invoice spam every `abuse_window_minutes`. Abusive IPs can be banned for
+//! `abuse_ban_hours`, but only behind trusted proxies, otherwise the logged IPs
+//! could belong to the proxy. A summary of every run which found something is
+//! posted to the infrastructure room.
+
+use crate::db::{
+    self,
+    log::{request::blocking_queries::TopIp, LogPool},
+    main::{conf::schema::Conf, MainPool},
+};
+use crate::service::{
+    ban,
+    cidr::{self, Cidr},
+    client_ip,
+    conf::SharedConf,
+    matrix::{self, ROOM_INFRASTRUCTURE},
+};
+use crate::Result;
+use std::fmt;
+use time::{Duration, OffsetDateTime};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Scraping,
+    FailedSignins,
+    InvoiceSpam,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Scraping => "too many requests",
+            Kind::FailedSignins => "too many failed sign ins",
+            Kind::InvoiceSpam => "too many invoices",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Banned {
+        ban_id: i64,
+    },
+    /// Bans are disabled, or the subject is a username
+    Reported,
+    Allowlisted,
+    /// Flagged for something else in the same run, or banned before
+    AlreadyBanned,
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub kind: Kind,
+    /// An IP, or a username for sign in brute force spread over many IPs
+    pub subject: String,
+    pub count: i64,
+    pub outcome: Outcome,
+}
+
+pub fn init(
+    main_pool: &MainPool,
+    log_pool: &LogPool,
+    conf: &SharedConf,
+    shutdown: CancellationToken,
+) {
+    let main_pool = main_pool.clone();
+    let log_pool = log_pool.clone();
+    let conf = conf.clone();
+    tokio::spawn(async move {
+        info!("abuse detector: started");
+        let mut since = OffsetDateTime::now_utc();
+        loop {
+            let minutes = conf.load().abuse_window_minutes.max(1) as u64;
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = tokio::time::sleep(std::time::Duration::from_secs(minutes * 60)) => {}
+            }
+            let now = OffsetDateTime::now_utc();
+            let conf = conf.load();
+            let ban = client_ip::behind_trusted_proxy();
+            match run(&conf, since, ban, &main_pool, &log_pool).await {
+                Ok(findings) if findings.is_empty() => {}
+                Ok(findings) => {
+                    info!(findings = findings.len(), "abuse detector: found abuse");
+                    let client = matrix::try_client(&main_pool);
+                    matrix::send_message(
+                        &client,
+                        ROOM_INFRASTRUCTURE,
+                        &summary(&findings, since, now),
+                    );
+                }
+                Err(err) => warn!(%err, "abuse detector: run failed"),
+            }
+            since = now;
+        }
+        info!("abuse detector: stopped");
+    });
+}
+
+/// Checks the requests made since `since` and bans the abusive IPs, if `ban`
+/// is set and bans are enabled
+pub async fn run(
+    conf: &Conf,
+    since: OffsetDateTime,
+    ban: bool,
+    main_pool: &MainPool,
+    log_pool: &LogPool,
+) -> Result<Vec<Finding>> {
+    let mut flagged: Vec<(Kind, TopIp)> = vec![];
+    let mut findings = vec![];
+    if conf.abuse_max_requests > 0 {
+        let ips =
+            db::log::request::queries::select_busy_ips(since, conf.abuse_max_requests, log_pool)
+                .await?;
+        flagged.extend(ips.into_iter().map(|it| (Kind::Scraping, it)));
+    }
+    if conf.abuse_max_failed_signins > 0 {
+        let ips = db::log::request::queries::select_failed_signin_ips(
+            since,
+            conf.abuse_max_failed_signins,
+            log_pool,
+        )
+        .await?;
+        flagged.extend(ips.into_iter().map(|it| (Kind::FailedSignins, it)));
+        let usernames = db::log::request::queries::select_failed_signin_usernames(
+            since,
+            conf.abuse_max_failed_signins,
+            log_pool,
+        )
+        .await?;
+        findings.extend(usernames.into_iter().map(|it| Finding {
+            kind: Kind::FailedSignins,
+            subject: format!("{} (from {} IPs)", it.username, it.unique_ips),
+            count: it.count,
+            outcome: Outcome::Reported,
+        }));
+    }
+    if conf.abuse_max_invoices > 0 {
+        let ips =
+            db::log::request::queries::select_invoice_ips(since, conf.abuse_max_invoices, log_pool)
+                .await?;
+        flagged.extend(ips.into_iter().map(|it| (Kind::InvoiceSpam, it)));
+    }
+    if flagged.is_empty() {
+        return Ok(findings);
+    }
+
+    let allowlist: Vec<Cidr> = conf
+        .abuse_allowlist
+        .iter()
+        .filter_map(|it| it.parse().ok())
+        .collect();
+    let mut banned: Vec<Cidr> = db::main::ban::queries::select_active(main_pool)
+        .await?
+        .into_iter()
+        .filter_map(|it| it.ip?.parse().ok())
+        .collect();
+    let minutes = (OffsetDateTime::now_utc() - since).whole_minutes().max(1);
+    let mut new_bans = 0;
+    for (kind, TopIp { ip, count }) in flagged {
+        let addr = cidr::parse_ip(&ip);
+        let outcome = match addr {
+            Some(addr) if allowlist.iter().any(|it| it.contains(&addr)) => Outcome::Allowlisted,
+            Some(addr) if banned.iter().any(|it| it.contains(&addr)) => Outcome::AlreadyBanned,
+            Some(addr) if ban && conf.abuse_ban_hours > 0 => {
+                let ban = db::main::ban::queries::insert(
+                    addr.to_string(),
+                    format!("{kind}, {count} in {minutes} minutes"),
+                    Duration::hours(conf.abuse_ban_hours),
+                    main_pool,
+                )
+                .await?;
+                warn!(ip, %kind, count, ban_id = ban.id, "abuse detector: banned IP");
+                banned.push(addr.into());
+                new_bans += 1;
+                Outcome::Banned { ban_id: ban.id }
+            }
+            _ => Outcome::Reported,
+        };
+        findings.push(Finding {
+            kind,
+            subject: ip,
+            count,
+            outcome,
+        });
+    }
+    if new_bans > 0 {
+        ban::invalidate();
+    }
+    Ok(findings)
+}
+
+fn summary(findings: &[Finding], since: OffsetDateTime, until: OffsetDateTime) -> String {
+    let mut message = format!(
+        "Abuse detector found {} issue(s) in the last {} minutes:",
+        findings.len(),
+        (until - since).whole_minutes(),
+    );
+    for finding in findings {
+        let outcome = match finding.outcome {
+            Outcome::Banned { ban_id } => format!("banned (ban id = {ban_id})"),
+            Outcome::Reported => "not banned".into(),
+            Outcome::Allowlisted => "allowlisted".into(),
+            Outcome::AlreadyBanned => "already banned".into(),
+        };
+        message.push_str(&format!(
+            "\n- {}: {} ({}), {outcome}",
+            finding.subject, finding.kind, finding.count,
+        ));
+    }
+    message
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Kind, Outcome};
+    use crate::db::log::request::blocking_queries::InsertArgs;
+    use crate::db::main::conf::schema::Conf;
+    use crate::db::main::test::pool;
+    use crate::{db, Result};
+    use actix_web::test;
+    use time::{Duration, OffsetDateTime};
+
+    fn request(ip: &str, path: &str, body: Option<&str>, rpc_failed: bool) -> InsertArgs {
+        InsertArgs {
+            ip: ip.into(),
+            user_agent: None,
+            user_id: None,
+            method: "POST".into(),
+            path: path.into(),
+            query: None,
+            body: body.map(Into::into),
+            response_code: 200,
+            processing_time_ns: 1000000,
+            rpc_failed,
+        }
+    }
+
+    #[test]
+    async fn run() -> Result<()> {
+        let main_pool = pool();
+        let log_pool = db::log::test::pool();
+        let signin = r#"{"jsonrpc":"2.0","method":"signin","params":{"username":"satoshi","password":"x"},"id":1}"#;
+        for _ in 0..5 {
+            for ip in ["10.0.0.1", "10.0.0.2", "192.168.0.1"] {
+                db::log::request::queries::insert(
+                    request(ip, "/rpc", Some(signin), true),
+                    &log_pool,
+                )
+                .await?;
+            }
+            db::log::request::queries::insert(
+                request("10.0.0.3", "/v4/place-boosts", None, false),
+                &log_pool,
+            )
+            .await?;
+        }
+        db::main::ban::queries::insert(
+            "10.0.0.2".into(),
+            "spam".into(),
+            Duration::days(1),
+            &main_pool,
+        )
+        .await?;
+        let conf = Conf {
+            abuse_max_requests: 0,
+            abuse_max_failed_signins: 5,
+            abuse_max_invoices: 5,
+            abuse_ban_hours: 1,
+            abuse_allowlist: vec!["192.168.0.0/16".into()],
+            ..Default::default()
+        };
+        let since = OffsetDateTime::now_utc() - Duration::minutes(10);
+
+        let findings = super::run(&conf, since, true, &main_pool, &log_pool).await?;
+        let outcome = |kind: Kind, subject: &str| {
+            findings
+                .iter()
+                .find(|it| it.kind == kind && it.subject == subject)
+                .map(|it| &it.outcome)
+        };
+        assert_eq!(4 + 1, findings.len());
+        assert_eq!(
+            Some(&Outcome::Reported),
+            outcome(Kind::FailedSignins, "satoshi (from 3 IPs)")
+        );
+        assert!(matches!(
+            outcome(Kind::FailedSignins, "10.0.0.1"),
+            Some(Outcome::Banned { .. })
+        ));
+        assert_eq!(
+            Some(&Outcome::AlreadyBanned),
+            outcome(Kind::FailedSignins, "10.0.0.2")
+        );
+        assert_eq!(
+            Some(&Outcome::Allowlisted),
+            outcome(Kind::FailedSignins, "192.168.0.1")
+        );
+        assert!(matches!(
+            outcome(Kind::InvoiceSpam, "10.0.0.3"),
+            Some(Outcome::Banned { .. })
+        ));
+        let bans = db::main::ban::queries::select_active(&main_pool).await?;
+        assert_eq!(3, bans.len());
+
+        // nothing gets banned twice
+        let findings = super::run(&conf, since, true, &main_pool, &log_pool).await?;
+        assert!(findings
+            .iter()
+            .all(|it| !matches!(it.outcome, Outcome::Banned { .. })));
+        assert_eq!(
+            3,
+            db::main::ban::queries::select_active(&main_pool)
+                .await?
+                .len()
+        );
+
+        let summary = super::summary(&findings, since, OffsetDateTime::now_utc());
+        assert!(summary.starts_with("Abuse detector found 5 issue(s) in the last 10 minutes:"));
+        assert!(summary.contains("- 10.0.0.1: too many failed sign ins (5), already banned"));
+        Ok(())
+    }
+
+    #[test]
+    async fn run_reports_only_without_ban_hours() -> Result<()> {
+        let main_pool = pool();
+        let log_pool = db::log::test::pool();
+        for _ in 0..3 {
+            db::log::request::queries::insert(
+                request("10.0.0.1", "/v4/places", None, false),
+                &log_pool,
+            )
+            .await?;
+        }
+        let conf = Conf {
+            abuse_max_requests: 3,
+            ..Default::default()
+        };
+        let since = OffsetDateTime::now_utc() - Duration::minutes(10);
+        let findings = super::run(&conf, since, true, &main_pool, &log_pool).await?;
+        assert_eq!(1, findings.len());
+        assert_eq!(Kind::Scraping, findings[0].kind);
+        assert_eq!(Outcome::Reported, findings[0].outcome);
+        assert!(db::main::ban::queries::select_active(&main_pool)
+            .await?
+            .is_empty());
+
+        // without trusted proxies the logged IP could be the proxy
+        let conf = Conf {
+            abuse_ban_hours: 24,
+            ..conf
+        };
+        let findings = super::run(&conf, since, false, &main_pool, &log_pool).await?;
+        assert_eq!(Outcome::Reported, findings[0].outcome);
+        assert!(db::main::ban::queries::select_active(&main_pool)
+            .await?
+            .is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+//! API token secrets are never stored, only their HMAC-SHA256 under a server
+//! side key, so a leaked DB copy can't be used to sign in. A short prefix of
+//! each secret is stored in the clear to tell tokens apart.
+//!
+//! The key is 32 random bytes in base64 (`openssl rand -base64 32`), supplied
+//! by `BTCMAP_TOKEN_HASH_KEY` or by a file at `BTCMAP_TOKEN_HASH_KEY_FILE`.
+//! Changing it invalidates every issued token, and the server won't start
+//! without it.
+
+use crate::db::{self, main::access_token::schema::AccessToken, main::MainPool};
+use crate::Result;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{env, fs, sync::OnceLock};
+use time::{Duration, OffsetDateTime};
+use tracing::info;
+use uuid::Uuid;
+
+/// `last_used_at` is only as precise as this, which spares a DB write per request
+const LAST_USED_AT_PRECISION: Duration = Duration::minutes(1);
+
+/// Makes leaked tokens easy to spot, such as by secret scanners
+pub const SECRET_PREFIX: &str = "btcmap_";
+
+/// Number of random characters kept in the visible prefix
+const PREFIX_RANDOM_LEN: usize = 8;
+
+/// Expiring tokens can't be issued for longer than this
+pub const MAX_LIFETIME_SECS: i64 = 365 * 24 * 60 * 60;
+
+static KEY: OnceLock<Vec<u8>> = OnceLock::new();
+
+pub fn new_secret() -> String {
+    format!("{SECRET_PREFIX}{}", Uuid::new_v4().simple())
+}
+
+/// The part of a secret which is stored in the clear, such as `btcmap_1a2b3c4d`
+pub fn prefix(secret: &str) -> String {
+    let random = secret.strip_prefix(SECRET_PREFIX).unwrap_or(secret);
+    let visible = secret.len() - random.len() + PREFIX_RANDOM_LEN.min(random.len());
+    secret.chars().take(visible).collect()
+}
+
+pub fn hash(secret: &str) -> String {
+    hash_with_key(key(), secret)
+}
+
+fn hash_with_key(key: &[u8], secret: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(secret.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// When a token issued at `now` for `secs` seconds expires
+pub fn expires_at(now: OffsetDateTime, secs: i64) -> Result<OffsetDateTime> {
+    if !(1..=MAX_LIFETIME_SECS).contains(&secs) {
+        return Err(format!("expires_in_secs must be between 1 and {MAX_LIFETIME_SECS}").into());
+    }
+    now.checked_add(Duration::seconds(secs))
+        .ok_or_else(|| "expires_in_secs is out of range".into())
+}
+
+/// Records a use of the token
+pub async fn touch(token: &AccessToken, pool: &MainPool) -> Result<()> {
+    let now = OffsetDateTime::now_utc();
+    if token
+        .last_used_at
+        .is_some_and(|it| now - it < LAST_USED_AT_PRECISION)
+    {
+        return Ok(());
+    }
+    db::main::access_token::queries::set_last_used_at(token.id, now, pool).await
+}
+
+fn key() -> &'static [u8] {
+    KEY.get_or_init(Vec::new)
+}
+
+fn key_from_env() -> Result<Vec<u8>> {
+    let raw = match (
+        env::var("BTCMAP_TOKEN_HASH_KEY"),
+        env::var("BTCMAP_TOKEN_HASH_KEY_FILE"),
+    ) {
+        (Ok(_), Ok(_)) => {
+            return Err(
+                "set either BTCMAP_TOKEN_HASH_KEY or BTCMAP_TOKEN_HASH_KEY_FILE, not both".into(),
+            )
+        }
+        (Ok(key), _) => key,
+        (_, Ok(path)) => fs::read_to_string(&path)
+            .map_err(|e| format!("can't read token hash key file {path}: {e}"))?,
+        _ => return Ok(vec![]),
+    };
+    let key = BASE64_STANDARD.decode(raw.trim())?;
+    if key.len() != 32 {
+        return Err("token hash key must be 32 bytes long".into());
+    }
+    Ok(key)
+}
+
+/// Loads the hash key and replaces the secrets of tokens issued before they
+/// were hashed with their hashes. Hashes made without a key would be easy to
+/// brute force and would break once a key is set, so the key is required.
+pub async fn init(pool: &MainPool) -> Result<()> {
+    let key = key_from_env()?;
+    if key.is_empty() {
+        return Err(
+            "BTCMAP_TOKEN_HASH_KEY or BTCMAP_TOKEN_HASH_KEY_FILE has to be set to hash API tokens"
+                .into(),
+        );
+    }
+    KEY.set(key)
+        .map_err(|_| "token hash key is already loaded")?;
+    let hashed = db::main::access_token::queries::hash_plaintext_secrets(pool).await?;
+    if hashed > 0 {
+        info!(hashed, "hashed plaintext API token secrets");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn new_secret() {
+        let secret = super::new_secret();
+        assert!(secret.starts_with(super::SECRET_PREFIX));
+        assert_eq!(39, secret.len());
+        assert_ne!(secret, super::new_secret());
+    }
+
+    #[test]
+    fn expires_at() {
+        let now = time::OffsetDateTime::now_utc();
+        assert_eq!(
+            now + time::Duration::hours(1),
+            super::expires_at(now, 3600).unwrap()
+        );
+        assert!(super::expires_at(now, super::MAX_LIFETIME_SECS).is_ok());
+        for secs in [0, -1, i64::MIN, super::MAX_LIFETIME_SECS + 1, i64::MAX] {
+            assert!(super::expires_at(now, secs).is_err(), "{secs}");
+        }
+    }
+
+    #[test]
+    fn prefix() {
+        assert_eq!("btcmap_1a2b3c4d", super::prefix("btcmap_1a2b3c4d5e6f"));
+        // tokens issued before the btcmap_ prefix are plain UUIDs
+        assert_eq!(
+            "f47ac10b",
+            super::prefix("f47ac10b-58cc-4372-a567-0e02b2c3d479")
+        );
+        assert_eq!("abc", super::prefix("abc"));
+    }
+
+    #[test]
+    fn hash() {
+        let hash = super::hash_with_key(b"key", "secret");
+        assert_eq!(64, hash.len());
+        assert_eq!(hash, super::hash_with_key(b"key", "secret"));
+        assert_ne!(hash, super::hash_with_key(b"other key", "secret"));
+        assert_ne!(hash, super::hash_with_key(b"key", "other secret"));
+    }
+}
@@ -204,6 +204,61 @@ pub async fn find_areas_by_lat_lon(lat: f64, lon: f64, pool: &Pool) -> Result<Ve
     Ok(result)
 }
 
+/// Checks whether a point falls inside any of the area geometries
+pub fn point_inside_area(area: &Area, coord: geo::Coord) -> Result<bool> {
+    let geometries = area.geo_json_geometries()?;
+    for geometry in &geometries {
+        match &geometry.value {
+            geojson::GeometryValue::Polygon { coordinates: _ } => {
+                let poly: Polygon = (&geometry.value).try_into().unwrap();
+                if poly.contains(&coord) {
+                    return Ok(true);
+                }
+            }
+            geojson::GeometryValue::MultiPolygon { coordinates: _ } => {
+                let multi_poly: MultiPolygon = (&geometry.value).try_into().unwrap();
+                if multi_poly.contains(&coord) {
+                    return Ok(true);
+                }
+            }
+            geojson::GeometryValue::LineString { coordinates: _ } => {
+                let line_string: LineString = (&geometry.value).try_into().unwrap();
+                if line_string.contains(&coord) {
+                    return Ok(true);
+                }
+            }
+            _ => continue,
+        }
+    }
+    Ok(false)
+}
+
+/// Checks whether every polygon of `inner` lies within one of the `outer` areas
+pub fn area_inside_area(inner: &Area, outer: &Area) -> Result<bool> {
+    let inner = polygons(inner)?;
+    if inner.0.is_empty() {
+        return Ok(false);
+    }
+    Ok(polygons(outer)?.contains(&inner))
+}
+
+fn polygons(area: &Area) -> Result<MultiPolygon> {
+    let mut res = vec![];
+    for geometry in area.geo_json_geometries()? {
+        match &geometry.value {
+            geojson::GeometryValue::Polygon { coordinates: _ } => {
+                res.push((&geometry.value).try_into().unwrap());
+            }
+            geojson::GeometryValue::MultiPolygon { coordinates: _ } => {
+                let multi_poly: MultiPolygon = (&geometry.value).try_into().unwrap();
+                res.extend(multi_poly);
+            }
+            _ => continue,
+        }
+    }
+    Ok(MultiPolygon(res))
+}
+
 #[derive(Serialize)]
 pub struct TrendingArea {
     pub id: i64,
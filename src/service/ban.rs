@@ -1,5 +1,12 @@
-use crate::db;
-use crate::db::main::MainPool;
+//! Active bans are kept in memory, so checking a request doesn't hit the DB.
+//! The cache is reloaded every `RELOAD_INTERVAL`, or on the next request
+//! after `invalidate`. Hits are counted in memory too and written on reload.
+
+use crate::db::main::conf::schema::Conf;
+use crate::db::{self, main::ban::schema::Ban, main::user::schema::Role, main::MainPool};
+use crate::service::{cidr::Cidr, client_ip};
+use crate::Result;
+use actix_web::http::header;
 use actix_web::web::Data;
 use actix_web::{
     body::MessageBody,
@@ -7,33 +14,476 @@ use actix_web::{
     middleware::Next,
     Error,
 };
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    mem,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+use time::OffsetDateTime;
+use tracing::warn;
+
+/// Picks up bans added outside of this process, such as manual inserts
+const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+const MIN_IPV4_PREFIX_LEN: u8 = 8;
+const MIN_IPV6_PREFIX_LEN: u8 = 32;
+
+/// Set by `invalidate`, makes the next request reload the cache
+static STALE: AtomicBool = AtomicBool::new(false);
+
+/// Shared by all workers, so it has to be created outside of the app factory
+#[derive(Default)]
+pub struct BanCache {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    rules: Vec<Rule>,
+    loaded_at: Option<Instant>,
+    /// Hits which aren't written yet, by ban id
+    hits: HashMap<i64, (i64, OffsetDateTime)>,
+}
+
+struct Rule {
+    id: i64,
+    ip: Option<Cidr>,
+    user_agent: Option<Regex>,
+    reason: String,
+    end_at: Option<OffsetDateTime>,
+}
+
+impl Rule {
+    fn new(ban: Ban) -> Result<Self> {
+        Ok(Rule {
+            id: ban.id,
+            ip: ban.ip.as_deref().map(str::parse).transpose()?,
+            user_agent: ban
+                .user_agent
+                .as_deref()
+                .map(Regex::new)
+                .transpose()
+                .map_err(|e| e.to_string())?,
+            reason: ban.reason,
+            end_at: ban.end_at,
+        })
+    }
+
+    fn matches(&self, ip: Option<&IpAddr>, user_agent: Option<&str>, now: OffsetDateTime) -> bool {
+        if self.end_at.is_some_and(|it| it <= now) {
+            return false;
+        }
+        let ip_matches = match (&self.ip, ip) {
+            (Some(range), Some(ip)) => range.contains(ip),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let user_agent_matches = match (&self.user_agent, user_agent) {
+            (Some(pattern), Some(user_agent)) => pattern.is_match(user_agent),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        ip_matches && user_agent_matches
+    }
+}
+
+/// Makes the next request reload the bans, call it after every change
+pub fn invalidate() {
+    STALE.store(true, Ordering::Relaxed);
+}
+
+/// Checks that an IP range can be used in a ban. Wider ranges would lock out a
+/// good part of the internet, and likely ourselves too.
+pub fn validate_ip(ip: &str) -> Result<Cidr> {
+    let range: Cidr = ip.parse()?;
+    let min = if range.is_ipv4() {
+        MIN_IPV4_PREFIX_LEN
+    } else {
+        MIN_IPV6_PREFIX_LEN
+    };
+    if range.prefix_len() < min {
+        return Err(format!("{ip} is too broad, the prefix has to be at least /{min}").into());
+    }
+    Ok(range)
+}
+
+/// Checks that a user agent pattern can be used in a ban. Patterns which match
+/// an empty string, such as `.*`, match every request.
+pub fn validate_user_agent(pattern: &str) -> Result<()> {
+    let regex = Regex::new(pattern).map_err(|e| format!("invalid user agent pattern: {e}"))?;
+    if regex.is_match("") {
+        return Err("user agent pattern can't match every user agent".into());
+    }
+    Ok(())
+}
+
+impl BanCache {
+    /// Marks the cache as fresh, so concurrent requests don't reload it too
+    fn start_reload(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let stale = STALE.swap(false, Ordering::Relaxed)
+            || state
+                .loaded_at
+                .is_none_or(|it| now.saturating_duration_since(it) >= RELOAD_INTERVAL);
+        if stale {
+            state.loaded_at = Some(now);
+        }
+        stale
+    }
+
+    /// Writes the hits counted so far and loads the active bans. Hits which
+    /// couldn't be written are kept for the next reload.
+    pub async fn reload(&self, pool: &MainPool) -> Result<()> {
+        let pending = mem::take(&mut self.state.lock().unwrap().hits);
+        if !pending.is_empty() {
+            let hits = pending
+                .iter()
+                .map(|(id, (count, last_hit_at))| (*id, *count, *last_hit_at))
+                .collect();
+            if let Err(e) = db::main::ban::queries::add_hits(hits, pool).await {
+                self.restore_hits(pending);
+                return Err(e);
+            }
+        }
+        let rules = db::main::ban::queries::select_active(pool)
+            .await?
+            .into_iter()
+            .filter_map(|ban| {
+                let id = ban.id;
+                Rule::new(ban)
+                    .inspect_err(|e| warn!(id, error = %e, "skipping invalid ban"))
+                    .ok()
+            })
+            .collect();
+        self.state.lock().unwrap().rules = rules;
+        Ok(())
+    }
+
+    fn restore_hits(&self, pending: HashMap<i64, (i64, OffsetDateTime)>) {
+        let mut state = self.state.lock().unwrap();
+        for (id, (count, last_hit_at)) in pending {
+            let hits = state.hits.entry(id).or_insert((0, last_hit_at));
+            hits.0 += count;
+            hits.1 = hits.1.max(last_hit_at);
+        }
+    }
+
+    /// Returns the reason of the first matching ban and counts a hit
+    pub fn find(
+        &self,
+        ip: Option<&IpAddr>,
+        user_agent: Option<&str>,
+        now: OffsetDateTime,
+    ) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        let (id, reason) = state
+            .rules
+            .iter()
+            .find(|it| it.matches(ip, user_agent, now))
+            .map(|it| (it.id, it.reason.clone()))?;
+        let hits = state.hits.entry(id).or_insert((0, now));
+        hits.0 += 1;
+        hits.1 = now;
+        Some(reason)
+    }
+}
+
+/// Places which no real client would boost, such as ones hidden from the
+/// map, are listed in `honeypot_place_ids`. Returns true if `place_id` is one
+/// of them, after banning `ip` for `honeypot_ban_days`. Admins pick these
+/// places, so the address is banned even without trusted proxies.
+pub async fn check_honeypot(
+    place_id: i64,
+    ip: Option<IpAddr>,
+    conf: &Conf,
+    pool: &MainPool,
+) -> Result<bool> {
+    let place_id = place_id.to_string();
+    if !conf.honeypot_place_ids.contains(&place_id) {
+        return Ok(false);
+    }
+    if let Some(ip) = ip {
+        db::main::ban::queries::insert(
+            ip.to_string(),
+            "spam".into(),
+            time::Duration::days(conf.honeypot_ban_days.max(1)),
+            pool,
+        )
+        .await?;
+        invalidate();
+        warn!(%ip, place_id, "banned for boosting a honeypot place");
+    }
+    Ok(true)
+}
 
-#[allow(clippy::await_holding_refcell_ref)]
 pub async fn check_if_banned(
     req: ServiceRequest,
     next: Next<impl MessageBody>,
 ) -> std::result::Result<ServiceResponse<impl MessageBody>, Error> {
-    let conn_info = req.connection_info();
-    let Some(addr) = conn_info.realip_remote_addr() else {
-        drop(conn_info);
+    let (Some(pool), Some(bans)) = (
+        req.app_data::<Data<MainPool>>().cloned(),
+        req.app_data::<Data<BanCache>>().cloned(),
+    ) else {
         return next.call(req).await;
     };
-    let Some(pool) = req.app_data::<Data<MainPool>>() else {
-        drop(conn_info);
-        return next.call(req).await;
+    if bans.start_reload(Instant::now()) {
+        if let Err(e) = bans.reload(&pool).await {
+            warn!(error = %e, "failed to reload bans");
+        }
+    }
+    let ip = client_ip::get(req.request());
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|it| it.to_str().ok());
+    match bans.find(ip.as_ref(), user_agent, OffsetDateTime::now_utc()) {
+        Some(_) if is_admin_rpc(&req, &pool).await => next.call(req).await,
+        Some(reason) => Err(actix_web::error::ErrorForbidden(format!(
+            "You are banned for the following reason: {reason}. You can contact us by the following email for more details: support@btcmap.org.",
+        ))),
+        None => next.call(req).await,
+    }
+}
+
+/// Admins have to be able to lift a ban which ended up matching them. Only
+/// checked for banned requests, so it doesn't cost a DB query otherwise.
+async fn is_admin_rpc(req: &ServiceRequest, pool: &MainPool) -> bool {
+    if req.path() != "/rpc" {
+        return false;
+    }
+    let Some(secret) = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|it| it.to_str().ok())
+        .and_then(|it| it.strip_prefix("Bearer "))
+        .map(String::from)
+    else {
+        return false;
+    };
+    let Ok(token) = db::main::access_token::queries::select_by_secret(secret, pool).await else {
+        return false;
     };
-    let addr = addr.to_owned();
-    let current_ban = db::main::ban::queries::select_by_ip(addr, pool).await?;
-    match current_ban {
-        Some(current_ban) => {
-            Err(actix_web::error::ErrorForbidden(format!(
-                "You are banned for the following reason: {}. You can contact us by the following email for more details: support@btcmap.org.",
-                current_ban.reason,
-            )))?
-        }
-        None => {
-            drop(conn_info);
-            next.call(req).await
+    let roles = if token.roles.is_empty() {
+        match db::main::user::queries::select_by_id(token.user_id, pool).await {
+            Ok(user) => user.roles,
+            Err(_) => return false,
+        }
+    } else {
+        token.roles
+    };
+    roles.contains(&Role::Admin) || roles.contains(&Role::Root)
+}
+
+#[cfg(test)]
+mod test {
+    use super::BanCache;
+    use crate::db::main::test::pool;
+    use crate::db::main::user::schema::Role;
+    use crate::{db, Result};
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use actix_web::{get, post, test, App, Responder};
+    use time::{Duration, OffsetDateTime};
+
+    #[get("/")]
+    async fn index() -> impl Responder {
+        "ok"
+    }
+
+    #[post("/rpc")]
+    async fn rpc() -> impl Responder {
+        "ok"
+    }
+
+    #[test]
+    async fn find() -> Result<()> {
+        let pool = pool();
+        db::main::ban::queries::insert_rule(
+            Some("10.0.0.0/8".into()),
+            None,
+            "range".into(),
+            None,
+            &pool,
+        )
+        .await?;
+        db::main::ban::queries::insert_rule(
+            Some("192.168.0.1".into()),
+            Some("^curl/".into()),
+            "curl".into(),
+            None,
+            &pool,
+        )
+        .await?;
+        db::main::ban::queries::insert_rule(
+            None,
+            Some("BadBot".into()),
+            "bot".into(),
+            Some(OffsetDateTime::now_utc() + Duration::hours(1)),
+            &pool,
+        )
+        .await?;
+        let bans = BanCache::default();
+        bans.reload(&pool).await?;
+
+        let now = OffsetDateTime::now_utc();
+        let ip = |it: &str| it.parse().unwrap();
+        assert_eq!(
+            Some("range".into()),
+            bans.find(Some(&ip("10.1.2.3")), None, now)
+        );
+        assert_eq!(None, bans.find(Some(&ip("11.1.2.3")), None, now));
+        assert_eq!(
+            Some("curl".into()),
+            bans.find(Some(&ip("192.168.0.1")), Some("curl/8.0"), now)
+        );
+        assert_eq!(
+            None,
+            bans.find(Some(&ip("192.168.0.1")), Some("Firefox"), now)
+        );
+        assert_eq!(
+            None,
+            bans.find(Some(&ip("192.168.0.2")), Some("curl/8.0"), now)
+        );
+        assert_eq!(
+            Some("bot".into()),
+            bans.find(None, Some("Mozilla/5.0 (BadBot)"), now)
+        );
+        // expired bans stop matching before the next reload
+        let later = now + Duration::hours(2);
+        assert_eq!(None, bans.find(None, Some("BadBot"), later));
+
+        bans.reload(&pool).await?;
+        let most_hit = db::main::ban::queries::select_most_hit(10, &pool).await?;
+        assert_eq!(3, most_hit.len());
+        assert!(most_hit.iter().all(|it| it.hits == 1));
+        Ok(())
+    }
+
+    #[test]
+    async fn check_if_banned() -> Result<()> {
+        let pool = pool();
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(super::check_if_banned))
+                .app_data(Data::new(pool.clone()))
+                .app_data(Data::new(BanCache::default()))
+                .service(index)
+                .service(rpc),
+        )
+        .await;
+        let req = || {
+            TestRequest::get()
+                .uri("/")
+                .peer_addr("10.0.0.1:1234".parse().unwrap())
+                .to_request()
+        };
+        let res = test::call_service(&app, req()).await;
+        assert_eq!(StatusCode::OK, res.status());
+
+        db::main::ban::queries::insert("10.0.0.1".into(), "spam".into(), Duration::days(1), &pool)
+            .await?;
+        super::invalidate();
+        let res = test::try_call_service(&app, req()).await;
+        assert_eq!(
+            StatusCode::FORBIDDEN,
+            res.err().unwrap().as_response_error().status_code()
+        );
+
+        // bans keep matching clients behind a reverse proxy when no trusted
+        // proxies are configured
+        let res = test::try_call_service(
+            &app,
+            TestRequest::get()
+                .uri("/")
+                .peer_addr("127.0.0.1:1234".parse().unwrap())
+                .insert_header(("X-Forwarded-For", "10.0.0.1"))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(
+            StatusCode::FORBIDDEN,
+            res.err().unwrap().as_response_error().status_code()
+        );
+
+        // admins can still reach the RPC to lift the ban
+        let user = db::main::user::queries::insert("admin", "", &pool).await?;
+        db::main::user::queries::set_roles(user.id, &[Role::Admin], &pool).await?;
+        db::main::access_token::queries::insert(user.id, "".into(), "admin".into(), vec![], &pool)
+            .await?;
+        let user = db::main::user::queries::insert("user", "", &pool).await?;
+        db::main::access_token::queries::insert(user.id, "".into(), "user".into(), vec![], &pool)
+            .await?;
+        let rpc_call = |token: &str| {
+            TestRequest::post()
+                .uri("/rpc")
+                .peer_addr("10.0.0.1:1234".parse().unwrap())
+                .insert_header(("Authorization", format!("Bearer {token}")))
+                .to_request()
+        };
+        let res = test::try_call_service(&app, rpc_call("admin")).await;
+        assert_eq!(StatusCode::OK, res.ok().unwrap().status());
+        for token in ["user", "made-up"] {
+            let res = test::try_call_service(&app, rpc_call(token)).await;
+            assert_eq!(
+                StatusCode::FORBIDDEN,
+                res.err().unwrap().as_response_error().status_code()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    async fn reload_keeps_hits_on_error() -> Result<()> {
+        let pool = pool();
+        db::main::ban::queries::insert("10.0.0.1".into(), "spam".into(), Duration::days(1), &pool)
+            .await?;
+        let bans = BanCache::default();
+        bans.reload(&pool).await?;
+        let ip = "10.0.0.1".parse().unwrap();
+        assert!(bans
+            .find(Some(&ip), None, OffsetDateTime::now_utc())
+            .is_some());
+
+        let rename = |from: &'static str, to: &'static str| {
+            let pool = pool.clone();
+            async move {
+                pool.get()
+                    .await?
+                    .interact(move |conn| {
+                        conn.execute(&format!("ALTER TABLE {from} RENAME TO {to}"), [])
+                    })
+                    .await??;
+                Ok::<_, crate::Error>(())
+            }
+        };
+        rename("ban", "ban_tmp").await?;
+        assert!(bans.reload(&pool).await.is_err());
+        assert_eq!(1, bans.state.lock().unwrap().hits[&1].0);
+        rename("ban_tmp", "ban").await?;
+        bans.reload(&pool).await?;
+        assert!(bans.state.lock().unwrap().hits.is_empty());
+        let most_hit = db::main::ban::queries::select_most_hit(10, &pool).await?;
+        assert_eq!(1, most_hit[0].hits);
+        Ok(())
+    }
+
+    #[test]
+    async fn validate() {
+        assert!(super::validate_ip("10.0.0.0/8").is_ok());
+        assert!(super::validate_ip("10.0.0.0/7").is_err());
+        assert!(super::validate_ip("2001:db8::/32").is_ok());
+        assert!(super::validate_ip("::/0").is_err());
+        assert!(super::validate_user_agent("^curl/").is_ok());
+        for pattern in [".*", "^", "", "x*", "(", "a|"] {
+            assert!(super::validate_user_agent(pattern).is_err(), "{pattern}");
         }
     }
 }
@@ -0,0 +1,339 @@
+use crate::{
+    db::{
+        self,
+        main::{
+            conf::schema::BoostPrice, element::schema::Element,
+            element_boost::schema::ElementBoost, invoice::schema::Invoice,
+        },
+    },
+    service, Result,
+};
+use deadpool_sqlite::Pool;
+use serde_json::Value;
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+pub const MIN_DAYS: i64 = 1;
+pub const MAX_SPONSOR_NAME_LEN: usize = 64;
+pub const MAX_SPONSOR_MESSAGE_LEN: usize = 280;
+
+/// The longest configured tier, boosts can't be any longer
+pub fn max_days(prices: &[BoostPrice]) -> Option<i64> {
+    prices.iter().map(|it| it.days).max()
+}
+
+/// The configured tiers are points on a price curve which starts at zero,
+/// durations between them are interpolated and rounded up to a whole sat
+pub fn price(days: i64, prices: &[BoostPrice]) -> Result<i64> {
+    let max_days = max_days(prices).ok_or("boosts are not available")?;
+    if !(MIN_DAYS..=max_days).contains(&days) {
+        return Err(format!("days must be between {MIN_DAYS} and {max_days}").into());
+    }
+    let mut points: Vec<(i64, i64)> = prices.iter().map(|it| (it.days, it.sats)).collect();
+    points.push((0, 0));
+    points.sort();
+    let (from, to) = points
+        .windows(2)
+        .map(|it| (it[0], it[1]))
+        .find(|(from, to)| from.0 < days && days <= to.0)
+        .ok_or("invalid boost prices")?;
+    let extra_days = days - from.0;
+    let segment_days = to.0 - from.0;
+    let extra_sats = ((to.1 - from.1) * extra_days + segment_days - 1) / segment_days;
+    Ok(from.1 + extra_sats)
+}
+
+/// Inverse of `price`, amounts between two durations are rounded up to the
+/// longer one
+pub fn days_for_amount(amount_sats: i64, prices: &[BoostPrice]) -> Result<i64> {
+    let max_days = max_days(prices).ok_or("boosts are not available")?;
+    let min = price(MIN_DAYS, prices)?;
+    let max = price(max_days, prices)?;
+    if !(min..=max).contains(&amount_sats) {
+        return Err(format!("amount must be between {min} and {max} sats").into());
+    }
+    let mut days = MIN_DAYS;
+    while price(days, prices)? < amount_sats {
+        days += 1;
+    }
+    Ok(days)
+}
+
+/// Everything but the place and the payment method
+#[derive(Debug, Default)]
+pub struct BoostOrder {
+    pub days: i64,
+    /// Boost the place on this area's page only
+    pub area_id: Option<i64>,
+    /// Boost the place on its category page only
+    pub category: Option<String>,
+    pub sponsor_name: Option<String>,
+    pub sponsor_message: Option<String>,
+}
+
+fn non_empty(value: Option<String>, max_len: usize, name: &str) -> Result<Option<String>> {
+    let Some(value) = value.map(|it| it.trim().to_string()) else {
+        return Ok(None);
+    };
+    if value.chars().count() > max_len {
+        return Err(format!("{name} can't be longer than {max_len} characters").into());
+    }
+    Ok(Some(value).filter(|it| !it.is_empty()))
+}
+
+/// Records the boost and issues an invoice for it. The boost starts once
+/// that invoice is paid.
+pub async fn order(
+    element: &Element,
+    order: BoostOrder,
+    source: &str,
+    pool: &Pool,
+) -> Result<Invoice> {
+    if element.deleted_at.is_some() {
+        return Err("place is deleted".into());
+    }
+    if let Some(area_id) = order.area_id {
+        let area_elements =
+            db::main::area_element::queries::select_by_element_id(element.id, pool).await?;
+        if !area_elements
+            .iter()
+            .any(|it| it.area_id == area_id && it.deleted_at.is_none())
+        {
+            return Err("place is not in this area".into());
+        }
+    }
+    if let Some(category) = &order.category {
+        if element.tag("category").as_str() != Some(category) {
+            return Err("place is not in this category".into());
+        }
+    }
+    let sponsor_name = non_empty(order.sponsor_name, MAX_SPONSOR_NAME_LEN, "sponsor name")?;
+    let sponsor_message = non_empty(
+        order.sponsor_message,
+        MAX_SPONSOR_MESSAGE_LEN,
+        "sponsor message",
+    )?;
+    let conf = db::main::conf::queries::select(pool).await?;
+    let prices = service::exchange_rate::current_boost_prices(&conf).await?;
+    let sats = price(order.days, &prices)?;
+    let invoice = service::invoice::create(
+        source,
+        format!("element_boost:{}:{}", element.id, order.days),
+        sats,
+        pool,
+    )
+    .await?;
+    db::main::element_boost::queries::insert(
+        element.id,
+        Some(invoice.id),
+        order.days,
+        order.area_id,
+        order.category,
+        sponsor_name,
+        sponsor_message,
+        pool,
+    )
+    .await?;
+    Ok(invoice)
+}
+
+/// Starts the boost paid by this invoice. Invoices issued without an order,
+/// such as LNURL payments, buy anonymous global boosts.
+pub async fn on_paid(
+    invoice: &Invoice,
+    element_id: i64,
+    days: i64,
+    pool: &Pool,
+) -> Result<ElementBoost> {
+    let boost =
+        match db::main::element_boost::queries::select_by_invoice_id(invoice.id, pool).await? {
+            Some(boost) => boost,
+            None => {
+                db::main::element_boost::queries::insert(
+                    element_id,
+                    Some(invoice.id),
+                    days,
+                    None,
+                    None,
+                    None,
+                    None,
+                    pool,
+                )
+                .await?
+            }
+        };
+    start(&boost, pool).await
+}
+
+/// Free global boost, given out by admins
+pub async fn grant(element_id: i64, days: i64, pool: &Pool) -> Result<ElementBoost> {
+    let boost = db::main::element_boost::queries::insert(
+        element_id, None, days, None, None, None, None, pool,
+    )
+    .await?;
+    start(&boost, pool).await
+}
+
+/// Boosts with the same scope are queued, so buying twice adds up. Global
+/// boosts also update the `boost:expires` tag which the map highlights.
+async fn start(boost: &ElementBoost, pool: &Pool) -> Result<ElementBoost> {
+    let element = db::main::element::queries::select_by_id(boost.element_id, pool).await?;
+    let now = OffsetDateTime::now_utc();
+    let mut starts_at = db::main::element_boost::queries::select_max_expires_at(
+        boost.element_id,
+        boost.area_id,
+        boost.category.clone(),
+        pool,
+    )
+    .await?
+    .unwrap_or(now)
+    .max(now);
+    if boost.is_global() {
+        // the tag may predate boost records
+        starts_at = starts_at.max(element.boosted_until().unwrap_or(now));
+    }
+    let expires_at = starts_at.saturating_add(Duration::days(boost.days));
+    let boost = db::main::element_boost::queries::activate(boost.id, starts_at, expires_at, pool)
+        .await?
+        .ok_or("boost has already started")?;
+    if boost.is_global() {
+        db::main::element::queries::set_tag(
+            element.id,
+            "boost:expires",
+            &Value::String(expires_at.format(&Rfc3339)?),
+            pool,
+        )
+        .await?;
+    }
+    Ok(boost)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{days_for_amount, price, BoostOrder};
+    use crate::db::main::area::schema::Area;
+    use crate::db::main::conf::schema::BoostPrice;
+    use crate::db::main::test::pool;
+    use crate::service::overpass::OverpassElement;
+    use crate::{db, Result};
+    use actix_web::test;
+    use serde_json::json;
+
+    fn prices() -> Vec<BoostPrice> {
+        vec![
+            BoostPrice {
+                days: 90,
+                sats: 12000,
+                fiat: None,
+            },
+            BoostPrice {
+                days: 30,
+                sats: 5000,
+                fiat: None,
+            },
+            BoostPrice {
+                days: 365,
+                sats: 30000,
+                fiat: None,
+            },
+        ]
+    }
+
+    #[test]
+    async fn price_curve() {
+        assert_eq!(167, price(1, &prices()).unwrap());
+        assert_eq!(5000, price(30, &prices()).unwrap());
+        assert_eq!(8500, price(60, &prices()).unwrap());
+        assert_eq!(12000, price(90, &prices()).unwrap());
+        assert_eq!(30000, price(365, &prices()).unwrap());
+        assert!(price(0, &prices()).is_err());
+        assert!(price(366, &prices()).is_err());
+        assert!(price(30, &[]).is_err());
+    }
+
+    #[test]
+    async fn days_for_amount_inverts_price() {
+        assert_eq!(1, days_for_amount(167, &prices()).unwrap());
+        assert_eq!(30, days_for_amount(5000, &prices()).unwrap());
+        assert_eq!(31, days_for_amount(5001, &prices()).unwrap());
+        assert_eq!(31, days_for_amount(5100, &prices()).unwrap());
+        assert_eq!(38, days_for_amount(5934, &prices()).unwrap());
+        assert_eq!(39, days_for_amount(6000, &prices()).unwrap());
+        assert_eq!(365, days_for_amount(30000, &prices()).unwrap());
+        assert!(days_for_amount(166, &prices()).is_err());
+        assert!(days_for_amount(30001, &prices()).is_err());
+    }
+
+    #[test]
+    async fn scoped_and_global_boosts_queue_separately() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        db::main::element::queries::set_tag(element.id, "category", &json!("cafe"), &pool).await?;
+        let element = db::main::element::queries::select_by_id(element.id, &pool).await?;
+        let area = db::main::area::queries::insert(Area::mock_tags(), &pool).await?;
+        db::main::area_element::queries::insert(area.id, element.id, &pool).await?;
+        let prices_json = serde_json::to_string(&prices())?;
+        pool.get()
+            .await?
+            .interact(move |conn| {
+                conn.execute("UPDATE conf SET boost_element_prices = ?1", [prices_json])
+            })
+            .await??;
+
+        let first = super::grant(element.id, 10, &pool).await?;
+        let second = super::grant(element.id, 10, &pool).await?;
+        assert_eq!(first.expires_at, second.starts_at);
+        let element = db::main::element::queries::select_by_id(element.id, &pool).await?;
+        assert_eq!(
+            second.expires_at.unwrap().unix_timestamp(),
+            element.boosted_until().unwrap().unix_timestamp()
+        );
+
+        let invoice = super::order(
+            &element,
+            BoostOrder {
+                days: 45,
+                category: Some("cafe".into()),
+                sponsor_name: Some(" Satoshi ".into()),
+                sponsor_message: Some("".into()),
+                ..BoostOrder::default()
+            },
+            "fake",
+            &pool,
+        )
+        .await?;
+        assert_eq!(6750, invoice.amount_sats);
+        let boost = super::on_paid(&invoice, element.id, 45, &pool).await?;
+        assert_eq!(Some("cafe".to_string()), boost.category);
+        assert_eq!(Some("Satoshi".to_string()), boost.sponsor_name);
+        assert_eq!(None, boost.sponsor_message);
+        assert!(boost.starts_at.unwrap() < first.expires_at.unwrap());
+        assert!(super::on_paid(&invoice, element.id, 45, &pool)
+            .await
+            .is_err());
+        let element_after = db::main::element::queries::select_by_id(element.id, &pool).await?;
+        assert_eq!(element.boosted_until(), element_after.boosted_until());
+
+        let order = |area_id, category: Option<&str>| BoostOrder {
+            days: 30,
+            area_id,
+            category: category.map(Into::into),
+            ..BoostOrder::default()
+        };
+        assert!(
+            super::order(&element, order(Some(area.id), None), "fake", &pool)
+                .await
+                .is_ok()
+        );
+        assert!(
+            super::order(&element, order(Some(area.id + 1), None), "fake", &pool)
+                .await
+                .is_err()
+        );
+        assert!(
+            super::order(&element, order(None, Some("bar")), "fake", &pool)
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use crate::Error;
+use std::{
+    fmt::{self, Display},
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+/// Loopback, private, link-local, shared, documentation and multicast ranges
+const NON_PUBLIC_RANGES: &[&str] = &[
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "100.64.0.0/10",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.0.0.0/24",
+    "192.0.2.0/24",
+    "192.168.0.0/16",
+    "198.18.0.0/15",
+    "198.51.100.0/24",
+    "203.0.113.0/24",
+    "224.0.0.0/3",
+    "::/128",
+    "::1/128",
+    "fc00::/7",
+    "fe80::/10",
+    "ff00::/8",
+    "2001:db8::/32",
+];
+
+/// An IP range such as `192.168.0.0/16`. A plain address is a range of one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    pub fn is_ipv4(&self) -> bool {
+        self.addr.is_ipv4()
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                masked(u32::from(net).into(), self.prefix_len, 32)
+                    == masked(u32::from(*ip).into(), self.prefix_len, 32)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                masked(u128::from(net), self.prefix_len, 128)
+                    == masked(u128::from(*ip), self.prefix_len, 128)
+            }
+            (IpAddr::V6(_), IpAddr::V4(ip)) => self.contains(&IpAddr::V6(ip.to_ipv6_mapped())),
+            (IpAddr::V4(_), IpAddr::V6(ip)) => match ip.to_ipv4_mapped() {
+                Some(ip) => self.contains(&IpAddr::V4(ip)),
+                None => false,
+            },
+        }
+    }
+}
+
+fn masked(bits: u128, prefix_len: u8, width: u8) -> u128 {
+    if prefix_len == 0 {
+        return 0;
+    }
+    bits >> (width - prefix_len)
+}
+
+impl FromStr for Cidr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.trim().split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s.trim(), None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("invalid IP address: {addr}"))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse::<u8>()
+                .ok()
+                .filter(|it| *it <= max)
+                .ok_or_else(|| format!("invalid prefix length: {prefix_len}"))?,
+            None => max,
+        };
+        Ok(Cidr { addr, prefix_len })
+    }
+}
+
+impl From<IpAddr> for Cidr {
+    fn from(addr: IpAddr) -> Self {
+        let prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        Cidr { addr, prefix_len }
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Parses the client address reported by actix, which may carry a port
+pub fn parse_ip(addr: &str) -> Option<IpAddr> {
+    addr.parse::<IpAddr>()
+        .ok()
+        .or_else(|| addr.parse::<SocketAddr>().ok().map(|it| it.ip()))
+}
+
+/// False for addresses which don't belong to the public internet, such as
+/// loopback and private ones. Servers we connect to on behalf of strangers
+/// have to pass this check.
+pub fn is_public(ip: &IpAddr) -> bool {
+    static RANGES: OnceLock<Vec<Cidr>> = OnceLock::new();
+    !RANGES
+        .get_or_init(|| {
+            NON_PUBLIC_RANGES
+                .iter()
+                .map(|it| it.parse().expect("valid range"))
+                .collect()
+        })
+        .iter()
+        .any(|it| it.contains(ip))
+}
+
+#[cfg(test)]
+mod test {
+    use super::Cidr;
+    use crate::Result;
+    use std::net::IpAddr;
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn contains() -> Result<()> {
+        let cidr: Cidr = "192.168.0.0/16".parse()?;
+        assert!(cidr.contains(&ip("192.168.1.1")));
+        assert!(!cidr.contains(&ip("192.169.1.1")));
+        assert!(cidr.contains(&ip("::ffff:192.168.1.1")));
+
+        let cidr: Cidr = "10.0.0.1".parse()?;
+        assert!(cidr.contains(&ip("10.0.0.1")));
+        assert!(!cidr.contains(&ip("10.0.0.2")));
+
+        let cidr: Cidr = "2001:db8::/32".parse()?;
+        assert!(cidr.contains(&ip("2001:db8::1")));
+        assert!(!cidr.contains(&ip("2001:db9::1")));
+        assert!(!cidr.contains(&ip("192.168.1.1")));
+
+        let cidr: Cidr = "0.0.0.0/0".parse()?;
+        assert!(cidr.contains(&ip("8.8.8.8")));
+        Ok(())
+    }
+
+    #[test]
+    fn from_str() {
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0.0/x".parse::<Cidr>().is_err());
+        assert!("example.com".parse::<Cidr>().is_err());
+        assert_eq!(
+            "10.0.0.1/32",
+            "10.0.0.1".parse::<Cidr>().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn is_public() {
+        for addr in ["8.8.8.8", "1.1.1.1", "2606:4700::1111"] {
+            assert!(super::is_public(&ip(addr)), "{addr}");
+        }
+        for addr in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!super::is_public(&ip(addr)), "{addr}");
+        }
+    }
+
+    #[test]
+    fn parse_ip() {
+        assert_eq!("10.0.0.1".parse().ok(), super::parse_ip("10.0.0.1"));
+        assert_eq!("10.0.0.1".parse().ok(), super::parse_ip("10.0.0.1:8000"));
+        assert_eq!("::1".parse().ok(), super::parse_ip("[::1]:8000"));
+        assert_eq!(None, super::parse_ip("unknown"));
+    }
+}
@@ -0,0 +1,164 @@
+//! The address of the client which made a request. Headers such as
+//! `X-Forwarded-For` can be set by anyone, so when `BTCMAP_TRUSTED_PROXIES`
+//! is set they are only read for requests coming from one of those proxies.
+//! Without it we keep taking the proxy headers at face value, as deployments
+//! behind a reverse proxy would otherwise see every client as the proxy, but
+//! nothing should punish an IP unless `behind_trusted_proxy` says so.
+//! Everything which restricts or bans clients by IP has to go through `get`,
+//! and checks which grant access by IP through `verified`.
+
+use crate::service::cidr::Cidr;
+use crate::Result;
+use actix_web::{http::header::HeaderMap, HttpRequest};
+use std::{env, net::IpAddr, sync::OnceLock};
+use tracing::{info, warn};
+
+static TRUSTED_PROXIES: OnceLock<Vec<Cidr>> = OnceLock::new();
+
+/// Loads the trusted proxies from `BTCMAP_TRUSTED_PROXIES`, a comma-separated
+/// list of IPs and CIDR ranges
+pub fn init() -> Result<()> {
+    let proxies = env::var("BTCMAP_TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|it| !it.is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<Cidr>>>()
+        .map_err(|e| format!("invalid BTCMAP_TRUSTED_PROXIES: {e}"))?;
+    if proxies.is_empty() {
+        warn!("BTCMAP_TRUSTED_PROXIES is not set, client addresses are taken from proxy headers unverified");
+    } else {
+        info!(proxies = proxies.len(), "loaded trusted proxies");
+    }
+    TRUSTED_PROXIES
+        .set(proxies)
+        .map_err(|_| "trusted proxies are already loaded")?;
+    Ok(())
+}
+
+fn trusted_proxies() -> &'static [Cidr] {
+    TRUSTED_PROXIES.get_or_init(Vec::new)
+}
+
+/// Automatic bans are only safe when the proxies are known, otherwise every
+/// client behind a proxy looks like the proxy itself
+pub fn behind_trusted_proxy() -> bool {
+    !trusted_proxies().is_empty()
+}
+
+pub fn get(req: &HttpRequest) -> Option<IpAddr> {
+    let peer = req.peer_addr().map(|it| it.ip());
+    let trusted = trusted_proxies();
+    if trusted.is_empty() {
+        return req
+            .connection_info()
+            .realip_remote_addr()
+            .and_then(super::cidr::parse_ip)
+            .or(peer);
+    }
+    resolve(peer, req.headers(), trusted)
+}
+
+/// Same as `get`, but never takes an address from a header it can't verify,
+/// without trusted proxies that's always the peer address
+pub fn verified(req: &HttpRequest) -> Option<IpAddr> {
+    resolve(
+        req.peer_addr().map(|it| it.ip()),
+        req.headers(),
+        trusted_proxies(),
+    )
+}
+
+/// Walks `X-Forwarded-For` from the right, each trusted proxy vouches for the
+/// hop before it, and the first untrusted hop is the client
+fn resolve(peer: Option<IpAddr>, headers: &HeaderMap, trusted: &[Cidr]) -> Option<IpAddr> {
+    let mut client = peer?;
+    let forwarded_for: Vec<&str> = headers
+        .get_all("X-Forwarded-For")
+        .filter_map(|it| it.to_str().ok())
+        .flat_map(|it| it.split(','))
+        .map(str::trim)
+        .collect();
+    for hop in forwarded_for.iter().rev() {
+        if !trusted.iter().any(|it| it.contains(&client)) {
+            break;
+        }
+        match super::cidr::parse_ip(hop) {
+            Some(ip) => client = ip,
+            // a garbled hop can't be trusted, the proxy which added it is
+            // the last address we know for sure
+            None => break,
+        }
+    }
+    Some(client)
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+    use actix_web::test::TestRequest;
+    use std::net::IpAddr;
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    fn headers(forwarded_for: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for it in forwarded_for {
+            headers.append(
+                HeaderName::from_static("x-forwarded-for"),
+                HeaderValue::from_str(it).unwrap(),
+            );
+        }
+        headers
+    }
+
+    #[test]
+    fn resolve() {
+        let trusted = ["127.0.0.1".parse().unwrap(), "10.0.0.0/8".parse().unwrap()];
+        let resolve = |peer: &str, forwarded_for: &[&str]| {
+            super::resolve(Some(ip(peer)), &headers(forwarded_for), &trusted)
+        };
+        // untrusted peers can't pick their address
+        assert_eq!(Some(ip("1.1.1.1")), resolve("1.1.1.1", &["2.2.2.2"]));
+        assert_eq!(Some(ip("127.0.0.1")), resolve("127.0.0.1", &[]));
+        assert_eq!(Some(ip("2.2.2.2")), resolve("127.0.0.1", &["2.2.2.2"]));
+        // entries added by the client are to the left of the real address
+        assert_eq!(
+            Some(ip("2.2.2.2")),
+            resolve("127.0.0.1", &["6.6.6.6, 2.2.2.2"])
+        );
+        assert_eq!(
+            Some(ip("2.2.2.2")),
+            resolve("127.0.0.1", &["6.6.6.6", "2.2.2.2, 10.0.0.1"])
+        );
+        assert_eq!(Some(ip("127.0.0.1")), resolve("127.0.0.1", &["garbage"]));
+        assert_eq!(None, super::resolve(None, &headers(&[]), &trusted));
+    }
+
+    #[test]
+    fn get_without_trusted_proxies_reads_proxy_headers() {
+        // tests never load BTCMAP_TRUSTED_PROXIES
+        assert!(!super::behind_trusted_proxy());
+        let req = TestRequest::default()
+            .peer_addr("127.0.0.1:1234".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "2.2.2.2, 127.0.0.1"))
+            .to_http_request();
+        assert_eq!(Some(ip("2.2.2.2")), super::get(&req));
+        assert_eq!(Some(ip("127.0.0.1")), super::verified(&req));
+        let req = TestRequest::default()
+            .peer_addr("127.0.0.1:1234".parse().unwrap())
+            .to_http_request();
+        assert_eq!(Some(ip("127.0.0.1")), super::get(&req));
+    }
+
+    #[test]
+    fn resolve_without_trusted_proxies() {
+        assert_eq!(
+            Some(ip("127.0.0.1")),
+            super::resolve(Some(ip("127.0.0.1")), &headers(&["2.2.2.2"]), &[])
+        );
+    }
+}
@@ -1,8 +1,30 @@
 use crate::{
-    db::{self, main::element::schema::Element},
+    db::{
+        self,
+        main::{
+            conf::schema::Conf,
+            element::schema::Element,
+            element_comment::schema::{ElementComment, HiddenFrom},
+            element_comment_flag::schema::ElementCommentFlag,
+            user::schema::User,
+        },
+    },
+    service::{
+        self,
+        matrix::{self, ROOM_PLACE_COMMENTS},
+    },
     Result,
 };
 use deadpool_sqlite::Pool;
+use matrix_sdk::Client;
+use std::fmt;
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use tracing::warn;
+
+pub const MAX_FLAG_REASON_LEN: usize = 500;
+
+/// Lets admins vouch for a merchant's npub without waiting for an OSM edit
+pub const OWNER_NPUB_TAG: &str = "owner:npub";
 
 pub struct RefreshCommentCountTagRes {
     pub previous_count: i64,
@@ -51,10 +73,314 @@ pub async fn refresh_comment_count_tag(
     })
 }
 
+/// Called once a comment has been paid for. Comments which look like spam are
+/// held for review, the rest go live right away. Comments a moderator hid
+/// before they were paid for stay hidden until they're restored.
+pub async fn publish(id: i64, pool: &Pool, matrix_client: &Option<Client>) -> Result<()> {
+    let comment = db::main::element_comment::queries::select_by_id(id, pool).await?;
+    if let Some(hidden_at) = comment.hidden_at {
+        db::main::element_comment::queries::set_hidden_at(
+            comment.id,
+            Some((hidden_at, HiddenFrom::Held)),
+            pool,
+        )
+        .await?;
+        return Ok(());
+    }
+    let element = db::main::element::queries::select_by_id(comment.element_id, pool).await?;
+    let spam_score = service::spam::score(&comment.comment, &service::spam::default_scorers());
+    let comment =
+        db::main::element_comment::queries::set_spam_score(comment.id, spam_score, pool).await?;
+    if spam_score >= service::spam::HOLD_THRESHOLD {
+        db::main::element_comment::queries::set_hidden_at(
+            comment.id,
+            Some((OffsetDateTime::now_utc(), HiddenFrom::Held)),
+            pool,
+        )
+        .await?;
+        let message = format!(
+            "Comment {} held for review (spam score {spam_score}): {} https://btcmap.org/merchant/{}",
+            comment.id, comment.comment, element.id,
+        );
+        matrix::send_message(matrix_client, ROOM_PLACE_COMMENTS, &message);
+        return Ok(());
+    }
+    go_live(&comment, &element, pool).await?;
+    let message = format!(
+        "{} https://btcmap.org/merchant/{}",
+        comment.comment, element.id,
+    );
+    matrix::send_message(matrix_client, ROOM_PLACE_COMMENTS, &message);
+    Ok(())
+}
+
+async fn go_live(comment: &ElementComment, element: &Element, pool: &Pool) -> Result<()> {
+    db::main::element_comment::queries::set_deleted_at(comment.id, None, pool).await?;
+    refresh_comment_count_tag(element, pool).await?;
+    if let Err(err) = service::nostr::publish_comment(comment, element, pool).await {
+        warn!(%err, comment.id, "Failed to publish comment to nostr");
+    }
+    Ok(())
+}
+
+/// Only the `owner:npub` place tag counts, it's set by admins once a merchant
+/// proves control of the place. Anyone can edit OSM tags such as
+/// `contact:nostr`, so those are never trusted.
+pub fn is_owner(element: &Element, npub: &str) -> bool {
+    element.tag(OWNER_NPUB_TAG).as_str() == Some(npub)
+}
+
+/// Replies have to stay on the same place and can't target comments which
+/// aren't public
+pub async fn validate_parent(element: &Element, parent_id: i64, pool: &Pool) -> Result<()> {
+    let parent = db::main::element_comment::queries::select_by_id(parent_id, pool)
+        .await
+        .map_err(|_| format!("Comment {parent_id} doesn't exist"))?;
+    if parent.element_id != element.id || parent.deleted_at.is_some() {
+        return Err(format!("Comment {parent_id} can't be replied to").into());
+    }
+    Ok(())
+}
+
+/// Official responses are authenticated, so they skip the paywall and the
+/// spam filter
+pub async fn post_official(
+    element: &Element,
+    parent_id: Option<i64>,
+    comment: &str,
+    npub: &str,
+    pool: &Pool,
+) -> Result<ElementComment> {
+    if !is_owner(element, npub) {
+        return Err(format!("{npub} hasn't proven control of place {}", element.id).into());
+    }
+    if let Some(parent_id) = parent_id {
+        validate_parent(element, parent_id, pool).await?;
+    }
+    let comment = db::main::element_comment::queries::insert_threaded(
+        element.id, parent_id, true, None, comment, pool,
+    )
+    .await?;
+    refresh_comment_count_tag(element, pool).await?;
+    if let Err(err) = service::nostr::publish_comment(&comment, element, pool).await {
+        warn!(%err, comment.id, "Failed to publish comment to nostr");
+    }
+    Ok(comment)
+}
+
+/// Reasons for refusing a free comment, the caller can still pay for it
+#[derive(Debug, PartialEq)]
+pub enum FreeCommentDenial {
+    Disabled,
+    AccountTooNew { min_days: i64 },
+    NotAllowlisted,
+    RateLimited { per_day: i64 },
+}
+
+impl fmt::Display for FreeCommentDenial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FreeCommentDenial::Disabled => write!(f, "Free comments are disabled"),
+            FreeCommentDenial::AccountTooNew { min_days } => write!(
+                f,
+                "Accounts have to be at least {min_days} days old to comment for free"
+            ),
+            FreeCommentDenial::NotAllowlisted => {
+                write!(f, "Your npub is not allowed to comment for free")
+            }
+            FreeCommentDenial::RateLimited { per_day } => {
+                write!(f, "You can post up to {per_day} free comments per day")
+            }
+        }
+    }
+}
+
+pub async fn free_comment_denial(
+    user: &User,
+    conf: &Conf,
+    pool: &Pool,
+) -> Result<Option<FreeCommentDenial>> {
+    if conf.free_comments_per_day <= 0 {
+        return Ok(Some(FreeCommentDenial::Disabled));
+    }
+    let created_at = OffsetDateTime::parse(&user.created_at, &Rfc3339)?;
+    let min_age = Duration::days(conf.free_comment_min_account_age_days);
+    if OffsetDateTime::now_utc() - created_at < min_age {
+        return Ok(Some(FreeCommentDenial::AccountTooNew {
+            min_days: conf.free_comment_min_account_age_days,
+        }));
+    }
+    if !conf.free_comment_npub_allowlist.is_empty()
+        && !user
+            .npub
+            .as_ref()
+            .is_some_and(|npub| conf.free_comment_npub_allowlist.contains(npub))
+    {
+        return Ok(Some(FreeCommentDenial::NotAllowlisted));
+    }
+    let day_ago = OffsetDateTime::now_utc() - Duration::days(1);
+    let posted =
+        db::main::element_comment::queries::count_by_user_id_created_since(user.id, day_ago, pool)
+            .await?;
+    if posted >= conf.free_comments_per_day {
+        return Ok(Some(FreeCommentDenial::RateLimited {
+            per_day: conf.free_comments_per_day,
+        }));
+    }
+    Ok(None)
+}
+
+/// Free comments go through the same spam filter as the paid ones, callers
+/// are expected to check [free_comment_denial] first. Returns `None` if the
+/// author has reached the daily limit in the meantime.
+pub async fn post_free(
+    user: &User,
+    element: &Element,
+    parent_id: Option<i64>,
+    comment: &str,
+    per_day: i64,
+    pool: &Pool,
+) -> Result<Option<ElementComment>> {
+    if let Some(parent_id) = parent_id {
+        validate_parent(element, parent_id, pool).await?;
+    }
+    let Some(comment) = db::main::element_comment::queries::insert_unpublished_within_limit(
+        element.id,
+        parent_id,
+        user.id,
+        comment,
+        OffsetDateTime::now_utc() - Duration::days(1),
+        per_day,
+        pool,
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+    publish(comment.id, pool, &matrix::try_client(pool)).await?;
+    db::main::element_comment::queries::select_by_id(comment.id, pool)
+        .await
+        .map(Some)
+}
+
+/// Authors can fix their own public comments, edits are checked for spam
+/// since they skip moderation
+pub async fn edit_own(user: &User, id: i64, comment: &str, pool: &Pool) -> Result<ElementComment> {
+    let existing = own_public_comment(user, id, pool).await?;
+    let spam_score = service::spam::score(comment, &service::spam::default_scorers());
+    if spam_score >= service::spam::HOLD_THRESHOLD {
+        return Err("Edited comment looks like spam".into());
+    }
+    db::main::element_comment::queries::set_spam_score(existing.id, spam_score, pool).await?;
+    db::main::element_comment::queries::set_comment(existing.id, comment, pool).await
+}
+
+pub async fn delete_own(user: &User, id: i64, pool: &Pool) -> Result<ElementComment> {
+    let existing = own_public_comment(user, id, pool).await?;
+    let comment = db::main::element_comment::queries::set_deleted_at(
+        existing.id,
+        Some(OffsetDateTime::now_utc()),
+        pool,
+    )
+    .await?;
+    let element = db::main::element::queries::select_by_id(comment.element_id, pool).await?;
+    refresh_comment_count_tag(&element, pool).await?;
+    Ok(comment)
+}
+
+async fn own_public_comment(user: &User, id: i64, pool: &Pool) -> Result<ElementComment> {
+    let comment = db::main::element_comment::queries::select_by_id(id, pool).await?;
+    if comment.user_id != Some(user.id) || comment.deleted_at.is_some() {
+        return Err(rusqlite::Error::QueryReturnedNoRows.into());
+    }
+    Ok(comment)
+}
+
+/// Public comments can be flagged by anyone, flagged comments stay visible
+/// until a moderator hides them
+pub async fn flag(id: i64, reason: &str, pool: &Pool) -> Result<ElementCommentFlag> {
+    let reason = reason.trim();
+    if reason.is_empty() || reason.chars().count() > MAX_FLAG_REASON_LEN {
+        return Err(
+            format!("reason must be between 1 and {MAX_FLAG_REASON_LEN} characters").into(),
+        );
+    }
+    let comment = db::main::element_comment::queries::select_by_id(id, pool).await?;
+    if comment.deleted_at.is_some() {
+        return Err(rusqlite::Error::QueryReturnedNoRows.into());
+    }
+    db::main::element_comment_flag::queries::insert(comment.id, reason, pool).await
+}
+
+pub async fn hide(id: i64, pool: &Pool) -> Result<ElementComment> {
+    let comment = db::main::element_comment::queries::select_by_id(id, pool).await?;
+    let now = OffsetDateTime::now_utc();
+    if comment.hidden_at.is_none() {
+        let hidden_from = match comment.deleted_at {
+            Some(_) => HiddenFrom::Unpublished,
+            None => HiddenFrom::Public,
+        };
+        db::main::element_comment::queries::set_hidden_at(id, Some((now, hidden_from)), pool)
+            .await?;
+    }
+    if comment.deleted_at.is_none() {
+        db::main::element_comment::queries::set_deleted_at(id, Some(now), pool).await?;
+    }
+    on_moderated(id, pool).await
+}
+
+/// Brings back a hidden comment, to where it was before it got hidden.
+/// Comments which were held haven't been published yet, so they are
+/// announced as new ones, and comments which weren't public stay that way.
+pub async fn restore(id: i64, pool: &Pool) -> Result<ElementComment> {
+    let comment = db::main::element_comment::queries::select_by_id(id, pool).await?;
+    if comment.hidden_at.is_none() {
+        return Err(format!("Comment {id} is not hidden").into());
+    }
+    let element = db::main::element::queries::select_by_id(comment.element_id, pool).await?;
+    db::main::element_comment::queries::set_hidden_at(id, None, pool).await?;
+    match comment.hidden_from {
+        Some(HiddenFrom::Public) => {
+            db::main::element_comment::queries::set_deleted_at(id, None, pool).await?;
+        }
+        Some(HiddenFrom::Held) => go_live(&comment, &element, pool).await?,
+        Some(HiddenFrom::Unpublished) | None => {}
+    }
+    on_moderated(id, pool).await
+}
+
+pub async fn delete(id: i64, pool: &Pool) -> Result<ElementComment> {
+    let comment = db::main::element_comment::queries::select_by_id(id, pool).await?;
+    if comment.deleted_at.is_none() {
+        db::main::element_comment::queries::set_deleted_at(
+            id,
+            Some(OffsetDateTime::now_utc()),
+            pool,
+        )
+        .await?;
+    }
+    if comment.hidden_at.is_some() {
+        db::main::element_comment::queries::set_hidden_at(id, None, pool).await?;
+    }
+    on_moderated(id, pool).await
+}
+
+async fn on_moderated(id: i64, pool: &Pool) -> Result<ElementComment> {
+    let now = OffsetDateTime::now_utc();
+    db::main::element_comment_flag::queries::resolve_by_element_comment_id(id, now, pool).await?;
+    let comment = db::main::element_comment::queries::set_moderated_at(id, now, pool).await?;
+    let element = db::main::element::queries::select_by_id(comment.element_id, pool).await?;
+    refresh_comment_count_tag(&element, pool).await?;
+    Ok(comment)
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
-        db::{self, main::test::pool},
+        db::{
+            self,
+            main::{element_comment::schema::HiddenFrom, test::pool},
+        },
         service::overpass::OverpassElement,
         Result,
     };
@@ -91,4 +417,214 @@ mod test {
         assert_eq!(None, element.tags.get("comments"));
         Ok(())
     }
+
+    #[test]
+    async fn post_official() -> Result<()> {
+        let pool = pool();
+        let npub = "npub1merchant";
+        // OSM tags can be edited by anyone
+        let element = db::main::element::queries::insert(
+            OverpassElement::mock_with_tags(1, &[("contact:nostr", npub)]),
+            &pool,
+        )
+        .await?;
+        let question =
+            db::main::element_comment::queries::insert(element.id, "Open on Sunday?", &pool)
+                .await?;
+        assert!(
+            super::post_official(&element, Some(question.id), "Yes", npub, &pool)
+                .await
+                .is_err()
+        );
+        let element = db::main::element::queries::set_tag(
+            element.id,
+            super::OWNER_NPUB_TAG,
+            &Value::from(npub),
+            &pool,
+        )
+        .await?;
+        let reply = super::post_official(&element, Some(question.id), "Yes", npub, &pool).await?;
+        assert!(reply.official);
+        assert_eq!(Some(question.id), reply.parent_id);
+        let element = db::main::element::queries::select_by_id(element.id, &pool).await?;
+        assert_eq!(Value::Number(2.into()), element.tags["comments"]);
+
+        let other = db::main::element::queries::insert(OverpassElement::mock(2), &pool).await?;
+        assert!(super::validate_parent(&other, question.id, &pool)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    async fn free_comments() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let user = db::main::user::queries::insert("user", "", &pool).await?;
+        let mut conf = db::main::conf::queries::select(&pool).await?;
+        assert_eq!(
+            Some(super::FreeCommentDenial::AccountTooNew { min_days: 7 }),
+            super::free_comment_denial(&user, &conf, &pool).await?
+        );
+        conf.free_comment_min_account_age_days = 0;
+        conf.free_comments_per_day = 1;
+        assert_eq!(None, super::free_comment_denial(&user, &conf, &pool).await?);
+
+        let comment = super::post_free(&user, &element, None, "Nice place", 1, &pool)
+            .await?
+            .unwrap();
+        assert_eq!(Some(user.id), comment.user_id);
+        assert_eq!(None, comment.deleted_at);
+        assert_eq!(
+            Some(super::FreeCommentDenial::RateLimited { per_day: 1 }),
+            super::free_comment_denial(&user, &conf, &pool).await?
+        );
+        assert_eq!(
+            None,
+            super::post_free(&user, &element, None, "Again", 1, &pool).await?
+        );
+        conf.free_comment_npub_allowlist = vec!["npub1friend".into()];
+        assert_eq!(
+            Some(super::FreeCommentDenial::NotAllowlisted),
+            super::free_comment_denial(&user, &conf, &pool).await?
+        );
+
+        let comment = super::edit_own(&user, comment.id, "Very nice place", &pool).await?;
+        assert_eq!("Very nice place", comment.comment);
+        let other = db::main::user::queries::insert("other", "", &pool).await?;
+        assert!(super::edit_own(&other, comment.id, "Hacked", &pool)
+            .await
+            .is_err());
+        assert!(super::delete_own(&other, comment.id, &pool).await.is_err());
+        let comment = super::delete_own(&user, comment.id, &pool).await?;
+        assert!(comment.deleted_at.is_some());
+        let element = db::main::element::queries::select_by_id(element.id, &pool).await?;
+        assert_eq!(None, element.tags.get("comments"));
+        Ok(())
+    }
+
+    #[test]
+    async fn publish_holds_spam() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let comment = db::main::element_comment::queries::insert(
+            element.id,
+            "Airdrop giveaway https://bit.ly/x",
+            &pool,
+        )
+        .await?;
+        db::main::element_comment::queries::set_deleted_at(
+            comment.id,
+            Some(OffsetDateTime::now_utc()),
+            &pool,
+        )
+        .await?;
+        super::publish(comment.id, &pool, &None).await?;
+        let comment = db::main::element_comment::queries::select_by_id(comment.id, &pool).await?;
+        assert!(comment.deleted_at.is_some());
+        assert!(comment.hidden_at.is_some());
+        assert!(comment.spam_score >= crate::service::spam::HOLD_THRESHOLD);
+        let queue = db::main::element_comment::queries::select_moderation_queue(&pool).await?;
+        assert_eq!(1, queue.len());
+
+        let comment = super::restore(comment.id, &pool).await?;
+        assert_eq!(None, comment.deleted_at);
+        assert_eq!(None, comment.hidden_at);
+        let element = db::main::element::queries::select_by_id(element.id, &pool).await?;
+        assert_eq!(Value::Number(1.into()), element.tags["comments"]);
+        assert!(
+            db::main::element_comment::queries::select_moderation_queue(&pool)
+                .await?
+                .is_empty()
+        );
+        Ok(())
+    }
+
+    #[test]
+    async fn flag_hide_and_delete() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let comment = db::main::element_comment::queries::insert(element.id, "Rude", &pool).await?;
+        super::refresh_comment_count_tag(&element, &pool).await?;
+        assert!(super::flag(comment.id, " ", &pool).await.is_err());
+        super::flag(comment.id, "offensive", &pool).await?;
+        let queue = db::main::element_comment::queries::select_moderation_queue(&pool).await?;
+        assert_eq!(1, queue.len());
+
+        let comment = super::hide(comment.id, &pool).await?;
+        assert!(comment.deleted_at.is_some());
+        assert!(comment.hidden_at.is_some());
+        assert!(
+            db::main::element_comment::queries::select_moderation_queue(&pool)
+                .await?
+                .is_empty()
+        );
+        let element = db::main::element::queries::select_by_id(element.id, &pool).await?;
+        assert_eq!(None, element.tags.get("comments"));
+        assert!(super::flag(comment.id, "offensive", &pool).await.is_err());
+
+        let comment = super::restore(comment.id, &pool).await?;
+        assert_eq!(None, comment.deleted_at);
+        assert_eq!(None, comment.hidden_at);
+        let element = db::main::element::queries::select_by_id(element.id, &pool).await?;
+        assert_eq!(Value::Number(1.into()), element.tags["comments"]);
+
+        super::hide(comment.id, &pool).await?;
+        let comment = super::delete(comment.id, &pool).await?;
+        assert!(comment.deleted_at.is_some());
+        assert_eq!(None, comment.hidden_at);
+        assert!(super::restore(comment.id, &pool).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    async fn restore_keeps_unpaid_comments_unpublished() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let comment =
+            db::main::element_comment::queries::insert(element.id, "Great coffee", &pool).await?;
+        db::main::element_comment::queries::set_deleted_at(
+            comment.id,
+            Some(OffsetDateTime::now_utc()),
+            &pool,
+        )
+        .await?;
+        super::hide(comment.id, &pool).await?;
+        let comment = super::restore(comment.id, &pool).await?;
+        assert!(comment.deleted_at.is_some());
+        assert_eq!(None, comment.hidden_at);
+
+        // paying for a hidden comment doesn't publish it until it's restored
+        super::hide(comment.id, &pool).await?;
+        super::publish(comment.id, &pool, &None).await?;
+        let comment = db::main::element_comment::queries::select_by_id(comment.id, &pool).await?;
+        assert!(comment.deleted_at.is_some());
+        assert_eq!(Some(HiddenFrom::Held), comment.hidden_from);
+        let comment = super::restore(comment.id, &pool).await?;
+        assert_eq!(None, comment.deleted_at);
+        Ok(())
+    }
+
+    #[test]
+    async fn restore_keeps_author_deleted_comments_deleted() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let user = db::main::user::queries::insert("user", "", &pool).await?;
+        let comment = db::main::element_comment::queries::insert_threaded(
+            element.id,
+            None,
+            false,
+            Some(user.id),
+            "Closed for good",
+            &pool,
+        )
+        .await?;
+        super::delete_own(&user, comment.id, &pool).await?;
+        super::hide(comment.id, &pool).await?;
+        let comment = super::restore(comment.id, &pool).await?;
+        assert!(comment.deleted_at.is_some());
+        let element = db::main::element::queries::select_by_id(element.id, &pool).await?;
+        assert_eq!(None, element.tags.get("comments"));
+        Ok(())
+    }
 }
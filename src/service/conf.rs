@@ -0,0 +1,123 @@
+//! The `conf` row is loaded at startup and reloaded whenever its `updated_at`
+//! changes, so prices, CORS origins and the rest can be changed without a
+//! restart. Handlers read it through a `SharedConf` in app data.
+
+use crate::{
+    db::{self, main::conf::schema::Conf, main::MainPool},
+    Result,
+};
+use std::{
+    sync::{Arc, LazyLock, RwLock},
+    time::Duration,
+};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Picks up changes made outside of this process, such as manual edits
+pub const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Wakes up the watcher, see `notify_changed`
+static CHANGED: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// The current conf. Readers get a snapshot which never changes under them,
+/// reloads swap in a new one.
+#[derive(Clone)]
+pub struct SharedConf(Arc<RwLock<Arc<Conf>>>);
+
+impl SharedConf {
+    pub fn new(conf: Conf) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(conf))))
+    }
+
+    pub fn load(&self) -> Arc<Conf> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn store(&self, conf: Conf) {
+        *self.0.write().unwrap() = Arc::new(conf);
+    }
+}
+
+/// Writes made by this process apply right away instead of on the next poll
+pub fn notify_changed() {
+    CHANGED.notify_one();
+}
+
+pub async fn init(pool: &MainPool, shutdown: CancellationToken) -> Result<SharedConf> {
+    // covers secrets added by hand and the ones encrypted with a retired key
+    let reencrypted = db::main::conf::queries::reencrypt(pool).await?;
+    if !reencrypted.is_empty() {
+        warn!(
+            ?reencrypted,
+            "conf: encrypted secrets under the current key"
+        );
+    }
+    let conf = SharedConf::new(db::main::conf::queries::select(pool).await?);
+    let watched = conf.clone();
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        info!(
+            poll_interval_secs = POLL_INTERVAL.as_secs(),
+            "conf watcher: started"
+        );
+        loop {
+            let force = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = tokio::time::sleep(POLL_INTERVAL) => false,
+                _ = CHANGED.notified() => true,
+            };
+            if let Err(err) = reload(&watched, force, &pool).await {
+                warn!(%err, "conf watcher: failed to reload conf");
+            }
+        }
+        info!("conf watcher: stopped");
+    });
+    Ok(conf)
+}
+
+/// Returns true if a new conf was swapped in
+async fn reload(conf: &SharedConf, force: bool, pool: &MainPool) -> Result<bool> {
+    if !force && db::main::conf::queries::select_updated_at(pool).await? == conf.load().updated_at {
+        return Ok(false);
+    }
+    let new = db::main::conf::queries::select(pool).await?;
+    if new.updated_at == conf.load().updated_at {
+        return Ok(false);
+    }
+    info!(updated_at = new.updated_at, "conf watcher: conf reloaded");
+    conf.store(new);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::SharedConf;
+    use crate::db::main::test::pool;
+    use crate::{db, Result};
+    use actix_web::test;
+
+    #[test]
+    async fn reload_swaps_in_changed_conf() -> Result<()> {
+        let pool = pool();
+        let conf = SharedConf::new(db::main::conf::queries::select(&pool).await?);
+        let before = conf.load();
+        assert!(!super::reload(&conf, false, &pool).await?);
+
+        pool.get()
+            .await?
+            .interact(|conn| {
+                conn.execute(
+                    "UPDATE conf SET cors_origins = 'https://a.example.com', updated_at = 'changed'",
+                    [],
+                )
+            })
+            .await??;
+        assert!(super::reload(&conf, false, &pool).await?);
+        assert_eq!(vec!["https://a.example.com"], conf.load().cors_origins);
+        // snapshots taken before the reload stay as they were
+        assert!(before.cors_origins.is_empty());
+        assert!(!super::reload(&conf, true, &pool).await?);
+        Ok(())
+    }
+}
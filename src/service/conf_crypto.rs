@@ -0,0 +1,207 @@
+//! Secrets in the `conf` table, such as API keys and macaroons, are encrypted
+//! at rest so copies of main.db don't leak them.
+//!
+//! Keys are 32 random bytes in base64 (`openssl rand -base64 32`), supplied by
+//! `BTCMAP_CONF_KEY` or by a file at `BTCMAP_CONF_KEY_FILE`. Both can hold
+//! several keys, comma or newline separated. The first one encrypts new
+//! values, the rest can only decrypt, which allows rotating keys: put a new
+//! key first, restart, and every value is re-encrypted under it on startup.
+
+use crate::Result;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use sha2::{Digest, Sha256};
+use std::{env, fs, sync::OnceLock};
+use tracing::{info, warn};
+
+const PREFIX: &str = "enc:v1:";
+
+const NONCE_LEN: usize = 24;
+
+static KEYS: OnceLock<Keys> = OnceLock::new();
+
+struct Key {
+    /// Tells which key a value was encrypted with, without revealing the key
+    id: String,
+    cipher: XChaCha20Poly1305,
+}
+
+/// Encryption keys, newest first. Without keys, values are stored as is.
+#[derive(Default)]
+pub struct Keys(Vec<Key>);
+
+impl Keys {
+    pub fn new(keys: &[&str]) -> Result<Self> {
+        keys.iter()
+            .map(|key| {
+                let bytes = BASE64_STANDARD.decode(key.trim())?;
+                if bytes.len() != 32 {
+                    return Err("conf key must be 32 bytes long".into());
+                }
+                let id = Sha256::digest(&bytes)
+                    .iter()
+                    .take(4)
+                    .map(|b| format!("{b:02x}"))
+                    .collect();
+                let cipher = XChaCha20Poly1305::new_from_slice(&bytes)
+                    .map_err(|_| "conf key must be 32 bytes long")?;
+                Ok(Key { id, cipher })
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Keys)
+    }
+
+    pub fn from_env() -> Result<Self> {
+        let raw = match (
+            env::var("BTCMAP_CONF_KEY"),
+            env::var("BTCMAP_CONF_KEY_FILE"),
+        ) {
+            (Ok(_), Ok(_)) => {
+                return Err("set either BTCMAP_CONF_KEY or BTCMAP_CONF_KEY_FILE, not both".into())
+            }
+            (Ok(keys), _) => keys,
+            (_, Ok(path)) => fs::read_to_string(&path)
+                .map_err(|e| format!("can't read conf key file {path}: {e}"))?,
+            _ => String::new(),
+        };
+        let keys: Vec<&str> = raw
+            .split([',', '\n'])
+            .map(str::trim)
+            .filter(|it| !it.is_empty() && !it.starts_with('#'))
+            .collect();
+        Self::new(&keys)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Empty values stay empty, so it's still possible to tell which secrets
+    /// are set
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let Some(key) = self.0.first() else {
+            return Ok(plaintext.to_string());
+        };
+        if plaintext.is_empty() {
+            return Ok(String::new());
+        }
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = key
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| "failed to encrypt conf value")?;
+        let mut payload = nonce.to_vec();
+        payload.extend(ciphertext);
+        Ok(format!(
+            "{PREFIX}{}:{}",
+            key.id,
+            BASE64_STANDARD.encode(payload)
+        ))
+    }
+
+    /// Values which aren't encrypted are returned as is
+    pub fn decrypt(&self, value: &str) -> Result<String> {
+        let Some(encrypted) = value.strip_prefix(PREFIX) else {
+            return Ok(value.to_string());
+        };
+        let (id, payload) = encrypted
+            .split_once(':')
+            .ok_or("malformed encrypted conf value")?;
+        let key = self
+            .0
+            .iter()
+            .find(|it| it.id == id)
+            .ok_or_else(|| format!("conf value is encrypted with an unknown key {id}"))?;
+        let payload = BASE64_STANDARD.decode(payload)?;
+        if payload.len() < NONCE_LEN {
+            return Err("malformed encrypted conf value".into());
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let plaintext = key
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| format!("failed to decrypt conf value with key {id}"))?;
+        String::from_utf8(plaintext).map_err(|_| "conf value is not valid UTF-8".into())
+    }
+
+    /// Returns false if the value is in plaintext or encrypted with an old key
+    pub fn is_current(&self, value: &str) -> bool {
+        match self.0.first() {
+            Some(key) => value.is_empty() || value.starts_with(&format!("{PREFIX}{}:", key.id)),
+            None => true,
+        }
+    }
+}
+
+/// Loads the keys from the environment, has to be called before the first
+/// access to the conf table
+pub fn init() -> Result<()> {
+    let keys = Keys::from_env()?;
+    if keys.is_empty() {
+        warn!("BTCMAP_CONF_KEY is not set, conf secrets are stored in plaintext");
+    } else {
+        info!(keys = keys.0.len(), "loaded conf keys");
+    }
+    KEYS.set(keys).map_err(|_| "conf keys are already loaded")?;
+    Ok(())
+}
+
+/// The keys loaded by `init`, or no keys if it wasn't called
+pub fn keys() -> &'static Keys {
+    KEYS.get_or_init(Keys::default)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Keys;
+    use crate::Result;
+
+    const KEY_1: &str = "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=";
+    const KEY_2: &str = "AgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgI=";
+
+    #[test]
+    fn encrypt_and_decrypt() -> Result<()> {
+        let keys = Keys::new(&[KEY_1])?;
+        let encrypted = keys.encrypt("macaroon")?;
+        assert!(encrypted.starts_with(super::PREFIX));
+        assert!(!encrypted.contains("macaroon"));
+        assert_ne!(encrypted, keys.encrypt("macaroon")?);
+        assert_eq!("macaroon", keys.decrypt(&encrypted)?);
+        assert_eq!("", keys.encrypt("")?);
+        assert_eq!("plaintext", keys.decrypt("plaintext")?);
+        Ok(())
+    }
+
+    #[test]
+    fn no_keys() -> Result<()> {
+        let keys = Keys::default();
+        assert_eq!("macaroon", keys.encrypt("macaroon")?);
+        assert!(keys.is_current("macaroon"));
+        let encrypted = Keys::new(&[KEY_1])?.encrypt("macaroon")?;
+        assert!(keys.decrypt(&encrypted).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rotation() -> Result<()> {
+        let old = Keys::new(&[KEY_1])?;
+        let new = Keys::new(&[KEY_2, KEY_1])?;
+        let encrypted = old.encrypt("macaroon")?;
+        assert!(!new.is_current(&encrypted));
+        assert!(!new.is_current("macaroon"));
+        assert_eq!("macaroon", new.decrypt(&encrypted)?);
+        let reencrypted = new.encrypt(&new.decrypt(&encrypted)?)?;
+        assert!(new.is_current(&reencrypted));
+        assert!(old.decrypt(&reencrypted).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_key() {
+        assert!(Keys::new(&["c2hvcnQ="]).is_err());
+        assert!(Keys::new(&["not base64"]).is_err());
+    }
+}
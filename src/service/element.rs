@@ -357,6 +357,9 @@ pub const TAGS: &[&str] = &[
     "deleted_at",
     "verified_at",
     "comments",
+    "rating",
+    "rating_count",
+    "last_confirmed_payment_at",
     "description",
     "image",
     "payment_provider",
@@ -446,6 +449,25 @@ pub fn generate_tags(
                     res.insert("comments".to_string(), comments.into());
                 }
             }
+            "rating" => {
+                if let Some(rating) = element.rating() {
+                    res.insert("rating".to_string(), rating.into());
+                }
+            }
+            "rating_count" => {
+                let rating_count = element.rating_count();
+                if rating_count > 0 {
+                    res.insert("rating_count".to_string(), rating_count.into());
+                }
+            }
+            "last_confirmed_payment_at" => {
+                if let Some(date) = element.last_confirmed_payment_at() {
+                    res.insert(
+                        "last_confirmed_payment_at".into(),
+                        Value::String(date.format(&Rfc3339).unwrap_or_default()),
+                    );
+                }
+            }
             "phone" => {
                 if let Some(phone) = element.phone() {
                     res.insert("phone".to_string(), phone.into());
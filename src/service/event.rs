@@ -41,6 +41,12 @@ pub async fn on_new_event(
     info!(message);
     matrix::send_message(matrix_client, ROOM_OSM_CHANGES, &message);
 
+    if event.r#type == "create" || event.r#type == "update" {
+        if let Err(err) = service::nostr::publish_place(&element, pool).await {
+            warn!(%err, element.id, "Failed to publish place to nostr");
+        }
+    }
+
     if user.tags.get("osm:missing") == Some(&Value::Bool(true)) {
         info!(user.osm_data.id, "This user is missing from OSM, skipping");
         return Ok(());
@@ -0,0 +1,392 @@
+use crate::db::main::area::schema::Area;
+use crate::db::main::event::schema::Event;
+use crate::{db, Result};
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use chrono_tz::Tz;
+use cron::{Schedule, TimeUnitSpec};
+use deadpool_sqlite::Pool;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use time::{Date, Duration, OffsetDateTime};
+use tzf_rs::DefaultFinder;
+
+/// Recurring events have no per-occurrence end time, so every occurrence is
+/// assumed to last this long
+pub const OCCURRENCE_DURATION: Duration = Duration::hours(2);
+
+/// How far ahead recurring events are expanded by default
+pub const EXPANSION_WINDOW: Duration = Duration::days(90);
+
+/// Hard limit on the number of expanded occurrences per event, protects us
+/// from schedules such as "every second"
+pub const MAX_OCCURRENCES: usize = 500;
+
+/// Area tag holding an IANA timezone name, such as "Europe/Berlin"
+pub const TIMEZONE_TAG: &str = "timezone";
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Occurrence {
+    #[serde(with = "time::serde::rfc3339")]
+    pub starts_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub ends_at: OffsetDateTime,
+}
+
+/// Schedules use the 6 or 7 field syntax with seconds and optional years:
+/// "sec min hour day_of_month month day_of_week [year]". Days of week are
+/// numbered 1-7 starting from Sunday, names (Mon, Tue...) are also accepted.
+pub fn parse_schedule(cron_schedule: &str) -> Result<Schedule> {
+    Schedule::from_str(cron_schedule)
+        .map_err(|e| format!("Invalid cron_schedule {cron_schedule:?}: {e}").into())
+}
+
+/// Resolves event timezones, fetching every linked area only once. Meant to
+/// live for a single request or render.
+#[derive(Default)]
+pub struct Timezones {
+    areas: HashMap<i64, Option<Tz>>,
+}
+
+impl Timezones {
+    /// Cron schedules are interpreted in the local time of the event. An
+    /// area the event is linked to can override it with a valid timezone
+    /// tag, otherwise the timezone stored on the event is used. Events saved
+    /// before timezones were stored are looked up on the fly.
+    pub async fn get(&mut self, event: &Event, pool: &Pool) -> Result<Tz> {
+        if let Some(area_id) = event.area_id {
+            let tz = match self.areas.get(&area_id) {
+                Some(tz) => *tz,
+                None => {
+                    let tz = db::main::area::queries::select_by_id(area_id, pool)
+                        .await
+                        .ok()
+                        .and_then(|it| area_timezone(&it));
+                    self.areas.insert(area_id, tz);
+                    tz
+                }
+            };
+            if let Some(tz) = tz {
+                return Ok(tz);
+            }
+        }
+        if let Some(tz) = event.timezone.as_deref().and_then(|it| it.parse().ok()) {
+            return Ok(tz);
+        }
+        Ok(lookup_timezone(event.lat, event.lon))
+    }
+}
+
+/// Saves the timezone of the event location, callers are expected to do that
+/// whenever an event is created or moved
+pub async fn store_timezone(event: &Event, pool: &Pool) -> Result<Event> {
+    let tz = lookup_timezone(event.lat, event.lon);
+    db::main::event::queries::set_timezone(event.id, tz.name().to_string(), pool).await
+}
+
+fn area_timezone(area: &Area) -> Option<Tz> {
+    area.tags.get(TIMEZONE_TAG)?.as_str()?.parse().ok()
+}
+
+/// IANA timezone at the given location, such as "Europe/Berlin". Points the
+/// timezone boundaries don't cover fall back to a fixed offset derived from
+/// their longitude.
+pub fn lookup_timezone(lat: f64, lon: f64) -> Tz {
+    static FINDER: LazyLock<DefaultFinder> = LazyLock::new(DefaultFinder::new);
+    FINDER
+        .get_tz_name(lon, lat)
+        .parse()
+        .unwrap_or_else(|_| fallback_timezone(lon))
+}
+
+pub fn fallback_timezone(lon: f64) -> Tz {
+    let offset = (lon / 15.0).round().clamp(-12.0, 12.0) as i64;
+    // Etc/GMT zones use inverted signs, Etc/GMT-3 is UTC+3
+    let name = match offset {
+        0 => "Etc/GMT".to_string(),
+        offset if offset > 0 => format!("Etc/GMT-{offset}"),
+        offset => format!("Etc/GMT+{}", -offset),
+    };
+    name.parse().unwrap_or(Tz::UTC)
+}
+
+/// Returns the occurrences which overlap with the [from, to) range. One-off
+/// events have at most one occurrence, recurring events are bounded by their
+/// starts_at and ends_at dates and skip their excluded dates.
+pub fn occurrences(
+    event: &Event,
+    tz: Tz,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+) -> Result<Vec<Occurrence>> {
+    let Some(cron_schedule) = &event.cron_schedule else {
+        let Some(starts_at) = event.starts_at else {
+            return Ok(vec![]);
+        };
+        let ends_at = event.ends_at.unwrap_or(starts_at + OCCURRENCE_DURATION);
+        if ends_at <= from || starts_at >= to {
+            return Ok(vec![]);
+        }
+        return Ok(vec![Occurrence { starts_at, ends_at }]);
+    };
+    let schedule = parse_schedule(cron_schedule)?;
+    let mut after = from - OCCURRENCE_DURATION;
+    if let Some(starts_at) = event.starts_at {
+        after = after.max(starts_at - Duration::SECOND);
+    }
+    let to = event.ends_at.map(|it| it.min(to)).unwrap_or(to);
+    let mut res = vec![];
+    for local in schedule.after(&to_chrono(after).with_timezone(&tz)) {
+        let starts_at = from_chrono(&local);
+        if starts_at >= to || res.len() >= MAX_OCCURRENCES {
+            break;
+        }
+        if event.excluded_dates.contains(&to_date(&local)) {
+            continue;
+        }
+        res.push(Occurrence {
+            starts_at,
+            ends_at: starts_at + OCCURRENCE_DURATION,
+        });
+    }
+    Ok(res)
+}
+
+/// Translates a cron schedule into an RFC 5545 RRULE. Returns None if the
+/// schedule can't be expressed as a single rule, callers are expected to
+/// list the expanded occurrences instead.
+pub fn rrule(schedule: &Schedule, until: Option<OffsetDateTime>) -> Option<String> {
+    let times_per_day =
+        schedule.hours().count() * schedule.minutes().count() * schedule.seconds().count();
+    if !schedule.years().is_all() || times_per_day > 24 {
+        return None;
+    }
+    let freq = if !schedule.days_of_month().is_all() {
+        "MONTHLY"
+    } else if !schedule.days_of_week().is_all() {
+        "WEEKLY"
+    } else {
+        "DAILY"
+    };
+    let mut parts = vec![format!("FREQ={freq}")];
+    if let Some(until) = until {
+        parts.push(format!("UNTIL={}", format_utc(until)));
+    }
+    if !schedule.months().is_all() {
+        parts.push(format!("BYMONTH={}", join(schedule.months())));
+    }
+    if !schedule.days_of_month().is_all() {
+        parts.push(format!("BYMONTHDAY={}", join(schedule.days_of_month())));
+    }
+    if !schedule.days_of_week().is_all() {
+        let days: Vec<&str> = schedule
+            .days_of_week()
+            .iter()
+            .filter_map(|it| WEEKDAYS.get(it as usize - 1).copied())
+            .collect();
+        parts.push(format!("BYDAY={}", days.join(",")));
+    }
+    parts.push(format!("BYHOUR={}", join(schedule.hours())));
+    parts.push(format!("BYMINUTE={}", join(schedule.minutes())));
+    parts.push(format!("BYSECOND={}", join(schedule.seconds())));
+    Some(parts.join(";"))
+}
+
+/// Cron numbers days of week starting from Sunday
+const WEEKDAYS: [&str; 7] = ["SU", "MO", "TU", "WE", "TH", "FR", "SA"];
+
+fn join(spec: &impl TimeUnitSpec) -> String {
+    spec.iter()
+        .map(|it| it.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn format_utc(date_time: OffsetDateTime) -> String {
+    to_chrono(date_time).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+pub fn to_chrono(date_time: OffsetDateTime) -> DateTime<Utc> {
+    Utc.timestamp_opt(date_time.unix_timestamp(), 0)
+        .single()
+        .unwrap_or_default()
+}
+
+pub fn from_chrono<T: TimeZone>(date_time: &DateTime<T>) -> OffsetDateTime {
+    OffsetDateTime::from_unix_timestamp(date_time.timestamp()).unwrap_or(OffsetDateTime::UNIX_EPOCH)
+}
+
+pub fn to_date<T: TimeZone>(date_time: &DateTime<T>) -> Date {
+    let date = date_time.date_naive();
+    Date::from_ordinal_date(date.year(), date.ordinal() as u16).unwrap_or(Date::MIN)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fallback_timezone, occurrences, parse_schedule, rrule, Occurrence, Timezones};
+    use crate::db::main::area::schema::Area;
+    use crate::db::main::event::schema::Event;
+    use crate::db::main::test::pool;
+    use crate::{db, Result};
+    use chrono_tz::Tz;
+    use serde_json::json;
+    use time::macros::{date, datetime};
+    use time::OffsetDateTime;
+
+    fn event(cron_schedule: Option<&str>) -> Event {
+        Event {
+            id: 1,
+            area_id: None,
+            lat: 52.52,
+            lon: 13.40,
+            name: "Meetup".into(),
+            website: "https://example.com".into(),
+            starts_at: None,
+            ends_at: None,
+            cron_schedule: cron_schedule.map(Into::into),
+            excluded_dates: vec![],
+            timezone: None,
+            created_at: OffsetDateTime::UNIX_EPOCH,
+            updated_at: OffsetDateTime::UNIX_EPOCH,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn parse_schedule_rejects_garbage() {
+        assert!(parse_schedule("0 0 19 * * Thu").is_ok());
+        assert!(parse_schedule("every thursday").is_err());
+    }
+
+    #[test]
+    fn fallback_timezone_uses_inverted_etc_signs() {
+        assert_eq!(Tz::Etc__GMTMinus1, fallback_timezone(13.40));
+        assert_eq!(Tz::Etc__GMTPlus5, fallback_timezone(-74.0));
+        assert_eq!(Tz::Etc__GMT, fallback_timezone(0.1));
+    }
+
+    #[test]
+    fn lookup_timezone_finds_iana_name() {
+        assert_eq!(Tz::Europe__Berlin, super::lookup_timezone(52.52, 13.40));
+        assert_eq!(Tz::America__New_York, super::lookup_timezone(40.71, -74.0));
+        assert_eq!(Tz::Etc__GMTPlus2, super::lookup_timezone(0.0, -30.0));
+    }
+
+    #[test]
+    fn occurrences_one_off() -> Result<()> {
+        let mut event = event(None);
+        event.starts_at = Some(datetime!(2025-03-01 18:00 UTC));
+        let from = datetime!(2025-02-01 0:00 UTC);
+        let to = datetime!(2025-04-01 0:00 UTC);
+        assert_eq!(
+            vec![Occurrence {
+                starts_at: datetime!(2025-03-01 18:00 UTC),
+                ends_at: datetime!(2025-03-01 20:00 UTC),
+            }],
+            occurrences(&event, Tz::UTC, from, to)?,
+        );
+        assert!(occurrences(&event, Tz::UTC, to, to + super::EXPANSION_WINDOW)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn occurrences_follow_local_time_across_dst() -> Result<()> {
+        let mut event = event(Some("0 0 19 * * Thu"));
+        event.excluded_dates = vec![date!(2025 - 03 - 27)];
+        let res = occurrences(
+            &event,
+            Tz::Europe__Berlin,
+            datetime!(2025-03-17 0:00 UTC),
+            datetime!(2025-04-07 0:00 UTC),
+        )?;
+        let starts: Vec<OffsetDateTime> = res.into_iter().map(|it| it.starts_at).collect();
+        assert_eq!(
+            vec![
+                datetime!(2025-03-20 18:00 UTC),
+                datetime!(2025-04-03 17:00 UTC),
+            ],
+            starts,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn occurrences_bounded_by_series() -> Result<()> {
+        let mut event = event(Some("0 30 18 * * *"));
+        event.starts_at = Some(datetime!(2025-01-10 18:30 UTC));
+        event.ends_at = Some(datetime!(2025-01-12 23:59 UTC));
+        let res = occurrences(
+            &event,
+            Tz::UTC,
+            datetime!(2025-01-01 0:00 UTC),
+            datetime!(2025-02-01 0:00 UTC),
+        )?;
+        assert_eq!(3, res.len());
+        assert_eq!(datetime!(2025-01-10 18:30 UTC), res[0].starts_at);
+        Ok(())
+    }
+
+    #[test]
+    fn rrule_weekly() -> Result<()> {
+        assert_eq!(
+            Some(
+                "FREQ=WEEKLY;UNTIL=20251231T000000Z;BYDAY=TU,TH;BYHOUR=19;BYMINUTE=0;BYSECOND=0"
+                    .into()
+            ),
+            rrule(
+                &parse_schedule("0 0 19 * * Tue,Thu")?,
+                Some(datetime!(2025-12-31 0:00 UTC)),
+            ),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rrule_monthly() -> Result<()> {
+        assert_eq!(
+            Some("FREQ=MONTHLY;BYMONTH=1,7;BYMONTHDAY=1;BYHOUR=12;BYMINUTE=0;BYSECOND=0".into()),
+            rrule(&parse_schedule("0 0 12 1 1,7 *")?, None),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rrule_not_expressible() -> Result<()> {
+        assert_eq!(None, rrule(&parse_schedule("* * * * * *")?, None));
+        assert_eq!(None, rrule(&parse_schedule("0 0 12 * * * 2030")?, None));
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn timezone_from_area_tag() -> Result<()> {
+        let pool = pool();
+        let mut tags = Area::mock_tags();
+        tags.insert(super::TIMEZONE_TAG.into(), json!("Asia/Bangkok"));
+        let area = db::main::area::queries::insert(tags, &pool).await?;
+        let mut event = event(None);
+        event.area_id = Some(area.id);
+        assert_eq!(
+            Tz::Asia__Bangkok,
+            Timezones::default().get(&event, &pool).await?
+        );
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn timezone_stored_on_event() -> Result<()> {
+        let pool = pool();
+        let mut event = event(None);
+        event.timezone = Some("Europe/Madrid".into());
+        assert_eq!(
+            Tz::Europe__Madrid,
+            Timezones::default().get(&event, &pool).await?
+        );
+        event.timezone = None;
+        assert_eq!(
+            Tz::Europe__Berlin,
+            Timezones::default().get(&event, &pool).await?
+        );
+        Ok(())
+    }
+}
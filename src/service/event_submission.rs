@@ -0,0 +1,341 @@
+use crate::db::main::event::schema::Event;
+use crate::db::main::event_submission::schema::{EventSubmission, EventSubmissionStatus};
+use crate::db::main::user::schema::User;
+use crate::{db, service, Result};
+use deadpool_sqlite::Pool;
+use time::OffsetDateTime;
+use tracing::warn;
+
+/// Keeps a single user from flooding the moderation queue
+pub const MAX_PENDING_PER_USER: usize = 5;
+
+pub const MAX_NAME_LEN: usize = 200;
+
+pub const MAX_WEBSITE_LEN: usize = 2048;
+
+/// Checks the fields shared by submissions and their moderator edits
+pub fn validate(
+    lat: f64,
+    lon: f64,
+    name: &str,
+    website: &str,
+    starts_at: Option<OffsetDateTime>,
+    ends_at: Option<OffsetDateTime>,
+    cron_schedule: Option<&str>,
+) -> Result<()> {
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return Err("lat or lon out of range".into());
+    }
+    if name.trim().is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return Err(format!("name must be between 1 and {MAX_NAME_LEN} characters").into());
+    }
+    if !(website.starts_with("https://") || website.starts_with("http://"))
+        || website.len() > MAX_WEBSITE_LEN
+    {
+        return Err("website must be a http(s) URL".into());
+    }
+    if let (Some(starts_at), Some(ends_at)) = (starts_at, ends_at) {
+        if ends_at <= starts_at {
+            return Err("ends_at must be after starts_at".into());
+        }
+    }
+    if let Some(cron_schedule) = cron_schedule {
+        service::event_schedule::parse_schedule(cron_schedule)?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn submit(
+    user: &User,
+    lat: f64,
+    lon: f64,
+    name: String,
+    website: String,
+    starts_at: Option<OffsetDateTime>,
+    ends_at: Option<OffsetDateTime>,
+    cron_schedule: Option<String>,
+    pool: &Pool,
+) -> Result<EventSubmission> {
+    validate(
+        lat,
+        lon,
+        &name,
+        &website,
+        starts_at,
+        ends_at,
+        cron_schedule.as_deref(),
+    )?;
+    let pending = db::main::event_submission::queries::select_by_user_id(user.id, pool)
+        .await?
+        .into_iter()
+        .filter(|it| it.status == EventSubmissionStatus::Pending)
+        .count();
+    if pending >= MAX_PENDING_PER_USER {
+        return Err(format!(
+            "You already have {pending} pending submissions, wait until they are reviewed"
+        )
+        .into());
+    }
+    db::main::event_submission::queries::insert(
+        user.id,
+        None,
+        lat,
+        lon,
+        name,
+        website,
+        starts_at,
+        ends_at,
+        cron_schedule,
+        pool,
+    )
+    .await
+}
+
+/// Publishes the submission as an event and lets the submitter know
+pub async fn approve(
+    submission: &EventSubmission,
+    reviewer: &User,
+    note: Option<String>,
+    pool: &Pool,
+) -> Result<(EventSubmission, Event)> {
+    ensure_pending(submission)?;
+    let (submission, event) =
+        db::main::event_submission::queries::approve(submission.id, reviewer.id, note, pool)
+            .await?;
+    notify_submitter(&submission, pool).await;
+    Ok((submission, event))
+}
+
+pub async fn reject(
+    submission: &EventSubmission,
+    reviewer: &User,
+    reason: String,
+    pool: &Pool,
+) -> Result<EventSubmission> {
+    ensure_pending(submission)?;
+    let submission = db::main::event_submission::queries::set_review(
+        submission.id,
+        EventSubmissionStatus::Rejected,
+        reviewer.id,
+        Some(reason),
+        None,
+        pool,
+    )
+    .await?;
+    notify_submitter(&submission, pool).await;
+    Ok(submission)
+}
+
+pub fn ensure_pending(submission: &EventSubmission) -> Result<()> {
+    if submission.status != EventSubmissionStatus::Pending {
+        return Err(format!(
+            "Event submission {} has already been reviewed",
+            submission.id
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Submitters can always check the outcome via the REST API, the ones who
+/// linked a Nostr key also get a direct message. Delivery failures are
+/// logged, they shouldn't undo the review.
+async fn notify_submitter(submission: &EventSubmission, pool: &Pool) {
+    let user = match db::main::user::queries::select_by_id(submission.user_id, pool).await {
+        Ok(user) => user,
+        Err(err) => {
+            warn!(%err, submission_id = submission.id, "failed to load submitter");
+            return;
+        }
+    };
+    let Some(npub) = &user.npub else {
+        return;
+    };
+    if let Err(err) =
+        service::nostr::send_direct_message(npub, &notification(submission), pool).await
+    {
+        warn!(%err, submission_id = submission.id, "failed to notify submitter");
+    }
+}
+
+fn notification(submission: &EventSubmission) -> String {
+    let mut message = match submission.status {
+        EventSubmissionStatus::Approved => format!(
+            "Your event \"{}\" has been approved and is now listed on BTC Map",
+            submission.name
+        ),
+        EventSubmissionStatus::Rejected => {
+            format!("Your event \"{}\" has been rejected", submission.name)
+        }
+        EventSubmissionStatus::Pending => {
+            format!("Your event \"{}\" is waiting for review", submission.name)
+        }
+    };
+    if let Some(note) = &submission.review_note {
+        message.push_str(&format!("\n\n{note}"));
+    }
+    message
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::main::event_submission::schema::EventSubmissionStatus;
+    use crate::db::main::test::pool;
+    use crate::{db, Result};
+    use actix_web::test;
+    use time::macros::datetime;
+
+    #[test]
+    async fn validate() {
+        let valid = |name: &str, website: &str| {
+            super::validate(1.0, 2.0, name, website, None, None, None).is_ok()
+        };
+        assert!(valid("Meetup", "https://example.com"));
+        assert!(!valid(" ", "https://example.com"));
+        assert!(!valid("Meetup", "javascript:alert(1)"));
+        assert!(
+            super::validate(91.0, 0.0, "Meetup", "https://example.com", None, None, None).is_err()
+        );
+        assert!(super::validate(
+            1.0,
+            2.0,
+            "Meetup",
+            "https://example.com",
+            Some(datetime!(2025-01-02 0:00 UTC)),
+            Some(datetime!(2025-01-01 0:00 UTC)),
+            None,
+        )
+        .is_err());
+        assert!(super::validate(
+            1.0,
+            2.0,
+            "Meetup",
+            "https://example.com",
+            None,
+            None,
+            Some("every thursday"),
+        )
+        .is_err());
+    }
+
+    #[test]
+    async fn submit_limits_pending_submissions() -> Result<()> {
+        let pool = pool();
+        let user = db::main::user::queries::insert("user", "", &pool).await?;
+        for _ in 0..super::MAX_PENDING_PER_USER {
+            super::submit(
+                &user,
+                1.0,
+                2.0,
+                "Meetup".into(),
+                "https://example.com".into(),
+                None,
+                None,
+                None,
+                &pool,
+            )
+            .await?;
+        }
+        assert!(super::submit(
+            &user,
+            1.0,
+            2.0,
+            "Meetup".into(),
+            "https://example.com".into(),
+            None,
+            None,
+            None,
+            &pool,
+        )
+        .await
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    async fn approve_and_reject() -> Result<()> {
+        let pool = pool();
+        let user = db::main::user::queries::insert("user", "", &pool).await?;
+        let submission = super::submit(
+            &user,
+            1.0,
+            2.0,
+            "Meetup".into(),
+            "https://example.com".into(),
+            None,
+            None,
+            Some("0 0 19 * * Thu".into()),
+            &pool,
+        )
+        .await?;
+        let (approved, event) = super::approve(&submission, &user, None, &pool).await?;
+        assert_eq!(EventSubmissionStatus::Approved, approved.status);
+        assert_eq!(Some(event.id), approved.event_id);
+        assert_eq!("Meetup", event.name);
+        assert_eq!(Some("0 0 19 * * Thu".into()), event.cron_schedule);
+        assert!(super::reject(&approved, &user, "duplicate".into(), &pool)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    async fn approve_stores_timezone() -> Result<()> {
+        let pool = pool();
+        let user = db::main::user::queries::insert("user", "", &pool).await?;
+        let submission = super::submit(
+            &user,
+            52.52,
+            13.40,
+            "Meetup".into(),
+            "https://example.com".into(),
+            None,
+            None,
+            None,
+            &pool,
+        )
+        .await?;
+        let (_, event) = super::approve(&submission, &user, None, &pool).await?;
+        assert_eq!(Some("Europe/Berlin".into()), event.timezone);
+        let event = db::main::event::queries::select_by_id(event.id, &pool).await?;
+        assert_eq!(Some("Europe/Berlin".into()), event.timezone);
+        Ok(())
+    }
+
+    #[test]
+    async fn approve_twice() -> Result<()> {
+        let pool = pool();
+        let user = db::main::user::queries::insert("user", "", &pool).await?;
+        let submission = super::submit(
+            &user,
+            1.0,
+            2.0,
+            "Meetup".into(),
+            "https://example.com".into(),
+            None,
+            None,
+            None,
+            &pool,
+        )
+        .await?;
+        // both reviewers loaded the submission while it was pending
+        let (_, event) = super::approve(&submission, &user, None, &pool).await?;
+        let err = super::approve(&submission, &user, None, &pool)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            format!(
+                "Event submission {} has already been reviewed",
+                submission.id
+            ),
+            err.to_string()
+        );
+        let events = db::main::event::queries::select_all(&pool).await?;
+        assert_eq!(
+            vec![event.id],
+            events.iter().map(|it| it.id).collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,344 @@
+//! Fiat prices are converted to sats at quote time. BTC prices come from a
+//! pluggable provider and are cached, so quotes don't hit the provider on
+//! every request.
+
+use crate::{
+    db::main::conf::schema::{BoostPrice, Conf},
+    Result,
+};
+use futures_util::future::BoxFuture;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+use time::{Duration, OffsetDateTime};
+use tracing::warn;
+
+/// Cached rates younger than this are used without asking the provider
+pub const CACHE_TTL: Duration = Duration::minutes(5);
+
+const SATS_PER_BTC: f64 = 100_000_000.0;
+
+/// A source of BTC prices, such as an exchange or a price aggregator
+pub trait RateProvider: Send + Sync {
+    /// Price of one bitcoin in the given ISO 4217 currency
+    fn btc_price<'a>(&'a self, currency: &'a str) -> BoxFuture<'a, Result<f64>>;
+}
+
+pub fn provider(name: &str) -> Result<Box<dyn RateProvider>> {
+    match name {
+        "coingecko" => Ok(Box::new(CoinGeckoProvider)),
+        "fake" => Ok(Box::new(FakeProvider)),
+        _ => Err(format!("unknown exchange rate provider: {name}").into()),
+    }
+}
+
+pub struct CoinGeckoProvider;
+
+impl RateProvider for CoinGeckoProvider {
+    fn btc_price<'a>(&'a self, currency: &'a str) -> BoxFuture<'a, Result<f64>> {
+        Box::pin(async move {
+            if !currency.chars().all(|it| it.is_ascii_alphabetic()) {
+                return Err(format!("invalid currency: {currency}").into());
+            }
+            let currency = currency.to_lowercase();
+            let res = reqwest::Client::new()
+                .get(format!(
+                    "https://api.coingecko.com/api/v3/simple/price?ids=bitcoin&vs_currencies={currency}"
+                ))
+                .timeout(std::time::Duration::from_secs(10))
+                .send()
+                .await?;
+            if !res.status().is_success() {
+                return Err(format!("CoinGecko returned {}", res.status()).into());
+            }
+            let res: Value = res.json().await?;
+            res["bitcoin"][&currency]
+                .as_f64()
+                .ok_or_else(|| format!("CoinGecko has no {currency} price").into())
+        })
+    }
+}
+
+/// Rates set by `FakeProvider::set`, keyed by currency
+static FAKE_RATES: LazyLock<Mutex<HashMap<String, f64>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// In-memory provider for tests and local development. Currencies without a
+/// rate fail, the same way an unreachable provider would, so outside of tests
+/// it always leads to `exchange_rate_fallback`.
+pub struct FakeProvider;
+
+impl FakeProvider {
+    #[cfg(test)]
+    pub fn set(currency: &str, btc_price: Option<f64>) {
+        let mut rates = FAKE_RATES.lock().unwrap();
+        match btc_price {
+            Some(price) => rates.insert(currency.to_string(), price),
+            None => rates.remove(currency),
+        };
+    }
+}
+
+impl RateProvider for FakeProvider {
+    fn btc_price<'a>(&'a self, currency: &'a str) -> BoxFuture<'a, Result<f64>> {
+        Box::pin(async move {
+            FAKE_RATES
+                .lock()
+                .unwrap()
+                .get(currency)
+                .copied()
+                .ok_or_else(|| format!("no fake rate for {currency}").into())
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rate {
+    pub currency: String,
+    /// Price of one bitcoin
+    pub btc_price: f64,
+    pub fetched_at: OffsetDateTime,
+    /// Set if the provider couldn't be reached and the static rate was used
+    pub fallback: bool,
+}
+
+impl Rate {
+    /// Rounded up, so fiat prices never get cheaper than configured
+    pub fn to_sats(&self, fiat: f64) -> i64 {
+        (fiat / self.btc_price * SATS_PER_BTC).ceil() as i64
+    }
+
+    /// Rounded to cents
+    pub fn to_fiat(&self, sats: i64) -> f64 {
+        (sats as f64 / SATS_PER_BTC * self.btc_price * 100.0).round() / 100.0
+    }
+}
+
+/// Keyed by provider and currency
+static CACHE: LazyLock<Mutex<HashMap<(String, String), Rate>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// When fetching a rate last failed, keyed like `CACHE`. The provider isn't
+/// asked again before `CACHE_TTL` passes, so an outage doesn't slow down every
+/// quote.
+static FAILED_AT: LazyLock<Mutex<HashMap<(String, String), OffsetDateTime>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Returns `None` if fiat pricing is disabled
+pub async fn rate(conf: &Conf) -> Result<Option<Rate>> {
+    if conf.fiat_currency.is_empty() {
+        return Ok(None);
+    }
+    rate_at(conf, OffsetDateTime::now_utc()).await.map(Some)
+}
+
+/// Prefers a recently cached rate, then a fresh one, then a stale one within
+/// `exchange_rate_max_age_secs`, then the static fallback
+async fn rate_at(conf: &Conf, now: OffsetDateTime) -> Result<Rate> {
+    let key = (
+        conf.exchange_rate_provider.clone(),
+        conf.fiat_currency.clone(),
+    );
+    let cached = CACHE.lock().unwrap().get(&key).cloned();
+    if let Some(cached) = &cached {
+        if now - cached.fetched_at < CACHE_TTL {
+            return Ok(cached.clone());
+        }
+    }
+    let failed_recently = FAILED_AT
+        .lock()
+        .unwrap()
+        .get(&key)
+        .is_some_and(|failed_at| now - *failed_at < CACHE_TTL);
+    if failed_recently {
+        return unfetched_rate(conf, cached, now);
+    }
+    let fetched = match provider(&conf.exchange_rate_provider) {
+        Ok(provider) => provider.btc_price(&conf.fiat_currency).await,
+        Err(e) => Err(e),
+    };
+    match fetched {
+        Ok(btc_price) if btc_price > 0.0 => {
+            let rate = Rate {
+                currency: conf.fiat_currency.clone(),
+                btc_price,
+                fetched_at: now,
+                fallback: false,
+            };
+            FAILED_AT.lock().unwrap().remove(&key);
+            CACHE.lock().unwrap().insert(key, rate.clone());
+            Ok(rate)
+        }
+        res => {
+            let err = match res {
+                Err(e) => e.to_string(),
+                Ok(btc_price) => format!("invalid BTC price: {btc_price}"),
+            };
+            warn!(%err, currency = %conf.fiat_currency, "failed to fetch BTC price");
+            FAILED_AT.lock().unwrap().insert(key, now);
+            unfetched_rate(conf, cached, now)
+        }
+    }
+}
+
+/// Used while the provider can't be reached: a stale rate within
+/// `exchange_rate_max_age_secs`, then the static fallback
+fn unfetched_rate(conf: &Conf, cached: Option<Rate>, now: OffsetDateTime) -> Result<Rate> {
+    if let Some(cached) = cached {
+        if now - cached.fetched_at < Duration::seconds(conf.exchange_rate_max_age_secs) {
+            return Ok(cached);
+        }
+    }
+    if conf.exchange_rate_fallback > 0.0 {
+        return Ok(Rate {
+            currency: conf.fiat_currency.clone(),
+            btc_price: conf.exchange_rate_fallback,
+            fetched_at: now,
+            fallback: true,
+        });
+    }
+    Err(format!("no {} exchange rate available", conf.fiat_currency).into())
+}
+
+/// Boost tiers in sats, with fiat tiers converted at the given rate
+pub fn boost_prices(prices: &[BoostPrice], rate: Option<&Rate>) -> Result<Vec<BoostPrice>> {
+    prices
+        .iter()
+        .map(|it| {
+            let sats = match (it.fiat, rate) {
+                (Some(fiat), Some(rate)) => rate.to_sats(fiat),
+                (Some(_), None) => return Err("fiat currency is not set".into()),
+                (None, _) => it.sats,
+            };
+            Ok(BoostPrice {
+                days: it.days,
+                sats,
+                fiat: it.fiat,
+            })
+        })
+        .collect()
+}
+
+pub fn comment_price_sat(conf: &Conf, rate: Option<&Rate>) -> Result<i64> {
+    if conf.paywall_add_element_comment_price_fiat <= 0.0 {
+        return Ok(conf.paywall_add_element_comment_price_sat);
+    }
+    let rate = rate.ok_or("fiat currency is not set")?;
+    Ok(rate.to_sats(conf.paywall_add_element_comment_price_fiat))
+}
+
+/// Current boost tiers in sats, the rate is only needed for fiat tiers
+pub async fn current_boost_prices(conf: &Conf) -> Result<Vec<BoostPrice>> {
+    if conf.boost_element_prices.iter().all(|it| it.fiat.is_none()) {
+        return Ok(conf.boost_element_prices.clone());
+    }
+    boost_prices(&conf.boost_element_prices, rate(conf).await?.as_ref())
+}
+
+/// Current paid comment price in sats
+pub async fn current_comment_price_sat(conf: &Conf) -> Result<i64> {
+    if conf.paywall_add_element_comment_price_fiat <= 0.0 {
+        return Ok(conf.paywall_add_element_comment_price_sat);
+    }
+    comment_price_sat(conf, rate(conf).await?.as_ref())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FakeProvider, Rate};
+    use crate::db::main::conf::schema::{BoostPrice, Conf};
+    use crate::Result;
+    use actix_web::test;
+    use time::{Duration, OffsetDateTime};
+
+    fn conf(currency: &str) -> Conf {
+        Conf {
+            fiat_currency: currency.into(),
+            exchange_rate_provider: "fake".into(),
+            exchange_rate_max_age_secs: 3600,
+            ..Conf::default()
+        }
+    }
+
+    #[test]
+    async fn conversion() {
+        let rate = Rate {
+            currency: "USD".into(),
+            btc_price: 100_000.0,
+            fetched_at: OffsetDateTime::now_utc(),
+            fallback: false,
+        };
+        assert_eq!(5000, rate.to_sats(5.0));
+        assert_eq!(5.0, rate.to_fiat(5000));
+        assert_eq!(3334, rate.to_sats(3.3333));
+        assert_eq!(0.01, rate.to_fiat(7));
+    }
+
+    #[test]
+    async fn disabled_without_currency() -> Result<()> {
+        assert_eq!(None, super::rate(&conf("")).await?);
+        let prices = vec![BoostPrice {
+            days: 30,
+            sats: 5000,
+            fiat: Some(5.0),
+        }];
+        assert!(super::boost_prices(&prices, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    async fn cache_staleness_and_fallback() -> Result<()> {
+        // each test uses its own currency, the cache is shared
+        let mut conf = conf("XTS");
+        let now = OffsetDateTime::now_utc();
+        FakeProvider::set("XTS", Some(50_000.0));
+        assert_eq!(50_000.0, super::rate_at(&conf, now).await?.btc_price);
+
+        FakeProvider::set("XTS", Some(60_000.0));
+        let cached = super::rate_at(&conf, now + Duration::minutes(1)).await?;
+        assert_eq!(50_000.0, cached.btc_price);
+        let refreshed = super::rate_at(&conf, now + Duration::minutes(10)).await?;
+        assert_eq!(60_000.0, refreshed.btc_price);
+
+        FakeProvider::set("XTS", None);
+        let stale = super::rate_at(&conf, now + Duration::minutes(30)).await?;
+        assert_eq!(60_000.0, stale.btc_price);
+        assert!(!stale.fallback);
+        assert!(super::rate_at(&conf, now + Duration::hours(2))
+            .await
+            .is_err());
+
+        conf.exchange_rate_fallback = 40_000.0;
+        let fallback = super::rate_at(&conf, now + Duration::hours(2)).await?;
+        assert_eq!(40_000.0, fallback.btc_price);
+        assert!(fallback.fallback);
+
+        // the provider isn't asked again until the failure expires
+        FakeProvider::set("XTS", Some(70_000.0));
+        let fallback =
+            super::rate_at(&conf, now + Duration::hours(2) + Duration::minutes(1)).await?;
+        assert!(fallback.fallback);
+        let recovered =
+            super::rate_at(&conf, now + Duration::hours(2) + Duration::minutes(10)).await?;
+        assert_eq!(70_000.0, recovered.btc_price);
+
+        let prices = vec![
+            BoostPrice {
+                days: 30,
+                sats: 5000,
+                fiat: Some(2.0),
+            },
+            BoostPrice {
+                days: 90,
+                sats: 12000,
+                fiat: None,
+            },
+        ];
+        let prices = super::boost_prices(&prices, Some(&fallback))?;
+        assert_eq!(5000, prices[0].sats);
+        assert_eq!(12000, prices[1].sats);
+        Ok(())
+    }
+}
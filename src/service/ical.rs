@@ -0,0 +1,359 @@
+use crate::db::main::event::schema::Event;
+use crate::service::event_schedule::{
+    self, format_utc, from_chrono, parse_schedule, to_chrono, to_date, Timezones, EXPANSION_WINDOW,
+    OCCURRENCE_DURATION,
+};
+use crate::Result;
+use chrono::{DateTime, Offset, TimeDelta, TimeZone, Utc};
+use chrono_tz::{OffsetComponents, OffsetName, Tz, TzOffset};
+use deadpool_sqlite::Pool;
+use time::{Duration, OffsetDateTime};
+
+pub const CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+/// Past events are kept in the calendar for a while, so subscribers don't
+/// see them disappear right after they end
+pub const PAST_WINDOW: Duration = Duration::days(30);
+
+const LOCAL_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// Renders non-deleted events as an RFC 5545 calendar. Recurring events are
+/// interpreted in their local timezone, see [event_schedule::Timezones].
+pub async fn calendar(name: &str, events: Vec<Event>, pool: &Pool) -> Result<String> {
+    let mut timezones = Timezones::default();
+    let mut events_with_tz = Vec::with_capacity(events.len());
+    for event in events {
+        if event.deleted_at.is_some() {
+            continue;
+        }
+        let tz = timezones.get(&event, pool).await?;
+        events_with_tz.push((event, tz));
+    }
+    Ok(render(name, &events_with_tz, OffsetDateTime::now_utc()))
+}
+
+pub fn render(name: &str, events: &[(Event, Tz)], now: OffsetDateTime) -> String {
+    let mut vevents = vec![];
+    // Every TZID has to be defined by a VTIMEZONE, along with the earliest
+    // date it's referenced from
+    let mut timezones: Vec<(Tz, OffsetDateTime)> = vec![];
+    for (event, tz) in events {
+        let lines = match &event.cron_schedule {
+            Some(_) => recurring_vevent(event, *tz, now),
+            None => one_off_vevent(event, now),
+        };
+        let Some((lines, dtstart)) = lines else {
+            continue;
+        };
+        if event.cron_schedule.is_some() {
+            match timezones.iter_mut().find(|it| it.0 == *tz) {
+                Some(it) => it.1 = it.1.min(dtstart),
+                None => timezones.push((*tz, dtstart)),
+            }
+        }
+        vevents.extend(lines);
+    }
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".into(),
+        "PRODID:-//BTC Map//Events//EN".into(),
+        "CALSCALE:GREGORIAN".into(),
+        "METHOD:PUBLISH".into(),
+        format!("X-WR-CALNAME:{}", escape(name)),
+    ];
+    for (tz, since) in timezones {
+        lines.extend(vtimezone(
+            tz,
+            to_chrono(since - Duration::days(1)),
+            to_chrono(now + EXPANSION_WINDOW + Duration::days(366)),
+        ));
+    }
+    lines.extend(vevents);
+    lines.push("END:VCALENDAR".into());
+    lines.iter().map(|it| fold(it)).collect()
+}
+
+fn one_off_vevent(event: &Event, now: OffsetDateTime) -> Option<(Vec<String>, OffsetDateTime)> {
+    let starts_at = event.starts_at?;
+    let ends_at = event.ends_at.unwrap_or(starts_at + OCCURRENCE_DURATION);
+    if ends_at < now - PAST_WINDOW {
+        return None;
+    }
+    let mut lines = vevent_header(event);
+    lines.push(format!("DTSTART:{}", format_utc(starts_at)));
+    lines.push(format!("DTEND:{}", format_utc(ends_at)));
+    lines.push("END:VEVENT".into());
+    Some((lines, starts_at))
+}
+
+fn recurring_vevent(
+    event: &Event,
+    tz: Tz,
+    now: OffsetDateTime,
+) -> Option<(Vec<String>, OffsetDateTime)> {
+    if event.ends_at.is_some_and(|it| it < now - PAST_WINDOW) {
+        return None;
+    }
+    // Invalid schedules are rejected on write, but older rows weren't checked
+    let schedule = parse_schedule(event.cron_schedule.as_deref()?).ok()?;
+    let tzid = tz.name();
+    let mut lines = vevent_header(event);
+    let dtstart = match event_schedule::rrule(&schedule, event.ends_at) {
+        Some(rrule) => {
+            let series_start = event.starts_at.unwrap_or(event.created_at);
+            let first = schedule
+                .after(&to_chrono(series_start - Duration::SECOND).with_timezone(&tz))
+                .next()?;
+            lines.push(format!(
+                "DTSTART;TZID={tzid}:{}",
+                first.format(LOCAL_FORMAT)
+            ));
+            lines.push(format!("RRULE:{rrule}"));
+            let mut exdates = vec![];
+            for date in &event.excluded_dates {
+                let Some(midnight) = tz
+                    .with_ymd_and_hms(date.year(), date.month() as u32, date.day() as u32, 0, 0, 0)
+                    .earliest()
+                else {
+                    continue;
+                };
+                for local in schedule.after(&(midnight - TimeDelta::seconds(1))) {
+                    if to_date(&local) != *date {
+                        break;
+                    }
+                    exdates.push(local.format(LOCAL_FORMAT).to_string());
+                }
+            }
+            if !exdates.is_empty() {
+                lines.push(format!("EXDATE;TZID={tzid}:{}", exdates.join(",")));
+            }
+            from_chrono(&first)
+        }
+        None => {
+            // Schedules which don't map to a RRULE are expanded within the
+            // same window as the JSON API
+            let occurrences =
+                event_schedule::occurrences(event, tz, now - PAST_WINDOW, now + EXPANSION_WINDOW)
+                    .ok()?;
+            let first = occurrences.first()?.starts_at;
+            let local = |it: OffsetDateTime| {
+                to_chrono(it)
+                    .with_timezone(&tz)
+                    .format(LOCAL_FORMAT)
+                    .to_string()
+            };
+            lines.push(format!("DTSTART;TZID={tzid}:{}", local(first)));
+            let rdates: Vec<String> = occurrences
+                .iter()
+                .skip(1)
+                .map(|it| local(it.starts_at))
+                .collect();
+            if !rdates.is_empty() {
+                lines.push(format!("RDATE;TZID={tzid}:{}", rdates.join(",")));
+            }
+            first
+        }
+    };
+    lines.push(format!("DURATION:PT{}H", OCCURRENCE_DURATION.whole_hours()));
+    lines.push("END:VEVENT".into());
+    Some((lines, dtstart))
+}
+
+fn vevent_header(event: &Event) -> Vec<String> {
+    vec![
+        "BEGIN:VEVENT".into(),
+        format!("UID:event-{}@btcmap.org", event.id),
+        format!("DTSTAMP:{}", format_utc(event.updated_at)),
+        format!("LAST-MODIFIED:{}", format_utc(event.updated_at)),
+        format!("SUMMARY:{}", escape(&event.name)),
+        format!("URL:{}", event.website),
+        format!("GEO:{};{}", event.lat, event.lon),
+    ]
+}
+
+/// Describes every offset transition within the given range. Transitions
+/// are listed one by one instead of being summarized by RRULEs, as the
+/// rules change over time and the list is short for a few years.
+fn vtimezone(tz: Tz, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<String> {
+    let offset_at = |it: DateTime<Utc>| tz.offset_from_utc_datetime(&it.naive_utc());
+    let mut lines = vec!["BEGIN:VTIMEZONE".into(), format!("TZID:{}", tz.name())];
+    let mut prev = offset_at(from);
+    lines.extend(observance(&prev, &prev, from));
+    let mut day = from;
+    while day < to {
+        let next_day = day + TimeDelta::days(1);
+        let offset = offset_at(next_day);
+        if offset.fix() != prev.fix() {
+            let (mut lo, mut hi) = (day, next_day);
+            while hi - lo > TimeDelta::seconds(1) {
+                let mid = lo + (hi - lo) / 2;
+                if offset_at(mid).fix() == prev.fix() {
+                    lo = mid;
+                } else {
+                    hi = mid;
+                }
+            }
+            lines.extend(observance(&prev, &offset, hi));
+            prev = offset;
+        }
+        day = next_day;
+    }
+    lines.push("END:VTIMEZONE".into());
+    lines
+}
+
+fn observance(from: &TzOffset, to: &TzOffset, onset: DateTime<Utc>) -> Vec<String> {
+    let kind = if to.dst_offset().is_zero() {
+        "STANDARD"
+    } else {
+        "DAYLIGHT"
+    };
+    // Onsets are expressed in the local time which was in effect before them
+    let local_onset = onset.naive_utc() + TimeDelta::seconds(from.fix().local_minus_utc() as i64);
+    let mut lines = vec![
+        format!("BEGIN:{kind}"),
+        format!("DTSTART:{}", local_onset.format(LOCAL_FORMAT)),
+        format!(
+            "TZOFFSETFROM:{}",
+            format_offset(from.fix().local_minus_utc())
+        ),
+        format!("TZOFFSETTO:{}", format_offset(to.fix().local_minus_utc())),
+    ];
+    if let Some(abbreviation) = to.abbreviation() {
+        lines.push(format!("TZNAME:{}", escape(abbreviation)));
+    }
+    lines.push(format!("END:{kind}"));
+    lines
+}
+
+fn format_offset(seconds: i32) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let seconds = seconds.abs();
+    let res = format!("{sign}{:02}{:02}", seconds / 3600, seconds % 3600 / 60);
+    match seconds % 60 {
+        0 => res,
+        rem => format!("{res}{rem:02}"),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Content lines should not be longer than 75 octets, longer lines are split
+/// and continued with a leading space. Every line ends with CRLF.
+fn fold(line: &str) -> String {
+    let mut res = String::with_capacity(line.len() + 2);
+    let mut line_len = 0;
+    for c in line.chars() {
+        if line_len + c.len_utf8() > 75 {
+            res.push_str("\r\n ");
+            line_len = 1;
+        }
+        res.push(c);
+        line_len += c.len_utf8();
+    }
+    res.push_str("\r\n");
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fold, format_offset, render};
+    use crate::db::main::event::schema::Event;
+    use chrono_tz::Tz;
+    use time::macros::{date, datetime};
+
+    fn event(id: i64, cron_schedule: Option<&str>) -> Event {
+        Event {
+            id,
+            area_id: None,
+            lat: 52.52,
+            lon: 13.40,
+            name: "Bitcoin, Beer; Friends".into(),
+            website: "https://example.com".into(),
+            starts_at: None,
+            ends_at: None,
+            cron_schedule: cron_schedule.map(Into::into),
+            excluded_dates: vec![],
+            timezone: None,
+            created_at: datetime!(2025-01-01 0:00 UTC),
+            updated_at: datetime!(2025-01-01 0:00 UTC),
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn render_one_off() {
+        let mut one_off = event(1, None);
+        one_off.starts_at = Some(datetime!(2025-03-01 18:00 UTC));
+        let mut past = event(2, None);
+        past.starts_at = Some(datetime!(2024-01-01 18:00 UTC));
+        let tba = event(3, None);
+        let res = render(
+            "Test",
+            &[(one_off, Tz::UTC), (past, Tz::UTC), (tba, Tz::UTC)],
+            datetime!(2025-02-01 0:00 UTC),
+        );
+        assert!(res.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(res.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(1, res.matches("BEGIN:VEVENT").count());
+        assert!(res.contains("UID:event-1@btcmap.org\r\n"));
+        assert!(res.contains("SUMMARY:Bitcoin\\, Beer\\; Friends\r\n"));
+        assert!(res.contains("DTSTART:20250301T180000Z\r\n"));
+        assert!(res.contains("DTEND:20250301T200000Z\r\n"));
+        assert!(!res.contains("BEGIN:VTIMEZONE"));
+    }
+
+    #[test]
+    fn render_recurring() {
+        let mut recurring = event(1, Some("0 0 19 * * Thu"));
+        recurring.starts_at = Some(datetime!(2025-01-01 0:00 UTC));
+        recurring.excluded_dates = vec![date!(2025 - 03 - 27)];
+        let res = render(
+            "Test",
+            &[(recurring, Tz::Europe__Berlin)],
+            datetime!(2025-02-01 0:00 UTC),
+        );
+        assert!(res.contains("TZID:Europe/Berlin\r\n"));
+        assert!(res.contains("BEGIN:DAYLIGHT\r\nDTSTART:20250330T020000\r\nTZOFFSETFROM:+0100\r\nTZOFFSETTO:+0200\r\nTZNAME:CEST\r\nEND:DAYLIGHT\r\n"));
+        assert!(res.contains("DTSTART;TZID=Europe/Berlin:20250102T190000\r\n"));
+        assert!(res.contains("RRULE:FREQ=WEEKLY;BYDAY=TH;BYHOUR=19;BYMINUTE=0;BYSECOND=0\r\n"));
+        assert!(res.contains("EXDATE;TZID=Europe/Berlin:20250327T190000\r\n"));
+        assert!(res.contains("DURATION:PT2H\r\n"));
+    }
+
+    #[test]
+    fn render_recurring_without_rrule() {
+        let recurring = event(1, Some("0 0 12 1 * * 2025"));
+        let res = render(
+            "Test",
+            &[(recurring, Tz::UTC)],
+            datetime!(2025-02-15 0:00 UTC),
+        );
+        assert!(!res.contains("RRULE"));
+        assert!(res.contains("DTSTART;TZID=UTC:20250201T120000\r\n"));
+        assert!(res.contains("RDATE;TZID=UTC:20250301T120000,20250401T120000,20250501T120000\r\n"));
+    }
+
+    #[test]
+    fn format_offset_with_sign() {
+        assert_eq!("+0530", format_offset(19800));
+        assert_eq!("-0300", format_offset(-10800));
+        assert_eq!("+0000", format_offset(0));
+    }
+
+    #[test]
+    fn fold_long_lines() {
+        let line = "X".repeat(100);
+        let folded = fold(&line);
+        assert_eq!(
+            format!("{}\r\n {}\r\n", "X".repeat(75), "X".repeat(25)),
+            folded
+        );
+    }
+}
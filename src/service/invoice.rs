@@ -1,139 +1,185 @@
 use crate::{
     db::{
         self,
-        main::invoice::schema::{Invoice, InvoiceStatus},
+        main::invoice::schema::{Invoice, InvoiceStatus, InvoicedService},
     },
     service::{
-        self,
-        matrix::{self, ROOM_PLACE_BOOSTS, ROOM_PLACE_COMMENTS},
+        self, invoice_backend,
+        matrix::{self, ROOM_PLACE_BOOSTS},
     },
     Result,
 };
-use base64::Engine;
 use deadpool_sqlite::Pool;
 use matrix_sdk::Client;
-use serde::Deserialize;
-use serde_json::{json, Value};
+use std::sync::OnceLock;
 use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+
+/// Expired invoices can be renewed for this long, after that their pending
+/// actions are cleaned up
+pub const RENEWAL_WINDOW: Duration = Duration::days(1);
+
+/// Expired invoices which can't be canceled are given this long for a payment
+/// in flight to settle, then they are marked as expired anyway
+pub const EXPIRY_GRACE_PERIOD: Duration = Duration::minutes(10);
+
+/// Unpaid invoices which expired longer ago than this aren't checked with
+/// their backend anymore, they are just marked as expired
+pub const SYNC_WINDOW: Duration = Duration::hours(1);
+
+/// Paid invoices which still aren't fulfilled after this long are retried
+pub const FULFILLMENT_RETRY_DELAY: Duration = Duration::minutes(5);
+
+/// Waiters lagging behind by more than this many status changes re-read their
+/// invoice instead of missing an update
+const STATUS_CHANGES_CAPACITY: usize = 256;
+
+/// Backends without a push stream only report payments when asked, so waiters
+/// also check their invoice this often
+const WAIT_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
 
-#[derive(Deserialize)]
-pub struct CreateLNbitsInvoiceResponse {
-    pub payment_hash: String,
-    pub bolt11: String,
+/// Carries the UUIDs of invoices which just left the unpaid status
+fn status_changes() -> &'static broadcast::Sender<String> {
+    static STATUS_CHANGES: OnceLock<broadcast::Sender<String>> = OnceLock::new();
+    STATUS_CHANGES.get_or_init(|| broadcast::channel(STATUS_CHANGES_CAPACITY).0)
 }
 
-#[derive(Deserialize)]
-pub struct CreateLndInvoiceResponse {
-    pub r_hash: String,
-    pub payment_request: String,
+fn notify_status_changed(invoice: &Invoice) {
+    // an error only means that nobody is waiting
+    let _ = status_changes().send(invoice.uuid.clone());
 }
 
-pub async fn create(
-    source: &str,
-    description: String,
-    amount_sats: i64,
+/// Returns as soon as the invoice is no longer unpaid, or once the timeout
+/// passes. Either way, the latest version of the invoice is returned.
+pub async fn wait(
+    uuid: &str,
+    timeout: std::time::Duration,
     pool: &Pool,
+    matrix_client: &Option<Client>,
 ) -> Result<Invoice> {
-    let conf = db::main::conf::queries::select(pool).await?;
-    let client = reqwest::Client::new();
-
-    if source == "lnbits" {
-        if conf.lnbits_invoice_key.is_empty() {
-            Err("lnbits invoice key is not set")?
-        }
-        let args = json!({"out": false, "amount": amount_sats, "memo": description});
-        let lnbits_response = client
-            .post("https://core.btcmap.org/api/v1/payments")
-            .header("X-Api-Key", &conf.lnbits_invoice_key)
-            .json(&args)
-            .send()
-            .await?;
-        if !lnbits_response.status().is_success() {
-            return Err("Failed to generate LNBITS invoice".into());
-        }
-        let lnbits_response: CreateLNbitsInvoiceResponse = lnbits_response.json().await?;
-        let invoice = db::main::invoice::queries::insert(
-            source,
-            description,
-            amount_sats,
-            lnbits_response.payment_hash,
-            lnbits_response.bolt11,
-            InvoiceStatus::Unpaid,
-            pool,
-        )
-        .await?;
-        Ok(invoice)
-    } else if source == "lnd" {
-        if conf.lnd_invoices_macaroon.is_empty() {
-            Err("lnd invoices macaroon is not set")?
+    // subscribe first, so a change that happens while we read is not missed
+    let mut status_changes = status_changes().subscribe();
+    let invoice = db::main::invoice::queries::select_by_uuid(uuid, pool).await?;
+    if invoice.status != InvoiceStatus::Unpaid {
+        return Ok(invoice);
+    }
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut next_check = tokio::time::Instant::now();
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline) => break,
+            _ = tokio::time::sleep_until(next_check) => {
+                sync_unpaid_invoice(&invoice, pool, matrix_client).await?;
+                next_check += WAIT_CHECK_INTERVAL;
+            }
+            res = status_changes.recv() => match res {
+                Ok(changed) if changed != uuid => continue,
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
         }
-        let args = json!({"value": amount_sats, "memo": description, "expiry": 3600});
-        let lnd_response = client
-            .post("https://lnd.btcmap.org/v1/invoices")
-            .header("Grpc-Metadata-macaroon", &conf.lnd_invoices_macaroon)
-            .json(&args)
-            .send()
-            .await?;
-        if !lnd_response.status().is_success() {
-            return Err("Failed to generate lnd invoice".into());
+        let invoice = db::main::invoice::queries::select_by_uuid(uuid, pool).await?;
+        if invoice.status != InvoiceStatus::Unpaid {
+            return Ok(invoice);
         }
-        let lnd_response: CreateLndInvoiceResponse = lnd_response.json().await?;
-        let payment_hash_bytes = base64::engine::general_purpose::STANDARD
-            .decode(lnd_response.r_hash)
-            .unwrap();
-        let payment_hash_hex: String = payment_hash_bytes
-            .iter()
-            .map(|byte| format!("{:02x}", byte))
-            .collect();
-        let invoice = db::main::invoice::queries::insert(
-            source,
-            description,
-            amount_sats,
-            payment_hash_hex,
-            lnd_response.payment_request,
-            InvoiceStatus::Unpaid,
-            pool,
-        )
-        .await?;
-        Ok(invoice)
-    } else {
-        Err("unknown source")?
     }
+    db::main::invoice::queries::select_by_uuid(uuid, pool).await
 }
 
-#[derive(Deserialize)]
-pub struct CheckInvoiceResponse {
-    pub paid: bool,
+pub async fn create(
+    source: &str,
+    description: String,
+    amount_sats: i64,
+    pool: &Pool,
+) -> Result<Invoice> {
+    create_with_description_hash(source, description, None, amount_sats, pool).await
 }
 
-#[derive(Deserialize)]
-pub struct CheckLndInvoiceResponse {
-    pub state: String,
+/// The description is still stored, since it tells which action the invoice
+/// pays for, but only its hash ends up in the invoice
+pub async fn create_with_description_hash(
+    source: &str,
+    description: String,
+    description_hash: Option<[u8; 32]>,
+    amount_sats: i64,
+    pool: &Pool,
+) -> Result<Invoice> {
+    let conf = db::main::conf::queries::select(pool).await?;
+    let new_invoice = invoice_backend::backend(source, &conf)?
+        .create(&description, description_hash, amount_sats)
+        .await?;
+    let expires_at = OffsetDateTime::now_utc()
+        .saturating_add(Duration::seconds(invoice_backend::INVOICE_EXPIRY_SECS))
+        .format(&Rfc3339)?;
+    db::main::invoice::queries::insert(
+        source,
+        description,
+        amount_sats,
+        new_invoice.payment_hash,
+        new_invoice.payment_request,
+        InvoiceStatus::Unpaid,
+        Some(expires_at),
+        pool,
+    )
+    .await
 }
 
 pub async fn sync_unpaid_invoices(pool: &Pool, matrix_client: &Option<Client>) -> Result<i64> {
-    let conf = db::main::conf::queries::select(pool).await?;
-    if conf.lnbits_invoice_key.is_empty() {
-        Err("lnbits invoice key is not set")?
-    }
     let unpaid_invoices =
         db::main::invoice::queries::select_by_status(InvoiceStatus::Unpaid, pool).await?;
     let now = OffsetDateTime::now_utc();
-    let hour_ago = now.saturating_sub(Duration::hours(1)).format(&Rfc3339)?;
-    let unpaid_invoices: Vec<Invoice> = unpaid_invoices
-        .into_iter()
-        .filter(|it| it.created_at > hour_ago)
-        .collect();
     let mut affected_invoices = vec![];
     for invoice in unpaid_invoices {
-        if sync_unpaid_invoice(&invoice, pool, matrix_client).await? {
-            affected_invoices.push(invoice);
+        if effective_expires_at(&invoice).is_some_and(|it| it.saturating_add(SYNC_WINDOW) <= now) {
+            if let Err(err) = expire(&invoice, pool).await {
+                warn!(%err, invoice.id, "Failed to expire stale invoice");
+            }
+            continue;
         }
+        match sync_unpaid_invoice(&invoice, pool, matrix_client).await {
+            Ok(true) => affected_invoices.push(invoice),
+            Ok(false) => {}
+            Err(err) => warn!(%err, invoice.id, "Failed to sync unpaid invoice"),
+        }
+    }
+    let fulfilled = retry_unfulfilled_invoices(pool, matrix_client).await?;
+    if fulfilled > 0 {
+        info!(fulfilled, "fulfilled previously paid invoices");
+    }
+    let cleaned_up = cleanup_expired_invoices(pool).await?;
+    if cleaned_up > 0 {
+        info!(cleaned_up, "cleaned up expired invoices");
     }
     Ok(affected_invoices.len() as i64)
 }
 
-// Returns true if invoice was unpaid and became paid
+/// Invoices issued before expiry dates were stored expire
+/// `INVOICE_EXPIRY_SECS` after they were created
+fn effective_expires_at(invoice: &Invoice) -> Option<OffsetDateTime> {
+    invoice.expires_at().or_else(|| {
+        OffsetDateTime::parse(&invoice.created_at, &Rfc3339)
+            .ok()
+            .map(|it| it.saturating_add(Duration::seconds(invoice_backend::INVOICE_EXPIRY_SECS)))
+    })
+}
+
+async fn expire(invoice: &Invoice, pool: &Pool) -> Result<()> {
+    if let Some(invoice) = db::main::invoice::queries::transition_status(
+        invoice.id,
+        InvoiceStatus::Unpaid,
+        InvoiceStatus::Expired,
+        pool,
+    )
+    .await?
+    {
+        notify_status_changed(&invoice);
+    }
+    Ok(())
+}
+
+// Returns true if invoice was unpaid and became paid. Unpaid invoices past
+// their expiry date get one last check before being marked as expired.
 pub async fn sync_unpaid_invoice(
     invoice: &Invoice,
     pool: &Pool,
@@ -143,46 +189,178 @@ pub async fn sync_unpaid_invoice(
         return Ok(false);
     }
     let conf = db::main::conf::queries::select(pool).await?;
-    let client = reqwest::Client::new();
-    if invoice.source == "lnbits" {
-        let url = format!(
-            "https://core.btcmap.org/api/v1/payments/{}",
-            invoice.payment_hash,
-        );
-        let lnbits_response = client
-            .get(url)
-            .header("X-Api-Key", &conf.lnbits_invoice_key)
-            .send()
-            .await?;
-        if !lnbits_response.status().is_success() {
-            return Err("Failed to check LNBITS invoice".into());
+    let backend = invoice_backend::backend(&invoice.source, &conf)?;
+    if backend.check(&invoice.payment_hash).await? {
+        return mark_paid(invoice, pool, matrix_client).await;
+    }
+    let now = OffsetDateTime::now_utc();
+    if invoice.is_past_expiry(now) {
+        // a payment may still be in flight, so an invoice which can't be
+        // canceled is left for the next sync until the grace period is over
+        match backend.cancel(&invoice.payment_hash).await {
+            Ok(()) => expire(invoice, pool).await?,
+            Err(err)
+                if invoice
+                    .expires_at()
+                    .is_some_and(|it| it.saturating_add(EXPIRY_GRACE_PERIOD) <= now) =>
+            {
+                warn!(%err, invoice.id, "Failed to cancel expired invoice, expiring it anyway");
+                expire(invoice, pool).await?;
+            }
+            Err(err) => warn!(%err, invoice.id, "Failed to cancel expired invoice"),
         }
-        let lnbits_response: CheckInvoiceResponse = lnbits_response.json().await?;
-        if lnbits_response.paid {
-            db::main::invoice::queries::set_status(invoice.id, InvoiceStatus::Paid, pool).await?;
-            on_invoice_paid(invoice, pool, matrix_client).await?;
-            return Ok(true);
+    }
+    Ok(false)
+}
+
+pub fn is_renewable(invoice: &Invoice, now: OffsetDateTime) -> bool {
+    invoice.status == InvoiceStatus::Expired
+        && invoice.deleted_at.is_none()
+        && !matches!(invoice.service(), InvoicedService::Unknown(_))
+        && invoice
+            .expires_at()
+            .is_some_and(|it| it.saturating_add(RENEWAL_WINDOW) > now)
+}
+
+/// Issues a fresh invoice for the same pending action, at the current price.
+/// The expired invoice is retired, so it can only be renewed once. The new
+/// invoice comes from the same backend the payer was already using.
+pub async fn renew(invoice: &Invoice, pool: &Pool) -> Result<Invoice> {
+    let now = OffsetDateTime::now_utc();
+    if !is_renewable(invoice, now) {
+        return Err("only recently expired boost and comment invoices can be renewed".into());
+    }
+    let conf = db::main::conf::queries::select(pool).await?;
+    let amount_sats = match invoice.service() {
+        InvoicedService::Boost {
+            element_id,
+            duration_days,
+        } => {
+            db::main::element::queries::select_by_id(element_id, pool).await?;
+            let prices = service::exchange_rate::current_boost_prices(&conf).await?;
+            service::boost::price(duration_days, &prices)?
         }
-    } else if invoice.source == "lnd" {
-        let url = format!("https://lnd.btcmap.org/v1/invoice/{}", invoice.payment_hash,);
-        let lnd_response = client
-            .get(url)
-            .header("Grpc-Metadata-macaroon", &conf.lnd_invoices_macaroon)
-            .send()
-            .await?;
-        if !lnd_response.status().is_success() {
-            return Err("Failed to check lnd invoice".into());
+        InvoicedService::Comment { comment_id } => {
+            let comment =
+                db::main::element_comment::queries::select_by_id(comment_id, pool).await?;
+            if comment.deleted_at.is_none() {
+                return Err("comment is already published".into());
+            }
+            service::exchange_rate::current_comment_price_sat(&conf).await?
         }
-        let lnd_response: CheckLndInvoiceResponse = lnd_response.json().await?;
-        if lnd_response.state == "SETTLED" {
-            db::main::invoice::queries::set_status(invoice.id, InvoiceStatus::Paid, pool).await?;
-            on_invoice_paid(invoice, pool, matrix_client).await?;
-            return Ok(true);
+        InvoicedService::Unknown(_) => return Err("this invoice can't be renewed".into()),
+    };
+    if !db::main::invoice::queries::soft_delete(invoice.id, now.format(&Rfc3339)?, pool).await? {
+        return Err("invoice has already been renewed".into());
+    }
+    let renewed = create(
+        &invoice.source,
+        invoice.description.clone(),
+        amount_sats,
+        pool,
+    )
+    .await?;
+    if let Some(boost) =
+        db::main::element_boost::queries::select_by_invoice_id(invoice.id, pool).await?
+    {
+        db::main::element_boost::queries::set_invoice_id(boost.id, renewed.id, pool).await?;
+    }
+    if let InvoicedService::Comment { comment_id } = invoice.service() {
+        db::main::element_comment::queries::set_invoice_id(comment_id, renewed.id, pool).await?;
+    }
+    Ok(renewed)
+}
+
+/// Retires invoices which weren't renewed in time and removes the comments
+/// that were waiting for them
+pub async fn cleanup_expired_invoices(pool: &Pool) -> Result<i64> {
+    let now = OffsetDateTime::now_utc();
+    let expired_invoices =
+        db::main::invoice::queries::select_by_status(InvoiceStatus::Expired, pool).await?;
+    let mut cleaned_up = 0;
+    for invoice in expired_invoices {
+        if invoice.deleted_at.is_some() || is_renewable(&invoice, now) {
+            continue;
+        }
+        match cleanup_expired_invoice(&invoice, now, pool).await {
+            Ok(true) => cleaned_up += 1,
+            Ok(false) => {}
+            Err(err) => warn!(%err, invoice.id, "Failed to clean up expired invoice"),
         }
-    } else {
-        return Err("unknown invoice source".into());
     }
-    Ok(false)
+    Ok(cleaned_up)
+}
+
+async fn cleanup_expired_invoice(
+    invoice: &Invoice,
+    now: OffsetDateTime,
+    pool: &Pool,
+) -> Result<bool> {
+    if !db::main::invoice::queries::soft_delete(invoice.id, now.format(&Rfc3339)?, pool).await? {
+        return Ok(false);
+    }
+    if let InvoicedService::Comment { comment_id } = invoice.service() {
+        db::main::element_comment::queries::delete_unpublished(comment_id, pool).await?;
+    }
+    Ok(true)
+}
+
+/// Polling and the LND invoice stream can both see the same payment, only the
+/// first one to flip the status gets to run the paid actions. Waiters are
+/// woken up once those actions are done, so the boost or comment is live by
+/// the time they hear about it.
+pub async fn mark_paid(
+    invoice: &Invoice,
+    pool: &Pool,
+    matrix_client: &Option<Client>,
+) -> Result<bool> {
+    let Some(invoice) = db::main::invoice::queries::transition_status(
+        invoice.id,
+        InvoiceStatus::Unpaid,
+        InvoiceStatus::Paid,
+        pool,
+    )
+    .await?
+    else {
+        return Ok(false);
+    };
+    let res = fulfill(&invoice, pool, matrix_client).await;
+    notify_status_changed(&invoice);
+    res.map(|_| true)
+}
+
+/// Runs the paid actions and records that they are done. Invoices which got
+/// paid but weren't fulfilled, such as when the process died in between, are
+/// picked up by `retry_unfulfilled_invoices`.
+async fn fulfill(invoice: &Invoice, pool: &Pool, matrix_client: &Option<Client>) -> Result<()> {
+    on_invoice_paid(invoice, pool, matrix_client).await?;
+    db::main::invoice::queries::set_fulfilled_at(
+        invoice.id,
+        OffsetDateTime::now_utc().format(&Rfc3339)?,
+        pool,
+    )
+    .await
+}
+
+/// Re-runs the paid actions of invoices which were paid at least
+/// `FULFILLMENT_RETRY_DELAY` ago and still aren't fulfilled. The delay keeps it
+/// from racing with `mark_paid`, which fulfills the invoice right away.
+pub async fn retry_unfulfilled_invoices(
+    pool: &Pool,
+    matrix_client: &Option<Client>,
+) -> Result<i64> {
+    let paid_before = OffsetDateTime::now_utc()
+        .saturating_sub(FULFILLMENT_RETRY_DELAY)
+        .format(&Rfc3339)?;
+    let invoices = db::main::invoice::queries::select_unfulfilled(paid_before, pool).await?;
+    let mut fulfilled = 0;
+    for invoice in invoices {
+        match fulfill(&invoice, pool, matrix_client).await {
+            Ok(()) => fulfilled += 1,
+            Err(err) => warn!(%err, invoice.id, "Failed to fulfill paid invoice"),
+        }
+    }
+    Ok(fulfilled)
 }
 
 pub async fn on_invoice_paid(
@@ -199,16 +377,7 @@ pub async fn on_invoice_paid(
         }
         let id = id.parse::<i64>().unwrap_or(0);
         if *action == "publish" {
-            let comment = db::main::element_comment::queries::select_by_id(id, pool).await?;
-            let element =
-                db::main::element::queries::select_by_id(comment.element_id, pool).await?;
-            db::main::element_comment::queries::set_deleted_at(id, None, pool).await?;
-            service::comment::refresh_comment_count_tag(&element, pool).await?;
-            let message = format!(
-                "{} https://btcmap.org/merchant/{}",
-                comment.comment, element.id,
-            );
-            service::matrix::send_message(matrix_client, ROOM_PLACE_COMMENTS, &message);
+            service::comment::publish(id, pool, matrix_client).await?;
         }
     }
 
@@ -226,36 +395,22 @@ pub async fn on_invoice_paid(
         else {
             return Ok(());
         };
-        let boost_expires = if element.tags.contains_key("boost:expires") {
-            let now = OffsetDateTime::now_utc();
-            let now_str = now.format(&Rfc3339)?;
-
-            let current_boost_expires = element.tags["boost:expires"].as_str().unwrap_or(&now_str);
-            let mut current_boost_expires =
-                OffsetDateTime::parse(current_boost_expires, &Rfc3339).unwrap_or(now);
-
-            if current_boost_expires < now {
-                current_boost_expires = now
-            }
-
-            current_boost_expires + Duration::days(days)
-        } else {
-            OffsetDateTime::now_utc().saturating_add(Duration::days(days))
-        };
-        db::main::element::queries::set_tag(
-            element_id,
-            "boost:expires",
-            &Value::String(boost_expires.format(&Rfc3339)?),
-            pool,
-        )
-        .await?;
+        let boost = service::boost::on_paid(invoice, element.id, days, pool).await?;
         let message = format!(
-            "Boosted element since invoice has been paid (id = {}, name = {}, days = {})",
+            "Boosted element since invoice has been paid (id = {}, name = {}, days = {}, area_id = {:?}, category = {:?})",
             element_id,
             element.name(Some("en")),
             days,
+            boost.area_id,
+            boost.category,
         );
         matrix::send_message(matrix_client, ROOM_PLACE_BOOSTS, &message);
+        if let Err(err) = service::nostr::publish_boost(&element, days, pool).await {
+            warn!(%err, element.id, "Failed to publish boost to nostr");
+        }
+        if let Err(err) = service::zap::publish_receipt(invoice, pool).await {
+            warn!(%err, invoice.id, "Failed to publish zap receipt");
+        }
     }
 
     Ok(())
@@ -264,11 +419,15 @@ pub async fn on_invoice_paid(
 #[cfg(test)]
 mod test {
     use crate::{
-        db::{self, main::test::pool},
-        service::overpass::OverpassElement,
+        db::{self, main::invoice::schema::InvoiceStatus, main::test::pool},
+        service::{
+            invoice_backend::{self, FakeBackend},
+            overpass::OverpassElement,
+        },
         Result,
     };
     use actix_web::test;
+    use deadpool_sqlite::Pool;
     use serde_json::Value;
     use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
 
@@ -283,6 +442,7 @@ mod test {
             "",
             "",
             db::main::invoice::schema::InvoiceStatus::Unpaid,
+            None,
             &pool,
         )
         .await?;
@@ -315,6 +475,7 @@ mod test {
             "",
             "",
             db::main::invoice::schema::InvoiceStatus::Unpaid,
+            None,
             &pool,
         )
         .await?;
@@ -326,4 +487,259 @@ mod test {
         assert_eq!(9, (boost_expires - OffsetDateTime::now_utc()).whole_days());
         Ok(())
     }
+
+    #[test]
+    async fn fake_backend_boost_flow() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let invoice = super::create(
+            "fake",
+            format!("element_boost:{}:30", element.id),
+            100,
+            &pool,
+        )
+        .await?;
+        assert!(!super::sync_unpaid_invoice(&invoice, &pool, &None).await?);
+        assert!(FakeBackend::pay(&invoice.payment_hash));
+        assert!(super::sync_unpaid_invoice(&invoice, &pool, &None).await?);
+        let invoice = db::main::invoice::queries::select_by_uuid(invoice.uuid, &pool).await?;
+        assert_eq!(InvoiceStatus::Paid, invoice.status);
+        assert!(invoice.fulfilled_at.is_some());
+        let element = db::main::element::queries::select_by_id(element.id, &pool).await?;
+        assert!(element.boosted_until().is_some());
+        Ok(())
+    }
+
+    #[test]
+    async fn retry_unfulfilled_invoices() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        // paid, but the process died before the boost was applied
+        let invoice = db::main::invoice::queries::insert(
+            "fake",
+            format!("element_boost:{}:30", element.id),
+            100,
+            "",
+            "",
+            InvoiceStatus::Paid,
+            None,
+            &pool,
+        )
+        .await?;
+        assert_eq!(0, super::retry_unfulfilled_invoices(&pool, &None).await?);
+        let paid_at = (OffsetDateTime::now_utc() - Duration::minutes(10)).format(&Rfc3339)?;
+        pool.get()
+            .await?
+            .interact(move |conn| {
+                conn.execute(
+                    "UPDATE invoice SET updated_at = ?2 WHERE id = ?1",
+                    rusqlite::params![invoice.id, paid_at],
+                )
+            })
+            .await??;
+
+        assert_eq!(1, super::retry_unfulfilled_invoices(&pool, &None).await?);
+        assert_eq!(0, super::retry_unfulfilled_invoices(&pool, &None).await?);
+        let element = db::main::element::queries::select_by_id(element.id, &pool).await?;
+        assert!(element.boosted_until().is_some());
+        Ok(())
+    }
+
+    #[test]
+    async fn fake_backend_comment_flow() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let comment =
+            db::main::element_comment::queries::insert(element.id, "Paid in sats", &pool).await?;
+        db::main::element_comment::queries::set_deleted_at(
+            comment.id,
+            Some(OffsetDateTime::now_utc()),
+            &pool,
+        )
+        .await?;
+        let invoice = super::create(
+            "fake",
+            format!("element_comment:{}:publish", comment.id),
+            500,
+            &pool,
+        )
+        .await?;
+        assert!(FakeBackend::pay(&invoice.payment_hash));
+        assert!(super::sync_unpaid_invoice(&invoice, &pool, &None).await?);
+        let comment = db::main::element_comment::queries::select_by_id(comment.id, &pool).await?;
+        assert!(comment.deleted_at.is_none());
+        Ok(())
+    }
+
+    /// Fake invoice which expired the given time ago
+    async fn expired_invoice(
+        description: String,
+        ago: Duration,
+        pool: &Pool,
+    ) -> Result<db::main::invoice::schema::Invoice> {
+        let conf = db::main::conf::queries::select(pool).await?;
+        let new_invoice = invoice_backend::backend("fake", &conf)?
+            .create(&description, None, 500)
+            .await?;
+        db::main::invoice::queries::insert(
+            "fake",
+            description,
+            500,
+            new_invoice.payment_hash,
+            new_invoice.payment_request,
+            InvoiceStatus::Unpaid,
+            Some(
+                OffsetDateTime::now_utc()
+                    .saturating_sub(ago)
+                    .format(&Rfc3339)?,
+            ),
+            pool,
+        )
+        .await
+    }
+
+    #[test]
+    async fn expired_comment_invoice_can_be_renewed_once() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let comment =
+            db::main::element_comment::queries::insert(element.id, "Paid in sats", &pool).await?;
+        db::main::element_comment::queries::set_deleted_at(
+            comment.id,
+            Some(OffsetDateTime::now_utc()),
+            &pool,
+        )
+        .await?;
+        let invoice = expired_invoice(
+            format!("element_comment:{}:publish", comment.id),
+            Duration::minutes(1),
+            &pool,
+        )
+        .await?;
+        assert!(!super::sync_unpaid_invoice(&invoice, &pool, &None).await?);
+        let invoice = db::main::invoice::queries::select_by_uuid(invoice.uuid, &pool).await?;
+        assert_eq!(InvoiceStatus::Expired, invoice.status);
+        assert!(!FakeBackend::pay(&invoice.payment_hash));
+        assert!(super::is_renewable(&invoice, OffsetDateTime::now_utc()));
+
+        let renewed = super::renew(&invoice, &pool).await?;
+        assert_eq!(InvoiceStatus::Unpaid, renewed.status);
+        assert_eq!(invoice.description, renewed.description);
+        assert!(renewed.expires_at().is_some());
+        assert!(super::renew(&invoice, &pool).await.is_err());
+        assert!(FakeBackend::pay(&renewed.payment_hash));
+        assert!(super::sync_unpaid_invoice(&renewed, &pool, &None).await?);
+        let comment = db::main::element_comment::queries::select_by_id(comment.id, &pool).await?;
+        assert!(comment.deleted_at.is_none());
+        assert_eq!(Some(renewed.id), comment.invoice_id);
+        Ok(())
+    }
+
+    #[test]
+    async fn expired_invoice_which_cant_be_canceled() -> Result<()> {
+        let pool = pool();
+        let conf = db::main::conf::queries::select(&pool).await?;
+        let backend = invoice_backend::backend("fake", &conf)?;
+        for (ago, status) in [
+            (Duration::minutes(1), InvoiceStatus::Unpaid),
+            (
+                super::EXPIRY_GRACE_PERIOD + Duration::minutes(1),
+                InvoiceStatus::Expired,
+            ),
+        ] {
+            let invoice = expired_invoice("element_boost:1:10".into(), ago, &pool).await?;
+            // a second cancel fails
+            backend.cancel(&invoice.payment_hash).await?;
+            assert!(!super::sync_unpaid_invoice(&invoice, &pool, &None).await?);
+            let invoice = db::main::invoice::queries::select_by_uuid(invoice.uuid, &pool).await?;
+            assert_eq!(status, invoice.status);
+        }
+        Ok(())
+    }
+
+    #[test]
+    async fn sync_unpaid_invoices_skips_failures() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let insert = |source: &'static str, expires_at: OffsetDateTime| {
+            let pool = pool.clone();
+            async move {
+                db::main::invoice::queries::insert(
+                    source,
+                    "element_boost:1:10",
+                    0,
+                    "",
+                    "",
+                    InvoiceStatus::Unpaid,
+                    Some(expires_at.format(&Rfc3339)?),
+                    &pool,
+                )
+                .await
+            }
+        };
+        let now = OffsetDateTime::now_utc();
+        // no such backend, so every check fails
+        let broken = insert("unknown", now + Duration::minutes(30)).await?;
+        let stale = insert("unknown", now - super::SYNC_WINDOW - Duration::minutes(1)).await?;
+        let paid = super::create(
+            "fake",
+            format!("element_boost:{}:30", element.id),
+            100,
+            &pool,
+        )
+        .await?;
+        assert!(FakeBackend::pay(&paid.payment_hash));
+
+        assert_eq!(1, super::sync_unpaid_invoices(&pool, &None).await?);
+        let status = |uuid: String| {
+            let pool = pool.clone();
+            async move {
+                db::main::invoice::queries::select_by_uuid(uuid, &pool)
+                    .await
+                    .map(|it| it.status)
+            }
+        };
+        assert_eq!(InvoiceStatus::Unpaid, status(broken.uuid).await?);
+        assert_eq!(InvoiceStatus::Expired, status(stale.uuid).await?);
+        assert_eq!(InvoiceStatus::Paid, status(paid.uuid).await?);
+        Ok(())
+    }
+
+    #[test]
+    async fn cleanup_expired_invoices() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let mut comment_ids = vec![];
+        for ago in [Duration::hours(2), Duration::days(2)] {
+            let comment =
+                db::main::element_comment::queries::insert(element.id, "Pending", &pool).await?;
+            db::main::element_comment::queries::set_deleted_at(
+                comment.id,
+                Some(OffsetDateTime::now_utc()),
+                &pool,
+            )
+            .await?;
+            let invoice = expired_invoice(
+                format!("element_comment:{}:publish", comment.id),
+                ago,
+                &pool,
+            )
+            .await?;
+            super::sync_unpaid_invoice(&invoice, &pool, &None).await?;
+            comment_ids.push(comment.id);
+        }
+        assert_eq!(1, super::cleanup_expired_invoices(&pool).await?);
+        assert_eq!(0, super::cleanup_expired_invoices(&pool).await?);
+        assert!(
+            db::main::element_comment::queries::select_by_id(comment_ids[0], &pool)
+                .await
+                .is_ok()
+        );
+        assert!(
+            db::main::element_comment::queries::select_by_id(comment_ids[1], &pool)
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,354 @@
+use crate::{db::main::conf::schema::Conf, Result};
+use base64::Engine;
+use futures_util::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+/// Invoices are created with this expiry, in seconds
+pub const INVOICE_EXPIRY_SECS: i64 = 3600;
+
+pub struct NewInvoice {
+    /// Hex encoded
+    pub payment_hash: String,
+    pub payment_request: String,
+}
+
+/// A Lightning node or wallet service which can issue and track invoices.
+/// The invoice `source` column stores the backend name, so each invoice is
+/// always checked against the backend which issued it.
+pub trait InvoiceBackend: Send + Sync {
+    /// LNURL-pay invoices commit to the SHA-256 of their metadata instead of
+    /// carrying a description, see LUD-06
+    fn create<'a>(
+        &'a self,
+        description: &'a str,
+        description_hash: Option<[u8; 32]>,
+        amount_sats: i64,
+    ) -> BoxFuture<'a, Result<NewInvoice>>;
+    /// Returns true if the invoice has been paid
+    fn check<'a>(&'a self, payment_hash: &'a str) -> BoxFuture<'a, Result<bool>>;
+    fn cancel<'a>(&'a self, payment_hash: &'a str) -> BoxFuture<'a, Result<()>>;
+}
+
+pub fn backend(source: &str, conf: &Conf) -> Result<Box<dyn InvoiceBackend>> {
+    match source {
+        "lnbits" => {
+            if conf.lnbits_invoice_key.is_empty() {
+                Err("lnbits invoice key is not set")?
+            }
+            Ok(Box::new(LNbitsBackend {
+                url: conf.lnbits_url.trim_end_matches('/').to_string(),
+                invoice_key: conf.lnbits_invoice_key.clone(),
+            }))
+        }
+        "lnd" => {
+            if conf.lnd_invoices_macaroon.is_empty() {
+                Err("lnd invoices macaroon is not set")?
+            }
+            Ok(Box::new(LndBackend {
+                url: conf.lnd_url.trim_end_matches('/').to_string(),
+                macaroon: conf.lnd_invoices_macaroon.clone(),
+            }))
+        }
+        "fake" => Ok(Box::new(FakeBackend)),
+        _ => Err(format!("unknown invoice backend: {source}").into()),
+    }
+}
+
+pub struct LNbitsBackend {
+    pub url: String,
+    pub invoice_key: String,
+}
+
+#[derive(Deserialize)]
+struct CreateLNbitsInvoiceResponse {
+    payment_hash: String,
+    bolt11: String,
+}
+
+#[derive(Deserialize)]
+struct CheckLNbitsInvoiceResponse {
+    paid: bool,
+}
+
+impl InvoiceBackend for LNbitsBackend {
+    fn create<'a>(
+        &'a self,
+        description: &'a str,
+        description_hash: Option<[u8; 32]>,
+        amount_sats: i64,
+    ) -> BoxFuture<'a, Result<NewInvoice>> {
+        Box::pin(async move {
+            let mut args = json!({
+                "out": false,
+                "amount": amount_sats,
+                "memo": description,
+                "expiry": INVOICE_EXPIRY_SECS,
+            });
+            if let Some(hash) = description_hash {
+                args["description_hash"] = hex_encode(&hash).into();
+            }
+            let res = reqwest::Client::new()
+                .post(format!("{}/api/v1/payments", self.url))
+                .header("X-Api-Key", &self.invoice_key)
+                .json(&args)
+                .send()
+                .await?;
+            if !res.status().is_success() {
+                return Err("Failed to generate LNBITS invoice".into());
+            }
+            let res: CreateLNbitsInvoiceResponse = res.json().await?;
+            Ok(NewInvoice {
+                payment_hash: res.payment_hash,
+                payment_request: res.bolt11,
+            })
+        })
+    }
+
+    fn check<'a>(&'a self, payment_hash: &'a str) -> BoxFuture<'a, Result<bool>> {
+        Box::pin(async move {
+            let res = reqwest::Client::new()
+                .get(format!("{}/api/v1/payments/{payment_hash}", self.url))
+                .header("X-Api-Key", &self.invoice_key)
+                .send()
+                .await?;
+            if !res.status().is_success() {
+                return Err("Failed to check LNBITS invoice".into());
+            }
+            let res: CheckLNbitsInvoiceResponse = res.json().await?;
+            Ok(res.paid)
+        })
+    }
+
+    fn cancel<'a>(&'a self, _payment_hash: &'a str) -> BoxFuture<'a, Result<()>> {
+        // LNbits has no cancel endpoint, unpaid invoices just expire
+        Box::pin(async { Ok(()) })
+    }
+}
+
+pub struct LndBackend {
+    pub url: String,
+    pub macaroon: String,
+}
+
+#[derive(Deserialize)]
+struct CreateLndInvoiceResponse {
+    r_hash: String,
+    payment_request: String,
+}
+
+#[derive(Deserialize)]
+struct CheckLndInvoiceResponse {
+    state: String,
+}
+
+impl InvoiceBackend for LndBackend {
+    fn create<'a>(
+        &'a self,
+        description: &'a str,
+        description_hash: Option<[u8; 32]>,
+        amount_sats: i64,
+    ) -> BoxFuture<'a, Result<NewInvoice>> {
+        Box::pin(async move {
+            let mut args = json!({
+                "value": amount_sats,
+                "expiry": INVOICE_EXPIRY_SECS,
+            });
+            match description_hash {
+                Some(hash) => {
+                    args["description_hash"] = base64::engine::general_purpose::STANDARD
+                        .encode(hash)
+                        .into()
+                }
+                None => args["memo"] = description.into(),
+            }
+            let res = reqwest::Client::new()
+                .post(format!("{}/v1/invoices", self.url))
+                .header("Grpc-Metadata-macaroon", &self.macaroon)
+                .json(&args)
+                .send()
+                .await?;
+            if !res.status().is_success() {
+                return Err("Failed to generate lnd invoice".into());
+            }
+            let res: CreateLndInvoiceResponse = res.json().await?;
+            let payment_hash = base64::engine::general_purpose::STANDARD
+                .decode(res.r_hash)
+                .map_err(|e| format!("invalid r_hash: {e}"))?;
+            Ok(NewInvoice {
+                payment_hash: hex_encode(&payment_hash),
+                payment_request: res.payment_request,
+            })
+        })
+    }
+
+    fn check<'a>(&'a self, payment_hash: &'a str) -> BoxFuture<'a, Result<bool>> {
+        Box::pin(async move {
+            let res = reqwest::Client::new()
+                .get(format!("{}/v1/invoice/{payment_hash}", self.url))
+                .header("Grpc-Metadata-macaroon", &self.macaroon)
+                .send()
+                .await?;
+            if !res.status().is_success() {
+                return Err("Failed to check lnd invoice".into());
+            }
+            let res: CheckLndInvoiceResponse = res.json().await?;
+            Ok(res.state == "SETTLED")
+        })
+    }
+
+    fn cancel<'a>(&'a self, payment_hash: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let payment_hash = hex_decode(payment_hash)?;
+            let args = json!({
+                "payment_hash": base64::engine::general_purpose::STANDARD.encode(payment_hash),
+            });
+            let res = reqwest::Client::new()
+                .post(format!("{}/v2/invoices/cancel", self.url))
+                .header("Grpc-Metadata-macaroon", &self.macaroon)
+                .json(&args)
+                .send()
+                .await?;
+            if !res.status().is_success() {
+                return Err("Failed to cancel lnd invoice".into());
+            }
+            Ok(())
+        })
+    }
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hex_decode(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(format!("invalid payment hash: {hex}").into());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| format!("invalid payment hash: {hex}").into())
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FakeInvoiceState {
+    Unpaid,
+    Paid,
+    Canceled,
+}
+
+/// Shared by all `FakeBackend` instances, keyed by payment hash
+static FAKE_INVOICES: LazyLock<Mutex<HashMap<String, FakeInvoiceState>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// In-memory backend for tests and local development, no funds involved.
+/// Invoices stay unpaid until `FakeBackend::pay` is called.
+pub struct FakeBackend;
+
+impl FakeBackend {
+    /// Returns false if there is no such unpaid invoice
+    pub fn pay(payment_hash: &str) -> bool {
+        let mut invoices = FAKE_INVOICES.lock().unwrap();
+        match invoices.get_mut(payment_hash) {
+            Some(state) if *state == FakeInvoiceState::Unpaid => {
+                *state = FakeInvoiceState::Paid;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl InvoiceBackend for FakeBackend {
+    fn create<'a>(
+        &'a self,
+        _description: &'a str,
+        _description_hash: Option<[u8; 32]>,
+        amount_sats: i64,
+    ) -> BoxFuture<'a, Result<NewInvoice>> {
+        Box::pin(async move {
+            let payment_hash = format!(
+                "{}{}",
+                uuid::Uuid::new_v4().simple(),
+                uuid::Uuid::new_v4().simple(),
+            );
+            FAKE_INVOICES
+                .lock()
+                .unwrap()
+                .insert(payment_hash.clone(), FakeInvoiceState::Unpaid);
+            Ok(NewInvoice {
+                payment_request: format!("lnfake{amount_sats}:{payment_hash}"),
+                payment_hash,
+            })
+        })
+    }
+
+    fn check<'a>(&'a self, payment_hash: &'a str) -> BoxFuture<'a, Result<bool>> {
+        Box::pin(async move {
+            match FAKE_INVOICES.lock().unwrap().get(payment_hash) {
+                Some(state) => Ok(*state == FakeInvoiceState::Paid),
+                None => Err(format!("unknown fake invoice: {payment_hash}").into()),
+            }
+        })
+    }
+
+    fn cancel<'a>(&'a self, payment_hash: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut invoices = FAKE_INVOICES.lock().unwrap();
+            match invoices.get_mut(payment_hash) {
+                Some(state) if *state == FakeInvoiceState::Unpaid => {
+                    *state = FakeInvoiceState::Canceled;
+                    Ok(())
+                }
+                _ => Err(format!("can't cancel fake invoice: {payment_hash}").into()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{backend, FakeBackend};
+    use crate::{db::main::conf::schema::Conf, Result};
+    use actix_web::test;
+
+    #[test]
+    async fn backend_requires_credentials() -> Result<()> {
+        let conf = Conf::default();
+        assert!(backend("lnbits", &conf).is_err());
+        assert!(backend("lnd", &conf).is_err());
+        assert!(backend("unknown", &conf).is_err());
+        let conf = Conf {
+            lnd_invoices_macaroon: "macaroon".into(),
+            ..Conf::default()
+        };
+        assert!(backend("lnd", &conf).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    async fn fake_backend() -> Result<()> {
+        let backend = backend("fake", &Conf::default())?;
+        let invoice = backend.create("test", None, 100).await?;
+        assert_eq!(64, invoice.payment_hash.len());
+        assert!(!backend.check(&invoice.payment_hash).await?);
+        assert!(FakeBackend::pay(&invoice.payment_hash));
+        assert!(!FakeBackend::pay(&invoice.payment_hash));
+        assert!(backend.check(&invoice.payment_hash).await?);
+        assert!(backend.cancel(&invoice.payment_hash).await.is_err());
+
+        let invoice = backend.create("test", None, 100).await?;
+        backend.cancel(&invoice.payment_hash).await?;
+        assert!(!FakeBackend::pay(&invoice.payment_hash));
+        assert!(!backend.check(&invoice.payment_hash).await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use crate::db;
+use crate::db::main::conf::schema::Conf;
+use crate::db::main::MainPool;
+use crate::service::{self, matrix};
+use crate::Result;
+use base64::Engine;
+use deadpool_sqlite::Pool;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// First reconnect delay, doubled after every failed attempt
+pub const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+pub const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// How often we check whether LND got configured, if it wasn't at startup
+const NOT_CONFIGURED_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Listens to LND invoice updates, so boosts and comments go live as soon as
+/// they are paid. Polling via `sync_unpaid_invoices` still catches anything
+/// this task misses while disconnected.
+pub fn init(pool: &MainPool, shutdown: CancellationToken) {
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        info!("invoice stream: started");
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            let delay = match db::main::conf::queries::select(&pool).await {
+                Ok(conf) if conf.lnd_invoices_macaroon.is_empty() => NOT_CONFIGURED_INTERVAL,
+                Ok(conf) => {
+                    let res = tokio::select! {
+                        _ = shutdown.cancelled() => break,
+                        res = subscribe(&conf, &pool, &mut backoff) => res,
+                    };
+                    match res {
+                        Ok(()) => warn!("invoice stream: closed by lnd"),
+                        Err(err) => warn!(%err, "invoice stream: disconnected"),
+                    }
+                    let delay = backoff;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    delay
+                }
+                Err(err) => {
+                    warn!(%err, "invoice stream: failed to load conf");
+                    backoff
+                }
+            };
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = tokio::time::sleep(delay) => {}
+            }
+        }
+        info!("invoice stream: stopped");
+    });
+}
+
+/// Runs until the stream ends. Resets `backoff` once connected, so a long
+/// lived connection which drops reconnects quickly.
+async fn subscribe(conf: &Conf, pool: &Pool, backoff: &mut Duration) -> Result<()> {
+    let url = format!(
+        "{}/v1/invoices/subscribe",
+        conf.lnd_url.trim_end_matches('/')
+    );
+    let mut res = reqwest::Client::new()
+        .get(url)
+        .header("Grpc-Metadata-macaroon", &conf.lnd_invoices_macaroon)
+        .send()
+        .await?;
+    if !res.status().is_success() {
+        return Err(format!("lnd returned {}", res.status()).into());
+    }
+    info!("invoice stream: connected");
+    *backoff = INITIAL_BACKOFF;
+    let matrix_client = matrix::try_client(pool);
+    let mut buf: Vec<u8> = vec![];
+    while let Some(chunk) = res.chunk().await? {
+        buf.extend_from_slice(&chunk);
+        while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buf.drain(..=pos).collect();
+            // only transport errors end the stream, a single bad message
+            // shouldn't make us reconnect
+            let payment_hash = match settled_payment_hash(&line) {
+                Ok(Some(payment_hash)) => payment_hash,
+                Ok(None) => continue,
+                Err(err) => {
+                    warn!(%err, "invoice stream: skipped unreadable message");
+                    continue;
+                }
+            };
+            if let Err(err) = on_settled(&payment_hash, pool, &matrix_client).await {
+                warn!(%err, payment_hash, "invoice stream: failed to process payment");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct StreamMessage {
+    result: Option<StreamInvoice>,
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct StreamInvoice {
+    r_hash: String,
+    state: String,
+}
+
+/// Parses a single line of the stream, returning a hex payment hash if it
+/// reports a settled invoice
+fn settled_payment_hash(line: &[u8]) -> Result<Option<String>> {
+    if line.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    let message: StreamMessage = serde_json::from_slice(line)?;
+    if let Some(error) = message.error {
+        return Err(format!("lnd stream error: {error}").into());
+    }
+    let Some(invoice) = message.result else {
+        return Ok(None);
+    };
+    if invoice.state != "SETTLED" {
+        return Ok(None);
+    }
+    let payment_hash = base64::engine::general_purpose::STANDARD
+        .decode(invoice.r_hash)
+        .map_err(|e| format!("invalid r_hash: {e}"))?;
+    Ok(Some(
+        payment_hash.iter().map(|b| format!("{b:02x}")).collect(),
+    ))
+}
+
+async fn on_settled(
+    payment_hash: &str,
+    pool: &Pool,
+    matrix_client: &Option<matrix_sdk::Client>,
+) -> Result<()> {
+    // the node may be shared with other apps, not every invoice is ours
+    let Ok(invoice) = db::main::invoice::queries::select_by_payment_hash(payment_hash, pool).await
+    else {
+        return Ok(());
+    };
+    if invoice.source != "lnd" {
+        return Ok(());
+    }
+    if service::invoice::mark_paid(&invoice, pool, matrix_client).await? {
+        info!(
+            invoice.id,
+            invoice.description, "invoice stream: invoice paid"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::main::invoice::schema::InvoiceStatus;
+    use crate::db::main::test::pool;
+    use crate::service::overpass::OverpassElement;
+    use crate::{db, Result};
+    use actix_web::test;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    #[test]
+    async fn settled_payment_hash() -> Result<()> {
+        assert_eq!(None, super::settled_payment_hash(b"\n")?);
+        assert_eq!(
+            None,
+            super::settled_payment_hash(br#"{"result":{"r_hash":"AAE=","state":"OPEN"}}"#)?
+        );
+        assert_eq!(
+            Some("0001".to_string()),
+            super::settled_payment_hash(br#"{"result":{"r_hash":"AAE=","state":"SETTLED"}}"#)?
+        );
+        assert!(super::settled_payment_hash(br#"{"error":{"code":2}}"#).is_err());
+        assert!(super::settled_payment_hash(b"not json").is_err());
+        Ok(())
+    }
+
+    /// Minimal LND which streams the given lines and closes the connection
+    async fn lnd(lines: Vec<String>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut req = [0u8; 1024];
+            let _ = tokio::io::AsyncReadExt::read(&mut stream, &mut req).await;
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            for line in lines {
+                let line = format!("{line}\n");
+                let chunk = format!("{:x}\r\n{line}\r\n", line.len());
+                stream.write_all(chunk.as_bytes()).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            stream.write_all(b"0\r\n\r\n").await.unwrap();
+        });
+        url
+    }
+
+    #[test]
+    async fn subscribe_marks_settled_invoices_paid() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        let invoice = db::main::invoice::queries::insert(
+            "lnd",
+            format!("element_boost:{}:30", element.id),
+            100,
+            "0001",
+            "lnbc...",
+            InvoiceStatus::Unpaid,
+            None,
+            &pool,
+        )
+        .await?;
+        let url = lnd(vec![
+            r#"{"result":{"r_hash":"AAE=","state":"OPEN"}}"#.into(),
+            "not json".into(),
+            r#"{"error":{"code":2,"message":"oops"}}"#.into(),
+            r#"{"result":{"r_hash":"AAI=","state":"SETTLED"}}"#.into(),
+            r#"{"result":{"r_hash":"AAE=","state":"SETTLED"}}"#.into(),
+        ])
+        .await;
+        let mut conf = db::main::conf::queries::select(&pool).await?;
+        conf.lnd_url = url;
+        conf.lnd_invoices_macaroon = "macaroon".into();
+        let mut backoff = super::MAX_BACKOFF;
+        super::subscribe(&conf, &pool, &mut backoff).await?;
+        assert_eq!(super::INITIAL_BACKOFF, backoff);
+        let invoice = db::main::invoice::queries::select_by_uuid(invoice.uuid, &pool).await?;
+        assert_eq!(InvoiceStatus::Paid, invoice.status);
+        let element = db::main::element::queries::select_by_id(element.id, &pool).await?;
+        assert!(element.boosted_until().is_some());
+        Ok(())
+    }
+}
@@ -44,12 +44,18 @@ pub async fn get_node_stats(pool: &Pool) -> Result<NodeStats> {
     let client = reqwest::Client::new();
     let blockchain: BlockchainBalanceResponse = get(
         &client,
+        &conf.lnd_url,
         "/v1/balance/blockchain",
         &conf.lnd_readonly_macaroon,
     )
     .await?;
-    let channels: ChannelBalanceResponse =
-        get(&client, "/v1/balance/channels", &conf.lnd_readonly_macaroon).await?;
+    let channels: ChannelBalanceResponse = get(
+        &client,
+        &conf.lnd_url,
+        "/v1/balance/channels",
+        &conf.lnd_readonly_macaroon,
+    )
+    .await?;
     let parse = |s: &str| s.parse::<i64>().unwrap_or(0);
     let onchain_total_sat = parse(&blockchain.total_balance);
     let outbound = parse(&channels.local_balance.sat);
@@ -78,10 +84,11 @@ pub async fn get_node_stats(pool: &Pool) -> Result<NodeStats> {
 
 async fn get<T: for<'de> Deserialize<'de>>(
     client: &reqwest::Client,
+    base_url: &str,
     path: &str,
     macaroon: &str,
 ) -> Result<T> {
-    let url = format!("https://lnd.btcmap.org{path}");
+    let url = format!("{}{path}", base_url.trim_end_matches('/'));
     let response = client
         .get(&url)
         .header("Grpc-Metadata-macaroon", macaroon)
@@ -0,0 +1,24 @@
+use crate::db::main::element::schema::Element;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+/// Lightning Address usernames look like `boost-{place_id}`
+pub const BOOST_USERNAME_PREFIX: &str = "boost-";
+
+/// LUD-06 metadata, wallets show it to the payer and check that the invoice
+/// commits to its hash. The identifier is required for Lightning Addresses
+/// (LUD-16) and harmless for plain LNURLs, so it's always there.
+pub fn boost_metadata(element: &Element, identifier: &str) -> String {
+    json!([
+        [
+            "text/plain",
+            format!("Boost {} on BTC Map", element.name(None))
+        ],
+        ["text/identifier", identifier],
+    ])
+    .to_string()
+}
+
+pub fn description_hash(metadata: &str) -> [u8; 32] {
+    Sha256::digest(metadata.as_bytes()).into()
+}
@@ -1,10 +1,11 @@
 use crate::db::log::request::blocking_queries::InsertArgs;
 use crate::db::{self, log::LogPool};
+use crate::service::client_ip;
 use actix_http::h1;
 use actix_web::{
     dev::{self, forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
     web::{Bytes, Data},
-    Error,
+    Error, HttpMessage,
 };
 use futures_util::future::LocalBoxFuture;
 use std::{
@@ -15,6 +16,10 @@ use std::{
 
 pub struct Log;
 
+/// Put into the request extensions when an RPC method returns an error, RPC
+/// errors are sent with a 200 status so the log can't tell them apart otherwise
+pub struct RpcFailed;
+
 impl<S: 'static, B> Transform<S, ServiceRequest> for Log
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
@@ -72,11 +77,7 @@ where
             req.set_payload(bytes_to_payload(body));
             let res = svc.call(req).await?;
             let time_ns = Instant::now().duration_since(started_at).as_nanos();
-            let addr = res
-                .request()
-                .connection_info()
-                .realip_remote_addr()
-                .map(|s| s.to_owned());
+            let addr = client_ip::get(res.request()).map(|it| it.to_string());
             let user_agent = res
                 .request()
                 .headers()
@@ -87,6 +88,7 @@ where
             let method = res.request().method().as_str().to_owned();
             let query = res.request().uri().query();
             let response_code = res.status().as_u16() as i64;
+            let rpc_failed = res.request().extensions().contains::<RpcFailed>();
             let Some(addr) = addr else {
                 return Ok(res);
             };
@@ -101,6 +103,7 @@ where
                     body: body_str.map(ToString::to_string),
                     response_code,
                     processing_time_ns: time_ns as i64,
+                    rpc_failed,
                 },
                 &pool,
             )
@@ -110,7 +113,7 @@ where
     }
 }
 
-fn bytes_to_payload(buf: Bytes) -> Payload {
+pub fn bytes_to_payload(buf: Bytes) -> Payload {
     let (_, mut pl) = h1::Payload::create(true);
     pl.unread_data(buf);
     dev::Payload::from(pl)
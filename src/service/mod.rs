@@ -1,23 +1,42 @@
+pub mod abuse;
+pub mod api_token;
 pub mod area;
 pub mod area_element;
 pub mod auth;
 pub mod ban;
+pub mod boost;
+pub mod cidr;
+pub mod client_ip;
 pub mod comment;
+pub mod conf;
+pub mod conf_crypto;
 pub mod electrum_pinned;
 pub mod element;
 pub mod event;
+pub mod event_schedule;
+pub mod event_submission;
+pub mod exchange_rate;
 pub mod gitea;
+pub mod ical;
 pub mod invoice;
+pub mod invoice_backend;
+pub mod invoice_stream;
 pub mod lnd;
+pub mod lnurl;
 pub mod log;
 pub mod matrix;
 pub mod nip98;
+pub mod nostr;
 pub mod og;
 pub mod osm;
 pub mod overpass;
 pub mod ppq;
+pub mod rate_limit;
+pub mod review;
 pub mod search;
+pub mod spam;
 pub mod sync;
 pub mod user;
 pub mod wallet;
 pub mod wallet_cache;
+pub mod zap;
@@ -0,0 +1,700 @@
+use crate::db;
+use crate::db::main::conf::schema::Conf;
+use crate::db::main::element::schema::Element;
+use crate::db::main::element_comment::schema::ElementComment;
+use crate::db::main::nostr_outbox::schema::NostrOutboxItem;
+use crate::db::main::MainPool;
+use crate::service::cidr;
+use crate::Result;
+use deadpool_sqlite::Pool;
+use futures_util::future::join_all;
+use futures_util::{SinkExt, StreamExt};
+use nostr::event::{Event, EventBuilder};
+use nostr::key::{Keys, PublicKey};
+use nostr::nips::nip01::Coordinate;
+use nostr::nips::nip04;
+use nostr::{JsonUtil, Kind, RelayMessage, Tag, Timestamp};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::net::{lookup_host, TcpStream};
+use tokio::sync::Notify;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{Connector, MaybeTlsStream, WebSocketStream};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use url::{Host, Url};
+
+/// NIP-99 classified listing, parameterized-replaceable so every place has
+/// exactly one live listing per signer, addressed by its `d` tag.
+pub const KIND_PLACE_LISTING: u16 = 30402;
+
+/// Outbox items are retried this many times before they are given up on.
+pub const MAX_ATTEMPTS: i64 = 10;
+
+/// How often the outbox is flushed even if nobody woke the publisher up.
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Upper bound on connecting to a relay and waiting for its `OK` reply.
+pub const RELAY_TIMEOUT: Duration = Duration::from_secs(10);
+
+const FLUSH_BATCH_SIZE: i64 = 100;
+
+/// Geohash precisions emitted as `g` tags. Relays only match tags exactly, so
+/// clients searching a neighbourhood or a whole region need the coarser
+/// prefixes too.
+const GEOHASH_PRECISIONS: std::ops::RangeInclusive<usize> = 1..=9;
+
+fn wakeup() -> &'static Notify {
+    static WAKEUP: OnceLock<Notify> = OnceLock::new();
+    WAKEUP.get_or_init(Notify::new)
+}
+
+pub fn init(pool: &MainPool, shutdown: CancellationToken) {
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        info!(
+            flush_interval_secs = FLUSH_INTERVAL.as_secs(),
+            "nostr publisher: started"
+        );
+        loop {
+            match flush(&pool).await {
+                Ok(0) => {}
+                Ok(sent) => info!(sent, "nostr publisher: flushed outbox"),
+                Err(err) => warn!(%err, "nostr publisher: failed to flush outbox"),
+            }
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = wakeup().notified() => {}
+                _ = tokio::time::sleep(FLUSH_INTERVAL) => {}
+            }
+        }
+        info!("nostr publisher: stopped");
+    });
+}
+
+/// Returns `None` if publishing is not configured. An invalid key is logged
+/// and treated the same way, it shouldn't break the callers.
+pub fn signer(conf: &Conf) -> Option<Keys> {
+    if conf.nostr_secret_key.is_empty() || conf.nostr_relays.is_empty() {
+        return None;
+    }
+    match Keys::parse(&conf.nostr_secret_key) {
+        Ok(keys) => Some(keys),
+        Err(err) => {
+            warn!(%err, "nostr publisher: invalid secret key");
+            None
+        }
+    }
+}
+
+pub async fn publish_place(element: &Element, pool: &Pool) -> Result<()> {
+    let conf = db::main::conf::queries::select(pool).await?;
+    let Some(keys) = signer(&conf) else {
+        return Ok(());
+    };
+    let event = place_listing(element, &keys)?;
+    enqueue(&event, &conf, pool).await
+}
+
+pub async fn publish_comment(
+    comment: &ElementComment,
+    element: &Element,
+    pool: &Pool,
+) -> Result<()> {
+    let conf = db::main::conf::queries::select(pool).await?;
+    let Some(keys) = signer(&conf) else {
+        return Ok(());
+    };
+    let content = format!("{}\n\n{}", comment.comment, place_url(element));
+    let event = place_note(element, content, &keys)?;
+    enqueue(&event, &conf, pool).await
+}
+
+pub async fn publish_boost(element: &Element, days: i64, pool: &Pool) -> Result<()> {
+    let conf = db::main::conf::queries::select(pool).await?;
+    let Some(keys) = signer(&conf) else {
+        return Ok(());
+    };
+    let content = format!(
+        "{} has been boosted for {} days\n\n{}",
+        element.name(Some("en")),
+        days,
+        place_url(element),
+    );
+    let event = place_note(element, content, &keys)?;
+    enqueue(&event, &conf, pool).await
+}
+
+/// Sends an encrypted NIP-04 direct message, used to notify users who
+/// signed in with Nostr. Does nothing if publishing is not configured.
+pub async fn send_direct_message(npub: &str, content: &str, pool: &Pool) -> Result<()> {
+    let conf = db::main::conf::queries::select(pool).await?;
+    let Some(keys) = signer(&conf) else {
+        return Ok(());
+    };
+    let event = direct_message(npub, content, &keys)?;
+    enqueue(&event, &conf, pool).await
+}
+
+/// Stores one outbox item per configured relay and wakes up the publisher.
+async fn enqueue(event: &Event, conf: &Conf, pool: &Pool) -> Result<()> {
+    let json = event.as_json();
+    for relay_url in &conf.nostr_relays {
+        db::main::nostr_outbox::queries::insert(relay_url.clone(), json.clone(), pool).await?;
+    }
+    wakeup().notify_one();
+    Ok(())
+}
+
+/// Sends every due outbox item to its relay. Each event goes out to all of
+/// its relays at once. A relay which fails is backed off as a whole: its
+/// other items are postponed and skipped for the rest of the flush. Events
+/// which reached all of their relays are removed from the outbox. Returns
+/// the number of delivered items.
+pub async fn flush(pool: &Pool) -> Result<usize> {
+    let items = db::main::nostr_outbox::queries::select_due(
+        OffsetDateTime::now_utc(),
+        MAX_ATTEMPTS,
+        FLUSH_BATCH_SIZE,
+        pool,
+    )
+    .await?;
+    let mut events: Vec<(String, Vec<NostrOutboxItem>)> = vec![];
+    for item in items {
+        match events.iter_mut().find(|(event, _)| *event == item.event) {
+            Some((_, relay_items)) => relay_items.push(item),
+            None => events.push((item.event.clone(), vec![item])),
+        }
+    }
+    let mut failing_relays: HashSet<String> = HashSet::new();
+    let mut sent = 0;
+    for (event, items) in events {
+        let items: Vec<_> = items
+            .into_iter()
+            .filter(|it| !failing_relays.contains(&it.relay_url))
+            .collect();
+        let results = join_all(items.iter().map(|it| send(&it.relay_url, &event))).await;
+        for (item, res) in items.into_iter().zip(results) {
+            match res {
+                Ok(()) => {
+                    db::main::nostr_outbox::queries::set_sent(
+                        item.id,
+                        OffsetDateTime::now_utc(),
+                        pool,
+                    )
+                    .await?;
+                    sent += 1;
+                }
+                Err(err) => {
+                    warn!(
+                        id = item.id,
+                        relay_url = item.relay_url,
+                        attempts = item.attempts + 1,
+                        %err,
+                        "nostr publisher: failed to deliver event",
+                    );
+                    let next_attempt_at = next_attempt_at(&item);
+                    db::main::nostr_outbox::queries::set_failed(
+                        item.id,
+                        err.to_string(),
+                        next_attempt_at,
+                        pool,
+                    )
+                    .await?;
+                    db::main::nostr_outbox::queries::postpone_by_relay_url(
+                        item.relay_url.clone(),
+                        next_attempt_at,
+                        pool,
+                    )
+                    .await?;
+                    failing_relays.insert(item.relay_url);
+                }
+            }
+        }
+    }
+    if sent > 0 {
+        db::main::nostr_outbox::queries::delete_delivered(pool).await?;
+    }
+    Ok(sent)
+}
+
+fn next_attempt_at(item: &NostrOutboxItem) -> OffsetDateTime {
+    let delay_secs = 30i64.saturating_mul(1 << item.attempts.clamp(0, 10));
+    OffsetDateTime::now_utc() + time::Duration::seconds(delay_secs.min(6 * 60 * 60))
+}
+
+async fn send(relay_url: &str, event: &str) -> Result<()> {
+    let event = Event::from_json(event).map_err(|e| e.to_string())?;
+    tokio::time::timeout(RELAY_TIMEOUT, send_event(relay_url, event))
+        .await
+        .map_err(|_| format!("relay did not reply within {RELAY_TIMEOUT:?}"))?
+}
+
+/// Same as `send`, for relays picked by strangers, such as the ones listed in
+/// zap requests. Only `wss://` relays with public addresses are contacted, and
+/// the connection goes to the address which was checked, so the relay can't
+/// point its DNS somewhere else in between.
+pub async fn send_to_untrusted_relay(relay_url: &str, event: &Event) -> Result<()> {
+    tokio::time::timeout(RELAY_TIMEOUT, async {
+        let addr = public_relay_addr(relay_url).await?;
+        let stream = TcpStream::connect(addr).await.map_err(|e| e.to_string())?;
+        let (ws, _) = tokio_tungstenite::client_async_tls_with_config(
+            relay_url,
+            stream,
+            None,
+            Some(Connector::Rustls(tls_config())),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        publish_over(ws, event.clone()).await
+    })
+    .await
+    .map_err(|_| format!("relay did not reply within {RELAY_TIMEOUT:?}"))?
+}
+
+async fn public_relay_addr(relay_url: &str) -> Result<SocketAddr> {
+    let url = Url::parse(relay_url).map_err(|e| format!("{relay_url}: {e}"))?;
+    if url.scheme() != "wss" {
+        return Err(format!("{relay_url}: only wss:// relays are allowed").into());
+    }
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addrs: Vec<SocketAddr> = match url.host() {
+        Some(Host::Domain(domain)) => lookup_host((domain, port))
+            .await
+            .map_err(|e| format!("{relay_url}: {e}"))?
+            .collect(),
+        Some(Host::Ipv4(ip)) => vec![SocketAddr::new(ip.into(), port)],
+        Some(Host::Ipv6(ip)) => vec![SocketAddr::new(ip.into(), port)],
+        None => return Err(format!("{relay_url}: no host").into()),
+    };
+    if addrs.iter().any(|it| !cidr::is_public(&it.ip())) {
+        return Err(format!("{relay_url}: resolves to a non-public address").into());
+    }
+    addrs
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("{relay_url}: no addresses").into())
+}
+
+async fn send_event(relay_url: &str, event: Event) -> Result<()> {
+    let (ws, _) = tokio_tungstenite::connect_async_tls_with_config(
+        relay_url,
+        None,
+        false,
+        Some(Connector::Rustls(tls_config())),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    publish_over(ws, event).await
+}
+
+async fn publish_over(
+    mut ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    event: Event,
+) -> Result<()> {
+    let request = nostr::ClientMessage::event(event.clone()).as_json();
+    ws.send(Message::Text(request.into()))
+        .await
+        .map_err(|e| e.to_string())?;
+    let res = loop {
+        let Some(message) = ws.next().await else {
+            break Err("relay closed the connection".into());
+        };
+        let Message::Text(text) = message.map_err(|e| e.to_string())? else {
+            continue;
+        };
+        if let Ok(RelayMessage::Ok {
+            event_id,
+            status,
+            message,
+        }) = RelayMessage::from_json(text.as_str())
+        {
+            if event_id != event.id {
+                continue;
+            }
+            break if status {
+                Ok(())
+            } else {
+                Err(format!("relay rejected event: {message}").into())
+            };
+        }
+    };
+    let _ = ws.close(None).await;
+    res
+}
+
+fn tls_config() -> Arc<rustls::ClientConfig> {
+    static CONFIG: OnceLock<Arc<rustls::ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let roots = rustls::RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+                rustls::crypto::ring::default_provider(),
+            ))
+            .with_safe_default_protocol_versions()
+            .expect("ring supports the default protocol versions")
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+            Arc::new(config)
+        })
+        .clone()
+}
+
+fn place_url(element: &Element) -> String {
+    format!("https://btcmap.org/merchant/{}", element.id)
+}
+
+fn place_identifier(element: &Element) -> String {
+    format!("btcmap:place:{}", element.id)
+}
+
+fn place_listing(element: &Element, keys: &Keys) -> Result<Event> {
+    let name = element.name(Some("en"));
+    let description = element.description().unwrap_or_default();
+    let mut tags = vec![
+        Tag::identifier(place_identifier(element)),
+        Tag::parse(["title", &name]).map_err(|e| e.to_string())?,
+        Tag::parse([
+            "published_at",
+            &element.created_at.unix_timestamp().to_string(),
+        ])
+        .map_err(|e| e.to_string())?,
+        Tag::parse(["r", &place_url(element)]).map_err(|e| e.to_string())?,
+        Tag::parse(["r", &element.osm_url()]).map_err(|e| e.to_string())?,
+        Tag::hashtag("bitcoin"),
+        Tag::hashtag("btcmap"),
+    ];
+    if !description.is_empty() {
+        tags.push(Tag::parse(["summary", &description]).map_err(|e| e.to_string())?);
+    }
+    if let Some(address) = element.address() {
+        tags.push(Tag::parse(["location", &address]).map_err(|e| e.to_string())?);
+    }
+    let geohash = geohash(element.lat(), element.lon(), *GEOHASH_PRECISIONS.end());
+    for precision in GEOHASH_PRECISIONS {
+        tags.push(Tag::parse(["g", &geohash[..precision]]).map_err(|e| e.to_string())?);
+    }
+    let content = if description.is_empty() {
+        format!("{name} accepts bitcoin\n\n{}", place_url(element))
+    } else {
+        format!("{description}\n\n{}", place_url(element))
+    };
+    EventBuilder::new(Kind::Custom(KIND_PLACE_LISTING), content)
+        .tags(tags)
+        .custom_created_at(Timestamp::now())
+        .sign_with_keys(keys)
+        .map_err(|e| e.to_string().into())
+}
+
+fn direct_message(npub: &str, content: &str, keys: &Keys) -> Result<Event> {
+    let receiver = PublicKey::parse(npub).map_err(|e| e.to_string())?;
+    let content =
+        nip04::encrypt(keys.secret_key(), &receiver, content).map_err(|e| e.to_string())?;
+    EventBuilder::new(Kind::EncryptedDirectMessage, content)
+        .tag(Tag::public_key(receiver))
+        .sign_with_keys(keys)
+        .map_err(|e| e.to_string().into())
+}
+
+/// Short note referencing the place listing, used for comments and boosts.
+fn place_note(element: &Element, content: String, keys: &Keys) -> Result<Event> {
+    let coordinate = Coordinate::new(Kind::Custom(KIND_PLACE_LISTING), keys.public_key())
+        .identifier(place_identifier(element));
+    EventBuilder::new(Kind::TextNote, content)
+        .tag(Tag::coordinate(coordinate, None))
+        .tag(Tag::parse(["r", &place_url(element)]).map_err(|e| e.to_string())?)
+        .tag(Tag::hashtag("btcmap"))
+        .sign_with_keys(keys)
+        .map_err(|e| e.to_string().into())
+}
+
+/// Standard base32 geohash of the given precision
+pub fn geohash(lat: f64, lon: f64, precision: usize) -> String {
+    const BASE32: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+    let (mut lat_range, mut lon_range) = ((-90.0, 90.0), (-180.0, 180.0));
+    let mut res = String::with_capacity(precision);
+    let mut even_bit = true;
+    let (mut bits, mut ch) = (0, 0usize);
+    while res.len() < precision {
+        let (range, value) = if even_bit {
+            (&mut lon_range, lon)
+        } else {
+            (&mut lat_range, lat)
+        };
+        let mid = (range.0 + range.1) / 2.0;
+        ch <<= 1;
+        if value >= mid {
+            ch |= 1;
+            range.0 = mid;
+        } else {
+            range.1 = mid;
+        }
+        even_bit = !even_bit;
+        bits += 1;
+        if bits == 5 {
+            res.push(BASE32[ch] as char);
+            bits = 0;
+            ch = 0;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::main::test::pool;
+    use crate::service::overpass::OverpassElement;
+    use crate::{db, Result};
+    use actix_web::test;
+    use futures_util::{SinkExt, StreamExt};
+    use nostr::key::Keys;
+    use nostr::nips::nip19::ToBech32;
+    use nostr::{ClientMessage, JsonUtil, Kind, RelayMessage};
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::Message;
+
+    /// Minimal relay which stores every received event and replies with
+    /// `OK` (or rejects everything if `accept` is false).
+    async fn relay(accept: bool) -> (String, Arc<Mutex<Vec<nostr::Event>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(vec![]));
+        let received_clone = received.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let received = received_clone.clone();
+                tokio::spawn(async move {
+                    let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                    while let Some(Ok(Message::Text(text))) = ws.next().await {
+                        let Ok(ClientMessage::Event(event)) =
+                            ClientMessage::from_json(text.as_str())
+                        else {
+                            continue;
+                        };
+                        received.lock().unwrap().push(event.as_ref().clone());
+                        let reply = RelayMessage::ok(event.id, accept, "");
+                        ws.send(Message::Text(reply.as_json().into()))
+                            .await
+                            .unwrap();
+                    }
+                });
+            }
+        });
+        (url, received)
+    }
+
+    fn configure(relays: &[&str], conn: &rusqlite::Connection) {
+        conn.execute(
+            "UPDATE conf SET nostr_secret_key = ?1, nostr_relays = ?2",
+            [
+                Keys::generate().secret_key().to_secret_hex(),
+                relays.join(","),
+            ],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    async fn geohash() {
+        assert_eq!("u4pruydqqvj", super::geohash(57.64911, 10.40744, 11));
+        assert_eq!("ezs42", super::geohash(42.605, -5.603, 5));
+    }
+
+    #[test]
+    async fn signer_requires_key_and_relays() -> Result<()> {
+        let mut conf = db::main::conf::queries::select(&pool()).await?;
+        assert!(super::signer(&conf).is_none());
+        conf.nostr_secret_key = Keys::generate().secret_key().to_secret_hex();
+        assert!(super::signer(&conf).is_none());
+        conf.nostr_relays = vec!["wss://relay.example".into()];
+        assert!(super::signer(&conf).is_some());
+        conf.nostr_secret_key = "invalid".into();
+        assert!(super::signer(&conf).is_none());
+        Ok(())
+    }
+
+    #[test]
+    async fn place_listing() -> Result<()> {
+        let conn = db::main::test::conn();
+        let element = db::main::element::blocking_queries::insert(
+            &OverpassElement::mock_with_tags(1, &[("name", "Cafe"), ("description", "Coffee")]),
+            &conn,
+        )?;
+        let keys = Keys::generate();
+        let event = super::place_listing(&element, &keys)?;
+        assert_eq!(Kind::Custom(super::KIND_PLACE_LISTING), event.kind);
+        assert!(event.verify().is_ok());
+        assert_eq!(
+            Some(format!("btcmap:place:{}", element.id).as_str()),
+            event.tags.identifier()
+        );
+        let g: Vec<&str> = event
+            .tags
+            .iter()
+            .filter(|t| t.kind().as_str() == "g")
+            .filter_map(|t| t.content())
+            .collect();
+        assert_eq!(9, g.len());
+        assert_eq!("s", g[0]);
+        assert_eq!("s00000000", g[8]);
+        Ok(())
+    }
+
+    #[test]
+    async fn direct_message() -> Result<()> {
+        let sender = Keys::generate();
+        let receiver = Keys::generate();
+        let npub = receiver.public_key().to_bech32().unwrap();
+        let event = super::direct_message(&npub, "Approved", &sender)?;
+        assert_eq!(Kind::EncryptedDirectMessage, event.kind);
+        assert!(event.verify().is_ok());
+        assert_eq!(
+            Some(&receiver.public_key()),
+            event.tags.public_keys().next()
+        );
+        let content = nostr::nips::nip04::decrypt(
+            receiver.secret_key(),
+            &sender.public_key(),
+            &event.content,
+        )
+        .unwrap();
+        assert_eq!("Approved", content);
+        assert!(super::direct_message("invalid", "Approved", &sender).is_err());
+        Ok(())
+    }
+
+    #[test]
+    async fn publish_place_without_conf_is_noop() -> Result<()> {
+        let pool = pool();
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        super::publish_place(&element, &pool).await?;
+        let due = db::main::nostr_outbox::queries::select_due(
+            time::OffsetDateTime::now_utc(),
+            super::MAX_ATTEMPTS,
+            100,
+            &pool,
+        )
+        .await?;
+        assert!(due.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    async fn publish_and_flush() -> Result<()> {
+        let (relay_a, received_a) = relay(true).await;
+        let (relay_b, received_b) = relay(true).await;
+        let pool = pool();
+        pool.get()
+            .await?
+            .interact(move |conn| configure(&[&relay_a, &relay_b], conn))
+            .await?;
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        super::publish_place(&element, &pool).await?;
+        super::publish_boost(&element, 30, &pool).await?;
+        assert_eq!(4, super::flush(&pool).await?);
+        // every relay accepted both events, so nothing is left in the outbox
+        for id in 1..=4 {
+            assert!(db::main::nostr_outbox::queries::select_by_id(id, &pool)
+                .await
+                .is_err());
+        }
+        assert_eq!(0, super::flush(&pool).await?);
+        for received in [received_a, received_b] {
+            let received = received.lock().unwrap();
+            assert_eq!(2, received.len());
+            assert_eq!(Kind::Custom(super::KIND_PLACE_LISTING), received[0].kind);
+            assert_eq!(Kind::TextNote, received[1].kind);
+        }
+        Ok(())
+    }
+
+    #[test]
+    async fn flush_retries_rejected_events() -> Result<()> {
+        let (relay, received) = relay(false).await;
+        let pool = pool();
+        pool.get()
+            .await?
+            .interact(move |conn| configure(&[&relay], conn))
+            .await?;
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        super::publish_place(&element, &pool).await?;
+        assert_eq!(0, super::flush(&pool).await?);
+        assert_eq!(1, received.lock().unwrap().len());
+        let item = db::main::nostr_outbox::queries::select_by_id(1, &pool).await?;
+        assert_eq!(1, item.attempts);
+        assert!(item.sent_at.is_none());
+        assert!(item.last_error.unwrap().contains("rejected"));
+        assert!(item.next_attempt_at > time::OffsetDateTime::now_utc());
+        // not due yet, so nothing is sent
+        assert_eq!(0, super::flush(&pool).await?);
+        assert_eq!(1, received.lock().unwrap().len());
+        Ok(())
+    }
+
+    #[test]
+    async fn flush_backs_off_per_relay() -> Result<()> {
+        let (good, received_good) = relay(true).await;
+        let (bad, received_bad) = relay(false).await;
+        let pool = pool();
+        pool.get()
+            .await?
+            .interact(move |conn| configure(&[&good, &bad], conn))
+            .await?;
+        let element = db::main::element::queries::insert(OverpassElement::mock(1), &pool).await?;
+        super::publish_place(&element, &pool).await?;
+        super::publish_boost(&element, 30, &pool).await?;
+        assert_eq!(2, super::flush(&pool).await?);
+        assert_eq!(2, received_good.lock().unwrap().len());
+        // the failing relay isn't tried again for the second event
+        assert_eq!(1, received_bad.lock().unwrap().len());
+        let due = db::main::nostr_outbox::queries::select_due(
+            time::OffsetDateTime::now_utc(),
+            super::MAX_ATTEMPTS,
+            100,
+            &pool,
+        )
+        .await?;
+        assert!(due.is_empty());
+        // delivered items are kept until the failing relay catches up
+        let item = db::main::nostr_outbox::queries::select_by_id(1, &pool).await?;
+        assert!(item.sent_at.is_some());
+        Ok(())
+    }
+
+    #[test]
+    async fn public_relay_addr() {
+        for url in [
+            "ws://relay.example.com",
+            "https://relay.example.com",
+            "wss://127.0.0.1",
+            "wss://10.0.0.1:7000",
+            "wss://[::1]",
+            "wss://[::ffff:169.254.169.254]",
+            "wss://localhost",
+        ] {
+            assert!(super::public_relay_addr(url).await.is_err(), "{url}");
+        }
+        assert_eq!(
+            "1.1.1.1:443".parse::<std::net::SocketAddr>().unwrap(),
+            super::public_relay_addr("wss://1.1.1.1").await.unwrap()
+        );
+    }
+
+    #[test]
+    async fn send_to_untrusted_relay_skips_local_relays() {
+        let (url, received) = relay(true).await;
+        let event = nostr::EventBuilder::new(Kind::TextNote, "hi")
+            .sign_with_keys(&Keys::generate())
+            .unwrap();
+        assert!(super::send_to_untrusted_relay(&url, &event).await.is_err());
+        let url = url.replace("ws://", "wss://");
+        assert!(super::send_to_untrusted_relay(&url, &event).await.is_err());
+        assert!(received.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,627 @@
+//! Token bucket rate limits, see `RateLimit`. Requests are grouped by route
+//! and counted per API token, or per IP for anonymous requests. Limited
+//! requests get a 429 with a `Retry-After` header, and IPs which keep hitting
+//! the limits get banned for `rate_limit_ban_hours`. IPs come from
+//! `client_ip`, and anonymous requests are neither limited nor banned unless
+//! trusted proxies are configured.
+
+use crate::db::main::conf::schema::{RateLimit, RateLimitGroup};
+use crate::db::{self, main::MainPool};
+use crate::rest::error::RestApiError;
+use crate::service::{self, conf::SharedConf, log::bytes_to_payload};
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{self, HeaderMap, HeaderName, HeaderValue},
+        Method,
+    },
+    middleware::Next,
+    web::{Bytes, Data},
+    Error, ResponseError,
+};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Limited requests are counted over this window to decide on a ban
+const OFFENSE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// How often idle buckets are dropped
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+const RATE_LIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATE_LIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATE_LIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// Shared by all workers, so it has to be created outside of the app factory
+pub struct RateLimiter {
+    state: Mutex<State>,
+}
+
+struct State {
+    buckets: HashMap<(RateLimitGroup, String), Bucket>,
+    /// Limited requests by IP
+    offenses: HashMap<String, Offenses>,
+    pruned_at: Instant,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    /// Full buckets are the same as missing ones, so they can be dropped
+    full_at: Instant,
+}
+
+struct Offenses {
+    count: i64,
+    since: Instant,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: i64,
+    pub remaining: i64,
+    /// Seconds until the bucket is full again
+    pub reset_secs: u64,
+    /// Seconds until the next request is allowed, zero if it's allowed now
+    pub retry_after_secs: u64,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(State {
+                buckets: HashMap::new(),
+                offenses: HashMap::new(),
+                pruned_at: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Takes a token from the bucket of `key`, if there is one
+    pub fn check(
+        &self,
+        group: RateLimitGroup,
+        key: &str,
+        limit: &RateLimit,
+        now: Instant,
+    ) -> Decision {
+        let burst = limit.burst.max(1) as f64;
+        let per_sec = limit.per_minute.max(1) as f64 / 60.0;
+        let mut state = self.state.lock().unwrap();
+        state.prune(now);
+        let bucket = state
+            .buckets
+            .entry((group, key.to_string()))
+            .or_insert(Bucket {
+                tokens: burst,
+                updated_at: now,
+                full_at: now,
+            });
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * per_sec).min(burst);
+        bucket.updated_at = now;
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        let secs_until_full = (burst - bucket.tokens) / per_sec;
+        bucket.full_at = now + Duration::from_secs_f64(secs_until_full);
+        Decision {
+            allowed,
+            limit: limit.burst,
+            remaining: bucket.tokens.floor() as i64,
+            reset_secs: secs_until_full.ceil() as u64,
+            retry_after_secs: if allowed {
+                0
+            } else {
+                ((1.0 - bucket.tokens) / per_sec).ceil() as u64
+            },
+        }
+    }
+
+    /// API tokens are checked against the DB before they get a bucket, so
+    /// made up tokens can't be used to get around the IP limits
+    pub fn has_bucket(&self, group: RateLimitGroup, key: &str) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .buckets
+            .contains_key(&(group, key.to_string()))
+    }
+
+    /// Records a limited request and returns the number of them made from
+    /// this IP within `OFFENSE_WINDOW`
+    pub fn offense(&self, ip: &str, now: Instant) -> i64 {
+        let mut state = self.state.lock().unwrap();
+        let offenses = state.offenses.entry(ip.to_string()).or_insert(Offenses {
+            count: 0,
+            since: now,
+        });
+        if now.saturating_duration_since(offenses.since) > OFFENSE_WINDOW {
+            offenses.count = 0;
+            offenses.since = now;
+        }
+        offenses.count += 1;
+        offenses.count
+    }
+
+    pub fn forgive(&self, ip: &str) {
+        self.state.lock().unwrap().offenses.remove(ip);
+    }
+}
+
+impl State {
+    fn prune(&mut self, now: Instant) {
+        if now.saturating_duration_since(self.pruned_at) < PRUNE_INTERVAL {
+            return;
+        }
+        self.buckets.retain(|_, it| it.full_at > now);
+        self.offenses
+            .retain(|_, it| now.saturating_duration_since(it.since) <= OFFENSE_WINDOW);
+        self.pruned_at = now;
+    }
+}
+
+/// The group of a request, `rpc_method` is only known for `/rpc` calls
+pub fn group(method: &Method, path: &str, rpc_method: Option<&str>) -> Option<RateLimitGroup> {
+    if path == "/rpc" {
+        return Some(match rpc_method {
+            Some("signin" | "signup" | "change_password") => RateLimitGroup::Auth,
+            Some("create_invoice") => RateLimitGroup::Invoices,
+            _ => RateLimitGroup::Rpc,
+        });
+    }
+    let auth = match *method {
+        Method::POST => {
+            path == "/v4/users"
+                || path == "/v4/auth/nostr"
+                || path.starts_with("/v4/users/") && path.ends_with("/tokens")
+        }
+        Method::PUT => path == "/v4/users/me/password",
+        _ => false,
+    };
+    if auth {
+        return Some(RateLimitGroup::Auth);
+    }
+    let invoices = match *method {
+        Method::POST => {
+            path == "/v4/place-boosts"
+                || path == "/v4/place-comments"
+                || path.starts_with("/v4/invoices/") && path.ends_with("/renew")
+        }
+        Method::GET => path.starts_with("/v4/place-boosts/lnurlp/") && path.ends_with("/callback"),
+        _ => false,
+    };
+    if invoices {
+        return Some(RateLimitGroup::Invoices);
+    }
+    if *method == Method::POST
+        && path.starts_with("/v4/place-comments/")
+        && path.ends_with("/flags")
+    {
+        return Some(RateLimitGroup::Flags);
+    }
+    let read = *method == Method::GET || *method == Method::HEAD;
+    if read
+        && ["/v2/", "/v3/", "/v4/"]
+            .iter()
+            .any(|it| path.starts_with(it))
+    {
+        return Some(RateLimitGroup::Reads);
+    }
+    None
+}
+
+pub async fn limit(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> std::result::Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let (Some(conf), Some(limiter), Some(pool)) = (
+        req.app_data::<Data<SharedConf>>().cloned(),
+        req.app_data::<Data<RateLimiter>>().cloned(),
+        req.app_data::<Data<MainPool>>().cloned(),
+    ) else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    };
+    let conf = conf.load();
+    if conf.rate_limits.is_empty() {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+    let ip = service::client_ip::get(req.request()).map(|it| it.to_string());
+
+    let rpc_method = if req.path() == "/rpc" {
+        let body = req.extract::<Bytes>().await?;
+        let rpc_method = serde_json::from_slice::<Value>(&body)
+            .ok()
+            .and_then(|it| it.get("method")?.as_str().map(str::to_string));
+        req.set_payload(bytes_to_payload(body));
+        rpc_method
+    } else {
+        None
+    };
+    let Some(group) = group(req.method(), req.path(), rpc_method.as_deref()) else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    };
+    let Some(limit) = conf.rate_limits.iter().find(|it| it.group == group) else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    };
+
+    let token_key = match bearer_token(req.headers()) {
+        Some(secret) => {
+            let key = format!("token:{}", service::api_token::hash(&secret));
+            if limiter.has_bucket(group, &key)
+                || db::main::access_token::queries::select_by_secret(secret, &pool)
+                    .await
+                    .is_ok()
+            {
+                Some(key)
+            } else {
+                None
+            }
+        }
+        None => None,
+    };
+    let Some(key) =
+        token_key.or_else(|| ip_key(ip.as_deref(), service::client_ip::behind_trusted_proxy()))
+    else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    };
+
+    let decision = limiter.check(group, &key, limit, Instant::now());
+    if decision.allowed {
+        let mut res = next.call(req).await?;
+        insert_headers(res.headers_mut(), &decision);
+        return Ok(res.map_into_left_body());
+    }
+
+    if let Some(ip) = &ip {
+        let offenses = limiter.offense(ip, Instant::now());
+        warn!(
+            ip,
+            ?group,
+            path = req.path(),
+            rpc_method,
+            offenses,
+            "rate limit exceeded"
+        );
+        if should_ban(
+            offenses,
+            conf.rate_limit_ban_after,
+            service::client_ip::behind_trusted_proxy(),
+        ) {
+            let hours = conf.rate_limit_ban_hours.max(1);
+            db::main::ban::queries::insert(
+                ip.clone(),
+                format!("exceeded rate limits {offenses} times in an hour"),
+                time::Duration::hours(hours),
+                &pool,
+            )
+            .await?;
+            service::ban::invalidate();
+            limiter.forgive(ip);
+            warn!(ip, hours, "banned for exceeding rate limits");
+        }
+    }
+
+    let mut res = RestApiError::too_many_requests(format!(
+        "Rate limit exceeded, retry in {} seconds",
+        decision.retry_after_secs
+    ))
+    .error_response();
+    insert_headers(res.headers_mut(), &decision);
+    res.headers_mut().insert(
+        header::RETRY_AFTER,
+        HeaderValue::from(decision.retry_after_secs),
+    );
+    Ok(req.into_response(res).map_into_right_body())
+}
+
+/// Without trusted proxies an IP is either made up or the proxy's, which all
+/// the clients behind it share, so anonymous requests can't be told apart
+fn ip_key(ip: Option<&str>, behind_trusted_proxy: bool) -> Option<String> {
+    ip.filter(|_| behind_trusted_proxy)
+        .map(|it| format!("ip:{it}"))
+}
+
+/// Without trusted proxies all the clients behind a proxy share its IP, and
+/// banning it would ban everyone
+fn should_ban(offenses: i64, ban_after: i64, behind_trusted_proxy: bool) -> bool {
+    behind_trusted_proxy && ban_after > 0 && offenses >= ban_after
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(String::from)
+}
+
+fn insert_headers(headers: &mut HeaderMap, decision: &Decision) {
+    headers.insert(RATE_LIMIT_LIMIT, HeaderValue::from(decision.limit));
+    headers.insert(RATE_LIMIT_REMAINING, HeaderValue::from(decision.remaining));
+    headers.insert(RATE_LIMIT_RESET, HeaderValue::from(decision.reset_secs));
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RateLimiter, OFFENSE_WINDOW};
+    use crate::db::main::conf::schema::{Conf, RateLimit, RateLimitGroup};
+    use crate::db::main::test::pool;
+    use crate::service::conf::SharedConf;
+    use crate::{db, Result};
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::middleware::from_fn;
+    use actix_web::test::TestRequest;
+    use actix_web::web::{scope, Data};
+    use actix_web::{get, test, App, Responder};
+    use std::time::{Duration, Instant};
+
+    fn limit(burst: i64, per_minute: i64) -> RateLimit {
+        RateLimit {
+            group: RateLimitGroup::Reads,
+            burst,
+            per_minute,
+        }
+    }
+
+    #[test]
+    async fn check() {
+        let limiter = RateLimiter::default();
+        let limit = limit(2, 60);
+        let now = Instant::now();
+        let first = limiter.check(RateLimitGroup::Reads, "ip:1", &limit, now);
+        assert!(first.allowed);
+        assert_eq!(1, first.remaining);
+        assert_eq!(1, first.reset_secs);
+        assert!(
+            limiter
+                .check(RateLimitGroup::Reads, "ip:1", &limit, now)
+                .allowed
+        );
+        let limited = limiter.check(RateLimitGroup::Reads, "ip:1", &limit, now);
+        assert!(!limited.allowed);
+        assert_eq!(0, limited.remaining);
+        assert_eq!(1, limited.retry_after_secs);
+        // other keys and groups have their own buckets
+        assert!(
+            limiter
+                .check(RateLimitGroup::Reads, "ip:2", &limit, now)
+                .allowed
+        );
+        assert!(
+            limiter
+                .check(RateLimitGroup::Rpc, "ip:1", &limit, now)
+                .allowed
+        );
+        // a token a second
+        let later = now + Duration::from_secs(1);
+        assert!(
+            limiter
+                .check(RateLimitGroup::Reads, "ip:1", &limit, later)
+                .allowed
+        );
+        assert!(
+            !limiter
+                .check(RateLimitGroup::Reads, "ip:1", &limit, later)
+                .allowed
+        );
+    }
+
+    #[test]
+    async fn prune() {
+        let limiter = RateLimiter::default();
+        let limit = limit(1, 60);
+        let now = Instant::now();
+        limiter.check(RateLimitGroup::Reads, "ip:1", &limit, now);
+        assert!(limiter.has_bucket(RateLimitGroup::Reads, "ip:1"));
+        limiter.check(
+            RateLimitGroup::Reads,
+            "ip:2",
+            &limit,
+            now + Duration::from_secs(120),
+        );
+        assert!(!limiter.has_bucket(RateLimitGroup::Reads, "ip:1"));
+    }
+
+    #[test]
+    async fn offense() {
+        let limiter = RateLimiter::default();
+        let now = Instant::now();
+        assert_eq!(1, limiter.offense("1.1.1.1", now));
+        assert_eq!(2, limiter.offense("1.1.1.1", now));
+        assert_eq!(1, limiter.offense("2.2.2.2", now));
+        let later = now + OFFENSE_WINDOW + Duration::from_secs(1);
+        assert_eq!(1, limiter.offense("1.1.1.1", later));
+        limiter.forgive("1.1.1.1");
+        assert_eq!(1, limiter.offense("1.1.1.1", later));
+    }
+
+    #[test]
+    async fn group() {
+        use RateLimitGroup::*;
+        let cases = [
+            (Method::POST, "/rpc", Some("signin"), Some(Auth)),
+            (Method::POST, "/rpc", Some("create_invoice"), Some(Invoices)),
+            (Method::POST, "/rpc", Some("get_element"), Some(Rpc)),
+            (Method::POST, "/rpc", None, Some(Rpc)),
+            (Method::POST, "/v4/users", None, Some(Auth)),
+            (Method::POST, "/v4/users/satoshi/tokens", None, Some(Auth)),
+            (Method::PUT, "/v4/users/me/password", None, Some(Auth)),
+            (Method::POST, "/v4/auth/nostr", None, Some(Auth)),
+            (Method::POST, "/v4/place-boosts", None, Some(Invoices)),
+            (Method::POST, "/v4/place-comments", None, Some(Invoices)),
+            (Method::POST, "/v4/invoices/abc/renew", None, Some(Invoices)),
+            (
+                Method::GET,
+                "/v4/place-boosts/lnurlp/1/callback",
+                None,
+                Some(Invoices),
+            ),
+            (
+                Method::POST,
+                "/v4/place-comments/1/flags",
+                None,
+                Some(Flags),
+            ),
+            (Method::GET, "/v4/places", None, Some(Reads)),
+            (Method::GET, "/v2/elements", None, Some(Reads)),
+            (Method::GET, "/v3/elements", None, Some(Reads)),
+            (Method::PUT, "/v4/places/saved", None, None),
+            (Method::GET, "/feeds/places", None, None),
+        ];
+        for (method, path, rpc_method, expected) in cases {
+            assert_eq!(
+                expected,
+                super::group(&method, path, rpc_method),
+                "{method} {path}"
+            );
+        }
+    }
+
+    #[get("/places")]
+    async fn places() -> impl Responder {
+        "[]"
+    }
+
+    #[test]
+    async fn middleware() -> Result<()> {
+        let pool = pool();
+        let conf = SharedConf::new(Conf {
+            rate_limits: vec![limit(1, 1)],
+            rate_limit_ban_after: 2,
+            rate_limit_ban_hours: 1,
+            ..Default::default()
+        });
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(super::limit))
+                .app_data(Data::new(pool.clone()))
+                .app_data(Data::new(conf))
+                .app_data(Data::new(RateLimiter::default()))
+                .service(scope("v4").service(places)),
+        )
+        .await;
+        let user = db::main::user::queries::insert("user", "", &pool).await?;
+        db::main::access_token::queries::insert(user.id, "".into(), "secret".into(), vec![], &pool)
+            .await?;
+        let req = || {
+            TestRequest::get()
+                .uri("/v4/places")
+                .peer_addr("10.0.0.1:1234".parse().unwrap())
+                .insert_header(("Authorization", "Bearer secret"))
+                .to_request()
+        };
+
+        let res = test::call_service(&app, req()).await;
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("1", res.headers().get("ratelimit-limit").unwrap());
+        assert_eq!("0", res.headers().get("ratelimit-remaining").unwrap());
+
+        let res = test::call_service(&app, req()).await;
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+        assert_eq!("60", res.headers().get("retry-after").unwrap());
+        assert!(
+            db::main::ban::queries::select_by_ip("10.0.0.1".into(), &pool)
+                .await?
+                .is_none()
+        );
+
+        // the peer could be a proxy, so it doesn't get banned
+        let res = test::call_service(&app, req()).await;
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+        assert!(
+            db::main::ban::queries::select_by_ip("10.0.0.1".into(), &pool)
+                .await?
+                .is_none()
+        );
+        Ok(())
+    }
+
+    #[test]
+    async fn should_ban() {
+        assert!(super::should_ban(2, 2, true));
+        assert!(!super::should_ban(1, 2, true));
+        assert!(!super::should_ban(2, 2, false));
+        assert!(!super::should_ban(100, 0, true));
+    }
+
+    #[test]
+    async fn ip_key() {
+        assert_eq!(
+            Some("ip:1.1.1.1".to_string()),
+            super::ip_key(Some("1.1.1.1"), true)
+        );
+        assert_eq!(None, super::ip_key(Some("1.1.1.1"), false));
+        assert_eq!(None, super::ip_key(None, true));
+    }
+
+    #[test]
+    async fn anonymous_requests_need_trusted_proxies() {
+        let conf = SharedConf::new(Conf {
+            rate_limits: vec![limit(1, 1)],
+            ..Default::default()
+        });
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(super::limit))
+                .app_data(Data::new(pool()))
+                .app_data(Data::new(conf))
+                .app_data(Data::new(RateLimiter::default()))
+                .service(scope("v4").service(places)),
+        )
+        .await;
+        // tests never load BTCMAP_TRUSTED_PROXIES, so every client could be
+        // the proxy and nobody shares its bucket
+        for _ in 0..3 {
+            let req = TestRequest::get()
+                .uri("/v4/places")
+                .peer_addr("10.0.0.1:1234".parse().unwrap())
+                .to_request();
+            assert_eq!(StatusCode::OK, test::call_service(&app, req).await.status());
+        }
+    }
+
+    #[test]
+    async fn disabled_without_limits() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(super::limit))
+                .app_data(Data::new(pool()))
+                .app_data(Data::new(SharedConf::new(Conf::default())))
+                .app_data(Data::new(RateLimiter::default()))
+                .service(scope("v4").service(places)),
+        )
+        .await;
+        for _ in 0..10 {
+            let req = TestRequest::get().uri("/v4/places").to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(StatusCode::OK, res.status());
+            assert!(res.headers().get("ratelimit-limit").is_none());
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use crate::{
+    db::{
+        self,
+        main::{
+            element::schema::Element,
+            element_review::schema::ElementReview,
+            user::schema::{Role, User},
+        },
+    },
+    Result,
+};
+use deadpool_sqlite::Pool;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+pub const MIN_RATING: i64 = 1;
+pub const MAX_RATING: i64 = 5;
+
+/// Structured yes/no questions, free form answers go to comments
+pub const QUESTIONS: &[&str] = &[
+    "accepted_lightning",
+    "accepted_onchain",
+    "staff_knew_bitcoin",
+];
+
+/// A "yes" to any of these confirms that the place still takes bitcoin
+pub const PAYMENT_QUESTIONS: &[&str] = &["accepted_lightning", "accepted_onchain"];
+
+/// Anyone can create an account, so payment confirmations from fresh accounts
+/// don't mark a place as verified
+pub const TRUSTED_REVIEWER_MIN_ACCOUNT_AGE: Duration = Duration::days(30);
+
+pub const MAX_REVIEWS_PER_USER_PER_DAY: i64 = 20;
+pub const MAX_REVIEWS_PER_PLACE_PER_DAY: i64 = 20;
+
+pub const RATING_TAG: &str = "rating";
+pub const RATING_COUNT_TAG: &str = "rating_count";
+pub const LAST_CONFIRMED_PAYMENT_AT_TAG: &str = "last_confirmed_payment_at";
+
+pub fn validate(rating: i64, answers: &BTreeMap<String, bool>) -> Result<()> {
+    if !(MIN_RATING..=MAX_RATING).contains(&rating) {
+        return Err(format!("rating must be between {MIN_RATING} and {MAX_RATING}").into());
+    }
+    if let Some(unknown) = answers.keys().find(|it| !QUESTIONS.contains(&it.as_str())) {
+        return Err(format!("unknown question: {unknown}").into());
+    }
+    Ok(())
+}
+
+/// Posting a second review for the same place replaces the first one, so a
+/// single user can't inflate the rating
+pub async fn submit(
+    element: &Element,
+    user_id: i64,
+    rating: i64,
+    answers: BTreeMap<String, bool>,
+    pool: &Pool,
+) -> Result<ElementReview> {
+    if element.deleted_at.is_some() {
+        return Err("place is deleted".into());
+    }
+    validate(rating, &answers)?;
+    db::main::element_review::queries::upsert_within_limits(
+        element.id,
+        user_id,
+        rating,
+        answers,
+        OffsetDateTime::now_utc() - Duration::days(1),
+        MAX_REVIEWS_PER_USER_PER_DAY,
+        MAX_REVIEWS_PER_PLACE_PER_DAY,
+        pool,
+    )
+    .await
+}
+
+/// Admins are trusted right away, everyone else once their account is old
+/// enough
+pub fn is_trusted_reviewer(user: &User, now: OffsetDateTime) -> bool {
+    if user.deleted_at.is_some() {
+        return false;
+    }
+    if user.roles.contains(&Role::Admin) || user.roles.contains(&Role::Root) {
+        return true;
+    }
+    OffsetDateTime::parse(&user.created_at, &Rfc3339)
+        .is_ok_and(|created_at| now - created_at >= TRUSTED_REVIEWER_MIN_ACCOUNT_AGE)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    /// Average rating, rounded to one decimal place
+    pub rating: f64,
+    pub rating_count: i64,
+    pub last_confirmed_payment_at: Option<OffsetDateTime>,
+}
+
+/// Every review counts towards the rating, but only the ones by
+/// `trusted_reviewers` can confirm payments
+pub fn summarize(reviews: &[ElementReview], trusted_reviewers: &HashSet<i64>) -> Option<Summary> {
+    if reviews.is_empty() {
+        return None;
+    }
+    let rating_count = reviews.len() as i64;
+    let sum: i64 = reviews.iter().map(|it| it.rating).sum();
+    let rating = (sum as f64 / rating_count as f64 * 10.0).round() / 10.0;
+    let last_confirmed_payment_at = reviews
+        .iter()
+        .filter(|review| trusted_reviewers.contains(&review.user_id))
+        .filter(|review| {
+            PAYMENT_QUESTIONS
+                .iter()
+                .any(|question| review.answers.get(*question) == Some(&true))
+        })
+        .map(|it| it.updated_at)
+        .max();
+    Some(Summary {
+        rating,
+        rating_count,
+        last_confirmed_payment_at,
+    })
+}
+
+/// Copies review aggregates into place tags. Returns true if any tag changed.
+pub async fn refresh_rating_tags(element: &Element, pool: &Pool) -> Result<bool> {
+    let reviews = db::main::element_review::queries::select_by_element_id(element.id, pool).await?;
+    let now = OffsetDateTime::now_utc();
+    let mut trusted_reviewers = HashSet::new();
+    for user_id in reviews.iter().map(|it| it.user_id).collect::<HashSet<_>>() {
+        let user = db::main::user::queries::select_by_id(user_id, pool).await?;
+        if is_trusted_reviewer(&user, now) {
+            trusted_reviewers.insert(user_id);
+        }
+    }
+    let summary = summarize(&reviews, &trusted_reviewers);
+    let new_tags: [(&str, Option<Value>); 3] = [
+        (RATING_TAG, summary.as_ref().map(|it| it.rating.into())),
+        (
+            RATING_COUNT_TAG,
+            summary.as_ref().map(|it| it.rating_count.into()),
+        ),
+        (
+            LAST_CONFIRMED_PAYMENT_AT_TAG,
+            summary
+                .as_ref()
+                .and_then(|it| it.last_confirmed_payment_at)
+                .map(|it| it.format(&Rfc3339))
+                .transpose()?
+                .map(Into::into),
+        ),
+    ];
+    let mut changed = false;
+    for (name, value) in new_tags {
+        // avoid db writes for performance reasons
+        if element.tags.get(name) == value.as_ref() {
+            continue;
+        }
+        match value {
+            Some(value) => {
+                db::main::element::queries::set_tag(element.id, name, &value, pool).await?;
+            }
+            None => {
+                db::main::element::queries::remove_tag(element.id, name, pool).await?;
+            }
+        }
+        changed = true;
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_trusted_reviewer, summarize, validate};
+    use crate::db::main::element_review::schema::ElementReview;
+    use crate::db::main::user::schema::{Role, User};
+    use std::collections::{BTreeMap, HashSet};
+    use time::macros::datetime;
+
+    fn review(rating: i64, answers: &[(&str, bool)], date: time::OffsetDateTime) -> ElementReview {
+        ElementReview {
+            id: 1,
+            element_id: 1,
+            user_id: 1,
+            rating,
+            answers: answers.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
+            created_at: date,
+            updated_at: date,
+        }
+    }
+
+    #[test]
+    fn validate_rating_and_answers() {
+        let answers = BTreeMap::from([("accepted_lightning".to_string(), true)]);
+        assert!(validate(1, &answers).is_ok());
+        assert!(validate(5, &BTreeMap::new()).is_ok());
+        assert!(validate(0, &answers).is_err());
+        assert!(validate(6, &answers).is_err());
+        let answers = BTreeMap::from([("free_beer".to_string(), true)]);
+        assert!(validate(3, &answers).is_err());
+    }
+
+    #[test]
+    fn summarize_reviews() {
+        assert_eq!(None, summarize(&[], &HashSet::new()));
+        let reviews = [
+            review(
+                5,
+                &[("accepted_lightning", true)],
+                datetime!(2025-01-01 0:00 UTC),
+            ),
+            review(
+                4,
+                &[("accepted_onchain", true)],
+                datetime!(2025-02-01 0:00 UTC),
+            ),
+            review(
+                4,
+                &[("accepted_lightning", false)],
+                datetime!(2025-03-01 0:00 UTC),
+            ),
+            review(
+                1,
+                &[("staff_knew_bitcoin", true)],
+                datetime!(2025-04-01 0:00 UTC),
+            ),
+        ];
+        let summary = summarize(&reviews, &HashSet::from([1])).unwrap();
+        assert_eq!(3.5, summary.rating);
+        assert_eq!(4, summary.rating_count);
+        assert_eq!(
+            Some(datetime!(2025-02-01 0:00 UTC)),
+            summary.last_confirmed_payment_at
+        );
+        // untrusted reviewers still count towards the rating
+        let summary = summarize(&reviews, &HashSet::new()).unwrap();
+        assert_eq!(3.5, summary.rating);
+        assert_eq!(None, summary.last_confirmed_payment_at);
+        let summary = summarize(
+            &vec![review(4, &[], datetime!(2025-01-01 0:00 UTC)); 3],
+            &HashSet::from([1]),
+        )
+        .unwrap();
+        assert_eq!(4.0, summary.rating);
+        assert_eq!(None, summary.last_confirmed_payment_at);
+    }
+
+    #[test]
+    fn trusted_reviewers() {
+        let now = datetime!(2025-03-01 0:00 UTC);
+        let user = |roles: Vec<Role>, created_at: &str| User {
+            id: 1,
+            name: "satoshi".into(),
+            password: String::new(),
+            roles,
+            saved_places: vec![],
+            saved_areas: vec![],
+            npub: None,
+            geofence: vec![],
+            created_at: created_at.into(),
+            updated_at: created_at.into(),
+            deleted_at: None,
+        };
+        assert!(is_trusted_reviewer(
+            &user(vec![Role::User], "2025-01-01T00:00:00Z"),
+            now
+        ));
+        assert!(!is_trusted_reviewer(
+            &user(vec![Role::User], "2025-02-20T00:00:00Z"),
+            now
+        ));
+        assert!(is_trusted_reviewer(
+            &user(vec![Role::Admin], "2025-02-20T00:00:00Z"),
+            now
+        ));
+        let mut deleted = user(vec![Role::User], "2025-01-01T00:00:00Z");
+        deleted.deleted_at = Some("2025-02-01T00:00:00Z".into());
+        assert!(!is_trusted_reviewer(&deleted, now));
+    }
+}
@@ -0,0 +1,113 @@
+/// Comments scoring at or above this value are held for review instead of
+/// being published right after payment
+pub const HOLD_THRESHOLD: i64 = 10;
+
+/// A single spam heuristic. Scores from all scorers are summed, so each one
+/// should only return points for the signals it knows about.
+pub trait SpamScorer: Send + Sync {
+    fn score(&self, text: &str) -> i64;
+}
+
+/// Phrases which are common in scam and advertising comments
+pub struct KeywordScorer {
+    pub keywords: &'static [&'static str],
+    pub points: i64,
+}
+
+const KEYWORDS: &[&str] = &[
+    "airdrop",
+    "casino",
+    "crypto recovery",
+    "double your",
+    "giveaway",
+    "guaranteed profit",
+    "investment opportunity",
+    "recovery expert",
+    "seed phrase",
+    "telegram me",
+    "viagra",
+    "whatsapp me",
+];
+
+impl Default for KeywordScorer {
+    fn default() -> Self {
+        KeywordScorer {
+            keywords: KEYWORDS,
+            points: 5,
+        }
+    }
+}
+
+impl SpamScorer for KeywordScorer {
+    fn score(&self, text: &str) -> i64 {
+        let text = text.to_lowercase();
+        self.keywords
+            .iter()
+            .filter(|keyword| text.contains(*keyword))
+            .count() as i64
+            * self.points
+    }
+}
+
+/// Genuine reviews rarely link anywhere, and almost never via URL shorteners
+pub struct LinkScorer;
+
+const SHORTENERS: &[&str] = &["bit.ly/", "tinyurl.com/", "t.me/", "goo.gl/", "cutt.ly/"];
+
+impl SpamScorer for LinkScorer {
+    fn score(&self, text: &str) -> i64 {
+        let text = text.to_lowercase();
+        let links = text
+            .split_whitespace()
+            .filter(|word| {
+                word.contains("http://") || word.contains("https://") || word.starts_with("www.")
+            })
+            .count() as i64;
+        let shorteners = SHORTENERS
+            .iter()
+            .filter(|shortener| text.contains(*shortener))
+            .count() as i64;
+        let links_score = match links {
+            0 => 0,
+            1 => 3,
+            n => 3 + (n - 1) * 5,
+        };
+        links_score + shorteners * 5
+    }
+}
+
+pub fn default_scorers() -> Vec<Box<dyn SpamScorer>> {
+    vec![Box::new(KeywordScorer::default()), Box::new(LinkScorer)]
+}
+
+pub fn score(text: &str, scorers: &[Box<dyn SpamScorer>]) -> i64 {
+    scorers.iter().map(|it| it.score(text)).sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{default_scorers, score, KeywordScorer, LinkScorer, SpamScorer, HOLD_THRESHOLD};
+
+    #[test]
+    fn keyword_scorer() {
+        let scorer = KeywordScorer::default();
+        assert_eq!(0, scorer.score("Great coffee, paid with lightning"));
+        assert_eq!(5, scorer.score("Join our GIVEAWAY"));
+        assert_eq!(10, scorer.score("Airdrop! Double your sats"));
+    }
+
+    #[test]
+    fn link_scorer() {
+        assert_eq!(0, LinkScorer.score("Accepts onchain and lightning"));
+        assert_eq!(3, LinkScorer.score("Menu: https://example.com"));
+        assert_eq!(8, LinkScorer.score("https://a.com and www.b.com"));
+        assert_eq!(8, LinkScorer.score("Click https://bit.ly/abc"));
+    }
+
+    #[test]
+    fn combined_score() {
+        let scorers = default_scorers();
+        assert!(score("Friendly staff, fast payment", &scorers) < HOLD_THRESHOLD);
+        assert!(score("Crypto recovery expert, DM https://t.me/scam", &scorers) >= HOLD_THRESHOLD);
+    }
+}
@@ -0,0 +1,232 @@
+//! NIP-57 zaps, a Nostr flavour of LNURL-pay. Zappers send a signed zap
+//! request with the LNURL callback, the invoice commits to that request and,
+//! once it's paid, we publish a zap receipt signed by the BTC Map key.
+//! See https://github.com/nostr-protocol/nips/blob/master/57.md
+
+use crate::{
+    db::{self, main::invoice::schema::Invoice},
+    service, Result,
+};
+use deadpool_sqlite::Pool;
+use futures_util::future::join_all;
+use nostr::event::{Event, EventBuilder};
+use nostr::key::{Keys, PublicKey};
+use nostr::{JsonUtil, Kind, Tag};
+use tracing::warn;
+
+/// Receipts go to the relays listed in the zap request, capped so a single
+/// request can't make us talk to an unbounded number of relays. Those relays
+/// are picked by the zapper, so they only get a single try each and never go
+/// through the outbox, where a slow relay would hold up our own events.
+pub const MAX_RECEIPT_RELAYS: usize = 10;
+
+fn tag_values<'a>(event: &'a Event, name: &str) -> Vec<&'a [String]> {
+    event
+        .tags
+        .iter()
+        .map(Tag::as_slice)
+        .filter(|it| it.first().map(String::as_str) == Some(name))
+        .collect()
+}
+
+fn relays(event: &Event) -> Vec<String> {
+    tag_values(event, "relays")
+        .iter()
+        .flat_map(|it| it.iter().skip(1))
+        .filter(|it| it.starts_with("wss://"))
+        .take(MAX_RECEIPT_RELAYS)
+        .cloned()
+        .collect()
+}
+
+/// Validates a zap request the way NIP-57 asks LNURL servers to. Only zaps
+/// addressed to `recipient` are accepted, since that's the key which signs
+/// the receipts.
+pub fn verify_request(json: &str, amount_msat: i64, recipient: &PublicKey) -> Result<Event> {
+    let event = Event::from_json(json).map_err(|e| format!("invalid zap request: {e}"))?;
+    if event.kind != Kind::ZapRequest {
+        return Err("zap request must be of kind 9734".into());
+    }
+    event
+        .verify()
+        .map_err(|e| format!("invalid zap request signature: {e}"))?;
+    let p = tag_values(&event, "p");
+    if p.len() != 1 || p[0].get(1) != Some(&recipient.to_hex()) {
+        return Err("zap request must have a single p tag with our pubkey".into());
+    }
+    if tag_values(&event, "e").len() > 1 {
+        return Err("zap request can't have more than one e tag".into());
+    }
+    if let Some(amount) = tag_values(&event, "amount")
+        .first()
+        .and_then(|it| it.get(1))
+    {
+        if *amount != amount_msat.to_string() {
+            return Err("zap request amount doesn't match".into());
+        }
+    }
+    if relays(&event).is_empty() {
+        return Err("zap request must list relays for the receipt".into());
+    }
+    Ok(event)
+}
+
+/// The zap request is embedded exactly as received, since the invoice commits
+/// to the hash of that string
+pub fn receipt(invoice: &Invoice, zap_request_json: &str, keys: &Keys) -> Result<Event> {
+    let zap_request = Event::from_json(zap_request_json).map_err(|e| e.to_string())?;
+    let mut tags = vec![];
+    for name in ["p", "e", "a"] {
+        for values in tag_values(&zap_request, name) {
+            tags.push(Tag::parse(values.iter().take(2)).map_err(|e| e.to_string())?);
+        }
+    }
+    tags.push(Tag::parse(["P", &zap_request.pubkey.to_hex()]).map_err(|e| e.to_string())?);
+    tags.push(Tag::parse(["bolt11", &invoice.payment_request]).map_err(|e| e.to_string())?);
+    tags.push(Tag::parse(["description", zap_request_json]).map_err(|e| e.to_string())?);
+    EventBuilder::new(Kind::ZapReceipt, "")
+        .tags(tags)
+        .sign_with_keys(keys)
+        .map_err(|e| e.to_string().into())
+}
+
+/// Does nothing for regular invoices or if Nostr publishing isn't configured
+pub async fn publish_receipt(invoice: &Invoice, pool: &Pool) -> Result<()> {
+    let Some(zap_request) = &invoice.zap_request else {
+        return Ok(());
+    };
+    let conf = db::main::conf::queries::select(pool).await?;
+    let Some(keys) = service::nostr::signer(&conf) else {
+        return Ok(());
+    };
+    let event = receipt(invoice, zap_request, &keys)?;
+    let zap_request = Event::from_json(zap_request).map_err(|e| e.to_string())?;
+    let relays = relays(&zap_request);
+    let invoice_id = invoice.id;
+    tokio::spawn(async move {
+        let results = join_all(
+            relays
+                .iter()
+                .map(|it| service::nostr::send_to_untrusted_relay(it, &event)),
+        )
+        .await;
+        for (relay_url, res) in relays.iter().zip(results) {
+            if let Err(err) = res {
+                warn!(%err, invoice_id, relay_url, "Failed to publish zap receipt");
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::main::invoice::schema::InvoiceStatus;
+    use crate::db::main::test::pool;
+    use crate::{db, Result};
+    use actix_web::test;
+    use nostr::event::{Event, EventBuilder};
+    use nostr::key::Keys;
+    use nostr::{JsonUtil, Kind, Tag};
+
+    fn zap_request(keys: &Keys, tags: &[&[&str]]) -> Event {
+        EventBuilder::new(Kind::ZapRequest, "Zap!")
+            .tags(
+                tags.iter()
+                    .map(|it| Tag::parse(it.iter().copied()).unwrap()),
+            )
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    #[test]
+    async fn verify_request() -> Result<()> {
+        let zapper = Keys::generate();
+        let btcmap = Keys::generate();
+        let pubkey = btcmap.public_key().to_hex();
+        let valid = zap_request(
+            &zapper,
+            &[
+                &["p", &pubkey],
+                &["amount", "21000"],
+                &["relays", "wss://relay.example.com"],
+            ],
+        );
+        assert!(super::verify_request(&valid.as_json(), 21000, &btcmap.public_key()).is_ok());
+        assert!(super::verify_request(&valid.as_json(), 42000, &btcmap.public_key()).is_err());
+        assert!(super::verify_request(&valid.as_json(), 21000, &zapper.public_key()).is_err());
+        let tampered = valid.as_json().replace("Zap!", "Zap?");
+        assert!(super::verify_request(&tampered, 21000, &btcmap.public_key()).is_err());
+        let no_relays = zap_request(&zapper, &[&["p", &pubkey]]);
+        assert!(super::verify_request(&no_relays.as_json(), 21000, &btcmap.public_key()).is_err());
+        let note = EventBuilder::new(Kind::TextNote, "")
+            .tags([
+                Tag::parse(["p", &pubkey]).unwrap(),
+                Tag::parse(["relays", "wss://relay.example.com"]).unwrap(),
+            ])
+            .sign_with_keys(&zapper)
+            .unwrap();
+        assert!(super::verify_request(&note.as_json(), 21000, &btcmap.public_key()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    async fn publish_receipt() -> Result<()> {
+        let pool = pool();
+        let zapper = Keys::generate();
+        let btcmap = Keys::generate();
+        pool.get()
+            .await?
+            .interact({
+                let secret_key = btcmap.secret_key().to_secret_hex();
+                move |conn| {
+                    conn.execute(
+                        "UPDATE conf SET nostr_secret_key = ?1, nostr_relays = 'wss://btcmap.example.com'",
+                        [secret_key],
+                    )
+                }
+            })
+            .await??;
+        let request = zap_request(
+            &zapper,
+            &[
+                &["p", &btcmap.public_key().to_hex()],
+                &["relays", "wss://127.0.0.1", "ws://a.example.com"],
+            ],
+        );
+        assert_eq!(vec!["wss://127.0.0.1"], super::relays(&request));
+        let invoice = db::main::invoice::queries::insert(
+            "fake",
+            "element_boost:1:30",
+            21,
+            "hash",
+            "lnbc...",
+            InvoiceStatus::Paid,
+            None,
+            &pool,
+        )
+        .await?;
+        super::publish_receipt(&invoice, &pool).await?;
+        let invoice =
+            db::main::invoice::queries::set_zap_request(invoice.id, request.as_json(), &pool)
+                .await?;
+        super::publish_receipt(&invoice, &pool).await?;
+        // zappers pick the relays, so receipts stay out of the outbox
+        assert!(db::main::nostr_outbox::queries::select_by_id(1, &pool)
+            .await
+            .is_err());
+
+        let receipt = super::receipt(&invoice, &request.as_json(), &btcmap)?;
+        receipt.verify().unwrap();
+        assert_eq!(Kind::ZapReceipt, receipt.kind);
+        assert_eq!(btcmap.public_key(), receipt.pubkey);
+        let description = super::tag_values(&receipt, "description")[0][1].clone();
+        assert_eq!(request, Event::from_json(description).unwrap());
+        assert_eq!(
+            zapper.public_key().to_hex(),
+            super::tag_values(&receipt, "P")[0][1]
+        );
+        assert_eq!("lnbc...", super::tag_values(&receipt, "bolt11")[0][1]);
+        Ok(())
+    }
+}